@@ -0,0 +1,37 @@
+//! G^s by `modpow` vs the precomputed `G_TABLE`, and what that does to repeated verifications.
+//!
+//!     cargo bench --bench fixed_base
+//!
+//! On a dev container: G^s 5.5ms -> 1.6ms, a whole modp verification 9.4ms -> 4.0ms.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use priv_access::crypto::{get_random_secret, power_g, power_mod, G, G_TABLE, P, Q};
+use priv_access::zkp::{SchnorrProver, SchnorrVerifier};
+
+fn bench_fixed_base(c: &mut Criterion) {
+    once_cell::sync::Lazy::force(&G_TABLE);
+    let exp = get_random_secret();
+
+    let mut group = c.benchmark_group("g_pow");
+    group.bench_function("modpow", |b| b.iter(|| power_mod(&G, &exp, &P)));
+    group.bench_function("table", |b| b.iter(|| power_g(&exp)));
+    group.finish();
+
+    let proof = SchnorrProver::new(get_random_secret()).generate_proof("t1q7hk9vj".to_string(), "");
+    let mut group = c.benchmark_group("verify_proof");
+    // The old verification, G^s by modpow, beside the real one
+    group.bench_function("modpow", |b| {
+        b.iter(|| {
+            let s: num_bigint::BigUint = proof.response.parse().unwrap();
+            let y: num_bigint::BigUint = proof.public_key.parse().unwrap();
+            let r: num_bigint::BigUint = proof.commitment.parse().unwrap();
+            let c = SchnorrVerifier::challenge(&proof).unwrap().parse::<num_bigint::BigUint>().unwrap() % &*Q;
+            power_mod(&G, &s, &P) == r * power_mod(&y, &c, &P) % &*P
+        })
+    });
+    group.bench_function("table", |b| b.iter(|| SchnorrVerifier::verify_proof(&proof).is_ok()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_fixed_base);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! Batch vs one-by-one verification of 100 modp proofs.
+//!
+//!     cargo bench --bench verify_batch
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use priv_access::crypto::get_random_secret;
+use priv_access::zkp::{Proof, SchnorrProver, SchnorrVerifier};
+
+const PROOFS: usize = 100;
+
+fn proofs() -> Vec<Proof> {
+    (0..PROOFS)
+        .map(|_| SchnorrProver::new(get_random_secret()).generate_proof("t1q7hk9vj".to_string(), ""))
+        .collect()
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let proofs = proofs();
+    assert!(SchnorrVerifier::verify_batch(&proofs).iter().all(|ok| *ok));
+
+    let mut group = c.benchmark_group("verify_100_proofs");
+    group.sample_size(10);
+    // A batch of one is a plain G^s == R * Y^c check, without verify_proof's logging
+    group.bench_function("sequential", |b| {
+        b.iter(|| proofs.iter().map(|p| SchnorrVerifier::verify_batch(std::slice::from_ref(p))[0]).collect::<Vec<_>>())
+    });
+    group.bench_function("batch", |b| b.iter(|| SchnorrVerifier::verify_batch(&proofs)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_verify);
+criterion_main!(benches);
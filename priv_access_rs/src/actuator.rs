@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::door_status::{DoorCommand, DOOR_COMMAND_TX, DOOR_STATUS_TX, INSTANCE_ID};
+
+// Redis key holding the current leader's instance id
+const LEADER_KEY: &str = "privaccess:actuator:leader";
+// Channel the hardware bridge listens on for lock commands
+const ACTUATION_CHANNEL: &str = "privaccess:actuate";
+// A dead leader's lease runs out after this long and another instance takes over
+const LEASE_MS: u64 = 10_000;
+const RENEW_EVERY: Duration = Duration::from_secs(3);
+
+// Single-instance deployments are always the leader
+static IS_LEADER: AtomicBool = AtomicBool::new(true);
+
+pub fn is_leader() -> bool {
+    IS_LEADER.load(Ordering::SeqCst)
+}
+
+/// Forward unlocks and manual locks to the door hardware. Every instance sees every event
+/// through the door status stream (mirrored over Redis), but only the leader sends the command,
+/// so a lock never gets the same command from two replicas.
+pub fn start(redis_url: Option<&str>) {
+    let client = match redis_url.map(redis::Client::open) {
+        Some(Ok(client)) => Some(client),
+        Some(Err(e)) => {
+            println!("WARNING: Invalid PRIVACCESS_REDIS_URL ({}), actuating from this instance only", e);
+            None
+        }
+        None => None,
+    };
+
+    if let Some(client) = client.clone() {
+        IS_LEADER.store(false, Ordering::SeqCst);
+        tokio::spawn(hold_leadership(client));
+    }
+    tokio::spawn(forward_door_events(client));
+    tokio::spawn(handle_commands());
+}
+
+// Display commands become status events, so every display and the leader (on whichever
+// instance it is) hears about them
+async fn handle_commands() {
+    let mut rx = DOOR_COMMAND_TX.subscribe();
+    loop {
+        let (door, command) = match rx.recv().await {
+            Ok(event) => event,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!(missed = n, "Missed door commands");
+                continue;
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        };
+        match command {
+            DoorCommand::ManualLock => crate::door_status::notify_channel(door, "locked"),
+        }
+    }
+}
+
+// The leadership lease: a Redis key holding the leader's instance id, which expires unless renewed
+struct Lease<'a> {
+    key: &'a str,
+    instance: &'a str,
+}
+
+impl Lease<'_> {
+    // Renew the lease if `held`, otherwise try to take it. True if this instance holds it afterwards.
+    async fn claim(&self, conn: &mut redis::aio::MultiplexedConnection, held: bool) -> bool {
+        if held {
+            // Only extend the lease if we still own it
+            let renew = redis::Script::new(
+                "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) else return 0 end",
+            );
+            let renewed: redis::RedisResult<i64> = renew.key(self.key).arg(self.instance).arg(LEASE_MS).invoke_async(conn).await;
+            matches!(renewed, Ok(1))
+        } else {
+            let acquired: redis::RedisResult<Option<String>> = redis::cmd("SET")
+                .arg(self.key)
+                .arg(self.instance)
+                .arg("NX")
+                .arg("PX")
+                .arg(LEASE_MS)
+                .query_async(conn)
+                .await;
+            matches!(acquired, Ok(Some(_)))
+        }
+    }
+}
+
+async fn hold_leadership(client: redis::Client) {
+    let lease = Lease { key: LEADER_KEY, instance: &INSTANCE_ID };
+    loop {
+        let leader = match client.get_multiplexed_async_connection().await {
+            Ok(mut conn) => lease.claim(&mut conn, is_leader()).await,
+            // Can't reach Redis, so we can't prove we still hold the lease
+            Err(_) => false,
+        };
+
+        if leader != is_leader() {
+            tracing::info!(instance = %*INSTANCE_ID, leader, "{}", if leader { "Took over door actuation" } else { "Released door actuation" });
+        }
+        IS_LEADER.store(leader, Ordering::SeqCst);
+        tokio::time::sleep(RENEW_EVERY).await;
+    }
+}
+
+async fn forward_door_events(client: Option<redis::Client>) {
+    let mut rx = DOOR_STATUS_TX.subscribe();
+    loop {
+        let (door, status) = match rx.recv().await {
+            Ok(event) => event,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                tracing::warn!(missed = n, "Missed door events");
+                continue;
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        };
+        let command = match status.as_str() {
+            "unlocked" => "unlock",
+            "locked" => "lock",
+            _ => continue,
+        };
+        relay(client.as_ref(), is_leader(), &door, command).await;
+    }
+}
+
+// Every instance sees every door event; only the leader passes it on to the hardware
+async fn relay(client: Option<&redis::Client>, leader: bool, door: &str, command: &str) {
+    if leader {
+        actuate(client, door, command).await;
+    }
+}
+
+async fn actuate(client: Option<&redis::Client>, door: &str, command: &str) {
+    tracing::info!(door_id = %door, %command, "Actuating door");
+    let Some(client) = client else {
+        return;
+    };
+    let payload = serde_json::json!({ "door": door, "command": command, "instance": *INSTANCE_ID }).to_string();
+    let published = match client.get_multiplexed_async_connection().await {
+        Ok(mut conn) => redis::AsyncCommands::publish::<_, _, ()>(&mut conn, ACTUATION_CHANNEL, payload).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = published {
+        tracing::warn!(door_id = %door, %command, error = %e, "Failed to send door command");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::redis_url;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn without_redis_every_event_is_relayed_without_blocking() {
+        // A single instance leads from the start, with no lease to win
+        assert!(is_leader());
+        relay(None, true, "test/solo", "unlock").await;
+
+        // Nothing listens on port 1. A follower drops the event; a leader whose Redis is down
+        // logs the failed publish and carries on
+        let unreachable = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+        for leader in [false, true] {
+            tokio::time::timeout(Duration::from_secs(5), relay(Some(&unreachable), leader, "test/solo", "lock")).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at PRIVACCESS_TEST_REDIS_URL"]
+    async fn only_the_lease_holder_sends_commands() {
+        let url = redis_url();
+        let client = redis::Client::open(url.as_str()).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let mut hardware = client.get_async_pubsub().await.unwrap();
+        hardware.subscribe(ACTUATION_CHANNEL).await.unwrap();
+
+        // Two instances contending for a lease of their own
+        let key = format!("privaccess:test:leader:{}", rand::random::<u32>());
+        let first = Lease { key: &key, instance: "first" };
+        let second = Lease { key: &key, instance: "second" };
+        assert!(first.claim(&mut conn, false).await);
+        assert!(!second.claim(&mut conn, false).await);
+        assert!(first.claim(&mut conn, true).await);
+
+        // Both see the unlock; one command reaches the hardware
+        let door = format!("test/{}", key);
+        for leader in [first.claim(&mut conn, true).await, second.claim(&mut conn, false).await] {
+            relay(Some(&client), leader, &door, "unlock").await;
+        }
+        let mut messages = hardware.on_message();
+        let msg = tokio::time::timeout(Duration::from_secs(5), messages.next()).await.unwrap().unwrap();
+        let command: serde_json::Value = serde_json::from_str(&msg.get_payload::<String>().unwrap()).unwrap();
+        assert_eq!((command["door"].as_str(), command["command"].as_str()), (Some(door.as_str()), Some("unlock")));
+        assert!(tokio::time::timeout(Duration::from_millis(500), messages.next()).await.is_err());
+
+        // Once the leader's lease is gone the other takes over, and the old one can't renew
+        redis::AsyncCommands::del::<_, ()>(&mut conn, &key).await.unwrap();
+        assert!(second.claim(&mut conn, false).await);
+        assert!(!first.claim(&mut conn, true).await);
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+const ADMINS_PATH: &str = "admins.json";
+
+/// The account a bare admin password logs in as, and the only one until more are added.
+pub const DEFAULT_ADMIN_ID: &str = "admin";
+
+// Demo admin password: Admin@1234 (override with PRIVACCESS_ADMIN_PASSWORD_HASH)
+pub const ADMIN_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$U4UIU3aKb868BQDHlIWscw$CzoDssam7lPymdXH/rOWph+6FnmFrL9iDCbqNi+TTeI";
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// An admin login. Only the Argon2 hash of the password is kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Admin {
+    pub id: String,
+    // Shown in the audit log; defaults to the id
+    #[serde(default)]
+    pub name: String,
+    password_hash: String,
+}
+
+impl Admin {
+    pub fn password_hash(&self) -> &str {
+        &self.password_hash
+    }
+
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() { &self.id } else { &self.name }
+    }
+}
+
+/// Why an admin couldn't be added or removed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminError {
+    InvalidId,
+    /// Passwords are at least 8 characters
+    WeakPassword,
+    DuplicateId(String),
+    NotFound,
+    /// Removing them would leave nobody able to manage admins
+    LastAdmin,
+    Hash(String),
+}
+
+impl std::fmt::Display for AdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminError::InvalidId => write!(f, "Admin ID must be 1-32 letters, digits, '-' or '_'"),
+            AdminError::WeakPassword => write!(f, "Password must be at least {} characters", MIN_PASSWORD_LEN),
+            AdminError::DuplicateId(id) => write!(f, "Admin ID '{}' already exists", id),
+            AdminError::NotFound => write!(f, "Admin not found"),
+            AdminError::LastAdmin => write!(f, "The last admin can't be removed"),
+            AdminError::Hash(e) => write!(f, "Could not hash password: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+fn default_admins() -> Vec<Admin> {
+    let password_hash = crate::config::CONFIG.admin_password_hash.as_deref().unwrap_or(ADMIN_PASSWORD_HASH);
+    vec![Admin { id: DEFAULT_ADMIN_ID.to_string(), name: String::new(), password_hash: password_hash.to_string() }]
+}
+
+// Until the first change is saved, the single default admin is used
+static ADMINS: Lazy<RwLock<Vec<Admin>>> = Lazy::new(|| {
+    let admins = fs::read_to_string(ADMINS_PATH)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_else(default_admins);
+    RwLock::new(admins)
+});
+
+fn save(admins: &Vec<Admin>) {
+    if let Ok(content) = serde_json::to_string_pretty(admins) {
+        if let Err(e) = fs::write(ADMINS_PATH, content) {
+            tracing::warn!(path = ADMINS_PATH, error = %e, "Could not write admins file");
+        }
+    }
+}
+
+/// Look up an admin; ids are case-insensitive.
+pub fn find(id: &str) -> Option<Admin> {
+    ADMINS.read().unwrap().iter().find(|a| a.id.eq_ignore_ascii_case(id)).cloned()
+}
+
+pub fn list() -> Vec<Admin> {
+    ADMINS.read().unwrap().clone()
+}
+
+/// Add an admin, hashing their password before it's stored.
+pub fn add(id: &str, name: &str, password: &str) -> Result<Admin, AdminError> {
+    let id = id.trim();
+    if !crate::faculty::valid_id(id) {
+        return Err(AdminError::InvalidId);
+    }
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(AdminError::WeakPassword);
+    }
+    // Hash before taking the lock; Argon2 is deliberately slow
+    let password_hash = crate::rbac::hash_password(password).map_err(|e| AdminError::Hash(e.to_string()))?;
+    let record = Admin { id: id.to_string(), name: name.trim().to_string(), password_hash };
+
+    let mut admins = ADMINS.write().unwrap();
+    if admins.iter().any(|a| a.id.eq_ignore_ascii_case(id)) {
+        return Err(AdminError::DuplicateId(id.to_string()));
+    }
+    admins.push(record.clone());
+    save(&admins);
+    Ok(record)
+}
+
+/// Remove an admin. Their sessions stop working on their next request.
+pub fn remove(id: &str) -> Result<Admin, AdminError> {
+    let mut admins = ADMINS.write().unwrap();
+    let index = admins.iter().position(|a| a.id.eq_ignore_ascii_case(id)).ok_or(AdminError::NotFound)?;
+    if admins.len() == 1 {
+        return Err(AdminError::LastAdmin);
+    }
+    let removed = admins.remove(index);
+    save(&admins);
+    Ok(removed)
+}
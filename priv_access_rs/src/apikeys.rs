@@ -0,0 +1,90 @@
+use std::fs;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const KEYS_PATH: &str = "api_keys.json";
+// Prefix makes keys easy to spot in configs and secret scanners
+const KEY_PREFIX: &str = "pak_";
+
+/// A provisioned machine credential. Only the SHA-256 of the key itself is kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub tenant: String,
+    pub permissions: Vec<String>,
+    pub created_by: String,
+    pub created_at: String,
+    pub revoked: bool,
+    key_hash: String,
+}
+
+static API_KEYS: Lazy<Mutex<Vec<ApiKey>>> = Lazy::new(|| {
+    let keys = fs::read_to_string(KEYS_PATH)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    Mutex::new(keys)
+});
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn random_string(len: usize) -> String {
+    use rand::Rng;
+    rand::rngs::OsRng
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn save(keys: &Vec<ApiKey>) {
+    if let Ok(content) = serde_json::to_string_pretty(keys) {
+        let _ = fs::write(KEYS_PATH, content);
+    }
+}
+
+/// Create a key for `tenant`. Returns the record and the plaintext key, which is never stored.
+pub fn create(tenant: &str, name: &str, permissions: Vec<String>, created_by: &str) -> (ApiKey, String) {
+    let secret = format!("{}{}", KEY_PREFIX, random_string(40));
+    let record = ApiKey {
+        id: random_string(12),
+        name: name.to_string(),
+        tenant: tenant.to_string(),
+        permissions,
+        created_by: created_by.to_string(),
+        created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        revoked: false,
+        key_hash: hash_key(&secret),
+    };
+    let mut keys = API_KEYS.lock().unwrap();
+    keys.push(record.clone());
+    save(&keys);
+    (record, secret)
+}
+
+/// Look up an active key by its plaintext value.
+pub fn find(key: &str) -> Option<ApiKey> {
+    let hash = hash_key(key);
+    API_KEYS.lock().unwrap().iter().find(|k| k.key_hash == hash && !k.revoked).cloned()
+}
+
+pub fn list(tenant: &str) -> Vec<ApiKey> {
+    API_KEYS.lock().unwrap().iter().filter(|k| k.tenant == tenant).cloned().collect()
+}
+
+/// Revoke a key of `tenant`. Returns false if no such key exists.
+pub fn revoke(tenant: &str, id: &str) -> bool {
+    let mut keys = API_KEYS.lock().unwrap();
+    let Some(key) = keys.iter_mut().find(|k| k.tenant == tenant && k.id == id) else {
+        return false;
+    };
+    key.revoked = true;
+    save(&keys);
+    true
+}
@@ -0,0 +1,35 @@
+//! One-time helper for operators: prints the Argon2 hash of a password or PIN, ready to paste
+//! into `PRIVACCESS_ADMIN_PASSWORD_HASH`, `admins.json` or `faculty.json`.
+//!
+//!     cargo run --bin hash_secret -- 'new admin password'
+//!
+//! With no argument the secret is read from the first line of stdin, which keeps it out of shell history.
+
+use priv_access::rbac::hash_password;
+
+fn main() {
+    let secret = match std::env::args().nth(1) {
+        Some(s) => s,
+        None => {
+            let mut line = String::new();
+            if let Err(e) = std::io::stdin().read_line(&mut line) {
+                eprintln!("Could not read secret from stdin: {}", e);
+                std::process::exit(1);
+            }
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if secret.trim().is_empty() {
+        eprintln!("Usage: hash_secret <password-or-pin>");
+        std::process::exit(1);
+    }
+
+    // Logins trim what the user typed, so hash the trimmed form too
+    match hash_password(secret.trim()) {
+        Ok(hash) => println!("{}", hash),
+        Err(e) => {
+            eprintln!("Hashing failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
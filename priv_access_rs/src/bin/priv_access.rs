@@ -0,0 +1,90 @@
+//! Offline proof tooling for tests, CI and load scripts; no server or Node.js needed.
+//!
+//!     cargo run --bin priv_access -- prove --role STUDENT --geohash t1q7hk9vj > proof.json
+//!     cargo run --bin priv_access -- verify --proof proof.json
+//!
+//! `verify` exits 0 for a valid proof and 1 otherwise.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use priv_access::rbac::get_role_secret;
+use priv_access::zkp::{Proof, SchnorrProver, SchnorrVerifier};
+
+#[derive(Parser)]
+#[command(name = "priv_access", about = "Generate and check PrivAccess proofs offline")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a JSON proof made with a role's built-in secret (default campus)
+    Prove {
+        #[arg(long)]
+        role: String,
+        #[arg(long)]
+        geohash: String,
+        /// Challenge nonce from /api/challenge; leave out for offline checks
+        #[arg(long, default_value = "")]
+        nonce: String,
+    },
+    /// Check a JSON proof; `-` reads it from stdin
+    Verify {
+        #[arg(long)]
+        proof: PathBuf,
+    },
+}
+
+fn read_proof(path: &PathBuf) -> Result<Proof, String> {
+    let content = if path.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin()).map_err(|e| e.to_string())?
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+    serde_json::from_str(&content).map_err(|e| format!("Not a proof: {}", e))
+}
+
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Prove { role, geohash, nonce } => {
+            let role = role.trim().to_uppercase();
+            let Some(secret) = get_role_secret(&role) else {
+                eprintln!("Unknown role '{}'", role);
+                return ExitCode::FAILURE;
+            };
+            let proof = SchnorrProver::new(secret).generate_proof(geohash, &nonce);
+            match serde_json::to_string_pretty(&proof) {
+                Ok(json) => {
+                    println!("{}", json);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("Could not encode proof: {}", e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Verify { proof } => {
+            let proof = match read_proof(&proof) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            match SchnorrVerifier::verify_proof(&proof) {
+                Ok(()) => {
+                    eprintln!("VALID");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("INVALID ({}): {}", e.code(), e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+    }
+}
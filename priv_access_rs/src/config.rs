@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use once_cell::sync::Lazy;
+
+// Every interface, so phones on the LAN can reach the server
+const DEFAULT_BIND: &str = "0.0.0.0:3000";
+
+// The self-signed pair next to Cargo.toml, for trying TLS on a LAN
+const DEFAULT_TLS_CERT: &str = "cert.pem";
+const DEFAULT_TLS_KEY: &str = "key.pem";
+
+// Generous for a person at a door, tight enough to stop PIN/password guessing
+const DEFAULT_RATE_LIMIT: u64 = 20;
+// An unlock is a challenge plus a verify, so this allows a few quick retries
+const DEFAULT_THROTTLE_RPS: u64 = 2;
+const DEFAULT_THROTTLE_BURST: u64 = 10;
+// Wrong PINs/passwords in a row before an account is locked, and for how long
+const DEFAULT_LOCKOUT_ATTEMPTS: u32 = 5;
+const DEFAULT_LOCKOUT_SECS: u64 = 5 * 60;
+
+// The group prime is 2048 bits, so an element is at most 617 decimal digits
+const DEFAULT_MAX_PROOF_FIELD_LEN: usize = 640;
+
+// A proof is made right after fetching its challenge, so a minute covers a slow phone
+const DEFAULT_PROOF_MAX_AGE_SECS: u64 = 60;
+// Phones on network time are rarely more than a few seconds off; others can sync via /api/time
+const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 10;
+
+// Without a database the whole history is held in memory; past this many entries it's archived
+const DEFAULT_MAX_MEMORY_LOGS: u64 = 10_000;
+
+// How long a door stays unlocked before it relocks on its own
+const DEFAULT_RELOCK_AFTER_SECS: u64 = 5;
+
+// Admin dashboard sessions end after this long without a request
+const DEFAULT_ADMIN_IDLE_SECS: u64 = 15 * 60;
+
+// How often the LAN IP put in QR codes is re-detected
+const DEFAULT_LAN_IP_POLL_SECS: u64 = 30;
+
+// How close (in metres) a shared GPS fix must be to the door
+const DEFAULT_PROXIMITY_RADIUS_M: u64 = 50;
+
+// Weakest BLE beacon signal (dBm) that still counts as standing at the door, a metre or two away
+const DEFAULT_BEACON_MIN_RSSI: i32 = -70;
+// A receiver that hasn't answered by then is treated as down
+const DEFAULT_WEBHOOK_TIMEOUT_SECS: u64 = 5;
+// Retries after the first attempt, backing off 1s, 2s, 4s...
+const DEFAULT_WEBHOOK_RETRIES: u32 = 3;
+
+// The server runs from priv_access_rs/, next to the circuit directory
+const DEFAULT_GROTH16_VKEY: &str = "../zkp_circom/verification_key.json";
+
+/// Deployment settings read from `PRIVACCESS_*` environment variables at first use.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Address the server listens on; `--bind` overrides it.
+    pub bind: SocketAddr,
+    /// Serve HTTPS with `tls_cert_path` / `tls_key_path`. Phones only share their location with
+    /// secure origins, so off localhost the geohash check needs it.
+    pub tls: bool,
+    /// PEM certificate chain for TLS.
+    pub tls_cert_path: String,
+    /// PEM private key for TLS.
+    pub tls_key_path: String,
+    /// Role -> whether it must pass the proximity check. Roles not listed require it.
+    pub require_proximity: HashMap<String, bool>,
+    /// Extra campuses served under `/t/:tenant/` alongside the default one.
+    pub tenants: Vec<String>,
+    /// Class sections students pick from and faculty assign to rooms.
+    pub sections: Vec<String>,
+    /// Redis used to share door status events between instances. None keeps them in-process.
+    pub redis_url: Option<String>,
+    /// Database for logs, assignments and registries: `sqlite://privaccess.db` or `postgres://...`.
+    pub database_url: Option<String>,
+    /// Unlock/login attempts allowed per client IP and role each minute. 0 disables limiting.
+    pub rate_limit: u64,
+    /// Sustained requests per second one IP may make to the verify/setup routes. 0 disables.
+    pub throttle_rps: u64,
+    /// Requests one IP may make to those routes back to back before the rate applies.
+    pub throttle_burst: u64,
+    /// Failed logins in a row that lock a faculty or admin account, whatever IPs they came from.
+    /// 0 disables lockout.
+    pub lockout_attempts: u32,
+    /// Seconds a locked account stays locked.
+    pub lockout_secs: u64,
+    /// Take the client address from X-Forwarded-For (only safe behind a trusted proxy).
+    pub trust_proxy: bool,
+    /// Longest decimal string accepted for a proof's public key, commitment or response.
+    pub max_proof_field_len: usize,
+    /// Browser origins allowed to call the JSON API cross-origin, e.g. `https://app.example.edu`.
+    pub cors_origins: Vec<String>,
+    /// Answer CORS requests from any origin. Only set from `*` in PRIVACCESS_CORS_ORIGINS under
+    /// PRIVACCESS_DEV_MODE, for trying a frontend from a local dev server.
+    pub cors_allow_any: bool,
+    /// Honour the `demo` flag clients send to `/verify`. Off, it's ignored and only the
+    /// Groth16 proof counts.
+    pub demo_mode: bool,
+    /// Entries the in-memory history may hold per campus before it's rotated to an archive file.
+    /// 0 never rotates.
+    pub max_memory_logs: usize,
+    /// Keep the Schnorr proof behind every granted access so it can be re-verified in a dispute.
+    pub store_proofs: bool,
+    /// Attach a server signature to every /api/verify response so clients can detect tampering.
+    pub sign_responses: bool,
+    /// Default for how far a client's GPS fix may be from a door, in metres. 0 turns the distance check off.
+    pub proximity_radius_m: u64,
+    /// Default for the weakest signal, in dBm, a door's BLE beacon may be heard at.
+    pub beacon_min_rssi: i32,
+    /// Argon2 hash replacing the built-in demo admin password.
+    pub admin_password_hash: Option<String>,
+    /// snarkjs verification key for the circom geohash circuit behind `/verify`.
+    pub groth16_vkey_path: String,
+    /// Oldest proof timestamp accepted, in seconds.
+    pub proof_max_age_secs: u64,
+    /// How far a client's clock may be off either way wherever its timestamps are checked.
+    pub max_clock_skew_secs: u64,
+    /// Seconds after an unlock before the door is sent "locked" again; a new unlock restarts
+    /// the wait. 0 leaves doors unlocked until something else locks them.
+    pub relock_after_secs: u64,
+    /// Seconds an admin cookie session may sit unused before it has to log in again.
+    pub admin_idle_secs: u64,
+    /// Seconds between checks of this machine's LAN IP, when listening on every interface.
+    /// 0 keeps the address detected at startup.
+    pub lan_ip_poll_secs: u64,
+    /// Hex safe prime replacing the RFC 3526 group for modp Schnorr proofs.
+    pub modp_prime_hex: Option<String>,
+    /// Hex generator for that prime; 2 if unset.
+    pub modp_generator_hex: Option<String>,
+    /// Origin faculty passkeys are bound to, e.g. `https://door.example.edu`. Unset, it's taken
+    /// from the request's Host: `http://` for localhost, `https://` otherwise.
+    pub webauthn_origin: Option<String>,
+    /// Where every grant and denial is POSTed as JSON; unset sends nothing.
+    pub webhook_url: Option<String>,
+    /// Seconds each webhook attempt may take.
+    pub webhook_timeout_secs: u64,
+    /// Further attempts after a failed webhook delivery.
+    pub webhook_retries: u32,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        // e.g. PRIVACCESS_BIND=127.0.0.1:3001
+        let default_bind: SocketAddr = DEFAULT_BIND.parse().unwrap();
+        let bind = match std::env::var("PRIVACCESS_BIND").ok().filter(|b| !b.trim().is_empty()) {
+            Some(b) => b.trim().parse().unwrap_or_else(|_| {
+                println!("WARNING: Ignoring invalid PRIVACCESS_BIND '{}', expected ip:port", b);
+                default_bind
+            }),
+            None => default_bind,
+        };
+
+        let tls = env_flag("PRIVACCESS_TLS", false);
+        let tls_cert_path = std::env::var("PRIVACCESS_TLS_CERT").ok()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_TLS_CERT.to_string());
+        let tls_key_path = std::env::var("PRIVACCESS_TLS_KEY").ok()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_TLS_KEY.to_string());
+
+        // Admins unlock remotely unless PRIVACCESS_ALLOW_ADMIN_REMOTE=false
+        let allow_admin_remote = env_flag("PRIVACCESS_ALLOW_ADMIN_REMOTE", true);
+
+        let mut require_proximity = HashMap::new();
+        require_proximity.insert("ADMIN".to_string(), !allow_admin_remote);
+        // e.g. PRIVACCESS_PROXIMITY="FACULTY=true,SECURITY=false"
+        if let Ok(spec) = std::env::var("PRIVACCESS_PROXIMITY") {
+            for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                match entry.split_once('=').and_then(|(role, flag)| parse_flag(flag).map(|f| (role, f))) {
+                    Some((role, flag)) => {
+                        require_proximity.insert(role.trim().to_uppercase(), flag);
+                    }
+                    None => println!("WARNING: Ignoring malformed PRIVACCESS_PROXIMITY entry '{}'", entry),
+                }
+            }
+        }
+
+        // e.g. PRIVACCESS_TENANTS="north,south"
+        let mut tenants = Vec::new();
+        if let Ok(spec) = std::env::var("PRIVACCESS_TENANTS") {
+            for id in spec.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+                let valid = id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if valid {
+                    tenants.push(id.to_lowercase());
+                } else {
+                    println!("WARNING: Ignoring invalid tenant id '{}' in PRIVACCESS_TENANTS", id);
+                }
+            }
+        }
+
+        // e.g. PRIVACCESS_SECTIONS="CS-1,CS-2,EE-1"
+        let default_sections = || crate::rbac::DEFAULT_SECTIONS.iter().map(|s| s.to_string()).collect();
+        let sections = match std::env::var("PRIVACCESS_SECTIONS") {
+            Ok(spec) if !spec.trim().is_empty() => crate::rbac::parse_sections(&spec).unwrap_or_else(|e| {
+                println!("WARNING: Ignoring PRIVACCESS_SECTIONS ({}), using sections A-H", e);
+                default_sections()
+            }),
+            _ => default_sections(),
+        };
+
+        let redis_url = std::env::var("PRIVACCESS_REDIS_URL").ok().filter(|u| !u.trim().is_empty());
+
+        let database_url = std::env::var("PRIVACCESS_DATABASE_URL").ok().filter(|u| !u.trim().is_empty());
+
+        let rate_limit = env_number("PRIVACCESS_RATE_LIMIT", DEFAULT_RATE_LIMIT);
+        let throttle_rps = env_number("PRIVACCESS_THROTTLE_RPS", DEFAULT_THROTTLE_RPS);
+        let throttle_burst = env_number("PRIVACCESS_THROTTLE_BURST", DEFAULT_THROTTLE_BURST);
+        let lockout_attempts = env_number("PRIVACCESS_LOCKOUT_ATTEMPTS", DEFAULT_LOCKOUT_ATTEMPTS);
+        let lockout_secs = env_number("PRIVACCESS_LOCKOUT_SECS", DEFAULT_LOCKOUT_SECS);
+        let trust_proxy = env_flag("PRIVACCESS_TRUST_PROXY", false);
+        let max_proof_field_len = env_number("PRIVACCESS_MAX_PROOF_FIELD_LEN", DEFAULT_MAX_PROOF_FIELD_LEN as u64) as usize;
+
+        // e.g. PRIVACCESS_CORS_ORIGINS="https://app.example.edu,capacitor://localhost"
+        let dev_mode = env_flag("PRIVACCESS_DEV_MODE", false);
+        let mut cors_origins = Vec::new();
+        let mut cors_allow_any = false;
+        if let Ok(spec) = std::env::var("PRIVACCESS_CORS_ORIGINS") {
+            for origin in spec.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+                if origin == "*" {
+                    if dev_mode {
+                        println!("WARNING: PRIVACCESS_DEV_MODE is on, the API answers cross-origin requests from any site");
+                        cors_allow_any = true;
+                    } else {
+                        println!("WARNING: Ignoring '*' in PRIVACCESS_CORS_ORIGINS; list origins, or set PRIVACCESS_DEV_MODE=true for local testing");
+                    }
+                } else if axum::http::HeaderValue::from_str(origin).is_err() {
+                    println!("WARNING: Ignoring invalid origin '{}' in PRIVACCESS_CORS_ORIGINS", origin);
+                } else {
+                    cors_origins.push(origin.trim_end_matches('/').to_string());
+                }
+            }
+        }
+
+        let demo_mode = env_flag("PRIVACCESS_DEMO_MODE", false);
+        if demo_mode {
+            println!("WARNING: PRIVACCESS_DEMO_MODE is on, /verify accepts client-declared demo requests; don't use it in production");
+        }
+
+        let max_memory_logs = env_number("PRIVACCESS_MAX_MEMORY_LOGS", DEFAULT_MAX_MEMORY_LOGS) as usize;
+        let store_proofs = env_flag("PRIVACCESS_STORE_PROOFS", false);
+        let sign_responses = env_flag("PRIVACCESS_SIGN_RESPONSES", false);
+        let proof_max_age_secs = env_number("PRIVACCESS_PROOF_MAX_AGE_SECS", DEFAULT_PROOF_MAX_AGE_SECS);
+        // PRIVACCESS_PROOF_MAX_SKEW_SECS is the name from before it covered more than proofs
+        let legacy_skew = env_number("PRIVACCESS_PROOF_MAX_SKEW_SECS", DEFAULT_MAX_CLOCK_SKEW_SECS);
+        let max_clock_skew_secs = env_number("PRIVACCESS_MAX_CLOCK_SKEW_SECONDS", legacy_skew);
+        let relock_after_secs = env_number("PRIVACCESS_RELOCK_SECS", DEFAULT_RELOCK_AFTER_SECS);
+        let admin_idle_secs = env_number("PRIVACCESS_ADMIN_IDLE_SECS", DEFAULT_ADMIN_IDLE_SECS);
+        let lan_ip_poll_secs = env_number("PRIVACCESS_LAN_IP_POLL_SECS", DEFAULT_LAN_IP_POLL_SECS);
+        let proximity_radius_m = env_number("PRIVACCESS_PROXIMITY_RADIUS_M", DEFAULT_PROXIMITY_RADIUS_M);
+        let beacon_min_rssi = env_number("PRIVACCESS_BEACON_MIN_RSSI", DEFAULT_BEACON_MIN_RSSI);
+
+        // Produced by `cargo run --bin hash_secret`
+        let admin_password_hash = std::env::var("PRIVACCESS_ADMIN_PASSWORD_HASH").ok()
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty())
+            .filter(|h| {
+                let valid = argon2::PasswordHash::new(h).is_ok();
+                if !valid {
+                    println!("WARNING: Ignoring malformed PRIVACCESS_ADMIN_PASSWORD_HASH, keeping the demo admin password");
+                }
+                valid
+            });
+
+        let groth16_vkey_path = std::env::var("PRIVACCESS_GROTH16_VKEY").ok()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_GROTH16_VKEY.to_string());
+
+        // Checked for primality at startup, see crypto::CryptoParams::validate
+        let modp_prime_hex = std::env::var("PRIVACCESS_MODP_PRIME").ok().filter(|p| !p.trim().is_empty());
+        let modp_generator_hex = std::env::var("PRIVACCESS_MODP_GENERATOR").ok().filter(|g| !g.trim().is_empty());
+
+        // Passkeys registered under one origin don't work under another, so pin it behind a proxy
+        let webauthn_origin = std::env::var("PRIVACCESS_WEBAUTHN_ORIGIN").ok()
+            .map(|o| o.trim().trim_end_matches('/').to_string())
+            .filter(|o| !o.is_empty())
+            .filter(|o| {
+                let valid = crate::webauthn::RelyingParty::from_origin(o).is_some();
+                if !valid {
+                    println!("WARNING: Ignoring invalid PRIVACCESS_WEBAUTHN_ORIGIN '{}', expected e.g. https://door.example.edu", o);
+                }
+                valid
+            });
+
+        let webhook_url = std::env::var("PRIVACCESS_WEBHOOK_URL").ok()
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .filter(|u| {
+                let valid = reqwest::Url::parse(u).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+                if !valid {
+                    println!("WARNING: Ignoring invalid PRIVACCESS_WEBHOOK_URL '{}', expected an http(s) URL", u);
+                }
+                valid
+            });
+        let webhook_timeout_secs = env_number("PRIVACCESS_WEBHOOK_TIMEOUT_SECS", DEFAULT_WEBHOOK_TIMEOUT_SECS);
+        let webhook_retries = env_number("PRIVACCESS_WEBHOOK_RETRIES", DEFAULT_WEBHOOK_RETRIES);
+
+        Config {
+            bind, tls, tls_cert_path, tls_key_path, require_proximity, tenants, sections, redis_url, database_url, rate_limit, throttle_rps, throttle_burst, trust_proxy,
+            lockout_attempts, lockout_secs, max_proof_field_len, cors_origins, cors_allow_any, demo_mode, max_memory_logs, store_proofs, sign_responses, proximity_radius_m, beacon_min_rssi,
+            admin_password_hash, groth16_vkey_path, proof_max_age_secs, max_clock_skew_secs, relock_after_secs, admin_idle_secs, lan_ip_poll_secs, modp_prime_hex, modp_generator_hex,
+            webauthn_origin, webhook_url, webhook_timeout_secs, webhook_retries,
+        }
+    }
+
+    pub fn requires_proximity(&self, role: &str) -> bool {
+        self.require_proximity.get(role).copied().unwrap_or(true)
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(v) => parse_flag(&v).unwrap_or_else(|| {
+            println!("WARNING: Ignoring unrecognised value '{}' for {}", v, name);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+fn env_number<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(v) => v.trim().parse().unwrap_or_else(|_| {
+            println!("WARNING: Ignoring unrecognised value '{}' for {}", v, name);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+pub static CONFIG: Lazy<Config> = Lazy::new(Config::from_env);
@@ -1,32 +1,328 @@
 use num_bigint::BigUint;
-use num_traits::{Num, One};
+use num_traits::{Num, One, Zero};
 use once_cell::sync::Lazy;
 
 // NIST 2048-bit Prime (or smaller safe prime from Python code)
 // We use the same hex strings as in the Python code for compatibility.
-// WARNING: This 1024-bit prime is for educational/demo safety. Use 2048+ or ECC for production.
+// This is the 2048-bit MODP prime from RFC 3526 (group 14); clients can fetch it from /api/params.
 const PRIME_HEX: &str = "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3BE39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF6955817183995497CEA956AE515D2261898FA051015728E5A8AACAA68FFFFFFFFFFFFFFFF";
 const GENERATOR_HEX: &str = "02";
 
-pub static P: Lazy<BigUint> = Lazy::new(|| BigUint::from_str_radix(PRIME_HEX, 16).unwrap());
-pub static G: Lazy<BigUint> = Lazy::new(|| BigUint::from_str_radix(GENERATOR_HEX, 16).unwrap());
-pub static Q: Lazy<BigUint> = Lazy::new(|| (&*P - BigUint::one()) / 2u32);
+// Enough that a composite slipping through by chance is not a realistic concern
+const MILLER_RABIN_ROUNDS: usize = 32;
+
+// Trial division by these weeds out most composites before any modpow
+const SMALL_PRIMES: &[u32] = &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97];
+
+/// Why a modp group was refused, or an operation on one couldn't be done.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CryptoError {
+    /// A value isn't valid hex
+    InvalidHex { field: &'static str },
+    /// P is composite
+    CompositePrime,
+    /// (P-1)/2 is composite, so P isn't a safe prime
+    NotSafePrime,
+    /// G doesn't generate the order-Q subgroup
+    BadGenerator,
+    /// Modular arithmetic with a modulus of 0
+    ZeroModulus,
+}
+
+impl std::fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoError::InvalidHex { field } => write!(f, "{} is not a hex number", field),
+            CryptoError::CompositePrime => write!(f, "P is not prime"),
+            CryptoError::NotSafePrime => write!(f, "(P-1)/2 is not prime, so P is not a safe prime"),
+            CryptoError::BadGenerator => write!(f, "G does not generate the subgroup of order (P-1)/2"),
+            CryptoError::ZeroModulus => write!(f, "modulus is zero"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+/// The Schnorr group: safe prime `p`, generator `g` of the subgroup of prime order `q = (p-1)/2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptoParams {
+    pub p: BigUint,
+    pub g: BigUint,
+    pub q: BigUint,
+}
+
+impl CryptoParams {
+    /// RFC 3526 group 14, which the browser client also ships with.
+    pub fn rfc3526() -> Self {
+        Self::from_hex(PRIME_HEX, GENERATOR_HEX).expect("built-in group is valid hex")
+    }
+
+    /// Build a group from hex strings. Only parses; call `validate` before trusting it.
+    pub fn from_hex(prime_hex: &str, generator_hex: &str) -> Result<Self, CryptoError> {
+        let parse = |field, hex: &str| {
+            let hex = hex.trim();
+            let hex = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X")).unwrap_or(hex);
+            BigUint::from_str_radix(hex, 16).map_err(|_| CryptoError::InvalidHex { field })
+        };
+        let p = parse("P", prime_hex)?;
+        let g = parse("G", generator_hex)?;
+        let q = if p > BigUint::one() { (&p - BigUint::one()) / 2u32 } else { BigUint::zero() };
+        Ok(CryptoParams { p, g, q })
+    }
+
+    /// Check that P is a safe prime and G generates the order-Q subgroup, so proofs over this
+    /// group mean what they claim.
+    pub fn validate(&self) -> Result<(), CryptoError> {
+        if !is_probable_prime(&self.p, MILLER_RABIN_ROUNDS) {
+            return Err(CryptoError::CompositePrime);
+        }
+        if !is_probable_prime(&self.q, MILLER_RABIN_ROUNDS) {
+            return Err(CryptoError::NotSafePrime);
+        }
+        // With Q prime, any G other than 1 with G^Q = 1 has order exactly Q
+        let g_ok = self.g > BigUint::one() && self.g < self.p && self.g.modpow(&self.q, &self.p).is_one();
+        if !g_ok {
+            return Err(CryptoError::BadGenerator);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a group from hex and check it, so a bad `P` or `G` is an error rather than a proof
+/// that means nothing.
+pub fn parse_params(prime_hex: &str, generator_hex: &str) -> Result<CryptoParams, CryptoError> {
+    let params = CryptoParams::from_hex(prime_hex, generator_hex)?;
+    params.validate()?;
+    Ok(params)
+}
+
+/// Miller-Rabin with `rounds` random bases.
+pub fn is_probable_prime(n: &BigUint, rounds: usize) -> bool {
+    use num_bigint::RandBigInt;
+
+    let two = BigUint::from(2u32);
+    if *n < two {
+        return false;
+    }
+    for &small in SMALL_PRIMES {
+        if (n % small).is_zero() {
+            return *n == BigUint::from(small);
+        }
+    }
+
+    // n - 1 = d * 2^s with d odd
+    let n_minus_one = n - BigUint::one();
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
 
-pub fn get_random_secret() -> BigUint {
     let mut rng = rand::thread_rng();
-    // Generate a random BigUint below Q (simplified, actual distribution might need more care for security)
-    // num-bigint's rand integration:
+    'witness: for _ in 0..rounds {
+        let a = rng.gen_biguint_range(&two, &n_minus_one);
+        let mut x = a.modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&two, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+// PRIVACCESS_MODP_PRIME / PRIVACCESS_MODP_GENERATOR if set, else RFC 3526; parsed, not validated
+static CONFIGURED: Lazy<Result<CryptoParams, CryptoError>> = Lazy::new(|| {
+    let config = &crate::config::CONFIG;
+    match &config.modp_prime_hex {
+        Some(prime) => CryptoParams::from_hex(prime, config.modp_generator_hex.as_deref().unwrap_or(GENERATOR_HEX)),
+        None => Ok(CryptoParams::rfc3526()),
+    }
+});
+
+/// The configured group, or why its hex doesn't parse. Check this before the first use of
+/// `P`, `G` or `Q`; the server does at startup, and also validates the group.
+pub fn params() -> Result<&'static CryptoParams, CryptoError> {
+    CONFIGURED.as_ref().map_err(Clone::clone)
+}
+
+/// The group in use. Panics on first access if `params()` is an error; `SchnorrProver::try_new`
+/// and `SchnorrVerifier` check `params()` first and return the error instead.
+pub static PARAMS: Lazy<CryptoParams> = Lazy::new(|| {
+    params().cloned().unwrap_or_else(|e| panic!("invalid PRIVACCESS_MODP_PRIME / PRIVACCESS_MODP_GENERATOR: {}", e))
+});
+
+pub static P: Lazy<BigUint> = Lazy::new(|| PARAMS.p.clone());
+pub static G: Lazy<BigUint> = Lazy::new(|| PARAMS.g.clone());
+pub static Q: Lazy<BigUint> = Lazy::new(|| PARAMS.q.clone());
+
+/// Uniform secret in [1, Q), drawn from the OS CSPRNG. Used for private keys and proof nonces.
+pub fn get_random_secret() -> BigUint {
+    random_below(&Q)
+}
+
+/// Uniform in [1, bound). num-bigint samples by rejection, masking to the bit length of the
+/// range and redrawing anything past it, so there is no modulo bias.
+fn random_below(bound: &BigUint) -> BigUint {
     use num_bigint::RandBigInt;
-    let limit = &*Q - BigUint::one();
-    rng.gen_biguint_range(&BigUint::one(), &limit)
+    rand::rngs::OsRng.gen_biguint_range(&BigUint::one(), bound)
+}
+
+/// Jacobi symbol (a/n) for odd n. For a prime n it is the Legendre symbol: 1 for a
+/// quadratic residue, -1 for a non-residue, 0 when n divides a. Far cheaper than Euler's criterion.
+pub fn jacobi(a: &BigUint, n: &BigUint) -> i32 {
+    let mut a = a % n;
+    let mut n = n.clone();
+    let mut t = 1;
+    while !a.is_zero() {
+        let twos = a.trailing_zeros().unwrap_or(0);
+        a >>= twos;
+        let n_mod_8 = (&n % 8u32).to_u32_digits().first().copied().unwrap_or(0);
+        if twos % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            t = -t;
+        }
+        std::mem::swap(&mut a, &mut n);
+        if (&a % 4u32) == BigUint::from(3u32) && (&n % 4u32) == BigUint::from(3u32) {
+            t = -t;
+        }
+        a %= &n;
+    }
+    if n.is_one() { t } else { 0 }
 }
 
+/// base^exp mod `mod_val`. Panics on a zero modulus; the group's `P` never is one.
 pub fn power_mod(base: &BigUint, exp: &BigUint, mod_val: &BigUint) -> BigUint {
     base.modpow(exp, mod_val)
 }
 
+/// `power_mod` for a modulus that didn't come from a validated group.
+pub fn try_power_mod(base: &BigUint, exp: &BigUint, mod_val: &BigUint) -> Result<BigUint, CryptoError> {
+    if mod_val.is_zero() {
+        return Err(CryptoError::ZeroModulus);
+    }
+    Ok(power_mod(base, exp, mod_val))
+}
+
+// Bits per table digit: 2^5 - 1 entries per row, ~3.4MB for a 2048-bit group
+const G_TABLE_WINDOW_BITS: u64 = 5;
+
+/// Exponentiation of one fixed base by table lookup. Row i holds base^(d * 2^(w*i)) for every
+/// nonzero w-bit digit d, so base^e is one multiplication per nonzero digit of e: about 400
+/// for a 2048-bit exponent, against ~2,000 squarings for `modpow`. Worth it for G, which every
+/// proof and verification raises to a full-size power.
+pub struct FixedBaseTable {
+    base: BigUint,
+    modulus: BigUint,
+    window_bits: u64,
+    rows: Vec<Vec<BigUint>>,
+}
+
+impl FixedBaseTable {
+    /// Precompute for exponents up to `max_exp_bits` long; longer ones fall back to `modpow`.
+    pub fn new(base: &BigUint, modulus: &BigUint, max_exp_bits: u64, window_bits: u64) -> Self {
+        let row_count = max_exp_bits.div_ceil(window_bits);
+        let mut rows = Vec::with_capacity(row_count as usize);
+        let mut row_base = base % modulus;
+        for _ in 0..row_count {
+            let mut row = Vec::with_capacity((1 << window_bits) - 1);
+            let mut entry = row_base.clone();
+            for _ in 1..(1u64 << window_bits) {
+                row.push(entry.clone());
+                entry = &entry * &row_base % modulus;
+            }
+            // entry is now row_base^(2^w), the next row's base
+            rows.push(row);
+            row_base = entry;
+        }
+        FixedBaseTable { base: base.clone(), modulus: modulus.clone(), window_bits, rows }
+    }
+
+    pub fn pow(&self, exp: &BigUint) -> BigUint {
+        if exp.bits() > self.rows.len() as u64 * self.window_bits {
+            return power_mod(&self.base, exp, &self.modulus);
+        }
+        let mut acc = BigUint::one() % &self.modulus;
+        for (i, row) in self.rows.iter().enumerate() {
+            let low = i as u64 * self.window_bits;
+            let digit = (0..self.window_bits).rev().fold(0usize, |d, j| (d << 1) | exp.bit(low + j) as usize);
+            if digit != 0 {
+                acc = acc * &row[digit - 1] % &self.modulus;
+            }
+        }
+        acc
+    }
+}
+
+/// G's table for the group in use, built on first use; the server builds it at startup.
+pub static G_TABLE: Lazy<FixedBaseTable> = Lazy::new(|| FixedBaseTable::new(&G, &P, P.bits(), G_TABLE_WINDOW_BITS));
+
+/// G^exp mod P, by `G_TABLE`.
+pub fn power_g(exp: &BigUint) -> BigUint {
+    G_TABLE.pow(exp)
+}
+
 #[allow(dead_code)]
 pub fn str_to_int(s: &str) -> BigUint {
     BigUint::from_bytes_be(s.as_bytes())
 }
- 
\ No newline at end of file
+ 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_secret_stays_in_range() {
+        for _ in 0..1000 {
+            let x = get_random_secret();
+            assert!(x >= BigUint::one() && x < *Q);
+        }
+    }
+
+    #[test]
+    fn fixed_base_table_matches_modpow() {
+        let table = FixedBaseTable::new(&G, &P, P.bits(), G_TABLE_WINDOW_BITS);
+        let mut exps: Vec<BigUint> = (0..8).map(|_| get_random_secret()).collect();
+        exps.extend([BigUint::zero(), BigUint::one(), BigUint::from(31u32), BigUint::from(32u32), &*Q - 1u32, &*P - 1u32]);
+        // Longer than the table covers
+        exps.push(&*P << 3);
+        for e in &exps {
+            assert_eq!(table.pow(e), power_mod(&G, e, &P), "G^{}", e);
+        }
+
+        // Windows that don't divide the exponent length, and a base other than G
+        let small = FixedBaseTable::new(&BigUint::from(5u32), &BigUint::from(1009u32), 10, 3);
+        for e in 0..2000u32 {
+            assert_eq!(small.pow(&BigUint::from(e)), BigUint::from(5u32).modpow(&BigUint::from(e), &BigUint::from(1009u32)));
+        }
+    }
+
+    #[test]
+    fn bad_params_are_errors_not_panics() {
+        // 23 = 2 * 11 + 1, and 4 generates the order-11 subgroup
+        let group = parse_params("0x17", "4").unwrap();
+        assert_eq!(group.q, BigUint::from(11u32));
+        assert_eq!(parse_params("zz", "4"), Err(CryptoError::InvalidHex { field: "P" }));
+        assert_eq!(parse_params("17", ""), Err(CryptoError::InvalidHex { field: "G" }));
+        assert_eq!(parse_params("15", "4"), Err(CryptoError::CompositePrime));
+        // 13 is prime but 6 isn't
+        assert_eq!(parse_params("D", "4"), Err(CryptoError::NotSafePrime));
+        assert_eq!(parse_params("17", "5"), Err(CryptoError::BadGenerator));
+        assert_eq!(try_power_mod(&G, &Q, &BigUint::zero()), Err(CryptoError::ZeroModulus));
+    }
+
+    #[test]
+    fn small_bound_hits_every_value() {
+        // With bound 5 every one of 1..=4 should turn up, and nothing else
+        let bound = BigUint::from(5u32);
+        let mut seen = [0usize; 5];
+        for _ in 0..2000 {
+            let x = random_below(&bound);
+            assert!(x >= BigUint::one() && x < bound);
+            seen[x.to_u32_digits()[0] as usize] += 1;
+        }
+        assert_eq!(seen[0], 0);
+        assert!(seen[1..].iter().all(|&n| n > 350), "skewed: {:?}", seen);
+    }
+}
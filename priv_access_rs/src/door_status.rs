@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use futures::StreamExt;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+
+use crate::tenant::TenantState;
+
+// Redis channel every instance publishes to and listens on
+const REDIS_CHANNEL: &str = "privaccess:door_status";
+
+// Real-time door status signaling, keyed by door_channel()
+pub static DOOR_STATUS_TX: Lazy<broadcast::Sender<(String, String)>> = Lazy::new(|| {
+    let (tx, _) = broadcast::channel(100);
+    tx
+});
+
+/// What a door display can ask for over its WebSocket, e.g. `{"cmd":"manual_lock"}`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum DoorCommand {
+    /// A guard at the door forces it locked
+    ManualLock,
+}
+
+// Commands from door displays, keyed by door_channel(); the actuator carries them out
+pub static DOOR_COMMAND_TX: Lazy<broadcast::Sender<(String, DoorCommand)>> = Lazy::new(|| {
+    let (tx, _) = broadcast::channel(100);
+    tx
+});
+
+// Identifies this process to other replicas. Lets the bridge drop events this process published
+// itself (they were already delivered locally).
+pub static INSTANCE_ID: Lazy<String> = Lazy::new(|| {
+    use rand::Rng;
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(12)
+        .map(char::from)
+        .collect()
+});
+
+// When each door channel last unlocked (unix secs), so a display that connects right after
+// an unlock still learns about it
+static LAST_UNLOCK: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// How long after an unlock a newly connected display is still told about it. Kept shorter
+// than the display's 8s reset so it doesn't replay its own unlock after reloading.
+const UNLOCK_REPLAY_SECS: u64 = 5;
+
+// Pending auto-relock per door channel: the unlock generation it belongs to and its task
+static RELOCK_TIMERS: Lazy<Mutex<HashMap<String, (u64, tokio::task::AbortHandle)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static RELOCK_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// Flipped once the server starts shutting down, so display streams end instead of holding it open
+static SHUTTING_DOWN: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+
+// Set once the Redis bridge is started; None means single-instance mode
+static REDIS: OnceCell<redis::Client> = OnceCell::new();
+
+#[derive(Serialize, Deserialize)]
+struct StatusEvent {
+    instance: String,
+    channel: String,
+    status: String,
+}
+
+// Door ids are only unique within a campus, so status events carry the tenant too
+pub fn door_channel(tenant: &TenantState, door_id: &str) -> String {
+    format!("{}/{}", tenant.id, door_id)
+}
+
+/// Push a status change to every SSE and WebSocket subscriber of this door, on this instance and,
+/// when Redis is configured, on every other instance.
+pub fn notify(tenant: &TenantState, door_id: &str, status: &str) {
+    notify_channel(door_channel(tenant, door_id), status);
+}
+
+/// Send `status` to every door display connected to this instance, e.g. "ip_changed" when this
+/// machine's LAN address moves. Not relayed through Redis, since other instances have their own.
+pub fn notify_all_local(status: &str) {
+    for tenant in crate::tenant::tenant_ids().iter().filter_map(|id| crate::tenant::get_tenant(id)) {
+        let doors: Vec<String> = tenant.doors.read().unwrap().keys().cloned().collect();
+        for door_id in doors {
+            deliver(door_channel(&tenant, &door_id), status.to_string());
+        }
+    }
+}
+
+/// `notify` for a channel name that already carries its tenant.
+pub fn notify_channel(channel: String, status: &str) {
+    deliver(channel.clone(), status.to_string());
+
+    if let Some(client) = REDIS.get() {
+        let client = client.clone();
+        let event = StatusEvent { instance: INSTANCE_ID.clone(), channel, status: status.to_string() };
+        tokio::spawn(async move {
+            if let Err(e) = publish(&client, &event).await {
+                tracing::warn!(error = %e, "Failed to publish door status to Redis");
+            }
+        });
+    }
+}
+
+// Hand an event to local subscribers. Unlocks are remembered even if nobody is listening.
+// Every instance sees every unlock, so each runs its own relock timer and delivers the
+// "locked" locally; the actuator leader's timer is the one that reaches the hardware.
+fn deliver(channel: String, status: String) {
+    let unlocked = status == "unlocked";
+    if unlocked {
+        LAST_UNLOCK.lock().unwrap().insert(channel.clone(), now_secs());
+        schedule_relock(&channel);
+    } else if status == "locked" {
+        LAST_UNLOCK.lock().unwrap().remove(&channel);
+        if let Some((_, timer)) = RELOCK_TIMERS.lock().unwrap().remove(&channel) {
+            timer.abort();
+        }
+    }
+    if DOOR_STATUS_TX.send((channel.clone(), status.clone())).is_err() {
+        let note = if unlocked { ", kept as latest status" } else { "" };
+        tracing::debug!(door_id = %channel, %status, "No display listening{}", note);
+    }
+}
+
+// (Re)start the door's relock timer. A timer that was already pending is cancelled, so a
+// second unlock during the window extends it instead of locking early.
+fn schedule_relock(channel: &str) {
+    let delay = crate::config::CONFIG.relock_after_secs;
+    if delay == 0 {
+        return;
+    }
+    let generation = RELOCK_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let mut timers = RELOCK_TIMERS.lock().unwrap();
+    let task_channel = channel.to_string();
+    let task = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+        // A later unlock may have replaced this timer just as it fired
+        let current = {
+            let mut timers = RELOCK_TIMERS.lock().unwrap();
+            let current = timers.get(&task_channel).is_some_and(|(g, _)| *g == generation);
+            if current {
+                timers.remove(&task_channel);
+            }
+            current
+        };
+        if current {
+            tracing::info!(door_id = %task_channel, after_secs = delay, "Relocking");
+            deliver(task_channel, "locked".to_string());
+        }
+    });
+    if let Some((_, previous)) = timers.insert(channel.to_string(), (generation, task.abort_handle())) {
+        previous.abort();
+    }
+}
+
+/// Tell every SSE and WebSocket display stream to finish.
+pub fn begin_shutdown() {
+    SHUTTING_DOWN.send_replace(true);
+}
+
+/// Resolves once `begin_shutdown` has been called.
+pub async fn shutdown_started() {
+    let mut rx = SHUTTING_DOWN.subscribe();
+    let _ = rx.wait_for(|down| *down).await;
+}
+
+/// True if the door unlocked within the replay window.
+pub fn recent_unlock(channel: &str) -> bool {
+    LAST_UNLOCK.lock().unwrap()
+        .get(channel)
+        .is_some_and(|at| now_secs().saturating_sub(*at) <= UNLOCK_REPLAY_SECS)
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+async fn publish(client: &redis::Client, event: &StatusEvent) -> redis::RedisResult<()> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    let payload = serde_json::to_string(event).expect("status event serializes");
+    redis::AsyncCommands::publish::<_, _, ()>(&mut conn, REDIS_CHANNEL, payload).await
+}
+
+/// Mirror door status events through Redis pub/sub so displays attached to any instance
+/// see unlocks processed by any other. Reconnects in the background if Redis goes away.
+pub fn start_redis_bridge(url: &str) -> redis::RedisResult<()> {
+    let client = redis::Client::open(url)?;
+    let _ = REDIS.set(client.clone());
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = forward_remote_events(&client).await {
+                tracing::warn!(error = %e, "Door status bridge to Redis lost, retrying in 5s");
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+    Ok(())
+}
+
+async fn forward_remote_events(client: &redis::Client) -> redis::RedisResult<()> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(REDIS_CHANNEL).await?;
+    tracing::info!(instance = %*INSTANCE_ID, "Door status bridge to Redis connected");
+
+    let mut messages = pubsub.on_message();
+    while let Some(msg) = messages.next().await {
+        let payload: String = msg.get_payload()?;
+        match serde_json::from_str::<StatusEvent>(&payload) {
+            Ok(event) if event.instance != *INSTANCE_ID => deliver(event.channel, event.status),
+            Ok(_) => {}
+            Err(_) => tracing::warn!("Ignoring malformed door status event from Redis"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::redis_url;
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at PRIVACCESS_TEST_REDIS_URL"]
+    async fn status_events_cross_instances_through_redis() {
+        let url = redis_url();
+        // Another instance, as far as Redis can tell: its own connections and instance id
+        let other = redis::Client::open(url.as_str()).unwrap();
+        let mut pubsub = other.get_async_pubsub().await.unwrap();
+        pubsub.subscribe(REDIS_CHANNEL).await.unwrap();
+        start_redis_bridge(&url).unwrap();
+        let mut displays = DOOR_STATUS_TX.subscribe();
+        let wait = std::time::Duration::from_secs(5);
+
+        // What happens here is published for the other instance
+        notify_channel("redis-test/here".to_string(), "connected");
+        let msg = tokio::time::timeout(wait, pubsub.on_message().next()).await.unwrap().unwrap();
+        let event: StatusEvent = serde_json::from_str(&msg.get_payload::<String>().unwrap()).unwrap();
+        assert_eq!(event.instance, *INSTANCE_ID);
+        assert_eq!((event.channel.as_str(), event.status.as_str()), ("redis-test/here", "connected"));
+
+        // What happens there reaches displays attached here. The bridge subscribes in the
+        // background, so keep publishing until it's listening.
+        let theirs = StatusEvent { instance: "other-instance".to_string(), channel: "redis-test/there".to_string(), status: "connected".to_string() };
+        let received = tokio::time::timeout(wait, async {
+            loop {
+                publish(&other, &theirs).await.unwrap();
+                let next = tokio::time::timeout(std::time::Duration::from_millis(200), displays.recv()).await;
+                if let Ok(Ok((channel, status))) = next {
+                    if channel == "redis-test/there" {
+                        return status;
+                    }
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(received, "connected");
+    }
+}
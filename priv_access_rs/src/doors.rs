@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::WeeklySchedule;
+use crate::tenant::DEFAULT_TENANT;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Door {
+    pub name: String,
+    pub secret_qr: String,
+    pub geohash_prefix: String,
+    pub qr_url: Option<String>,
+    pub floor: i32,
+    // Where the door is (centre of its geohash cell) and how close a GPS fix must be. 0 = no distance check.
+    pub lat: f64,
+    pub lon: f64,
+    pub radius_meters: u32,
+    // BLE beacon mounted at the door, if any, and the weakest signal (dBm) it may be heard at
+    pub beacon_id: Option<String>,
+    pub beacon_min_rssi: i32,
+    // Left out of the public door list; only admins see it there
+    pub private: bool,
+    // When it may be unlocked; None = any time
+    pub schedule: Option<WeeklySchedule>,
+}
+
+/// Why beacon data sent with an unlock doesn't place the client at the door.
+#[derive(Debug, PartialEq)]
+pub enum BeaconError {
+    WrongBeacon,
+    TooWeak { rssi: i32, min_rssi: i32 },
+}
+
+impl BeaconError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BeaconError::WrongBeacon => "beacon_mismatch",
+            BeaconError::TooWeak { .. } => "beacon_too_weak",
+        }
+    }
+}
+
+impl std::fmt::Display for BeaconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BeaconError::WrongBeacon => write!(f, "Beacon heard is not this door's"),
+            BeaconError::TooWeak { rssi, min_rssi } => write!(f, "Beacon signal too weak ({} dBm, need {} dBm)", rssi, min_rssi),
+        }
+    }
+}
+
+impl Door {
+    pub fn new(name: &str, secret_qr: &str, geohash_prefix: &str, floor: i32) -> Result<Door, crate::geo::GeoError> {
+        let (lat, lon) = crate::geo::center(geohash_prefix)?;
+        Ok(Door {
+            name: name.to_string(),
+            secret_qr: secret_qr.to_string(),
+            geohash_prefix: geohash_prefix.to_string(),
+            qr_url: None,
+            floor,
+            lat,
+            lon,
+            radius_meters: crate::config::CONFIG.proximity_radius_m.min(u32::MAX as u64) as u32,
+            beacon_id: None,
+            beacon_min_rssi: crate::config::CONFIG.beacon_min_rssi,
+            private: false,
+            schedule: None,
+        })
+    }
+
+    pub fn with_radius(mut self, radius_meters: u32) -> Door {
+        self.radius_meters = radius_meters;
+        self
+    }
+
+    pub fn with_beacon(mut self, beacon_id: &str, min_rssi: i32) -> Door {
+        self.beacon_id = Some(beacon_id.to_string());
+        self.beacon_min_rssi = min_rssi;
+        self
+    }
+
+    pub fn with_schedule(mut self, schedule: WeeklySchedule) -> Door {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    /// Check a beacon reading from the client against this door's beacon. Ids are compared
+    /// case-insensitively, since scanners print UUIDs in either case.
+    pub fn check_beacon(&self, beacon_id: &str, rssi: i32) -> Result<(), BeaconError> {
+        if !self.beacon_id.as_deref().is_some_and(|id| id.eq_ignore_ascii_case(beacon_id.trim())) {
+            return Err(BeaconError::WrongBeacon);
+        }
+        if rssi < self.beacon_min_rssi {
+            return Err(BeaconError::TooWeak { rssi, min_rssi: self.beacon_min_rssi });
+        }
+        Ok(())
+    }
+
+    /// Metres from a client's GPS fix to the door.
+    pub fn distance_to(&self, lat: f64, lon: f64) -> Result<f64, crate::geo::GeoError> {
+        let user = crate::geo::checked_point(lat, lon)?;
+        Ok(crate::geo::distance_meters(user, (self.lat, self.lon)))
+    }
+}
+
+// Tiered halls are big enough that someone inside can be well away from the door
+const TIERED_RADIUS_M: u32 = 80;
+
+// Demo door set every campus starts with
+fn default_doors() -> HashMap<String, Door> {
+    let mut m = HashMap::new();
+    let default_geo = "t1q7hk9vj"; // shared base location for demo
+    let door = |name: &str, secret: &str, floor: i32| Door::new(name, secret, default_geo, floor).expect("demo geohash is valid");
+
+    // Floor 1
+    m.insert("room101".to_string(), door("Room 101", "s101", 1));
+    m.insert("tiered102".to_string(), door("Tiered 102", "s102", 1).with_radius(TIERED_RADIUS_M));
+    m.insert("lab103".to_string(), door("Lab 103", "s103", 1));
+    // Floor 2
+    m.insert("room201".to_string(), door("Room 201", "s201", 2));
+    m.insert("tiered202".to_string(), door("Tiered 202", "s202", 2).with_radius(TIERED_RADIUS_M));
+    m.insert("lab203".to_string(), door("Lab 203", "s203", 2));
+    // Floor 3
+    m.insert("room301".to_string(), door("Room 301", "s301", 3));
+    m.insert("tiered302".to_string(), door("Tiered 302", "s302", 3).with_radius(TIERED_RADIUS_M));
+    m.insert("lab303".to_string(), door("Lab 303", "s303", 3));
+    m
+}
+
+/// One `[[door]]` table in `doors*.toml`.
+#[derive(Serialize, Deserialize)]
+struct DoorEntry {
+    id: String,
+    name: String,
+    geohash_prefix: String,
+    secret_qr: String,
+    #[serde(default = "default_floor")]
+    floor: i32,
+    // Falls back to PRIVACCESS_PROXIMITY_RADIUS_M
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    radius_meters: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    beacon_id: Option<String>,
+    // Falls back to PRIVACCESS_BEACON_MIN_RSSI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    beacon_min_rssi: Option<i32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    private: bool,
+    // A sub-table, so it has to come after the plain keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schedule: Option<WeeklySchedule>,
+}
+
+fn default_floor() -> i32 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+struct DoorsFile {
+    #[serde(default)]
+    door: Vec<DoorEntry>,
+}
+
+// Same naming as the history files: the default campus gets the plain name
+pub fn doors_path(tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        "doors.toml".to_string()
+    } else {
+        format!("doors_{}.toml", tenant)
+    }
+}
+
+fn parse(content: &str) -> Result<HashMap<String, Door>, String> {
+    let file: DoorsFile = toml::from_str(content).map_err(|e| e.to_string())?;
+    let mut doors = HashMap::new();
+    for entry in file.door {
+        if entry.id.trim().is_empty() {
+            return Err(format!("door '{}' has an empty id", entry.name));
+        }
+        if entry.geohash_prefix.is_empty() {
+            return Err(format!("door '{}' has an empty geohash_prefix", entry.id));
+        }
+        let mut door = Door::new(&entry.name, &entry.secret_qr, &entry.geohash_prefix, entry.floor)
+            .map_err(|e| format!("door '{}': {}", entry.id, e))?;
+        if let Some(radius) = entry.radius_meters {
+            door = door.with_radius(radius);
+        }
+        match entry.beacon_id.as_deref().map(str::trim) {
+            Some("") => return Err(format!("door '{}' has an empty beacon_id", entry.id)),
+            Some(beacon_id) => {
+                door = door.with_beacon(beacon_id, entry.beacon_min_rssi.unwrap_or(crate::config::CONFIG.beacon_min_rssi));
+            }
+            None => {}
+        }
+        door.private = entry.private;
+        door.schedule = entry.schedule;
+        if doors.insert(entry.id.clone(), door).is_some() {
+            return Err(format!("door id '{}' is used more than once", entry.id));
+        }
+    }
+    Ok(doors)
+}
+
+/// Write a campus's doors back out, sorted by id so the file diffs cleanly.
+pub fn save(tenant: &str, doors: &HashMap<String, Door>) -> Result<(), String> {
+    let mut entries: Vec<DoorEntry> = doors.iter()
+        .map(|(id, door)| DoorEntry {
+            id: id.clone(),
+            name: door.name.clone(),
+            geohash_prefix: door.geohash_prefix.clone(),
+            secret_qr: door.secret_qr.clone(),
+            floor: door.floor,
+            radius_meters: Some(door.radius_meters),
+            beacon_id: door.beacon_id.clone(),
+            beacon_min_rssi: door.beacon_id.as_ref().map(|_| door.beacon_min_rssi),
+            private: door.private,
+            schedule: door.schedule.clone(),
+        })
+        .collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    let content = toml::to_string_pretty(&DoorsFile { door: entries }).map_err(|e| e.to_string())?;
+    fs::write(doors_path(tenant), content).map_err(|e| e.to_string())
+}
+
+/// Read a campus's doors from its TOML file. A campus without one starts from the demo
+/// doors, which are written out so there's a file to edit.
+pub fn load(tenant: &str) -> Result<HashMap<String, Door>, String> {
+    let path = doors_path(tenant);
+    match fs::read_to_string(&path) {
+        Ok(content) => parse(&content).map_err(|e| format!("{}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let doors = default_doors();
+            if let Err(e) = save(tenant, &doors) {
+                tracing::warn!(%path, error = %e, "Could not write doors file, demo doors stay in memory only");
+            }
+            Ok(doors)
+        }
+        Err(e) => Err(format!("{}: {}", path, e)),
+    }
+}
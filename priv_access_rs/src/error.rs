@@ -0,0 +1,154 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Stable, machine-readable error codes. Frontends switch on these; the messages are for people
+/// and may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    // Generic codes, one per status, for errors nothing more specific applies to
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    RateLimited,
+    Internal,
+    Unavailable,
+
+    DoorNotFound,
+    InvalidRole,
+    InvalidSection,
+    /// The student's section is assigned to another room
+    WrongRoom,
+    /// No room has been assigned to the student's section yet
+    NoRoomAssigned,
+    /// Another faculty member already holds the section in a different room
+    SectionConflict,
+    /// The client's location doesn't match the door: too far away or the wrong floor
+    LocationMismatch,
+    /// The coordinates or geohash sent aren't a valid location
+    InvalidLocation,
+    QrExpired,
+    QrInvalid,
+    QrReused,
+    /// A proof-carrying request came without its proof or public signals
+    MissingProof,
+    /// The proof isn't well-formed
+    MalformedProof,
+    /// The proof is well-formed but doesn't verify
+    InvalidProof,
+    /// The proof's timestamp is outside the accepted window
+    ProofExpired,
+    /// The challenge nonce is missing, used, expired or for another door
+    ChallengeInvalid,
+    /// The proof's public key has been revoked by an admin
+    KeyRevoked,
+    /// The door's schedule doesn't allow unlocking it right now
+    OutsideSchedule,
+    /// The guest pass is missing, forged, expired, used, or for another door
+    GuestPassInvalid,
+    /// The passkey registration or assertion is malformed or doesn't verify
+    PasskeyInvalid,
+    /// The server can't check this kind of proof right now
+    VerifierUnavailable,
+    /// Too many wrong PINs or passwords in a row; the account is locked for a while
+    AccountLocked,
+}
+
+impl ErrorCode {
+    /// Code for an error that only has a status to go on.
+    pub fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::Unavailable,
+            s if s.is_server_error() => ErrorCode::Internal,
+            _ => ErrorCode::BadRequest,
+        }
+    }
+}
+
+/// Error returned by API handlers. Renders as
+/// `{"status": "failed", "message": ..., "error": {"code": ..., "message": ...}}`; the top-level
+/// `status`/`message` pair is the shape older clients read.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: ErrorCode,
+    pub message: String,
+    /// Finer-grained detail some endpoints have always sent, e.g. `proof_expired`
+    pub reason: Option<&'static str>,
+    /// The request field at fault, for validation errors
+    pub field: Option<&'static str>,
+    /// Seconds to send as `Retry-After`, for errors that clear on their own
+    pub retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError { status, code: ErrorCode::for_status(status), message: message.into(), reason: None, field: None, retry_after: None }
+    }
+
+    /// An error with a specific code rather than the generic one for its status.
+    pub fn coded(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError { status, code, message: message.into(), reason: None, field: None, retry_after: None }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    pub fn with_reason(mut self, reason: &'static str) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn with_field(mut self, field: &'static str) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
+    }
+
+    /// The JSON body, for channels such as WebSockets that send errors without an HTTP status.
+    pub fn to_json(&self) -> Value {
+        let mut body = json!({
+            "status": "failed",
+            "message": self.message,
+            "error": { "code": self.code, "message": self.message },
+        });
+        if let Some(reason) = self.reason {
+            body["reason"] = json!(reason);
+        }
+        if let Some(field) = self.field {
+            body["error"]["field"] = json!(field);
+        }
+        body
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Json(self.to_json())).into_response();
+        if let Some(secs) = self.retry_after {
+            response.headers_mut().insert(axum::http::header::RETRY_AFTER, secs.into());
+        }
+        response
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+const FACULTY_PATH: &str = "faculty.json";
+
+/// A faculty login. Only the Argon2 hash of the PIN is kept.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Faculty {
+    pub id: String,
+    // Shown on assignments; defaults to the id
+    #[serde(default)]
+    pub name: String,
+    pin_hash: String,
+}
+
+impl Faculty {
+    pub fn pin_hash(&self) -> &str {
+        &self.pin_hash
+    }
+
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() { &self.id } else { &self.name }
+    }
+
+    /// Whether a section assignment recorded under `name` is this member's. Assignments record
+    /// who made them by name, which is the id unless one was given.
+    pub fn assigned(&self, name: &str) -> bool {
+        name.eq_ignore_ascii_case(&self.id) || name.eq_ignore_ascii_case(self.display_name())
+    }
+}
+
+/// Why a faculty member couldn't be added.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FacultyError {
+    InvalidId,
+    /// PINs are 4 to 12 digits
+    InvalidPin,
+    DuplicateId(String),
+    Hash(String),
+}
+
+impl std::fmt::Display for FacultyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FacultyError::InvalidId => write!(f, "Faculty ID must be 1-32 letters, digits, '-' or '_'"),
+            FacultyError::InvalidPin => write!(f, "PIN must be 4 to 12 digits"),
+            FacultyError::DuplicateId(id) => write!(f, "Faculty ID '{}' already exists", id),
+            FacultyError::Hash(e) => write!(f, "Could not hash PIN: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FacultyError {}
+
+// Demo PINs: Fac1 1234, Fac2 5678, Fac3 9876, Fac4 5432
+fn default_faculty() -> Vec<Faculty> {
+    [
+        ("Fac1", "$argon2id$v=19$m=19456,t=2,p=1$Agcg97dUuoWPbR/d30On1g$DfSaLhCNZ//h6KV1ywvAoHGuM0jJGuEzN2/4/Ev6cnM"),
+        ("Fac2", "$argon2id$v=19$m=19456,t=2,p=1$skVCMaGEz+ZERnWIQqXZwA$sgRNn4Qx32AxtLpFLVZ3sSR85T7w8cQ8cQVL+t+gcsc"),
+        ("Fac3", "$argon2id$v=19$m=19456,t=2,p=1$C/VKmzPdE1W7wZ31jNmtkw$LJYoiyDkjF/u/Ee1ggTK4Wol0BOrhLSVfPePUekyDKU"),
+        ("Fac4", "$argon2id$v=19$m=19456,t=2,p=1$EO6ETKjUuFQq5Qou0NpiHw$dPbnYpD6Lpv0hoHhgKBAJajNpykeMF7zTpptwyEvdec"),
+    ]
+    .into_iter()
+    .map(|(id, pin_hash)| Faculty { id: id.to_string(), name: String::new(), pin_hash: pin_hash.to_string() })
+    .collect()
+}
+
+// Until the first change is saved, the demo accounts are used
+static FACULTY: Lazy<RwLock<Vec<Faculty>>> = Lazy::new(|| {
+    let faculty = fs::read_to_string(FACULTY_PATH)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_else(default_faculty);
+    RwLock::new(faculty)
+});
+
+fn save(faculty: &Vec<Faculty>) {
+    if let Ok(content) = serde_json::to_string_pretty(faculty) {
+        if let Err(e) = fs::write(FACULTY_PATH, content) {
+            tracing::warn!(path = FACULTY_PATH, error = %e, "Could not write faculty file");
+        }
+    }
+}
+
+pub(crate) fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 32 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Look up a faculty member; ids are case-insensitive.
+pub fn find(id: &str) -> Option<Faculty> {
+    FACULTY.read().unwrap().iter().find(|f| f.id.eq_ignore_ascii_case(id)).cloned()
+}
+
+pub fn list() -> Vec<Faculty> {
+    FACULTY.read().unwrap().clone()
+}
+
+/// Onboard a faculty member, hashing their PIN before it's stored.
+pub fn add(id: &str, name: &str, pin: &str) -> Result<Faculty, FacultyError> {
+    let (id, pin) = (id.trim(), pin.trim());
+    if !valid_id(id) {
+        return Err(FacultyError::InvalidId);
+    }
+    if !(4..=12).contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err(FacultyError::InvalidPin);
+    }
+    // Hash before taking the lock; Argon2 is deliberately slow
+    let pin_hash = crate::rbac::hash_password(pin).map_err(|e| FacultyError::Hash(e.to_string()))?;
+    let record = Faculty { id: id.to_string(), name: name.trim().to_string(), pin_hash };
+
+    let mut faculty = FACULTY.write().unwrap();
+    if faculty.iter().any(|f| f.id.eq_ignore_ascii_case(id)) {
+        return Err(FacultyError::DuplicateId(id.to_string()));
+    }
+    faculty.push(record.clone());
+    save(&faculty);
+    Ok(record)
+}
+
+/// Remove a faculty member. Returns the removed record, or None if no such id exists.
+pub fn remove(id: &str) -> Option<Faculty> {
+    let mut faculty = FACULTY.write().unwrap();
+    let index = faculty.iter().position(|f| f.id.eq_ignore_ascii_case(id))?;
+    let removed = faculty.remove(index);
+    save(&faculty);
+    Some(removed)
+}
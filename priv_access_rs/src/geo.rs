@@ -0,0 +1,141 @@
+// Geohash and coordinate handling for the proximity checks. Client location data is untrusted, so nothing
+// here panics: every failure is a GeoError that the verify path turns into a clean denial.
+
+const BASE32: &str = "0123456789bcdefghjkmnpqrstuvwxyz";
+
+// Precision 12 is already finer than a phone's GPS fix
+pub const MAX_GEOHASH_LEN: usize = 12;
+
+// Mean Earth radius (IUGG)
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Why a client-supplied geohash was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoError {
+    Empty,
+    TooLong { max: usize },
+    InvalidChar(char),
+    /// Latitude/longitude that are NaN, infinite or off the globe
+    InvalidCoordinates,
+}
+
+impl GeoError {
+    /// Stable identifier for API clients and logs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GeoError::Empty => "geohash_empty",
+            GeoError::TooLong { .. } => "geohash_too_long",
+            GeoError::InvalidChar(_) => "geohash_invalid_char",
+            GeoError::InvalidCoordinates => "invalid_coordinates",
+        }
+    }
+}
+
+impl std::fmt::Display for GeoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeoError::Empty => write!(f, "Geohash is empty"),
+            GeoError::TooLong { max } => write!(f, "Geohash is longer than {} characters", max),
+            GeoError::InvalidChar(c) => write!(f, "Geohash contains invalid character {:?}", c),
+            GeoError::InvalidCoordinates => write!(f, "Latitude/longitude out of range"),
+        }
+    }
+}
+
+/// Check `geohash` only uses the geohash base32 alphabet (either case) and is a sensible length.
+pub fn validate(geohash: &str) -> Result<&str, GeoError> {
+    if geohash.is_empty() {
+        return Err(GeoError::Empty);
+    }
+    if let Some(c) = geohash.chars().find(|c| !BASE32.contains(c.to_ascii_lowercase())) {
+        return Err(GeoError::InvalidChar(c));
+    }
+    if geohash.len() > MAX_GEOHASH_LEN {
+        return Err(GeoError::TooLong { max: MAX_GEOHASH_LEN });
+    }
+    Ok(geohash)
+}
+
+/// The first `len` characters of a valid geohash (all of it if shorter).
+pub fn prefix(geohash: &str, len: usize) -> Result<&str, GeoError> {
+    // Validated geohashes are ASCII, so byte slicing can't split a character
+    let geohash = validate(geohash)?;
+    Ok(&geohash[..geohash.len().min(len)])
+}
+
+/// Check a client-supplied position, folding longitude 180 onto -180 (the same meridian).
+pub fn checked_point(lat: f64, lon: f64) -> Result<(f64, f64), GeoError> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        // Also catches NaN, which fails every range check
+        return Err(GeoError::InvalidCoordinates);
+    }
+    Ok((lat, if lon == 180.0 { -180.0 } else { lon }))
+}
+
+/// Geohash of a position. `precision` is clamped to 1..=12; 9 characters is a ~5m cell.
+pub fn encode(lat: f64, lon: f64, precision: usize) -> Result<String, GeoError> {
+    let (lat, lon) = checked_point(lat, lon)?;
+    // The encoder's fixed-point trick only works strictly below the north pole
+    let lat = lat.min(f64::from_bits(90f64.to_bits() - 1));
+    geohash::encode(geohash::Coord { x: lon, y: lat }, precision.clamp(1, MAX_GEOHASH_LEN))
+        .map_err(|_| GeoError::InvalidCoordinates)
+}
+
+/// Bounding box of a geohash cell as (min_lat, min_lon, max_lat, max_lon).
+pub fn decode(geohash: &str) -> Result<(f64, f64, f64, f64), GeoError> {
+    let geohash = validate(geohash)?.to_ascii_lowercase();
+    let bbox = geohash::decode_bbox(&geohash).map_err(|_| GeoError::Empty)?;
+    Ok((bbox.min().y, bbox.min().x, bbox.max().y, bbox.max().x))
+}
+
+/// Centre of a geohash cell as (lat, lon).
+pub fn center(geohash: &str) -> Result<(f64, f64), GeoError> {
+    let (min_lat, min_lon, max_lat, max_lon) = decode(geohash)?;
+    Ok(((min_lat + max_lat) / 2.0, (min_lon + max_lon) / 2.0))
+}
+
+/// Great-circle distance between two (lat, lon) points. Haversine works on the raw longitude
+/// difference, so pairs either side of the antimeridian come out as close as they are.
+pub fn distance_meters(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_geohashes_are_errors_not_panics() {
+        // "a", "i", "l" and "o" aren't in the alphabet; "é" is multi-byte, so byte slicing it would panic
+        let cases = [
+            ("", GeoError::Empty),
+            ("tdr1a", GeoError::InvalidChar('a')),
+            ("tdr1 wxyz", GeoError::InvalidChar(' ')),
+            ("é", GeoError::InvalidChar('é')),
+            ("tdr1\u{0}", GeoError::InvalidChar('\u{0}')),
+            ("0123456789bcd", GeoError::TooLong { max: MAX_GEOHASH_LEN }),
+        ];
+        for (geohash, expected) in cases {
+            assert_eq!(validate(geohash), Err(expected.clone()), "{:?}", geohash);
+            assert_eq!(prefix(geohash, 3), Err(expected.clone()), "{:?}", geohash);
+            assert_eq!(decode(geohash), Err(expected.clone()), "{:?}", geohash);
+            assert_eq!(center(geohash), Err(expected), "{:?}", geohash);
+        }
+        // Upper case is the same cell
+        assert_eq!(center("TDR1W"), center("tdr1w"));
+    }
+
+    #[test]
+    fn impossible_positions_are_refused() {
+        for (lat, lon) in [(90.1, 0.0), (0.0, -180.5), (f64::NAN, 0.0), (0.0, f64::INFINITY)] {
+            assert_eq!(checked_point(lat, lon), Err(GeoError::InvalidCoordinates));
+            assert_eq!(encode(lat, lon, 9), Err(GeoError::InvalidCoordinates));
+        }
+        assert_eq!(checked_point(10.0, 180.0), Ok((10.0, -180.0)));
+        assert_eq!(encode(90.0, 0.0, 1).map(|g| g.len()), Ok(1));
+    }
+}
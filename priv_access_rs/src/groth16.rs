@@ -0,0 +1,198 @@
+//! Native Groth16 verification (BN254) for the circom geohash circuit, in place of shelling out
+//! to `zkp_circom/verify_proof.js`. The verification key, proofs and public signals are all in
+//! snarkjs's JSON format, so the browser prover works unchanged.
+
+use std::path::Path;
+
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::PrimeField;
+use ark_groth16::{prepare_verifying_key, Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
+use num_bigint::BigUint;
+use num_traits::Num;
+use serde::Deserialize;
+
+/// Why a key or proof couldn't be used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Groth16Error {
+    /// There's no verification key file at this path
+    Missing(String),
+    /// The verification key file couldn't be read
+    Io(String),
+    /// Not the JSON shape snarkjs produces
+    Json(String),
+    /// A key for another proof system or curve
+    Unsupported(String),
+    /// A coordinate or signal isn't a canonical decimal field element
+    Malformed { field: &'static str },
+    /// A point that isn't on the curve or in the prime-order subgroup
+    InvalidPoint { field: &'static str },
+    /// The proof carries a different number of public signals than the circuit has
+    WrongSignalCount { expected: usize, got: usize },
+}
+
+impl Groth16Error {
+    /// Stable identifier for API clients and logs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Groth16Error::Missing(_) => "vkey_missing",
+            Groth16Error::Io(_) => "vkey_unreadable",
+            Groth16Error::Json(_) => "groth16_malformed",
+            Groth16Error::Unsupported(_) => "vkey_unsupported",
+            Groth16Error::Malformed { .. } => "groth16_malformed",
+            Groth16Error::InvalidPoint { .. } => "groth16_invalid_point",
+            Groth16Error::WrongSignalCount { .. } => "groth16_signal_count",
+        }
+    }
+}
+
+impl std::fmt::Display for Groth16Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Groth16Error::Missing(path) => write!(f, "Verification key {} does not exist", path),
+            Groth16Error::Io(e) => write!(f, "Could not read verification key: {}", e),
+            Groth16Error::Json(e) => write!(f, "Malformed Groth16 JSON: {}", e),
+            Groth16Error::Unsupported(what) => write!(f, "Unsupported verification key: {}", what),
+            Groth16Error::Malformed { field } => write!(f, "'{}' is not a valid field element", field),
+            Groth16Error::InvalidPoint { field } => write!(f, "'{}' is not a valid curve point", field),
+            Groth16Error::WrongSignalCount { expected, got } => write!(f, "Expected {} public signals, got {}", expected, got),
+        }
+    }
+}
+
+impl std::error::Error for Groth16Error {}
+
+#[derive(Deserialize)]
+struct SnarkjsKey {
+    protocol: String,
+    curve: String,
+    vk_alpha_1: Vec<String>,
+    vk_beta_2: Vec<Vec<String>>,
+    vk_gamma_2: Vec<Vec<String>>,
+    vk_delta_2: Vec<Vec<String>>,
+    #[serde(rename = "IC")]
+    ic: Vec<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct SnarkjsProof {
+    pi_a: Vec<String>,
+    pi_b: Vec<Vec<String>>,
+    pi_c: Vec<String>,
+}
+
+// Values at or above the modulus are refused rather than reduced, so every element has one encoding
+fn parse_field<F: PrimeField>(field: &'static str, value: &str) -> Result<F, Groth16Error> {
+    let n = BigUint::from_str_radix(value, 10).map_err(|_| Groth16Error::Malformed { field })?;
+    let modulus: BigUint = F::MODULUS.into();
+    if n >= modulus {
+        return Err(Groth16Error::Malformed { field });
+    }
+    Ok(F::from(n))
+}
+
+// snarkjs writes projective coordinates with z = 1, or z = 0 for the point at infinity
+fn parse_g1(field: &'static str, coords: &[String]) -> Result<G1Affine, Groth16Error> {
+    let (x, y, z) = match coords {
+        [x, y, z] => (x, y, z.as_str()),
+        [x, y] => (x, y, "1"),
+        _ => return Err(Groth16Error::Malformed { field }),
+    };
+    let point = match z {
+        "0" => G1Affine::zero(),
+        "1" => G1Affine::new_unchecked(parse_field::<Fq>(field, x)?, parse_field::<Fq>(field, y)?),
+        _ => return Err(Groth16Error::Malformed { field }),
+    };
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(Groth16Error::InvalidPoint { field });
+    }
+    Ok(point)
+}
+
+fn parse_fq2(field: &'static str, pair: &[String]) -> Result<Fq2, Groth16Error> {
+    match pair {
+        [c0, c1] => Ok(Fq2::new(parse_field(field, c0)?, parse_field(field, c1)?)),
+        _ => Err(Groth16Error::Malformed { field }),
+    }
+}
+
+fn parse_g2(field: &'static str, coords: &[Vec<String>]) -> Result<G2Affine, Groth16Error> {
+    let (x, y, z) = match coords {
+        [x, y, z] => (x, y, parse_fq2(field, z)?),
+        [x, y] => (x, y, Fq2::from(1u64)),
+        _ => return Err(Groth16Error::Malformed { field }),
+    };
+    let point = if z == Fq2::from(0u64) {
+        G2Affine::zero()
+    } else if z == Fq2::from(1u64) {
+        G2Affine::new_unchecked(parse_fq2(field, x)?, parse_fq2(field, y)?)
+    } else {
+        return Err(Groth16Error::Malformed { field });
+    };
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(Groth16Error::InvalidPoint { field });
+    }
+    Ok(point)
+}
+
+/// A verification key prepared once and shared by every request.
+pub struct Groth16Verifier {
+    pvk: PreparedVerifyingKey<Bn254>,
+}
+
+impl Groth16Verifier {
+    /// Parse a snarkjs `verification_key.json`.
+    pub fn from_json(json: &str) -> Result<Self, Groth16Error> {
+        let key: SnarkjsKey = serde_json::from_str(json).map_err(|e| Groth16Error::Json(e.to_string()))?;
+        if key.protocol != "groth16" {
+            return Err(Groth16Error::Unsupported(format!("protocol '{}'", key.protocol)));
+        }
+        if key.curve != "bn128" && key.curve != "bn254" {
+            return Err(Groth16Error::Unsupported(format!("curve '{}'", key.curve)));
+        }
+        if key.ic.is_empty() {
+            return Err(Groth16Error::Malformed { field: "IC" });
+        }
+        let vk = VerifyingKey::<Bn254> {
+            alpha_g1: parse_g1("vk_alpha_1", &key.vk_alpha_1)?,
+            beta_g2: parse_g2("vk_beta_2", &key.vk_beta_2)?,
+            gamma_g2: parse_g2("vk_gamma_2", &key.vk_gamma_2)?,
+            delta_g2: parse_g2("vk_delta_2", &key.vk_delta_2)?,
+            gamma_abc_g1: key.ic.iter().map(|p| parse_g1("IC", p)).collect::<Result<_, _>>()?,
+        };
+        Ok(Groth16Verifier { pvk: prepare_verifying_key(&vk) })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Groth16Error> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Groth16Error::Missing(path.display().to_string()),
+            _ => Groth16Error::Io(e.to_string()),
+        })?;
+        Self::from_json(&json)
+    }
+
+    /// How many public signals a proof for this circuit carries.
+    pub fn public_signal_count(&self) -> usize {
+        self.pvk.vk.gamma_abc_g1.len() - 1
+    }
+
+    /// Check a snarkjs proof against its public signals (decimal strings). Ok(false) is a
+    /// well-formed proof that doesn't verify; Err is input that isn't a proof at all.
+    pub fn verify(&self, proof: &serde_json::Value, public_signals: &serde_json::Value) -> Result<bool, Groth16Error> {
+        let proof: SnarkjsProof = serde_json::from_value(proof.clone()).map_err(|e| Groth16Error::Json(e.to_string()))?;
+        let signals: Vec<String> = serde_json::from_value(public_signals.clone()).map_err(|e| Groth16Error::Json(e.to_string()))?;
+        if signals.len() != self.public_signal_count() {
+            return Err(Groth16Error::WrongSignalCount { expected: self.public_signal_count(), got: signals.len() });
+        }
+
+        let proof = Proof::<Bn254> {
+            a: parse_g1("pi_a", &proof.pi_a)?,
+            b: parse_g2("pi_b", &proof.pi_b)?,
+            c: parse_g1("pi_c", &proof.pi_c)?,
+        };
+        let inputs: Vec<Fr> = signals.iter().map(|s| parse_field("public_signals", s)).collect::<Result<_, _>>()?;
+        // The signal count was checked above, which is the only way verification can error
+        Ok(Groth16::<Bn254>::verify_proof(&self.pvk, &proof, &inputs).unwrap_or(false))
+    }
+}
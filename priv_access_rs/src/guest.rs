@@ -0,0 +1,132 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+
+// Longest pass an admin can mint; visitors get a day at most
+pub const MAX_GUEST_MINUTES: u64 = 24 * 60;
+
+// HMAC key for guest passes. Set PRIVACCESS_GUEST_SECRET to keep passes valid across restarts,
+// otherwise a fresh key is generated on every boot.
+static GUEST_SECRET: Lazy<Vec<u8>> = Lazy::new(|| {
+    match std::env::var("PRIVACCESS_GUEST_SECRET") {
+        Ok(s) if !s.is_empty() => s.into_bytes(),
+        _ => {
+            use rand::RngCore;
+            let mut key = vec![0u8; 32];
+            rand::rngs::OsRng.fill_bytes(&mut key);
+            key
+        }
+    }
+});
+
+/// What a guest pass grants: one unlock of one door before `expires_at` (unix secs).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuestPass {
+    pub door_id: String,
+    pub expires_at: u64,
+    /// Random per pass; claimed when the pass is used so it can't be used twice
+    pub nonce: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GuestPassError {
+    Missing,
+    Malformed,
+    BadSignature,
+    Expired,
+    WrongDoor,
+}
+
+impl GuestPassError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            GuestPassError::Missing => "guest_pass_missing",
+            GuestPassError::Malformed => "guest_pass_malformed",
+            GuestPassError::BadSignature => "guest_pass_bad_signature",
+            GuestPassError::Expired => "guest_pass_expired",
+            GuestPassError::WrongDoor => "guest_pass_wrong_door",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            GuestPassError::Missing => "No guest pass sent",
+            GuestPassError::Malformed | GuestPassError::BadSignature => "Invalid guest pass",
+            GuestPassError::Expired => "Guest pass expired",
+            GuestPassError::WrongDoor => "Guest pass is for another door",
+        }
+    }
+}
+
+// Passes are bound to their campus too, so one minted on one tenant is useless on another
+fn mac(tenant: &str, door_id: &str, expires_at: u64, nonce: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&GUEST_SECRET).expect("HMAC takes any key length");
+    mac.update(format!("{}\n{}\n{}\n{}", tenant, door_id, expires_at, nonce).as_bytes());
+    mac
+}
+
+/// Mint a signed pass for `door_id`, as `base64url(door).expiry.nonce.hex(hmac)`.
+pub fn issue(tenant: &str, door_id: &str, expires_at: u64) -> (String, GuestPass) {
+    let mut bytes = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut bytes);
+    let nonce = hex::encode(bytes);
+    let signature = hex::encode(mac(tenant, door_id, expires_at, &nonce).finalize().into_bytes());
+    let token = format!("{}.{}.{}.{}", URL_SAFE_NO_PAD.encode(door_id), expires_at, nonce, signature);
+    (token, GuestPass { door_id: door_id.to_string(), expires_at, nonce })
+}
+
+/// Check a pass's signature, expiry and door. Single use is up to the caller, who claims
+/// `nonce` in storage once everything else has passed.
+pub fn validate(token: &str, tenant: &str, door_id: &str, now: u64) -> Result<GuestPass, GuestPassError> {
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(GuestPassError::Missing);
+    }
+    let parts: Vec<&str> = token.split('.').collect();
+    let [door, expires_at, nonce, signature] = parts[..] else {
+        return Err(GuestPassError::Malformed);
+    };
+    let door = URL_SAFE_NO_PAD.decode(door).ok()
+        .and_then(|d| String::from_utf8(d).ok())
+        .ok_or(GuestPassError::Malformed)?;
+    let expires_at: u64 = expires_at.parse().map_err(|_| GuestPassError::Malformed)?;
+    let signature = hex::decode(signature).map_err(|_| GuestPassError::Malformed)?;
+
+    mac(tenant, &door, expires_at, nonce)
+        .verify_slice(&signature)
+        .map_err(|_| GuestPassError::BadSignature)?;
+    if now >= expires_at {
+        return Err(GuestPassError::Expired);
+    }
+    if door != door_id {
+        return Err(GuestPassError::WrongDoor);
+    }
+    Ok(GuestPass { door_id: door, expires_at, nonce: nonce.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issued_pass_validates_until_expiry() {
+        let (token, pass) = issue("default", "LAB-1.2", 1_000);
+        assert_eq!(validate(&token, "default", "LAB-1.2", 999), Ok(pass));
+        assert_eq!(validate(&token, "default", "LAB-1.2", 1_000), Err(GuestPassError::Expired));
+        assert_eq!(validate(&token, "default", "LAB-2", 999), Err(GuestPassError::WrongDoor));
+    }
+
+    #[test]
+    fn tampered_or_foreign_pass_is_rejected() {
+        let (token, _) = issue("default", "LAB-1", 1_000);
+        assert_eq!(validate(&token, "north", "LAB-1", 0), Err(GuestPassError::BadSignature));
+
+        let extended = token.replacen(".1000.", ".9000.", 1);
+        assert_eq!(validate(&extended, "default", "LAB-1", 0), Err(GuestPassError::BadSignature));
+
+        assert_eq!(validate("", "default", "LAB-1", 0), Err(GuestPassError::Missing));
+        assert_eq!(validate("a.b.c", "default", "LAB-1", 0), Err(GuestPassError::Malformed));
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use k256::elliptic_curve::Field;
+use once_cell::sync::Lazy;
+
+use crate::crypto::get_random_secret;
+use crate::zkp::{EccSchnorr, Proof, SchnorrProver, SchnorrVerifier, VerifyError};
+
+// How often the verifier self-test re-runs after startup
+const SELF_TEST_INTERVAL: Duration = Duration::from_secs(60);
+
+// None until the first self-test has run, then the latest outcome
+static VERIFIER_STATUS: Lazy<Mutex<Option<Result<(), String>>>> = Lazy::new(|| Mutex::new(None));
+
+// Outcomes of the one-off startup checks, by component name
+static STARTUP_CHECKS: Lazy<Mutex<BTreeMap<&'static str, Check>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+#[derive(Clone, Debug)]
+struct Check {
+    result: Result<(), String>,
+    // An optional component failing is reported but doesn't make the instance unready
+    required: bool,
+}
+
+/// Round-trip a freshly generated proof in each scheme through `verify`: an honest proof must
+/// pass and the same proof with a tampered response must fail. Either going wrong means the
+/// verifier can't be trusted to make access decisions.
+pub fn check_verifier(verify: impl Fn(&Proof) -> Result<(), VerifyError>) -> Result<(), String> {
+    let nonce = "00000000000000000000000000000000";
+    let modp = SchnorrProver::new(get_random_secret()).generate_proof("s00000000".to_string(), nonce);
+    let ecc = EccSchnorr::prove(&k256::Scalar::random(&mut rand::rngs::OsRng), "s00000000", nonce);
+
+    for mut proof in [modp, ecc] {
+        let scheme = format!("{:?}", proof.scheme).to_lowercase();
+        verify(&proof).map_err(|e| format!("rejected a valid {} proof: {}", scheme, e))?;
+
+        // Flip the last digit of the response, which is valid in both encodings
+        let last = proof.response.pop().unwrap_or('0');
+        proof.response.push(if last == '1' { '2' } else { '1' });
+        if verify(&proof).is_ok() {
+            return Err(format!("accepted a tampered {} proof", scheme));
+        }
+    }
+    Ok(())
+}
+
+fn record(result: Result<(), String>) {
+    let mut status = VERIFIER_STATUS.lock().unwrap();
+    let changed = status.as_ref() != Some(&result);
+    if changed {
+        match &result {
+            Ok(()) => tracing::info!("Verifier self-test passed, instance is ready"),
+            Err(e) => tracing::warn!(error = %e, "Verifier self-test failed, instance not ready"),
+        }
+    }
+    *status = Some(result);
+}
+
+/// Run the self-test now and then every minute in the background.
+pub fn start() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(SELF_TEST_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = tokio::task::spawn_blocking(|| check_verifier(SchnorrVerifier::verify_proof))
+                .await
+                .unwrap_or_else(|e| Err(format!("self-test panicked: {}", e)));
+            record(result);
+        }
+    });
+}
+
+/// Record how a startup check of `component` went, e.g. template loading.
+pub fn report(component: &'static str, result: Result<(), String>) {
+    STARTUP_CHECKS.lock().unwrap().insert(component, Check { result, required: true });
+}
+
+/// Like `report`, for a component only some features need; a failure is listed but the
+/// instance still takes traffic.
+pub fn report_optional(component: &'static str, result: Result<(), String>) {
+    STARTUP_CHECKS.lock().unwrap().insert(component, Check { result, required: false });
+}
+
+/// Every check by name: Ok, or why it failed.
+pub fn checks() -> BTreeMap<&'static str, Result<(), String>> {
+    let mut all: BTreeMap<_, _> = STARTUP_CHECKS.lock().unwrap()
+        .iter()
+        .map(|(name, check)| (*name, check.result.clone()))
+        .collect();
+    all.insert("verifier", verifier_status());
+    all
+}
+
+fn verifier_status() -> Result<(), String> {
+    VERIFIER_STATUS.lock().unwrap()
+        .clone()
+        .unwrap_or_else(|| Err("verifier self-test has not run yet".to_string()))
+}
+
+/// Ok once the latest self-test and every required startup check passed; otherwise the
+/// failing checks, which mean this instance shouldn't take traffic.
+pub fn readiness() -> Result<(), Vec<(&'static str, String)>> {
+    let mut failed: Vec<(&'static str, String)> = STARTUP_CHECKS.lock().unwrap()
+        .iter()
+        .filter(|(_, check)| check.required)
+        .filter_map(|(name, check)| check.result.clone().err().map(|e| (*name, e)))
+        .collect();
+    if let Err(e) = verifier_status() {
+        failed.push(("verifier", e));
+    }
+    if failed.is_empty() { Ok(()) } else { Err(failed) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_real_verifier_passes_its_self_test() {
+        assert_eq!(check_verifier(SchnorrVerifier::verify_proof), Ok(()));
+    }
+
+    #[test]
+    fn a_broken_verifier_makes_the_instance_unready() {
+        // One that waves everything through is as broken as one that refuses everything
+        let lax = check_verifier(|_| Ok(())).unwrap_err();
+        assert!(lax.starts_with("accepted a tampered"), "{}", lax);
+        let strict = check_verifier(|_| Err(VerifyError::EquationMismatch)).unwrap_err();
+        assert!(strict.starts_with("rejected a valid"), "{}", strict);
+
+        record(check_verifier(|_| Ok(())));
+        let failed = readiness().unwrap_err();
+        assert!(failed.iter().any(|(name, e)| *name == "verifier" && e.starts_with("accepted a tampered")), "{:?}", failed);
+        assert!(checks()["verifier"].is_err());
+
+        record(check_verifier(SchnorrVerifier::verify_proof));
+        assert_eq!(readiness(), Ok(()));
+        report_optional("groth16_key", Err("vkey.json is missing".to_string()));
+        assert_eq!(readiness(), Ok(()));
+        report("templates", Err("missing index.html".to_string()));
+        assert_eq!(readiness(), Err(vec![("templates", "missing index.html".to_string())]));
+    }
+}
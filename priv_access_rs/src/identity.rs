@@ -0,0 +1,247 @@
+//! Identity proofs accepted at `/api/verify`: the Schnorr proofs in `zkp`, or an ed25519 signature
+//! for clients that already hold an ed25519 key (e.g. in a phone's secure enclave).
+
+use std::fs;
+use std::sync::RwLock;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::zkp::Proof;
+
+const ED25519_KEYS_PATH: &str = "ed25519_keys.json";
+
+/// Which identity proof a request carries, from its `identity` field.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentityKind {
+    /// A Schnorr proof in `proof`
+    #[default]
+    Schnorr,
+    /// An ed25519 signature in `ed25519`
+    Ed25519,
+}
+
+/// An ed25519 signature over `message(door_id, nonce, geohash)`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Ed25519Proof {
+    /// 32-byte public key, hex
+    pub public_key: String,
+    /// 64-byte signature, hex
+    pub signature: String,
+    /// Single-use nonce from `GET /api/challenge`
+    pub nonce: String,
+}
+
+/// The identity proof a request carries.
+#[derive(Debug, Clone, Copy)]
+pub enum IdentityProof<'a> {
+    Schnorr(&'a Proof),
+    Ed25519(&'a Ed25519Proof),
+}
+
+impl IdentityProof<'_> {
+    pub fn public_key(&self) -> &str {
+        match self {
+            IdentityProof::Schnorr(proof) => &proof.public_key,
+            IdentityProof::Ed25519(proof) => &proof.public_key,
+        }
+    }
+
+    /// The `/api/challenge` nonce the proof answers.
+    pub fn nonce(&self) -> &str {
+        match self {
+            IdentityProof::Schnorr(proof) => &proof.nonce,
+            IdentityProof::Ed25519(proof) => &proof.nonce,
+        }
+    }
+}
+
+/// What an ed25519 client signs: the door, the challenge nonce and the geohash it sends, concatenated.
+pub fn message(door_id: &str, nonce: &str, geohash: &str) -> String {
+    format!("{}{}{}", door_id, nonce, geohash)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Ed25519Error {
+    /// A field isn't hex of the right length, or the public key isn't a curve point
+    Malformed { field: &'static str },
+    /// The signature doesn't verify under the public key
+    BadSignature,
+}
+
+impl Ed25519Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Ed25519Error::Malformed { .. } => "proof_malformed",
+            Ed25519Error::BadSignature => "invalid_signature",
+        }
+    }
+
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            Ed25519Error::Malformed { field } => Some(field),
+            Ed25519Error::BadSignature => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Ed25519Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ed25519Error::Malformed { field } => write!(f, "ed25519 field '{}' is not valid hex of the right length", field),
+            Ed25519Error::BadSignature => write!(f, "Invalid ed25519 signature"),
+        }
+    }
+}
+
+fn hex_array<const N: usize>(value: &str, field: &'static str) -> Result<[u8; N], Ed25519Error> {
+    let bytes = hex::decode(value.trim()).map_err(|_| Ed25519Error::Malformed { field })?;
+    bytes.try_into().map_err(|_| Ed25519Error::Malformed { field })
+}
+
+fn parse_public_key(public_key: &str) -> Result<VerifyingKey, Ed25519Error> {
+    VerifyingKey::from_bytes(&hex_array(public_key, "public_key")?)
+        .map_err(|_| Ed25519Error::Malformed { field: "public_key" })
+}
+
+/// Check that `public_key` is a hex-encoded ed25519 key, e.g. before registering it.
+pub fn check_public_key(public_key: &str) -> Result<(), Ed25519Error> {
+    parse_public_key(public_key).map(|_| ())
+}
+
+impl Ed25519Proof {
+    fn parse(&self) -> Result<(VerifyingKey, Signature), Ed25519Error> {
+        let key = parse_public_key(&self.public_key)?;
+        let signature = Signature::from_bytes(&hex_array(&self.signature, "signature")?);
+        Ok((key, signature))
+    }
+
+    /// Parse without verifying, so a malformed proof is rejected before its nonce is spent.
+    pub fn check_format(&self) -> Result<(), Ed25519Error> {
+        self.parse().map(|_| ())
+    }
+
+    /// Check the signature over this door, the proof's nonce and `geohash`. Strict verification,
+    /// so a signature can't be re-encoded into a second valid one.
+    pub fn verify(&self, door_id: &str, geohash: &str) -> Result<(), Ed25519Error> {
+        let (key, signature) = self.parse()?;
+        key.verify_strict(message(door_id, &self.nonce, geohash).as_bytes(), &signature)
+            .map_err(|_| Ed25519Error::BadSignature)
+    }
+}
+
+/// An ed25519 key an admin has allowed to prove a role, and for faculty, one member.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RegisteredKey {
+    pub public_key: String,
+    pub tenant: String,
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub faculty_id: Option<String>,
+}
+
+/// ed25519 keys accepted at `/api/verify`; a signature from any other key is refused however
+/// valid it is. Kept in `ed25519_keys.json`.
+pub struct KeyRegistry {
+    keys: RwLock<Vec<RegisteredKey>>,
+}
+
+// Hex clients may send in either case
+fn normalize(public_key: &str) -> String {
+    public_key.trim().to_ascii_lowercase()
+}
+
+impl KeyRegistry {
+    pub fn load() -> Self {
+        let keys = fs::read_to_string(ED25519_KEYS_PATH)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        KeyRegistry { keys: RwLock::new(keys) }
+    }
+
+    fn save(keys: &[RegisteredKey]) {
+        if let Ok(content) = serde_json::to_string_pretty(keys) {
+            let _ = fs::write(ED25519_KEYS_PATH, content);
+        }
+    }
+
+    /// Returns false if the key is already registered on this tenant.
+    pub fn register(&self, mut key: RegisteredKey) -> bool {
+        key.public_key = normalize(&key.public_key);
+        let mut keys = self.keys.write().unwrap();
+        if keys.iter().any(|k| k.tenant == key.tenant && k.public_key == key.public_key) {
+            return false;
+        }
+        keys.push(key);
+        Self::save(&keys);
+        true
+    }
+
+    /// Returns false if the key wasn't registered on this tenant.
+    pub fn remove(&self, tenant: &str, public_key: &str) -> bool {
+        let public_key = normalize(public_key);
+        let mut keys = self.keys.write().unwrap();
+        let before = keys.len();
+        keys.retain(|k| !(k.tenant == tenant && k.public_key == public_key));
+        let removed = keys.len() != before;
+        if removed {
+            Self::save(&keys);
+        }
+        removed
+    }
+
+    pub fn list(&self, tenant: &str) -> Vec<RegisteredKey> {
+        self.keys.read().unwrap().iter().filter(|k| k.tenant == tenant).cloned().collect()
+    }
+
+    /// Whether `public_key` may prove `role` on `tenant`. Faculty keys also have to belong to the
+    /// faculty member whose credentials came with the request.
+    pub fn allows(&self, tenant: &str, public_key: &str, role: &str, faculty_id: Option<&str>) -> bool {
+        let public_key = normalize(public_key);
+        self.keys.read().unwrap().iter().any(|k| {
+            k.tenant == tenant
+                && k.public_key == public_key
+                && k.role == role
+                && (role != "FACULTY" || k.faculty_id.as_deref().zip(faculty_id).is_some_and(|(a, b)| a.eq_ignore_ascii_case(b.trim())))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const NONCE: &str = "0123456789abcdef0123456789abcdef";
+
+    fn signed(key: &SigningKey, door_id: &str, geohash: &str) -> Ed25519Proof {
+        let signature = key.sign(message(door_id, NONCE, geohash).as_bytes());
+        Ed25519Proof {
+            public_key: hex::encode(key.verifying_key().to_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+            nonce: NONCE.to_string(),
+        }
+    }
+
+    #[test]
+    fn signature_is_bound_to_door_nonce_and_geohash() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let proof = signed(&key, "lab103", "t1q7hk9vj");
+        assert_eq!(proof.verify("lab103", "t1q7hk9vj"), Ok(()));
+        assert_eq!(proof.verify("lab203", "t1q7hk9vj"), Err(Ed25519Error::BadSignature));
+        assert_eq!(proof.verify("lab103", "t1q7hk9vk"), Err(Ed25519Error::BadSignature));
+        let other_nonce = Ed25519Proof { nonce: "ff".repeat(16), ..proof };
+        assert_eq!(other_nonce.verify("lab103", "t1q7hk9vj"), Err(Ed25519Error::BadSignature));
+    }
+
+    #[test]
+    fn malformed_fields_are_named() {
+        let mut proof = signed(&SigningKey::from_bytes(&[7u8; 32]), "lab103", "t1q7hk9vj");
+        proof.signature.truncate(100);
+        assert_eq!(proof.check_format(), Err(Ed25519Error::Malformed { field: "signature" }));
+        proof.public_key = "zz".to_string();
+        assert_eq!(proof.check_format().unwrap_err().field(), Some("public_key"));
+    }
+}
@@ -0,0 +1,71 @@
+//! PrivAccess: zero-knowledge, role-based door access.
+//!
+//! `crypto`, `zkp`, `identity`, `rbac`, `geo`, `groth16` and `offline` are the client-facing API: everything needed
+//! to build and check proofs the server accepts, e.g. from a CLI or mobile app.
+//!
+//! ```no_run
+//! use priv_access::zkp::{Proof, SchnorrProver};
+//!
+//! // `secret` comes from /mobile/setup, `nonce` from /api/challenge
+//! # let (secret, nonce) = (num_bigint::BigUint::from(42u32), "00");
+//! let proof: Proof = SchnorrProver::new(secret).generate_proof("t1q7hk9vj".to_string(), nonce);
+//! let body = serde_json::to_string(&proof).unwrap();
+//! ```
+//!
+//! The remaining modules are the server's own plumbing, shared with the `priv_access_rs` binary.
+
+pub mod crypto;
+pub mod geo;
+pub mod groth16;
+pub mod identity;
+pub mod offline;
+pub mod rbac;
+pub mod zkp;
+
+#[doc(hidden)]
+pub mod actuator;
+#[doc(hidden)]
+pub mod admins;
+#[doc(hidden)]
+pub mod apikeys;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod door_status;
+#[doc(hidden)]
+pub mod doors;
+#[doc(hidden)]
+pub mod error;
+#[doc(hidden)]
+pub mod faculty;
+#[doc(hidden)]
+pub mod guest;
+#[doc(hidden)]
+pub mod health;
+#[doc(hidden)]
+pub mod merkle;
+#[doc(hidden)]
+pub mod metrics;
+#[doc(hidden)]
+pub mod qr;
+#[doc(hidden)]
+pub mod ratelimit;
+#[doc(hidden)]
+pub mod revocation;
+#[doc(hidden)]
+pub mod schedule;
+#[doc(hidden)]
+pub mod session;
+#[doc(hidden)]
+pub mod storage;
+#[doc(hidden)]
+pub mod tenant;
+#[doc(hidden)]
+pub mod validate;
+#[doc(hidden)]
+pub mod webauthn;
+#[doc(hidden)]
+pub mod webhook;
+
+#[cfg(test)]
+mod test_support;
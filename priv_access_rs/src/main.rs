@@ -1,7 +1,5 @@
-use std::process::Command;
-use std::fs;
-use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use axum::{
     extract::{Query, Json},
@@ -10,14 +8,15 @@ use axum::{
     Router,
     http::StatusCode,
 };
+use clap::Parser;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tera::{Tera, Context};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::services::ServeDir;
-use once_cell::sync::Lazy;
-use qrcode::QrCode;
-use base64::{Engine as _, engine::general_purpose};
-use tokio::sync::broadcast;
+use tower_http::trace::TraceLayer;
+use tracing_subscriber::EnvFilter;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -27,151 +26,221 @@ struct ZkProofPayload {
     demo: Option<bool>,
     user_hash: Option<String>,
     allowed_prefix: Option<String>,
+    // Demo clients say which door they're at and share their GPS fix
+    door_id: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct AccessHistory {
-    role: String,
-    door_name: String,
-    section: String,
-    timestamp: String,
-    status: String,
-    faculty_name: Option<String>,
-    faculty_id: Option<String>,
-}
-
-static ACCESS_LOGS: Lazy<std::sync::Mutex<Vec<AccessHistory>>> = Lazy::new(|| {
-    let logs = load_history();
-    std::sync::Mutex::new(logs)
-});
-
-fn load_history() -> Vec<AccessHistory> {
-    if let Ok(content) = fs::read_to_string("access_history.json") {
-        if let Ok(logs) = serde_json::from_str(&content) {
-            return logs;
-        }
+impl Validate for ZkProofPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("user_hash", &self.user_hash, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("allowed_prefix", &self.allowed_prefix, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        Ok(())
     }
-    Vec::new()
 }
 
-fn save_history(logs: &Vec<AccessHistory>) {
-    if let Ok(content) = serde_json::to_string_pretty(logs) {
-        let _ = fs::write("access_history.json", content);
+async fn verify_zkp(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidJson(payload): ValidJson<ZkProofPayload>,
+) -> impl IntoResponse {
+    // A client can only ask for demo mode on servers started with PRIVACCESS_DEMO_MODE; elsewhere the
+    // flag is ignored. Demo mode is held to the same per-door radius as /api/verify
+    let demo = payload.demo == Some(true);
+    if demo && !priv_access::config::CONFIG.demo_mode {
+        tracing::debug!("Ignoring demo flag, demo mode is off");
+    }
+    if demo && priv_access::config::CONFIG.demo_mode {
+        if let (Some(door_id), Some(lat), Some(lon)) = (&payload.door_id, payload.lat, payload.lon) {
+            let door = match tenant.doors.read().unwrap().get(door_id) {
+                Some(d) => d.clone(),
+                None => return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Unknown door").into_response(),
+            };
+            match door.distance_to(lat, lon) {
+                Ok(d) if door.radius_meters == 0 || d <= door.radius_meters as f64 => {}
+                Ok(d) => {
+                    tracing::info!(door_id = %door_id, distance_m = d, result = "denied", "Demo proof rejected, too far from the door");
+                    let msg = format!("Access denied: {:.0}m from the door (limit {}m)", d, door.radius_meters);
+                    return ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::LocationMismatch, msg).into_response();
+                }
+                Err(e) => return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidLocation, e.to_string()).into_response(),
+            }
+        }
     }
-}
-
-// Section to Room mapping: stores which section is assigned to which room and by which faculty
-// Map: Section -> (RoomID, FacultyName)
-static SECTION_ROOM_MAP: Lazy<std::sync::Mutex<HashMap<String, (String, String)>>> = Lazy::new(|| {
-    std::sync::Mutex::new(HashMap::new())
-});
-
 
-async fn verify_zkp(Json(payload): Json<ZkProofPayload>) -> impl IntoResponse {
     // === REAL ZKP VERIFICATION (No Bypass) ===
     let proof = match payload.proof {
         Some(p) => p,
-        None => return (StatusCode::BAD_REQUEST, "Missing proof").into_response(),
+        None => return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MissingProof, "Missing proof").into_response(),
     };
     let public_signals = match payload.public_signals {
         Some(s) => s,
-        None => return (StatusCode::BAD_REQUEST, "Missing public signals").into_response(),
+        None => return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MissingProof, "Missing public signals").into_response(),
     };
 
-    let proof_path = "zkp_circom/tmp_proof.json";
-    let public_path = "zkp_circom/tmp_public.json";
-    let vkey_path = "zkp_circom/verification_key.json";
-
-    if let Err(e) = fs::write(proof_path, proof.to_string()) {
-        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write proof: {}", e)).into_response();
-    }
-    if let Err(e) = fs::write(public_path, public_signals.to_string()) {
-        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write public signals: {}", e)).into_response();
-    }
-
-    // 2. Call Node.js verifier
-    let status = Command::new("node")
-        .arg("zkp_circom/verify_proof.js")
-        .arg(proof_path)
-        .arg(public_path)
-        .arg(vkey_path)
-        .status();
-
-    let status = match status {
-        Ok(s) => s,
-        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to execute Node.js verifier: {}", e)).into_response(),
+    // Verified in-process against the key loaded at startup; pairings are CPU-bound, so off the runtime
+    let verified = match state.groth16.clone() {
+        Some(verifier) => {
+            let signals = public_signals.clone();
+            tokio::task::spawn_blocking(move || verifier.verify(&proof, &signals)).await
+        }
+        // A deployment problem, not a bad proof: 503, so it isn't mistaken for a denial
+        None => {
+            let vkey_path = &priv_access::config::CONFIG.groth16_vkey_path;
+            let missing = !std::path::Path::new(vkey_path).exists();
+            tracing::warn!(vkey = %vkey_path, missing, "Groth16 proof not checked, ZKP verifier not configured");
+            return ApiError::coded(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::VerifierUnavailable, "ZKP verifier not configured").into_response();
+        }
+    };
+    let is_valid_proof = match verified {
+        Ok(Ok(valid)) => valid,
+        Ok(Err(e)) => return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MalformedProof, e.to_string()).into_response(),
+        Err(e) => return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Verifier failed: {}", e)).into_response(),
     };
 
     let is_valid_signal = public_signals.as_array()
-        .and_then(|arr| arr.get(0))
+        .and_then(|arr| arr.first())
         .and_then(|val| val.as_str())
         .unwrap_or("0");
 
-    if status.success() && is_valid_signal == "1" {
-        (StatusCode::OK, "Access granted").into_response()
+    if is_valid_proof && is_valid_signal == "1" {
+        Json(json!({"status": "success", "message": "Access granted"})).into_response()
     } else {
-        println!("Verification failed: status={:?}, isValid={}", status, is_valid_signal);
-        (StatusCode::FORBIDDEN, "Access denied: Invalid Proof or Location").into_response()
+        tracing::info!(valid_proof = is_valid_proof, is_valid_signal = %is_valid_signal, result = "denied", "Groth16 verification failed");
+        ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::InvalidProof, "Access denied: Invalid Proof or Location").into_response()
     }
 }
 
-mod crypto;
-mod rbac;
-mod zkp;
-
-use crate::crypto::{P, G, power_mod, get_random_secret};
-use crate::rbac::get_role_secret;
-use crate::zkp::{SchnorrVerifier, Proof};
+use priv_access::crypto::{P, G, power_g, get_random_secret};
+use priv_access::door_status::{door_channel, DOOR_STATUS_TX};
+use priv_access::doors::Door;
+use priv_access::error::{ApiError, ErrorCode};
+use priv_access::identity::{Ed25519Error, Ed25519Proof, IdentityKind, IdentityProof};
+use priv_access::metrics::DenialReason;
+use priv_access::qr::{cached_png_data_url, cached_qr_svg, qr_png_data_url, render_qr_svg, QrFormat};
+use priv_access::rbac::{
+    authorize, authorize_credentials, proves_identity, section_allows, within_schedule, AccessDecision, AccessRequest, Credentials,
+    Permission, SectionState,
+};
+use priv_access::session::{AdminAuth, AuthedPrincipal};
+use priv_access::webauthn::{AssertionResponse, RegistrationResponse, RelyingParty, WebAuthnError};
+use priv_access::storage::AccessHistory;
+use priv_access::tenant::{AssignmentAudit, Tenant, TenantState};
+use priv_access::validate::{self, FieldError, Validate, ValidJson, ValidQuery};
+use priv_access::zkp::{SchnorrVerifier, Proof, VerifyError};
 
 // --- App State ---
 
 struct AppState {
     tera: Tera,
+    // Logs, assignments and registries; memory, SQLite or Redis depending on config
+    storage: Arc<dyn priv_access::storage::Storage>,
+    // Shared through Redis when configured, otherwise per process
+    limiter: priv_access::ratelimit::RateLimiter,
+    // Per-IP token buckets for the verify and setup routes
+    throttle: priv_access::ratelimit::IpThrottle,
+    // How old a proof's timestamp may be; widen for high-latency deployments
+    proof_window: priv_access::zkp::ProofWindow,
+    // Circom geohash circuit key for /verify; None if it couldn't be loaded
+    groth16: Option<Arc<priv_access::groth16::Groth16Verifier>>,
+    // Public keys refused at /api/verify, e.g. from a lost device
+    revoked: priv_access::revocation::RevocationList,
+    // ed25519 keys allowed to stand in for a Schnorr proof
+    ed25519_keys: priv_access::identity::KeyRegistry,
+    // Faculty passkeys, accepted in place of a PIN
+    passkeys: priv_access::webauthn::PasskeyRegistry,
+    // This machine's LAN IP for QR URLs; kept current by the LAN IP poller
+    lan_ip: std::sync::RwLock<String>,
+    // Class sections, from PRIVACCESS_SECTIONS
+    sections: Vec<String>,
+    // Role -> whether it must show it's at the door, from PRIVACCESS_PROXIMITY
+    require_proximity: HashMap<String, bool>,
 }
 
-static USED_NONCES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+impl AppState {
+    // Roles not listed must be at the door
+    fn requires_proximity(&self, role: &str) -> bool {
+        self.require_proximity.get(role).copied().unwrap_or(true)
+    }
+}
 
 // --- Constants & Data ---
 
-#[derive(Serialize, Clone, Debug)]
-struct Door {
-    name: String,
-    #[allow(dead_code)]
-    secret_qr: String,
-    geohash_prefix: String,
-    qr_url: Option<String>,
-    floor: i32,
-}
+// How long a dynamic QR is accepted after it was drawn
+const QR_LIFETIME_SECS: u64 = 15;
 
-static DOORS: Lazy<HashMap<String, Door>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    let default_geo = "t1q7hk9vj".to_string(); // shared base location for demo
-    
-    // Floor 1
-    m.insert("room101".to_string(), Door { name: "Room 101".to_string(), secret_qr: "s101".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 1 });
-    m.insert("tiered102".to_string(), Door { name: "Tiered 102".to_string(), secret_qr: "s102".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 1 });
-    m.insert("lab103".to_string(), Door { name: "Lab 103".to_string(), secret_qr: "s103".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 1 });
-    // Floor 2
-    m.insert("room201".to_string(), Door { name: "Room 201".to_string(), secret_qr: "s201".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 2 });
-    m.insert("tiered202".to_string(), Door { name: "Tiered 202".to_string(), secret_qr: "s202".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 2 });
-    m.insert("lab203".to_string(), Door { name: "Lab 203".to_string(), secret_qr: "s203".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 2 });
-    // Floor 3
-    m.insert("room301".to_string(), Door { name: "Room 301".to_string(), secret_qr: "s301".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 3 });
-    m.insert("tiered302".to_string(), Door { name: "Tiered 302".to_string(), secret_qr: "s302".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 3 });
-    m.insert("lab303".to_string(), Door { name: "Lab 303".to_string(), secret_qr: "s303".to_string(), geohash_prefix: default_geo.clone(), qr_url: None, floor: 3 });
-    m
-});
-
-// Real-time door status signaling
-static DOOR_STATUS_TX: Lazy<broadcast::Sender<(String, String)>> = Lazy::new(|| {
-    let (tx, _) = broadcast::channel(100);
-    tx
-});
+// A dynamic QR is only accepted for 15s, so its nonce only needs remembering a little longer than that
+const NONCE_TTL_SECS: u64 = 60;
+
+// How long a proof challenge nonce from /api/challenge stays usable
+const CHALLENGE_TTL_SECS: u64 = 60;
+
+// A passkey challenge outlives the browser's own timeout a little, for slow round trips
+const PASSKEY_CHALLENGE_TTL_SECS: u64 = priv_access::webauthn::CEREMONY_TIMEOUT_MS / 1000 + 30;
+
+// Largest request body accepted. A full proof payload is a couple of KB.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+// Where the current version of the JSON API is served
+const API_PREFIX: &str = "/api/v1";
+
+// How long in-flight requests may keep running after a shutdown signal
+const DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// Below this a discrete log in the group is within reach
+const MIN_MODP_BITS: u64 = 2048;
+
+// Unlocks, denials and warnings, without per-request or proof-internals noise
+const DEFAULT_LOG_FILTER: &str = "info";
+
+// Pages rendered by the handlers; Tera loads an empty set if run from the wrong directory
+const REQUIRED_TEMPLATES: [&str; 3] = ["index.html", "door_display.html", "mobile_app.html"];
 
 // --- Routes ---
 
+#[derive(Parser)]
+#[command(name = "priv_access_rs", about = "PrivAccess door access server")]
+struct ServerArgs {
+    /// Address to listen on, e.g. 127.0.0.1:3001 (default: PRIVACCESS_BIND, else 0.0.0.0:3000)
+    #[arg(long)]
+    bind: Option<std::net::SocketAddr>,
+}
+
+// Where the server actually listens, once bound
+static LISTEN_ADDR: OnceCell<std::net::SocketAddr> = OnceCell::new();
+
+// Set once TLS is actually being served, so URLs handed to phones say https
+static SERVING_TLS: OnceCell<bool> = OnceCell::new();
+
+fn url_scheme() -> &'static str {
+    if SERVING_TLS.get().copied().unwrap_or(false) { "https" } else { "http" }
+}
+
+// The certificate pair from PRIVACCESS_TLS_CERT / _KEY, or None to serve plain HTTP. With TLS
+// asked for, a pair that can't be loaded is an error: quietly serving HTTP would send door
+// credentials in the clear to clients that expect HTTPS.
+async fn load_tls_config() -> Result<Option<axum_server::tls_rustls::RustlsConfig>, String> {
+    let config = &priv_access::config::CONFIG;
+    if !config.tls {
+        return Ok(None);
+    }
+    axum_server::tls_rustls::RustlsConfig::from_pem_file(&config.tls_cert_path, &config.tls_key_path)
+        .await
+        .map(Some)
+        .map_err(|e| format!("PRIVACCESS_TLS is on but {} / {} couldn't be loaded ({})", config.tls_cert_path, config.tls_key_path, e))
+}
+
 #[tokio::main]
 async fn main() {
+    let args = ServerArgs::parse();
+
+    // RUST_LOG filters, e.g. `priv_access=debug` for the ZKP equations or `tower_http=debug` for every request
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER)))
+        .init();
+
     // Initialize Tera
     let tera = match Tera::new("templates/**/*.html") {
         Ok(t) => t,
@@ -181,707 +250,5014 @@ async fn main() {
         }
     };
 
-    let state = Arc::new(AppState { tera });
+    let missing: Vec<&str> = REQUIRED_TEMPLATES.iter()
+        .copied()
+        .filter(|name| !tera.get_template_names().any(|loaded| loaded == *name))
+        .collect();
+    if !missing.is_empty() {
+        println!("WARNING: Templates not loaded: {} (run from the priv_access_rs directory)", missing.join(", "));
+    }
+    priv_access::health::report("templates", if missing.is_empty() { Ok(()) } else { Err(format!("missing {}", missing.join(", "))) });
 
-    // Build Router
-    let app = Router::new()
-        .route("/", get(index))
-        .route("/history", get(api_get_history))
-        .route("/api/room_qrs", get(api_room_qrs))
-        .route("/api/check_assignment", get(api_check_assignment))
-        .route("/api/dynamic_qr/:door_id", get(api_dynamic_qr))
-        .route("/api/dynamic_qrs_all", get(api_dynamic_qrs_all))
-        .route("/door/:door_id", get(door_display))
-        .route("/door/:door_id/status", get(door_status_stream))
-        .route("/s/:door_id", get(short_scan))
-        .route("/api/notify_status", post(api_notify_status)) 
-        .route("/mobile/scan", get(mobile_scan))
-        .route("/mobile/setup", get(mobile_setup))
-        .route("/api/verify", post(api_verify))
-        .route("/verify", post(verify_zkp))
-        .nest_service("/static", ServeDir::new("static"))
-        .with_state(state);
+    // Without Redis, door status stays in-process (single instance)
+    if let Some(url) = &priv_access::config::CONFIG.redis_url {
+        if let Err(e) = priv_access::door_status::start_redis_bridge(url) {
+            println!("WARNING: Invalid PRIVACCESS_REDIS_URL ({}), door status stays local", e);
+        }
+    }
+
+    // Everything modp rests on this group, so refuse to start on a bad one
+    let params = match priv_access::crypto::params().and_then(|p| p.validate().map(|()| p)) {
+        Ok(params) => params,
+        Err(e) => {
+            println!("\n❌ CRYPTO PARAMS ERROR: {}", e);
+            ::std::process::exit(1);
+        }
+    };
+    priv_access::health::report("crypto_params", Ok(()));
+    // Every proof and verification raises G to a full-size power; build its table before the first one
+    once_cell::sync::Lazy::force(&priv_access::crypto::G_TABLE);
+    if params.p.bits() < MIN_MODP_BITS {
+        println!("WARNING: {}-bit modp prime is below the {} bits recommended for Schnorr proofs", params.p.bits(), MIN_MODP_BITS);
+    }
+
+    // Load every campus's doors now so a broken doors.toml stops startup instead of the first request
+    priv_access::tenant::tenant_ids();
+
+    priv_access::actuator::start(priv_access::config::CONFIG.redis_url.as_deref());
+    priv_access::webhook::start();
+    priv_access::health::start();
+    if let Err(e) = priv_access::metrics::install() {
+        println!("WARNING: Metrics recorder not installed ({}), /metrics will be empty", e);
+    }
+
+    let storage = match priv_access::storage::from_config(&priv_access::config::CONFIG).await {
+        Ok(storage) => storage,
+        Err(e) => {
+            println!("\n❌ STORAGE ERROR: {}", e.0);
+            ::std::process::exit(1);
+        }
+    };
+
+    let limiter = match &priv_access::config::CONFIG.redis_url {
+        Some(url) => match priv_access::ratelimit::RateLimiter::redis(url).await {
+            Ok(limiter) => limiter,
+            Err(e) => {
+                println!("WARNING: Redis rate limiter unavailable ({}), limiting per instance", e);
+                priv_access::ratelimit::RateLimiter::local()
+            }
+        },
+        None => priv_access::ratelimit::RateLimiter::local(),
+    };
+
+    let vkey_path = &priv_access::config::CONFIG.groth16_vkey_path;
+    let groth16 = match priv_access::groth16::Groth16Verifier::load(vkey_path) {
+        Ok(verifier) => {
+            priv_access::health::report_optional("groth16_key", Ok(()));
+            Some(Arc::new(verifier))
+        }
+        Err(priv_access::groth16::Groth16Error::Missing(path)) => {
+            println!("WARNING: ZKP verifier not configured: {} is missing, /verify will answer 503", path);
+            priv_access::health::report_optional("groth16_key", Err(format!("{} is missing", path)));
+            None
+        }
+        Err(e) => {
+            println!("WARNING: Groth16 key {} not loaded ({}), /verify will answer 503", vkey_path, e);
+            priv_access::health::report_optional("groth16_key", Err(format!("{} not loaded: {}", vkey_path, e)));
+            None
+        }
+    };
+
+    let throttle = priv_access::ratelimit::IpThrottle::new(priv_access::config::CONFIG.throttle_rps, priv_access::config::CONFIG.throttle_burst);
+    throttle.start_eviction();
+
+    let proof_window = priv_access::zkp::ProofWindow {
+        max_age_secs: priv_access::config::CONFIG.proof_max_age_secs,
+        max_skew_secs: priv_access::config::CONFIG.max_clock_skew_secs,
+    };
 
+    let revoked = priv_access::revocation::RevocationList::load();
+    let ed25519_keys = priv_access::identity::KeyRegistry::load();
+    let passkeys = priv_access::webauthn::PasskeyRegistry::load();
 
-    let listener = match tokio::net::TcpListener::bind("0.0.0.0:3000").await {
+    // Kept to flush and close once the router (and its state) is done
+    let storage_on_exit = storage.clone();
+    let lan_ip = std::sync::RwLock::new(get_local_ip());
+    let state = Arc::new(AppState { tera, storage, limiter, throttle, proof_window, groth16, revoked, ed25519_keys, passkeys, lan_ip,
+        sections: priv_access::config::CONFIG.sections.clone(),
+        require_proximity: priv_access::config::CONFIG.require_proximity.clone(),
+    });
+
+    let app = build_router(state.clone());
+
+    // Tenant resolution runs before routing so `/t/:tenant/...` reaches the same routes
+    let app = tower::Layer::layer(&axum::middleware::from_fn(priv_access::tenant::resolve_tenant), app);
+
+    // Every request gets an x-request-id (kept if a proxy already set one), echoed in the response
+    // and attached to everything logged while handling it
+    let app = tower::ServiceBuilder::new()
+        .map_response(|res: axum::http::Response<_>| res.map(axum::body::Body::new))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(|req: &axum::extract::Request| {
+            let request_id = req.headers().get("x-request-id").and_then(|v| v.to_str().ok()).unwrap_or("-");
+            tracing::info_span!("request", method = %req.method(), path = %req.uri().path(), request_id = %request_id)
+        }))
+        .service(app);
+
+
+    let bind = args.bind.unwrap_or(priv_access::config::CONFIG.bind);
+    let listener = match tokio::net::TcpListener::bind(bind).await {
         Ok(l) => l,
         Err(e) => {
-            println!("\n❌ PORT CONFLICT ERROR: {}", e);
+            println!("\n❌ CANNOT LISTEN ON {}: {}", bind, e);
             println!("==================================================");
-            println!("ERROR: Port 3000 is still being held by an old process.");
-            println!("TO FIX THIS ON WINDOWS, RUN THESE COMMANDS:");
-            println!("1. netstat -ano | findstr :3000");
-            println!("2. taskkill /F /PID <THE_PID_FROM_STEP_1>");
+            if e.kind() == std::io::ErrorKind::AddrInUse {
+                println!("Another process (often an older PrivAccess) is using port {}.", bind.port());
+                println!("Find it with `lsof -i :{0}` (Linux/macOS) or `netstat -ano | findstr :{0}` (Windows) and stop it,", bind.port());
+            } else {
+                println!("Check that {} is an address of this machine and the port is allowed,", bind.ip());
+            }
+            println!("or choose another address with --bind or PRIVACCESS_BIND, e.g. --bind 127.0.0.1:3001");
             println!("==================================================\n");
             return;
         }
     };
-    
-    let lan_ip = get_local_ip();
+    let bound = listener.local_addr().unwrap_or(bind);
+    let _ = LISTEN_ADDR.set(bound);
+    let tls = match load_tls_config().await {
+        Ok(tls) => tls,
+        Err(e) => {
+            println!("\n❌ TLS ERROR: {}", e);
+            ::std::process::exit(1);
+        }
+    };
+    let _ = SERVING_TLS.set(tls.is_some());
+    let scheme = url_scheme();
+
+    // The LAN IP only ends up in URLs when listening on every interface
+    let poll_secs = priv_access::config::CONFIG.lan_ip_poll_secs;
+    if bound.ip().is_unspecified() && poll_secs > 0 {
+        spawn_lan_ip_poller(state.clone(), std::time::Duration::from_secs(poll_secs));
+    }
+
+    let lan_host = lan_host(&state);
     println!("\n{}", "=".repeat(50));
     println!("🚀 PRIVACCESS SYSTEM STARTED");
     println!("{}", "=".repeat(50));
     println!("🖥️  MAIN GATEWAY (Select Role):");
-    println!("   http://localhost:3000/");
-    println!("   http://{}:3000/ (LAN)", lan_ip);
+    println!("   {}://localhost:{}/", scheme, bound.port());
+    if !bound.ip().is_loopback() {
+        println!("   {}://{}/ (LAN)", scheme, lan_host);
+    }
     println!("{}", "-".repeat(50));
+    for id in priv_access::tenant::tenant_ids().iter().filter(|id| *id != priv_access::tenant::DEFAULT_TENANT) {
+        println!("🏫 Campus '{}': {}://{}/t/{}/", id, scheme, lan_host, id);
+    }
+    if state.requires_proximity("ADMIN") {
+        println!("🔒 Admin remote access DISABLED (proximity required)");
+    }
+    println!("{}\n", "=".repeat(50));
+
+    let server: futures::future::BoxFuture<'static, std::io::Result<()>> = match (tls, listener.into_std()) {
+        (Some(tls), Ok(listener)) => {
+            let handle = axum_server::Handle::new();
+            let on_signal = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                on_signal.graceful_shutdown(None);
+            });
+            // axum-server hands over hyper's body type rather than axum's
+            let app = tower::ServiceBuilder::new()
+                .map_request(|req: axum::http::Request<hyper::body::Incoming>| req.map(axum::body::Body::new))
+                .service(app);
+            let make_service = axum::ServiceExt::<axum::http::Request<hyper::body::Incoming>>::into_make_service_with_connect_info::<std::net::SocketAddr>(app);
+            Box::pin(axum_server::from_tcp_rustls(listener, tls).handle(handle).serve(make_service))
+        }
+        (Some(_), Err(e)) => {
+            println!("\n❌ CANNOT LISTEN ON {}: {}", bound, e);
+            return;
+        }
+        (None, Ok(listener)) => match tokio::net::TcpListener::from_std(listener) {
+            Ok(listener) => {
+                let make_service = axum::ServiceExt::<axum::extract::Request>::into_make_service_with_connect_info::<std::net::SocketAddr>(app);
+                Box::pin(std::future::IntoFuture::into_future(axum::serve(listener, make_service).with_graceful_shutdown(shutdown_signal())))
+            }
+            Err(e) => {
+                println!("\n❌ CANNOT LISTEN ON {}: {}", bound, e);
+                return;
+            }
+        },
+        (None, Err(e)) => {
+            println!("\n❌ CANNOT LISTEN ON {}: {}", bound, e);
+            return;
+        }
+    };
+
+    // Once a signal arrives, in-flight requests get DRAIN_TIMEOUT to finish
+    let drain_deadline = async {
+        priv_access::door_status::shutdown_started().await;
+        tokio::time::sleep(DRAIN_TIMEOUT).await;
+    };
+    tokio::select! {
+        result = server => {
+            if let Err(e) = result {
+                println!("\n❌ SERVER ERROR: {}", e);
+            }
+        }
+        _ = drain_deadline => {
+            tracing::warn!(timeout_secs = DRAIN_TIMEOUT.as_secs(), "Requests still running, shutting down anyway");
+        }
+    }
+
+    match storage_on_exit.close().await {
+        Ok(()) => println!("💾 Access logs saved"),
+        Err(e) => tracing::warn!(error = %e.0, "Failed to save access logs on shutdown"),
+    }
+    println!("👋 PRIVACCESS STOPPED");
     println!("{}\n", "=".repeat(50));
+}
+
+// Every route, before the tenant and request-id layers `main` wraps around it: pages, door
+// displays and operational endpoints, plus the JSON API under /api/v1 and its older paths
+fn build_router(state: Arc<AppState>) -> Router {
+    build_router_with_cors(state, cors_layer(&priv_access::config::CONFIG))
+}
+
+// `build_router` with the CORS policy given rather than read from the config
+fn build_router_with_cors(state: Arc<AppState>, cors: Option<tower_http::cors::CorsLayer>) -> Router {
+    let api = Router::new()
+        .nest(API_PREFIX, api_router(state.throttle.clone()))
+        .merge(legacy_api_routes(state.throttle.clone()));
+    let api = match cors {
+        Some(cors) => api.layer(cors),
+        None => api,
+    };
+    page_routes()
+        .merge(api)
+        .nest_service("/static", ServeDir::new("static"))
+        .layer(axum::extract::DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(state)
+}
+
+fn page_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(index))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .route("/door/:door_id", get(door_display))
+        .route("/door/:door_id/status", get(door_status_stream))
+        .route("/door/:door_id/ws", get(door_status_ws))
+        .route("/s/:door_id", get(short_scan))
+        .route("/mobile/scan", get(mobile_scan))
+}
+
+// Ctrl-C anywhere, or SIGTERM from systemd / `docker stop` on Unix
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 
-    axum::serve(listener, app).await.unwrap();
+    println!("\n{}", "=".repeat(50));
+    println!("🛑 PRIVACCESS SHUTTING DOWN");
+    println!("{}", "=".repeat(50));
+    println!("   Finishing in-flight requests, closing door displays...");
+    priv_access::door_status::begin_shutdown();
 }
 
 async fn index(
     axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
 ) -> impl IntoResponse {
-    let context = Context::new();
+    let mut context = Context::new();
+    context.insert("base_path", &tenant.base_path());
     match state.tera.render("index.html", &context) {
         Ok(html) => Html(html).into_response(),
-        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template Error: {}", err)).into_response(),
+        Err(err) => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Template Error: {}", err)).into_response(),
     }
 }
 
-async fn api_get_history() -> impl IntoResponse {
-    let logs = ACCESS_LOGS.lock().unwrap();
-    Json(json!(logs.clone()))
+// Liveness: the process is up and serving
+async fn healthz() -> impl IntoResponse {
+    Json(json!({"status": "ok"}))
 }
 
-#[derive(Deserialize)]
-struct CheckAssignmentParams {
-    section: String,
+// Readiness: only take traffic once templates and crypto params loaded and while the proof
+// verifier passes its self-test. Every check is listed either way.
+async fn readyz() -> impl IntoResponse {
+    let checks: serde_json::Map<String, serde_json::Value> = priv_access::health::checks()
+        .into_iter()
+        .map(|(name, result)| (name.to_string(), json!(result.err().unwrap_or_else(|| "ok".to_string()))))
+        .collect();
+    match priv_access::health::readiness() {
+        Ok(()) => Json(json!({"status": "ready", "checks": checks})).into_response(),
+        Err(failed) => {
+            let reason = failed.iter().map(|(name, e)| format!("{}: {}", name, e)).collect::<Vec<_>>().join("; ");
+            let failed: Vec<&str> = failed.iter().map(|(name, _)| *name).collect();
+            (StatusCode::SERVICE_UNAVAILABLE, Json(json!({"status": "not_ready", "failed": failed, "reason": reason, "checks": checks}))).into_response()
+        }
+    }
 }
 
-async fn api_check_assignment(Query(params): Query<CheckAssignmentParams>) -> impl IntoResponse {
-    let map = SECTION_ROOM_MAP.lock().unwrap();
-    
-    if let Some((room_id, faculty_name)) = map.get(&params.section) {
-        if let Some(door) = DOORS.get(room_id) {
-            let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-            let history = AccessHistory {
-                role: "STUDENT".to_string(),
-                door_name: door.name.clone(),
-                section: params.section.clone(),
-                timestamp,
-                status: "ASSIGNMENT FETCHED".to_string(),
-                faculty_name: Some(faculty_name.clone()),
-                faculty_id: None,
-            };
-            {
-                let mut logs = ACCESS_LOGS.lock().unwrap();
-                logs.push(history);
-                save_history(&logs);
-            }
+// Prometheus scrape target: access grants by role and door, denials by reason, /api/verify latency
+async fn metrics() -> impl IntoResponse {
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], priv_access::metrics::render())
+}
 
-            return Json(json!({
-                "assigned": true,
-                "room_name": door.name,
-                "room_id": room_id,
-                "faculty_name": faculty_name
-            }));
-        }
-    }
-    
-    // Log "No Room Allotted" check
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let history = AccessHistory {
-        role: "STUDENT".to_string(),
-        door_name: "Room lookup".to_string(),
-        section: params.section.clone(),
-        timestamp,
-        status: "DENIED: No room allotted".to_string(),
-        faculty_name: None,
-        faculty_id: None,
-    };
-    {
-        let mut logs = ACCESS_LOGS.lock().unwrap();
-        logs.push(history);
-        save_history(&logs);
+// Busiest hours are reported in server-local time. Older entries were logged as local
+// "YYYY-MM-DD HH:MM:SS"; newer ones are UTC RFC 3339.
+fn local_hour(timestamp: &str) -> Option<String> {
+    match chrono::DateTime::parse_from_rfc3339(timestamp) {
+        Ok(t) => Some(t.with_timezone(&chrono::Local).format("%H").to_string()),
+        Err(_) => timestamp.get(11..13).map(str::to_string),
     }
-
-    Json(json!({
-        "assigned": false,
-        "message": "No room is being alloted for ur section"
-    }))
 }
 
+// Entries per /history page unless ?limit= asks otherwise, and the most it may ask for
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+const MAX_HISTORY_LIMIT: usize = 1000;
 
 #[derive(Deserialize)]
-struct QrParams {
+struct HistoryParams {
     role: Option<String>,
-    section: Option<String>,
-    faculty_name: Option<String>,
-    faculty_id: Option<String>,
-    pin: Option<String>,
+    door: Option<String>,
+    // "granted" or "denied"; `outcome` is the older name
+    status: Option<String>,
+    outcome: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<usize>,
+    // How many of the newest matches to skip
+    offset: Option<usize>,
 }
 
-async fn api_room_qrs(
-    Query(q_params): Query<QrParams>,
-    req: axum::http::Request<axum::body::Body>,
-) -> impl IntoResponse {
-    let _host = req.headers()
-        .get("host")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("localhost:3000");
-
-    let mut room_qrs = Vec::new();
-    let is_student = q_params.role.as_deref() == Some("STUDENT");
-
-    // If student, add a special LOOKUP QR first
-    if is_student {
-        room_qrs.push(json!({
-            "id": "lookup",
-            "name": "LOOKUP MY ROOM",
-            "type": "lookup"
-        }));
-
-        // STOP HERE FOR STUDENTS - They shouldn't see classroom QRs unconditionally
-        return Json(room_qrs);
-    }
-
-    for (id, door) in DOORS.iter() {
-        room_qrs.push(json!({
-            "id": id,
-            "name": door.name,
-            "type": "door"
-        }));
+impl Validate for HistoryParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("door", &self.door, validate::MAX_NAME_LEN)?;
+        validate::opt_text("status", &self.status, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("outcome", &self.outcome, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("since", &self.since, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("until", &self.until, validate::MAX_TEXT_LEN)?;
+        Ok(())
     }
-    Json(room_qrs)
 }
 
-fn get_local_ip() -> String {
-    use std::net::UdpSocket;
-    let fallback = "127.0.0.1".to_string();
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
-        Ok(s) => s,
-        Err(_) => return fallback,
+async fn api_get_history(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<HistoryParams>,
+) -> impl IntoResponse {
+    let granted = match params.status.or(params.outcome).as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("granted") => Some(true),
+        Some("denied") => Some(false),
+        Some(_) => return ApiError::new(StatusCode::BAD_REQUEST, "status must be 'granted' or 'denied'").with_field("status").into_response(),
     };
-    if socket.connect("8.8.8.8:80").is_err() {
-        return fallback;
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if limit == 0 || limit > MAX_HISTORY_LIMIT {
+        let msg = format!("limit must be between 1 and {}", MAX_HISTORY_LIMIT);
+        return ApiError::new(StatusCode::BAD_REQUEST, msg).with_field("limit").into_response();
+    }
+    let offset = params.offset.unwrap_or(0);
+    let filter = priv_access::storage::LogFilter {
+        role: params.role.map(|r| r.to_uppercase()),
+        door_name: params.door,
+        granted,
+        since: params.since,
+        until: params.until,
+        limit: Some(limit),
+        offset,
+    };
+    let total = match state.storage.count_logs(&tenant.id, &filter).await {
+        Ok(n) => n,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    match state.storage.query_logs(&tenant.id, &filter).await {
+        Ok(items) => Json(json!({
+            "items": items,
+            "total": total,
+            "limit": limit,
+            "offset": offset
+        })).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
-    let ip = socket.local_addr()
-        .map(|addr| addr.ip().to_string())
-        .unwrap_or(fallback);
-    println!("DEBUG: Detected LAN IP for QR Code: {}", ip);
-    ip
 }
 
 #[derive(Deserialize)]
-struct DynamicQrParams {
+struct ExportParams {
+    // "csv" (default) or "json"
+    format: Option<String>,
     role: Option<String>,
-    section: Option<String>,
-    faculty_id: Option<String>,
-    pin: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
 }
 
-#[derive(Serialize)]
-struct DynamicQrRes {
-    door_id: String,
-    floor: i32,
-    timestamp: u64,
-    nonce: String,
-    url: String,
+impl Validate for ExportParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("format", &self.format, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("from", &self.from, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("to", &self.to, validate::MAX_TEXT_LEN)?;
+        Ok(())
+    }
 }
 
-async fn api_dynamic_qr(
-    axum::extract::Path(door_id): axum::extract::Path<String>,
-    Query(params): Query<DynamicQrParams>,
-    req: axum::http::Request<axum::body::Body>,
-) -> impl IntoResponse {
-    let door = match DOORS.get(&door_id) {
-        Some(d) => d,
-        None => return (StatusCode::NOT_FOUND, "Door Not Found").into_response(),
-    };
+const EXPORT_COLUMNS: &str = "id,timestamp,role,door_name,section,status,faculty_id,faculty_name,actor_id,denial_reason";
 
-    let host = req.headers()
-        .get("host")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("localhost:3000");
+// How many entries the export reads from storage at a time
+const EXPORT_PAGE_SIZE: usize = 500;
 
-    let is_local = host.starts_with("localhost") || host.starts_with("127.0.0.1");
-    let base_host = if is_local {
-        format!("{}:3000", get_local_ip())
+// RFC 4180: a field with a comma, quote or line break is quoted, with quotes doubled. A field a
+// spreadsheet would read as a formula gets a leading apostrophe first, so a door name or section
+// like `=HYPERLINK(...)` opens as text
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
     } else {
-        host.to_string()
-    };
+        value
+    }
+}
 
-    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-    
-    use rand::Rng;
-    let nonce: String = rand::thread_rng()
-        .sample_iter(&rand::distributions::Alphanumeric)
-        .take(16)
-        .map(char::from)
-        .collect();
+fn csv_row(entry: &AccessHistory) -> String {
+    let fields = [
+        entry.id.to_string(),
+        entry.timestamp.clone(),
+        entry.role.clone(),
+        entry.door_name.clone(),
+        entry.section.clone(),
+        entry.status.clone(),
+        entry.faculty_id.clone().unwrap_or_default(),
+        entry.faculty_name.clone().unwrap_or_default(),
+        entry.actor_id.clone().unwrap_or_default(),
+        entry.denial_reason.map(|r| r.to_string()).unwrap_or_default(),
+    ];
+    let mut row = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+    row.push_str("\r\n");
+    row
+}
 
-    // Use /s/ short redirect for QR efficiency, adding role and section if provided
-    let mut final_url = format!("http://{}/s/{}?ts={}&nonce={}&floor={}", 
-                               base_host, door_id, timestamp, nonce, door.floor);
-    
-    if let Some(r) = params.role {
-        final_url.push_str(&format!("&role={}", r));
-    }
-    if let Some(s) = params.section {
-        final_url.push_str(&format!("&section={}", s));
-    }
-    if let Some(fid) = params.faculty_id {
-        final_url.push_str(&format!("&faculty_id={}", encode_url(&fid)));
-    }
-    if let Some(p) = params.pin {
-        final_url.push_str(&format!("&pin={}", encode_url(&p)));
+// Compliance download of the access history as CSV or a JSON array
+async fn api_export_history(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<ExportParams>,
+) -> impl IntoResponse {
+    use futures::stream::StreamExt;
+
+    let format = params.format.as_deref().unwrap_or("csv").to_ascii_lowercase();
+    let (content_type, extension) = match format.as_str() {
+        "csv" => ("text/csv; charset=utf-8", "csv"),
+        "json" => ("application/json", "json"),
+        _ => return ApiError::new(StatusCode::BAD_REQUEST, "format must be 'csv' or 'json'").into_response(),
+    };
+    let filter = priv_access::storage::LogFilter {
+        role: params.role.map(|r| r.trim().to_uppercase()),
+        since: params.from,
+        until: params.to,
+        ..Default::default()
+    };
+    // Entries logged after the export starts aren't part of it
+    let total = match state.storage.count_logs(&tenant.id, &filter).await {
+        Ok(total) => total,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    tracing::info!(tenant = %tenant.id, entries = total, format = extension, by = %principal.id, "History exported");
+
+    // Read from storage a page at a time, oldest first, as the body is sent. Offsets count back
+    // from the newest entry, so each page is placed past whatever was logged since the export began
+    let json = extension == "json";
+    let storage = state.storage.clone();
+    let tenant_id = tenant.id.clone();
+    let pages = futures::stream::unfold((0usize, 0u64), move |(read, last_id)| {
+        let (storage, tenant_id, filter) = (storage.clone(), tenant_id.clone(), filter.clone());
+        async move {
+            if read >= total {
+                return None;
+            }
+            let remaining = total - read;
+            let take = remaining.min(EXPORT_PAGE_SIZE);
+            let page = match storage.count_logs(&tenant_id, &filter).await {
+                Ok(now) => {
+                    let page = priv_access::storage::LogFilter { limit: Some(take), offset: now.saturating_sub(total) + remaining - take, ..filter };
+                    storage.query_logs(&tenant_id, &page).await
+                }
+                Err(e) => Err(e),
+            };
+            let entries = match page {
+                // The log was archived underneath the export
+                Ok(entries) if entries.is_empty() => return None,
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::error!(tenant = %tenant_id, error = %e.0, "History export cut short");
+                    return Some((Err(std::io::Error::other(e.0)), (total, last_id)));
+                }
+            };
+            let mut chunk = String::new();
+            let mut newest = last_id;
+            for entry in &entries {
+                // An entry logged between the count and the read shifts the page by one
+                if entry.id != 0 && entry.id <= last_id {
+                    continue;
+                }
+                if json {
+                    if read > 0 || !chunk.is_empty() {
+                        chunk.push(',');
+                    }
+                    chunk.push_str(&serde_json::to_string(entry).unwrap_or_default());
+                } else {
+                    chunk.push_str(&csv_row(entry));
+                }
+                newest = newest.max(entry.id);
+            }
+            Some((Ok(chunk), (read + entries.len(), newest)))
+        }
+    });
+    let (header, footer) = if json { ("[".to_string(), "]") } else { (format!("{}\r\n", EXPORT_COLUMNS), "") };
+    let body = axum::body::Body::from_stream(
+        futures::stream::once(async move { Ok::<_, std::io::Error>(header) })
+            .chain(pages)
+            .chain(futures::stream::once(async move { Ok(footer.to_string()) })),
+    );
+
+    let filename = format!("access_history_{}_{}.{}", tenant.id, chrono::Utc::now().format("%Y%m%d-%H%M%S"), extension);
+    (
+        [
+            (axum::http::header::CONTENT_TYPE, content_type.to_string()),
+            (axum::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ).into_response()
+}
+
+#[derive(Deserialize)]
+struct ArchiveHistoryPayload {
+    password: Option<String>,
+    // Which admin `password` belongs to; the default admin if absent
+    admin_id: Option<String>,
+    // Needed with `password` once the admin has enrolled an authenticator app
+    totp_code: Option<String>,
+    // Must be true, so a stray POST can't empty the live log
+    #[serde(default)]
+    confirm: bool,
+}
+
+impl Validate for ArchiveHistoryPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_secret("password", &self.password)?;
+        validate::opt_text("admin_id", &self.admin_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("totp_code", &self.totp_code)?;
+        Ok(())
     }
+}
 
-    Json(DynamicQrRes {
-        door_id,
-        floor: door.floor,
-        timestamp,
-        nonce,
-        url: final_url,
-    }).into_response()
+// Endpoints that take the admin password in the body also accept a logged-in dashboard's session
+// cookie in its place
+fn admin_body_credentials(
+    password: Option<String>,
+    admin_id: Option<String>,
+    totp_code: Option<String>,
+    headers: &axum::http::HeaderMap,
+) -> Option<Credentials> {
+    match password.filter(|p| !p.trim().is_empty()) {
+        Some(password) => Some(admin_password(admin_id, password, totp_code)),
+        None => priv_access::session::admin_session_id(headers).map(Credentials::AdminSession),
+    }
 }
 
-fn encode_url(s: &str) -> String {
-    urlencoding::encode(s).to_string()
+fn admin_password(admin_id: Option<String>, password: String, totp_code: Option<String>) -> Credentials {
+    let id = admin_id.filter(|id| !id.trim().is_empty()).unwrap_or_else(|| priv_access::admins::DEFAULT_ADMIN_ID.to_string());
+    Credentials::AdminPassword { id, password, totp: totp_code }
 }
 
-async fn api_dynamic_qrs_all(
-    Query(params): Query<DynamicQrParams>,
-    req: axum::http::Request<axum::body::Body>,
+// Rotate the live log: everything so far goes to a timestamped archive file, then the log starts empty.
+// Takes the admin password in the body, like an admin unlock, or the admin session cookie.
+async fn api_archive_history(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<ArchiveHistoryPayload>,
 ) -> impl IntoResponse {
-    let host = req.headers()
-        .get("host")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("localhost:3000");
-
-    let is_local = host.starts_with("localhost") || host.starts_with("127.0.0.1");
-    let base_host = if is_local {
-        format!("{}:3000", get_local_ip())
-    } else {
-        host.to_string()
+    let credentials = admin_body_credentials(payload.password, payload.admin_id, payload.totp_code, &headers);
+    let principal = match authorize_credentials(credentials.as_ref(), &tenant.id, Permission::Delete) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    if !payload.confirm {
+        return ApiError::new(StatusCode::BAD_REQUEST, "Set \"confirm\": true to archive and clear the access history").into_response();
+    }
+    let archive = match state.storage.archive_logs(&tenant.id).await {
+        Ok(a) => a,
+        Err(e) => return ApiError::from(e).into_response(),
     };
+    tracing::info!(tenant = %tenant.id, entries = archive.count, path = %archive.path, by = %principal.id, "History archived");
+    Json(json!({
+        "status": "success",
+        "message": "Access history archived",
+        "archived": archive.count,
+        "archive_file": archive.path
+    })).into_response()
+}
 
-    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-    
-    use rand::Rng;
-    let mut responses = HashMap::new();
+// Merkle leaves hash each entry as compact JSON with sorted keys, which a verifier can rebuild
+// from the entry alone
+fn history_leaves(logs: &[AccessHistory]) -> Vec<priv_access::merkle::Hash> {
+    logs.iter()
+        .map(|entry| {
+            let canonical = json!(entry).to_string();
+            priv_access::merkle::leaf_hash(canonical.as_bytes())
+        })
+        .collect()
+}
 
-    for (id, door) in DOORS.iter() {
-        let nonce: String = rand::thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(16)
-            .map(char::from)
-            .collect();
-            
-        let mut url = format!("http://{}/s/{}?ts={}&nonce={}&floor={}", base_host, id, timestamp, nonce, door.floor);
-        if let Some(r) = &params.role {
-            url.push_str(&format!("&role={}", r));
-        }
-        if let Some(s) = &params.section {
-            url.push_str(&format!("&section={}", s));
-        }
-        if let Some(fid) = &params.faculty_id {
-            url.push_str(&format!("&faculty_id={}", encode_url(fid)));
-        }
-        if let Some(p) = &params.pin {
-            url.push_str(&format!("&pin={}", encode_url(p)));
-        }
+// A commitment to the whole log that can be published elsewhere
+async fn api_history_merkle_root(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    let logs = match state.storage.logs(&tenant.id).await {
+        Ok(logs) => logs,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let leaves = history_leaves(&logs);
+    Json(json!({
+        "status": "success",
+        "root": hex::encode(priv_access::merkle::root(&leaves)),
+        "size": leaves.len()
+    })).into_response()
+}
 
-        responses.insert(id.clone(), DynamicQrRes {
-            door_id: id.clone(),
-            floor: door.floor,
-            timestamp,
-            nonce,
-            url,
-        });
+// Inclusion proof for one entry against the current root
+async fn api_history_merkle_proof(
+    axum::extract::Path(id): axum::extract::Path<u64>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    let logs = match state.storage.logs(&tenant.id).await {
+        Ok(logs) => logs,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let Some(index) = logs.iter().position(|entry| id != 0 && entry.id == id) else {
+        return ApiError::new(StatusCode::NOT_FOUND, "Log entry not found").into_response();
+    };
+    let leaves = history_leaves(&logs);
+    Json(json!({
+        "status": "success",
+        "log_id": id,
+        "entry": logs[index],
+        "index": index,
+        "leaf": hex::encode(leaves[index]),
+        "path": priv_access::merkle::inclusion_proof(&leaves, index),
+        "root": hex::encode(priv_access::merkle::root(&leaves)),
+        "size": leaves.len()
+    })).into_response()
+}
+
+// The proof stored with a granted access (needs PRIVACCESS_STORE_PROOFS)
+async fn api_history_proof(
+    _admin: AdminAuth,
+    axum::extract::Path(id): axum::extract::Path<u64>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    match state.storage.proof(&tenant.id, id).await {
+        Ok(Some(proof)) => Json(json!({"status": "success", "log_id": id, "proof": proof})).into_response(),
+        Ok(None) => ApiError::new(StatusCode::NOT_FOUND, "No proof stored for this entry").into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
+}
 
-    Json(responses).into_response()
+// Re-run the Schnorr check on a stored proof, to show a logged access was genuinely proven
+async fn api_reverify_history(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::Path(id): axum::extract::Path<u64>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    let proof = match state.storage.proof(&tenant.id, id).await {
+        Ok(Some(proof)) => proof,
+        Ok(None) => return ApiError::new(StatusCode::NOT_FOUND, "No proof stored for this entry").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let result = SchnorrVerifier::verify_proof(&proof);
+    tracing::info!(entry = id, by = %principal.id, valid = result.is_ok(), "Stored proof re-verified");
+    match result {
+        Ok(()) => Json(json!({"status": "success", "log_id": id, "valid": true})).into_response(),
+        Err(e) => Json(json!({
+            "status": "success",
+            "log_id": id,
+            "valid": false,
+            "reason": e.code(),
+            "message": e.to_string()
+        })).into_response(),
+    }
 }
 
-// === 1. Door Display ===
+// Doors for a client's own picker, without their QR secrets. Private doors are listed for admins only.
+async fn api_list_doors(Tenant(tenant): Tenant, headers: axum::http::HeaderMap) -> impl IntoResponse {
+    let admin = authorize(&headers, &tenant.id, Permission::Delete).is_ok();
+    let mut doors: Vec<_> = tenant.doors.read().unwrap().iter()
+        .filter(|(_, door)| admin || !door.private)
+        .map(|(id, door)| {
+            let mut entry = json!({
+                "id": id,
+                "name": door.name,
+                "geohash_prefix": door.geohash_prefix
+            });
+            if admin {
+                entry["private"] = json!(door.private);
+            }
+            if let Some(schedule) = &door.schedule {
+                entry["schedule"] = json!(schedule);
+            }
+            entry
+        })
+        .collect();
+    doors.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    Json(doors)
+}
 
-async fn door_display(
+async fn api_delete_door(
+    AdminAuth(principal): AdminAuth,
     axum::extract::Path(door_id): axum::extract::Path<String>,
     axum::extract::State(state): axum::extract::State<Arc<AppState>>,
-    req: axum::http::Request<axum::body::Body>,
+    Tenant(tenant): Tenant,
 ) -> impl IntoResponse {
-    let door = match DOORS.get(&door_id) {
+    let removed = tenant.doors.write().unwrap().remove(&door_id);
+    let door = match removed {
         Some(d) => d,
-        None => return (StatusCode::NOT_FOUND, "Door Not Found").into_response(),
+        None => return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response(),
     };
 
-    let host = req.headers()
-        .get("host")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("localhost:3000");
-
-    let mobile_url = if host.starts_with("localhost") || host.starts_with("127.0.0.1") {
-        let lan_ip = get_local_ip();
-        format!("http://{}:3000/s/{}", lan_ip, door_id)
-    } else {
-        format!("http://{}/s/{}", host, door_id)
+    // Sections pointing at the deleted room no longer have anywhere to go
+    let orphaned = match state.storage.unassign_room(&tenant.id, &door_id).await {
+        Ok(sections) => sections,
+        Err(e) => return ApiError::from(e).into_response(),
     };
+    for section in &orphaned {
+        tenant.record_assignment_change(&principal.id, &principal.role, section, Some(door_id.clone()), None);
+    }
 
-    // GENERATE QR CODE SERVER-SIDE (Manual draw to avoid trait mismatches)
-    let code = QrCode::new(mobile_url.as_bytes()).unwrap();
-    let width = code.width();
-    let mut img = image::GrayImage::new(width as u32, width as u32);
-    
-    for (i, color) in code.to_colors().into_iter().enumerate() {
-        let x = (i % width) as u32;
-        let y = (i / width) as u32;
-        let pixel = if color == qrcode::Color::Dark {
-            image::Luma([0u8])
-        } else {
-            image::Luma([255u8])
-        };
-        img.put_pixel(x, y, pixel);
+    let doors = tenant.doors.read().unwrap().clone();
+    if let Err(e) = priv_access::doors::save(&tenant.id, &doors) {
+        tracing::warn!(path = %priv_access::doors::doors_path(&tenant.id), error = %e, "Could not update doors file");
     }
 
-    // Upscale for better quality
-    let upscaled = image::imageops::resize(&img, 400, 400, image::imageops::FilterType::Nearest);
-    
-    let mut buffer = std::io::Cursor::new(Vec::new());
-    let dynamic_image = image::DynamicImage::ImageLuma8(upscaled);
-    dynamic_image.write_to(&mut buffer, image::ImageFormat::Png).unwrap();
-    let b64 = general_purpose::STANDARD.encode(buffer.into_inner());
-    let qr_data_url = format!("data:image/png;base64,{}", b64);
+    tracing::info!(door_id = %door_id, door_name = %door.name, by = %principal.id, "Door deleted");
+    Json(json!({
+        "status": "success",
+        "message": format!("{} deleted", door.name),
+        "unassigned_sections": orphaned
+    })).into_response()
+}
 
-    let mut context = Context::new();
-    context.insert("door", door);
-    context.insert("door_id", &door_id);
-    context.insert("mobile_url", &mobile_url);
-    context.insert("qr_data_url", &qr_data_url);
+async fn api_reload_doors(
+    AdminAuth(principal): AdminAuth,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    // A bad file leaves the current doors in place
+    let doors = match priv_access::doors::load(&tenant.id) {
+        Ok(doors) => doors,
+        Err(e) => return ApiError::new(StatusCode::BAD_REQUEST, format!("Doors Not Reloaded: {}", e)).into_response(),
+    };
+    let mut ids: Vec<String> = doors.keys().cloned().collect();
+    ids.sort();
+    *tenant.doors.write().unwrap() = doors;
 
-    println!("TERMINAL: [DOOR {}] Initialized. Waiting for connection...", door_id);
+    tracing::info!(tenant = %tenant.id, doors = ids.len(), path = %priv_access::doors::doors_path(&tenant.id), by = %principal.id, "Doors reloaded");
+    Json(json!({
+        "status": "success",
+        "message": format!("{} doors loaded", ids.len()),
+        "doors": ids
+    })).into_response()
+}
 
-    match state.tera.render("door_display.html", &context) {
-        Ok(html) => Html(html).into_response(),
-        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template Error: {}", err)).into_response(),
-    }
+#[derive(Deserialize)]
+struct CheckAssignmentParams {
+    section: String,
 }
 
-// SSE handler for door display updates
-async fn door_status_stream(
-    ax_path: axum::extract::Path<String>,
-) -> impl IntoResponse {
-    use axum::response::sse::{Event, Sse};
-    use futures::stream::StreamExt;
-    use tokio_stream::wrappers::BroadcastStream;
+impl Validate for CheckAssignmentParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("section", &self.section, validate::MAX_KEYWORD_LEN)?;
+        Ok(())
+    }
+}
 
-    let door_id = ax_path.0;
-    let rx = DOOR_STATUS_TX.subscribe();
-    let stream = BroadcastStream::new(rx)
-        .filter_map(move |msg| {
-            let door_id = door_id.clone();
-            async move {
-                match msg {
-                    Ok((target_id, status)) if target_id == door_id => {
-                        Some(Ok::<Event, std::convert::Infallible>(Event::default().data(status)))
-                    },
-                    _ => None,
-                }
-            }
-        });
+// The room a section is assigned to, as (room id, room name, faculty name), logging the lookup
+// either way
+async fn lookup_section_room(state: &AppState, tenant: &TenantState, section: &str) -> Result<Option<(String, String, String)>, ApiError> {
+    let assignment = state.storage.assignment(&tenant.id, section).await?;
+    let found = assignment.and_then(|(room_id, faculty_name)| {
+        tenant.door(&room_id).map(|door| (room_id, door.name.clone(), faculty_name))
+    });
 
-    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+    let history = AccessHistory {
+        id: 0,
+        role: "STUDENT".to_string(),
+        door_name: found.as_ref().map_or("Room lookup".to_string(), |(_, name, _)| name.clone()),
+        section: section.to_string(),
+        timestamp: log_timestamp(),
+        status: if found.is_some() { "ASSIGNMENT FETCHED" } else { "DENIED: No room allotted" }.to_string(),
+        faculty_name: found.as_ref().map(|(_, _, faculty)| faculty.clone()),
+        faculty_id: None,
+        actor_id: None,
+        denial_reason: found.is_none().then_some(DenialReason::NoRoomAssigned),
+    };
+    record_access(state, tenant, history).await;
+    Ok(found)
 }
 
-// === 2. Mobile App ===
-#[derive(Deserialize)]
-struct ShortScanParams {
-    ts: Option<u64>,
-    nonce: Option<String>,
-    floor: Option<i32>,
-    role: Option<String>,
-    section: Option<String>,
-    faculty_id: Option<String>,
-    pin: Option<String>,
+async fn api_check_assignment(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<CheckAssignmentParams>,
+) -> impl IntoResponse {
+    match lookup_section_room(&state, &tenant, &params.section).await {
+        Ok(Some((room_id, room_name, faculty_name))) => Json(json!({
+            "assigned": true,
+            "room_name": room_name,
+            "room_id": room_id,
+            "faculty_name": faculty_name
+        })).into_response(),
+        Ok(None) => Json(json!({
+            "assigned": false,
+            "message": "No room is being alloted for ur section"
+        })).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
-async fn short_scan(
-    axum::extract::Path(door_id): axum::extract::Path<String>,
-    Query(params): Query<ShortScanParams>,
-) -> Redirect {
-    println!("TERMINAL: [DOOR {}] QR Scanned! Mobile connecting...", door_id);
-    let _ = DOOR_STATUS_TX.send((door_id.clone(), "connected".to_string()));
-    
-    let mut url = format!("/mobile/scan?door={}", door_id);
-    if let Some(ts) = params.ts { url.push_str(&format!("&ts={}", ts)); }
-    if let Some(nonce) = params.nonce { url.push_str(&format!("&nonce={}", nonce)); }
-    if let Some(floor) = params.floor { url.push_str(&format!("&floor={}", floor)); }
-    if let Some(role) = params.role { url.push_str(&format!("&role={}", role)); }
-    if let Some(section) = params.section { url.push_str(&format!("&section={}", section)); }
-    if let Some(fid) = params.faculty_id { url.push_str(&format!("&faculty_id={}", encode_url(&fid))); }
-    if let Some(p) = params.pin { url.push_str(&format!("&pin={}", encode_url(&p))); }
-    
-    Redirect::to(&url)
+// A student's room for the section they entered, with a link that opens the scan flow for it.
+// An unassigned section is a normal answer, not an error: the faculty just hasn't started yet.
+async fn api_my_room(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<CheckAssignmentParams>,
+) -> impl IntoResponse {
+    let Some(section) = priv_access::rbac::find_section(&state.sections, &params.section) else {
+        return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidSection, "Invalid Section Selected").with_field("section").into_response();
+    };
+    match lookup_section_room(&state, &tenant, section).await {
+        Ok(Some((room_id, room_name, faculty_name))) => Json(json!({
+            "status": "success",
+            "section": section,
+            "assigned": true,
+            "room_id": room_id,
+            "room_name": room_name,
+            "faculty_name": faculty_name,
+            "scan_url": format!("{}/mobile/scan?door={}&role=STUDENT&section={}", tenant.base_path(), encode_url(&room_id), encode_url(section))
+        })).into_response(),
+        Ok(None) => Json(json!({
+            "status": "success",
+            "section": section,
+            "assigned": false,
+            "state": "not_assigned",
+            "message": format!("Section {} has no room assigned yet. Check again once your faculty has started the class.", section)
+        })).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
 #[derive(Deserialize)]
-struct StatusNotifyPayload {
-    door_id: String,
-    status: String,
+struct RoomSectionsParams {
+    room_id: String,
+}
+
+impl Validate for RoomSectionsParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("room_id", &self.room_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
 }
 
-async fn api_notify_status(Json(payload): Json<StatusNotifyPayload>) -> impl IntoResponse {
-    println!("TERMINAL: [DOOR {}] Status Update: {}", payload.door_id, payload.status.to_uppercase());
-    let _ = DOOR_STATUS_TX.send((payload.door_id, payload.status));
-    StatusCode::OK
+// Every section currently assigned to a room; a combined lecture has several
+async fn api_room_sections(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<RoomSectionsParams>,
+) -> impl IntoResponse {
+    let room_id = params.room_id.trim();
+    let Some(door) = tenant.door(room_id) else {
+        return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response();
+    };
+    let sections = match state.storage.room_sections(&tenant.id, room_id).await {
+        Ok(s) => s,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let sections: Vec<serde_json::Value> = sections.into_iter()
+        .map(|(section, faculty_name)| json!({"section": section, "faculty_name": faculty_name}))
+        .collect();
+    Json(json!({
+        "status": "success",
+        "room_id": room_id,
+        "room_name": door.name,
+        "sections": sections
+    })).into_response()
 }
 
 #[derive(Deserialize)]
-struct ScanParams {
-    door: Option<String>,
+struct UnassignSectionPayload {
+    section: String,
 }
 
-async fn mobile_scan(
-    Query(params): Query<ScanParams>,
+impl Validate for UnassignSectionPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("section", &self.section, validate::MAX_KEYWORD_LEN)?;
+        Ok(())
+    }
+}
+
+// Release a section so another faculty member can take it. Only whoever assigned it, or an admin, can.
+async fn api_unassign_section(
     axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<UnassignSectionPayload>,
 ) -> impl IntoResponse {
-    let mut context = Context::new();
-    if let Some(d) = params.door {
-        context.insert("door_id", &d);
+    let principal = match authorize(&headers, &tenant.id, Permission::Write) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    let Some(section) = priv_access::rbac::find_section(&state.sections, &payload.section) else {
+        return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidSection, "Invalid Section Selected").into_response();
+    };
+    let (room, owner) = match state.storage.assignment(&tenant.id, section).await {
+        Ok(Some(a)) => a,
+        Ok(None) => return ApiError::new(StatusCode::NOT_FOUND, "Section is not assigned").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let owns = priv_access::faculty::find(&principal.id).is_some_and(|f| f.assigned(&owner));
+    if !owns && !principal.allows(Permission::Delete) {
+        return ApiError::forbidden(format!("Section {} was assigned by {}", section, owner)).into_response();
     }
 
-    context.insert("sections", crate::rbac::SECTIONS);
-
-    match state.tera.render("mobile_app.html", &context) {
-        Ok(html) => Html(html).into_response(),
-        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Template Error: {}", err)).into_response(),
+    if let Err(e) = state.storage.unassign(&tenant.id, section).await {
+        return ApiError::from(e).into_response();
     }
+    tenant.record_assignment_change(&principal.id, &principal.role, section, Some(room.clone()), None);
+    Json(json!({
+        "status": "success",
+        "message": format!("Section {} released from {}", section, room),
+        "section": section,
+        "room": room
+    })).into_response()
 }
 
 #[derive(Deserialize)]
-struct SetupParams {
+struct QrParams {
     role: Option<String>,
+    section: Option<String>,
+    faculty_name: Option<String>,
     faculty_id: Option<String>,
     pin: Option<String>,
-    password: Option<String>,
+    // `svg` adds each door's QR as inline SVG
+    format: Option<QrFormat>,
 }
 
-async fn mobile_setup(Query(params): Query<SetupParams>) -> impl IntoResponse {
-    let requested_role = params.role.unwrap_or_else(|| "STUDENT".to_string()).to_uppercase();
-    
-    if requested_role == "FACULTY" {
-        let fac_id = params.faculty_id.as_deref().unwrap_or("").trim();
-        let pin = params.pin.as_deref().unwrap_or("").trim();
-        let faculty_match = crate::rbac::FACULTIES.iter().find(|f| {
-            f.id.eq_ignore_ascii_case(fac_id) && f.pin == pin
-        });
-        if faculty_match.is_none() {
-            return (StatusCode::UNAUTHORIZED, Json(json!({"status": "failed", "message": "Invalid Faculty ID or PIN"}))).into_response();
-        }
-    } else if requested_role == "ADMIN" {
-        let pass = params.password.as_deref().unwrap_or("").trim();
-        if pass != crate::rbac::ADMIN_PASSWORD {
-            return (StatusCode::UNAUTHORIZED, Json(json!({"status": "failed", "message": "Incorrect Admin Password"}))).into_response();
-        }
+impl Validate for QrParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::section("section", &self.section)?;
+        validate::opt_text("faculty_name", &self.faculty_name, validate::MAX_NAME_LEN)?;
+        validate::opt_text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("pin", &self.pin)?;
+        Ok(())
     }
+}
 
-    let (secret, role_name) = match get_role_secret(&requested_role) {
-        Some(s) => (s, requested_role),
-        None => (get_random_secret(), "UNKNOWN".to_string()),
-    };
+async fn api_room_qrs(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(q_params): ValidQuery<QrParams>,
+    req: axum::http::Request<axum::body::Body>,
+) -> impl IntoResponse {
+    let base_url = public_base_url(&state, req.headers(), &tenant);
 
-    let public_key = power_mod(&G, &secret, &P);
+    let mut room_qrs = Vec::new();
+    let is_student = q_params.role.as_deref() == Some("STUDENT");
 
-    Json(json!({
-        "secret": secret.to_string(),
-        "public_key": public_key.to_string(),
-        "role": role_name
-    })).into_response()
+    // If student, add a special LOOKUP QR first, carrying their section when we know it
+    if is_student {
+        let mut url = format!("{}/mobile/scan?door=lookup&action=lookup&role=STUDENT", base_url);
+        if let Some(section) = q_params.section.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            url.push_str(&format!("&section={}", encode_url(&section.to_uppercase())));
+        }
+        room_qrs.push(json!({
+            "id": "lookup",
+            "name": "LOOKUP MY ROOM",
+            "type": "lookup",
+            "url": url
+        }));
+
+        // STOP HERE FOR STUDENTS - They shouldn't see classroom QRs unconditionally
+        return Json(room_qrs).into_response();
+    }
+
+    for (id, door) in tenant.doors.read().unwrap().iter() {
+        let url = format!("{}/s/{}", base_url, id);
+        let mut entry = json!({
+            "id": id,
+            "name": door.name,
+            "type": "door",
+            "url": url
+        });
+        if q_params.format == Some(QrFormat::Svg) {
+            match cached_qr_svg(&url) {
+                Ok(svg) => entry["qr_svg"] = json!(svg),
+                Err(e) => return qr_error(e).into_response(),
+            }
+        }
+        room_qrs.push(entry);
+    }
+    Json(room_qrs).into_response()
 }
 
-// === 3. Verification ===
-#[derive(Deserialize, Debug)]
-struct VerifyPayload {
-    door_id: String,
-    role: String,
-    proof: Proof,
-    geohash: String,
-    password: Option<String>,
-    pin: Option<String>,
+fn qr_error(e: qrcode::types::QrError) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, format!("Cannot encode QR code: {}", e))
+}
+
+fn get_local_ip() -> String {
+    use std::net::UdpSocket;
+    let fallback = "127.0.0.1".to_string();
+    let socket = match UdpSocket::bind("0.0.0.0:0") {
+        Ok(s) => s,
+        Err(_) => return fallback,
+    };
+    if socket.connect("8.8.8.8:80").is_err() {
+        return fallback;
+    }
+    let ip = socket.local_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or(fallback);
+    tracing::debug!(%ip, "Detected LAN IP for QR codes");
+    ip
+}
+
+// Re-detects the LAN IP every `every`. When it moves, QR codes already on screen point at an
+// address phones can't reach any more, so cached codes are dropped and displays told to redraw.
+fn spawn_lan_ip_poller(state: Arc<AppState>, every: std::time::Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let ip = get_local_ip();
+            let previous = std::mem::replace(&mut *state.lan_ip.write().unwrap(), ip.clone());
+            if previous == ip {
+                continue;
+            }
+            if ip == "127.0.0.1" {
+                tracing::warn!(%previous, "No network found, QR codes point at 127.0.0.1 until it's back");
+            } else {
+                tracing::info!(%previous, %ip, "LAN IP changed, refreshing door QR codes");
+            }
+            priv_access::qr::clear_cache();
+            priv_access::door_status::notify_all_local("ip_changed");
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct DynamicQrParams {
+    role: Option<String>,
     section: Option<String>,
-    faculty_name: Option<String>,
     faculty_id: Option<String>,
-    gps_valid: Option<bool>,
-    ip_city: Option<String>,
-    ip_region: Option<String>,
-    ip_country: Option<String>,
-    nonce: Option<String>,
-    qr_timestamp: Option<u64>,
-    floor: Option<i32>,
+    pin: Option<String>,
+    // `svg` adds the code itself as inline SVG, for displays that draw it without a JS library
+    format: Option<QrFormat>,
 }
 
-const EXPECTED_COUNTRY: &str = "India";
-const EXPECTED_REGION: &str = "Andhra Pradesh";
+impl Validate for DynamicQrParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::section("section", &self.section)?;
+        validate::opt_text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("pin", &self.pin)?;
+        Ok(())
+    }
+}
 
-async fn api_verify(Json(payload): Json<VerifyPayload>) -> impl IntoResponse {
-    let door_id = payload.door_id.trim();
-    println!("TERMINAL: [DOOR {}] RECEIVED ACCESS REQUEST FROM {}", door_id, payload.role);
+#[derive(Serialize)]
+struct DynamicQrRes {
+    door_id: String,
+    floor: i32,
+    timestamp: u64,
+    nonce: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    qr_svg: Option<String>,
+}
 
-    // 1. Check Door Existence
-    let door = match DOORS.get(door_id) {
+async fn api_dynamic_qr(
+    axum::extract::Path(door_id): axum::extract::Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<DynamicQrParams>,
+    req: axum::http::Request<axum::body::Body>,
+) -> impl IntoResponse {
+    let door = match tenant.door(&door_id) {
         Some(d) => d,
-        None => {
-            return (StatusCode::NOT_FOUND, Json(json!({"status": "failed", "message": "Door Not Found"}))).into_response()
+        None => return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response(),
+    };
+
+    let base_url = public_base_url(&state, req.headers(), &tenant);
+
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    
+    use rand::Rng;
+    let nonce: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+
+    // Use /s/ short redirect for QR efficiency, adding role and section if provided
+    let mut final_url = format!("{}/s/{}?ts={}&nonce={}&floor={}", 
+                               base_url, encode_url(&door_id), timestamp, encode_url(&nonce), door.floor);
+    
+    if let Some(r) = params.role {
+        final_url.push_str(&format!("&role={}", encode_url(&r)));
+    }
+    if let Some(s) = params.section {
+        final_url.push_str(&format!("&section={}", encode_url(&s)));
+    }
+    if let Some(fid) = params.faculty_id {
+        final_url.push_str(&format!("&faculty_id={}", encode_url(&fid)));
+    }
+    if let Some(p) = params.pin {
+        final_url.push_str(&format!("&pin={}", encode_url(&p)));
+    }
+
+    let qr_svg = match params.format {
+        Some(QrFormat::Svg) => match render_qr_svg(&final_url) {
+            Ok(svg) => Some(svg),
+            Err(e) => return qr_error(e).into_response(),
         },
+        _ => None,
+    };
+
+    Json(DynamicQrRes {
+        door_id,
+        floor: door.floor,
+        timestamp,
+        nonce,
+        url: final_url,
+        qr_svg,
+    }).into_response()
+}
+
+// host:port phones reach this server at: the bound address if it names one, otherwise
+// (listening on every interface) this machine's LAN IP
+fn lan_host(state: &AppState) -> String {
+    let addr = LISTEN_ADDR.get().copied().unwrap_or(priv_access::config::CONFIG.bind);
+    if addr.ip().is_unspecified() {
+        format!("{}:{}", state.lan_ip.read().unwrap(), addr.port())
+    } else {
+        addr.to_string()
+    }
+}
+
+// Absolute URL phones should use to reach this campus, e.g. http://192.168.1.5:3000/t/north,
+// or https:// when serving TLS
+fn public_base_url(state: &AppState, headers: &axum::http::HeaderMap, tenant: &TenantState) -> String {
+    let host = headers
+        .get("host")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+
+    let is_local = host.starts_with("localhost") || host.starts_with("127.0.0.1");
+    let base_host = if is_local {
+        lan_host(state)
+    } else {
+        host.to_string()
     };
+    format!("{}://{}{}", url_scheme(), base_host, tenant.base_path())
+}
+
+fn encode_url(s: &str) -> String {
+    urlencoding::encode(s).to_string()
+}
+
+async fn api_dynamic_qrs_all(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<DynamicQrParams>,
+    req: axum::http::Request<axum::body::Body>,
+) -> impl IntoResponse {
+    let base_url = public_base_url(&state, req.headers(), &tenant);
+
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    
+    use rand::Rng;
+    let mut responses = HashMap::new();
 
-    // 1.5 Dynamic QR Check (Anti-Replay / Location enforcement)
-    if payload.role != "ADMIN" {
-        let ts = payload.qr_timestamp.unwrap_or(0);
-        let current_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-        if current_time > ts + 15 {
-            log_denied(&payload, door, "QR Expired");
-            return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": "Location Check Failed: QR Code Expired (Took more than 15s)."}))).into_response();
+    for (id, door) in tenant.doors.read().unwrap().iter() {
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(16)
+            .map(char::from)
+            .collect();
+            
+        let mut url = format!("{}/s/{}?ts={}&nonce={}&floor={}", base_url, encode_url(id), timestamp, encode_url(&nonce), door.floor);
+        if let Some(r) = &params.role {
+            url.push_str(&format!("&role={}", encode_url(r)));
         }
-        
-        let nonce = payload.nonce.clone().unwrap_or_default();
-        if nonce.is_empty() {
-            log_denied(&payload, door, "Missing QR Nonce");
-            return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": "Location Check Failed: Invalid QR format"}))).into_response();
+        if let Some(s) = &params.section {
+            url.push_str(&format!("&section={}", encode_url(s)));
         }
-        
-        {
-            let mut nonces = USED_NONCES.lock().unwrap();
-            if nonces.contains(&nonce) {
-                log_denied(&payload, door, "QR Reused");
-                return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": "Location Check Failed: QR Code already used"}))).into_response();
-            }
-            nonces.insert(nonce);
+        if let Some(fid) = &params.faculty_id {
+            url.push_str(&format!("&faculty_id={}", encode_url(fid)));
         }
-
-        let sent_floor = payload.floor.unwrap_or(-1);
-        if sent_floor != door.floor {
-            log_denied(&payload, door, "Floor Mismatch");
-            return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": "Location Check Failed: Wrong Floor"}))).into_response();
+        if let Some(p) = &params.pin {
+            url.push_str(&format!("&pin={}", encode_url(p)));
         }
+
+        let qr_svg = match params.format {
+            Some(QrFormat::Svg) => match render_qr_svg(&url) {
+                Ok(svg) => Some(svg),
+                Err(e) => return qr_error(e).into_response(),
+            },
+            _ => None,
+        };
+
+        responses.insert(id.clone(), DynamicQrRes {
+            door_id: id.clone(),
+            floor: door.floor,
+            timestamp,
+            nonce,
+            url,
+            qr_svg,
+        });
     }
 
-    // 2. Authentication Logic
-    match payload.role.as_str() {
-        "ADMIN" => {
-            if payload.password.as_deref() != Some(crate::rbac::ADMIN_PASSWORD) {
-                log_denied(&payload, door, "Incorrect Admin Password");
-                return (StatusCode::UNAUTHORIZED, Json(json!({"status": "failed", "message": "Incorrect Admin Password"}))).into_response();
-            }
-            // Admin has remote access - Skip Proximity check
-            println!("TERMINAL: [DOOR {}] ADMIN REMOTE ACCESS GRANTED", door_id);
-        },
-        "FACULTY" => {
-            let pin = payload.pin.as_deref().unwrap_or("").trim();
-            let fac_id = payload.faculty_id.as_deref().unwrap_or("").trim();
-            let section = payload.section.as_deref().unwrap_or("");
+    Json(responses).into_response()
+}
 
-            let faculty_match = crate::rbac::FACULTIES.iter().find(|f| {
-                f.id.eq_ignore_ascii_case(fac_id) && f.pin == pin
-            });
+// === 1. Door Display ===
 
-            if faculty_match.is_none() {
-                println!("TERMINAL: [DOOR {}] FACULTY LOGIN FAILED: ID='{}', PIN='{}'", door_id, fac_id, pin);
-                log_denied(&payload, door, "Invalid Faculty Credentials");
-                return (StatusCode::UNAUTHORIZED, Json(json!({"status": "failed", "message": "Invalid ID or PIN for Faculty"}))).into_response();
-            }
-            // Location checks are satisfied via Dynamic QR proximity logically above
-            // We only optionally check GPS for backup logging
-            let is_gps_valid = payload.gps_valid.unwrap_or(false);
-            if !is_gps_valid {
-                println!("TERMINAL: [DOOR {}] Optional warning: Faculty skipped GPS lock, but dynamic QR passed.", door_id);
-            }
-            
-            // Store section-to-room mapping
-            if !section.is_empty() {
-                let faculty_name = payload.faculty_name.clone().unwrap_or_else(|| fac_id.to_string());
-                let mut map = SECTION_ROOM_MAP.lock().unwrap();
-                map.insert(section.to_string(), (door_id.to_string(), faculty_name.clone()));
-                println!("TERMINAL: [MAPPING] Section {} assigned to room {} by {}", section, door.name, faculty_name);
-            }
+// Side of the PNG door displays get, in pixels
+const DOOR_QR_SIZE: u32 = 400;
+
+#[derive(Deserialize)]
+struct DoorDisplayParams {
+    // `svg` renders the QR as vector markup, for large screens where a PNG pixelates
+    #[serde(default)]
+    format: QrFormat,
+}
+
+async fn door_display(
+    axum::extract::Path(door_id): axum::extract::Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    Query(params): Query<DoorDisplayParams>,
+    req: axum::http::Request<axum::body::Body>,
+) -> impl IntoResponse {
+    let door = match tenant.door(&door_id) {
+        Some(d) => d,
+        None => return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response(),
+    };
+
+    // The tenant prefix is part of the URL, so the same door id on another campus never matches
+    let mobile_url = format!("{}/s/{}", public_base_url(&state, req.headers(), &tenant), door_id);
+
+    let qr_svg = match params.format {
+        QrFormat::Svg => match cached_qr_svg(&mobile_url) {
+            Ok(svg) => Some(svg),
+            Err(e) => return qr_error(e).into_response(),
         },
-        "STUDENT" => {
-            let section = payload.section.as_deref().unwrap_or("").trim();
-            if section.is_empty() || !crate::rbac::SECTIONS.iter().any(|s| s.eq_ignore_ascii_case(section)) {
-                println!("TERMINAL: [DOOR {}] REJECTED: Invalid Section '{}' for Student", door_id, section);
-                log_denied(&payload, door, &format!("Invalid Section: {}", section));
-                return (StatusCode::BAD_REQUEST, Json(json!({"status": "failed", "message": "Invalid Section Selected"}))).into_response();
-            }
-
-            // SECTION RESTRICTION CHECK
-            {
-                let map = SECTION_ROOM_MAP.lock().unwrap();
-                match map.get(section) {
-                    Some((assigned_room, _)) if assigned_room == door_id => {
-                        // Correct room - continue to proximity check
-                    },
-                    Some((assigned_room, faculty)) => {
-                        let msg = format!("Access Denied: Your section is assigned to {} by {}", assigned_room, faculty);
-                        log_denied(&payload, door, &msg);
-                        return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": msg}))).into_response();
+        QrFormat::Png => None,
+    };
+
+    let qr_data_url = match cached_png_data_url(&mobile_url, DOOR_QR_SIZE) {
+        Ok(url) => url,
+        Err(e) => return qr_error(e).into_response(),
+    };
+
+    let mut context = Context::new();
+    context.insert("door", &door);
+    context.insert("door_id", &door_id);
+    context.insert("base_path", &tenant.base_path());
+    context.insert("mobile_url", &mobile_url);
+    context.insert("qr_data_url", &qr_data_url);
+    context.insert("qr_svg", &qr_svg);
+    context.insert("qr_format", if params.format == QrFormat::Svg { "svg" } else { "png" });
+
+    tracing::info!(door_id = %door_id, "Door display opened");
+
+    match state.tera.render("door_display.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(err) => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Template Error: {}", err)).into_response(),
+    }
+}
+
+// SSE handler for door display updates
+async fn door_status_stream(
+    ax_path: axum::extract::Path<String>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    use axum::response::sse::{Event, Sse};
+    use futures::stream::StreamExt;
+    use tokio_stream::wrappers::BroadcastStream;
+
+    let door_id = door_channel(&tenant, &ax_path.0);
+    let rx = DOOR_STATUS_TX.subscribe();
+
+    // Catch up a display that connected just after an unlock it would otherwise have missed
+    let replay: Vec<Result<Event, std::convert::Infallible>> = if priv_access::door_status::recent_unlock(&door_id) {
+        vec![Ok(Event::default().data("unlocked"))]
+    } else {
+        Vec::new()
+    };
+
+    let live = BroadcastStream::new(rx)
+        .filter_map(move |msg| {
+            let door_id = door_id.clone();
+            async move {
+                match msg {
+                    Ok((target_id, status)) if target_id == door_id => {
+                        Some(Ok::<Event, std::convert::Infallible>(Event::default().data(status)))
                     },
-                    None => {
-                        let msg = "No room is being alloted for ur section";
-                        log_denied(&payload, door, msg);
-                        return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": msg}))).into_response();
-                    }
+                    _ => None,
                 }
             }
+        });
+    let stream = futures::stream::iter(replay).chain(live)
+        .take_until(priv_access::door_status::shutdown_started());
 
-            // Location checks are satisfied via Dynamic QR proximity logically above
-            let is_gps_valid = payload.gps_valid.unwrap_or(false);
-            if !is_gps_valid {
-                println!("TERMINAL: [DOOR {}] Optional warning: Student skipped GPS lock, but dynamic QR passed.", door_id);
-            }
-        },
-        _ => return (StatusCode::BAD_REQUEST, Json(json!({"status": "failed", "message": "Invalid Role"}))).into_response(),
+    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+}
+
+// Two-way door channel: the same status events as the SSE stream, as `{"door_id", "status"}`
+// JSON, plus commands from the display such as `{"cmd":"manual_lock"}`. Locking can't let
+// anyone in, so displays don't authenticate for it.
+async fn door_status_ws(
+    ws: axum::extract::ws::WebSocketUpgrade,
+    axum::extract::Path(door_id): axum::extract::Path<String>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    if tenant.door(&door_id).is_none() {
+        return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response();
+    }
+    let channel = door_channel(&tenant, &door_id);
+    ws.on_upgrade(move |socket| door_socket(socket, door_id, channel)).into_response()
+}
+
+async fn door_socket(mut socket: axum::extract::ws::WebSocket, door_id: String, channel: String) {
+    use axum::extract::ws::Message;
+    use tokio::sync::broadcast::error::RecvError;
+
+    let event = |status: &str| Message::Text(json!({"door_id": door_id, "status": status}).to_string());
+    // Subscribed for as long as this function runs; returning drops it
+    let mut rx = DOOR_STATUS_TX.subscribe();
+    let shutdown = priv_access::door_status::shutdown_started();
+    tokio::pin!(shutdown);
+
+    if priv_access::door_status::recent_unlock(&channel) && socket.send(event("unlocked")).await.is_err() {
+        return;
     }
 
-    // 3. Verify Schnorr Proof (Identity Binding) - SKIP FOR ADMIN
-    if payload.role != "ADMIN" {
-        if !SchnorrVerifier::verify_proof(&payload.proof) {
-             log_denied(&payload, door, "Invalid Zero-Knowledge Proof");
-             return (StatusCode::FORBIDDEN, Json(json!({"status": "failed", "message": "Invalid Zero-Knowledge Proof"}))).into_response();
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            },
+            update = rx.recv() => match update {
+                Ok((target, status)) if target == channel => {
+                    if socket.send(event(&status)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<priv_access::door_status::DoorCommand>(&text) {
+                    Ok(command) => {
+                        tracing::info!(door_id = %channel, ?command, "Display command");
+                        let _ = priv_access::door_status::DOOR_COMMAND_TX.send((channel.clone(), command));
+                    }
+                    Err(_) => {
+                        let error = ApiError::new(StatusCode::BAD_REQUEST, "Unknown command").to_json().to_string();
+                        if socket.send(Message::Text(error)).await.is_err() {
+                            break;
+                        }
+                    }
+                },
+                // Pings are answered by axum; binary frames mean nothing here
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
         }
     }
+    tracing::debug!(door_id = %channel, "Display socket closed");
+}
 
-    // 4. Log Success
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let history = AccessHistory {
-        role: payload.role.clone(),
-        door_name: door.name.clone(),
-        section: payload.section.unwrap_or_else(|| "N/A".to_string()),
-        timestamp,
-        status: "GRANTED".to_string(),
-        faculty_name: payload.faculty_name.clone(),
-        faculty_id: payload.faculty_id.clone(),
-    };
-    
-    {
-        let mut logs = ACCESS_LOGS.lock().unwrap();
-        logs.push(history);
-        save_history(&logs);
+// === 2. Mobile App ===
+#[derive(Deserialize)]
+struct ShortScanParams {
+    ts: Option<u64>,
+    nonce: Option<String>,
+    floor: Option<i32>,
+    role: Option<String>,
+    section: Option<String>,
+    faculty_id: Option<String>,
+    pin: Option<String>,
+}
+
+impl Validate for ShortScanParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("nonce", &self.nonce, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::section("section", &self.section)?;
+        validate::opt_text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("pin", &self.pin)?;
+        Ok(())
     }
+}
 
-    let _ = DOOR_STATUS_TX.send((door_id.to_string(), "unlocked".to_string()));
+async fn short_scan(
+    axum::extract::Path(door_id): axum::extract::Path<String>,
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<ShortScanParams>,
+) -> Redirect {
+    tracing::info!(door_id = %door_id, "QR scanned, mobile connecting");
+    priv_access::door_status::notify(&tenant, &door_id, "connected");
+    
+    // Stay inside the campus the QR was printed for
+    let mut url = format!("{}/mobile/scan?door={}", tenant.base_path(), encode_url(&door_id));
+    if let Some(ts) = params.ts { url.push_str(&format!("&ts={}", ts)); }
+    if let Some(nonce) = params.nonce { url.push_str(&format!("&nonce={}", encode_url(&nonce))); }
+    if let Some(floor) = params.floor { url.push_str(&format!("&floor={}", floor)); }
+    if let Some(role) = params.role { url.push_str(&format!("&role={}", encode_url(&role))); }
+    if let Some(section) = params.section { url.push_str(&format!("&section={}", encode_url(&section))); }
+    if let Some(fid) = params.faculty_id { url.push_str(&format!("&faculty_id={}", encode_url(&fid))); }
+    if let Some(p) = params.pin { url.push_str(&format!("&pin={}", encode_url(&p))); }
     
+    Redirect::to(&url)
+}
+
+#[derive(Deserialize)]
+struct StatusNotifyPayload {
+    door_id: String,
+    status: String,
+}
+
+impl Validate for StatusNotifyPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        validate::text("status", &self.status, validate::MAX_KEYWORD_LEN)?;
+        Ok(())
+    }
+}
+
+// Door kiosks report status with an API key (or any credential with "write")
+async fn api_notify_status(
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<StatusNotifyPayload>,
+) -> impl IntoResponse {
+    if let Err(e) = authorize(&headers, &tenant.id, Permission::Write) {
+        return e.into_response();
+    }
+    tracing::info!(door_id = %payload.door_id, status = %payload.status, "Door status reported");
+    priv_access::door_status::notify(&tenant, &payload.door_id, &payload.status);
+    StatusCode::OK.into_response()
+}
+
+#[derive(Deserialize)]
+struct ScanParams {
+    door: Option<String>,
+    role: Option<String>,
+}
+
+impl Validate for ScanParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("door", &self.door, validate::MAX_ID_LEN)?;
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        Ok(())
+    }
+}
+
+async fn mobile_scan(
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<ScanParams>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let mut context = Context::new();
+    context.insert("base_path", &tenant.base_path());
+    if let Some(d) = params.door {
+        context.insert("door_id", &d);
+    }
+
+    context.insert("sections", &state.sections);
+    let faculty_ids: Vec<String> = priv_access::faculty::list().into_iter().map(|f| f.id).collect();
+    context.insert("faculty_ids", &faculty_ids);
+    context.insert("geohash_precision", &priv_access::zkp::CHALLENGE_GEOHASH_LEN);
+
+    // Let the page hide controls the role can't use; the API still enforces permissions
+    if let Some(role) = params.role {
+        let role = role.to_uppercase();
+        let permissions = priv_access::rbac::get_role_permissions(&role).unwrap_or_default();
+        context.insert("role", &role);
+        context.insert("permissions", &permissions);
+    }
+
+    match state.tera.render("mobile_app.html", &context) {
+        Ok(html) => Html(html).into_response(),
+        Err(err) => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Template Error: {}", err)).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SetupParams {
+    role: Option<String>,
+    faculty_id: Option<String>,
+    pin: Option<String>,
+    password: Option<String>,
+    admin_id: Option<String>,
+    totp_code: Option<String>,
+}
+
+impl Validate for SetupParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("pin", &self.pin)?;
+        validate::opt_secret("password", &self.password)?;
+        validate::opt_text("admin_id", &self.admin_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("totp_code", &self.totp_code)?;
+        Ok(())
+    }
+}
+
+async fn mobile_setup(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidQuery(params): ValidQuery<SetupParams>,
+) -> impl IntoResponse {
+    let requested_role = params.role.unwrap_or_else(|| "STUDENT".to_string()).to_uppercase();
+    let mut subject = requested_role.to_lowercase();
+
+    // Credential checks happen here, so cap how fast one client can guess
+    let ip = priv_access::ratelimit::client_ip(&headers, peer);
+    let key = format!("setup:{}:{}", ip, requested_role);
+    if let Err(e) = state.limiter.check(&key, priv_access::config::CONFIG.rate_limit).await {
+        return e.into_response();
+    }
+
+    let credentials = match requested_role.as_str() {
+        "FACULTY" => Some(Credentials::FacultyPin {
+            id: params.faculty_id.unwrap_or_default(),
+            pin: params.pin.unwrap_or_default(),
+        }),
+        "ADMIN" => Some(admin_password(params.admin_id, params.password.unwrap_or_default(), params.totp_code)),
+        _ => None,
+    };
+    if let Some(credentials) = credentials {
+        match authorize_credentials(Some(&credentials), &tenant.id, Permission::Read) {
+            Ok(principal) => subject = principal.id,
+            Err(e) => return e.into_response(),
+        }
+    }
+
+    let (secret, role_name) = match tenant.role_secret(&requested_role) {
+        Some(s) => (s, requested_role),
+        None => (get_random_secret(), "UNKNOWN".to_string()),
+    };
+
+    let public_key = power_g(&secret);
+
+    // Issue a session for known roles so follow-up calls don't resend credentials, and a
+    // refresh token to renew it with
+    let (token, refresh_token) = if role_name == "UNKNOWN" {
+        (None, None)
+    } else {
+        let (token, claims) = priv_access::session::issue_token(&subject, &role_name, &tenant.id);
+        (Some(token), Some(priv_access::session::issue_refresh_token(&claims)))
+    };
+
+    Json(json!({
+        "secret": secret.to_string(),
+        "public_key": public_key.to_string(),
+        "role": role_name,
+        "token": token,
+        "refresh_token": refresh_token
+    })).into_response()
+}
+
+// === Faculty analytics ===
+
+// Usage summary for one faculty member: unlocks and denials per door, and when they're busiest
+async fn api_faculty_stats(
+    axum::extract::Path(faculty_id): axum::extract::Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    let principal = match authorize(&headers, &tenant.id, Permission::Write) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    // Faculty only see their own numbers; admins can look at anyone's
+    if !principal.id.eq_ignore_ascii_case(&faculty_id) && !principal.allows(Permission::Delete) {
+        return ApiError::forbidden("Cannot view another faculty member's stats").into_response();
+    }
+
+    let logs = match state.storage.logs(&tenant.id).await {
+        Ok(logs) => logs,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    // door name -> (unlocks, denials)
+    let mut per_door: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut per_hour: HashMap<String, u64> = HashMap::new();
+    let mut per_reason: HashMap<DenialReason, u64> = HashMap::new();
+    let (mut unlocks, mut denials) = (0u64, 0u64);
+    // Only attempts made with this member's own credentials. Denied entries keep the `faculty_id`
+    // the client claimed, so anyone could log those under it
+    let own = |l: &&AccessHistory| l.role == "FACULTY" && l.actor_id.as_deref().is_some_and(|id| id.eq_ignore_ascii_case(&faculty_id));
+    for entry in logs.iter().filter(own) {
+        let counts = per_door.entry(entry.door_name.clone()).or_default();
+        if entry.status == "GRANTED" {
+            unlocks += 1;
+            counts.0 += 1;
+            if let Some(hour) = local_hour(&entry.timestamp) {
+                *per_hour.entry(hour).or_default() += 1;
+            }
+        } else if entry.status.starts_with("DENIED") {
+            denials += 1;
+            counts.1 += 1;
+            // Entries from before reasons were stored only count toward the total
+            if let Some(reason) = entry.denial_reason {
+                *per_reason.entry(reason).or_default() += 1;
+            }
+        }
+    }
+
+    let mut doors: Vec<_> = per_door.into_iter().collect();
+    doors.sort_by(|a, b| b.1.0.cmp(&a.1.0).then_with(|| a.0.cmp(&b.0)));
+    let doors: Vec<_> = doors.into_iter()
+        .map(|(door, (unlocks, denials))| json!({"door_name": door, "unlocks": unlocks, "denials": denials}))
+        .collect();
+
+    let mut hours: Vec<_> = per_hour.into_iter().collect();
+    hours.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let busiest_hours: Vec<_> = hours.into_iter()
+        .take(3)
+        .map(|(hour, count)| json!({"hour": format!("{}:00", hour), "unlocks": count}))
+        .collect();
+
+    Json(json!({
+        "faculty_id": faculty_id,
+        "total_unlocks": unlocks,
+        "total_denials": denials,
+        "denials_by_reason": per_reason.into_iter().map(|(reason, count)| (reason.to_string(), count)).collect::<std::collections::BTreeMap<_, _>>(),
+        "doors": doors,
+        "busiest_hours": busiest_hours
+    })).into_response()
+}
+
+// === Faculty ===
+
+#[derive(Deserialize)]
+struct CreateFacultyPayload {
+    id: String,
+    #[serde(default)]
+    name: String,
+    pin: String,
+}
+
+impl Validate for CreateFacultyPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("id", &self.id, validate::MAX_ID_LEN)?;
+        validate::text("name", &self.name, validate::MAX_NAME_LEN)?;
+        validate::secret("pin", &self.pin)?;
+        Ok(())
+    }
+}
+
+fn faculty_json(f: &priv_access::faculty::Faculty) -> serde_json::Value {
+    json!({ "id": f.id, "name": f.display_name() })
+}
+
+async fn api_list_faculty(_admin: AdminAuth) -> impl IntoResponse {
+    let faculty: Vec<_> = priv_access::faculty::list().iter().map(faculty_json).collect();
+    Json(json!({ "faculty": faculty })).into_response()
+}
+
+async fn api_create_faculty(
+    AdminAuth(principal): AdminAuth,
+    ValidJson(payload): ValidJson<CreateFacultyPayload>,
+) -> impl IntoResponse {
+    let faculty = match tokio::task::spawn_blocking(move || priv_access::faculty::add(&payload.id, &payload.name, &payload.pin)).await {
+        Ok(Ok(f)) => f,
+        Ok(Err(e @ priv_access::faculty::FacultyError::DuplicateId(_))) => return ApiError::new(StatusCode::CONFLICT, e.to_string()).into_response(),
+        Ok(Err(e @ priv_access::faculty::FacultyError::Hash(_))) => return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Ok(Err(e)) => return ApiError::new(StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        Err(e) => return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    tracing::info!(faculty_id = %faculty.id, by = %principal.id, "Faculty added");
+    (StatusCode::CREATED, Json(json!({
+        "status": "success",
+        "faculty": faculty_json(&faculty)
+    }))).into_response()
+}
+
+// Faculty span every campus, so sections they hold anywhere are released with them
+async fn api_delete_faculty(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::Path(faculty_id): axum::extract::Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(faculty) = priv_access::faculty::remove(&faculty_id) else {
+        return ApiError::new(StatusCode::NOT_FOUND, "Faculty not found").into_response();
+    };
+
+    let mut released = Vec::new();
+    for campus in priv_access::tenant::tenant_ids().iter().filter_map(|id| priv_access::tenant::get_tenant(id)) {
+        for section in &state.sections {
+            let assignment = match state.storage.assignment(&campus.id, section).await {
+                Ok(a) => a,
+                Err(e) => return ApiError::from(e).into_response(),
+            };
+            let Some((room, _)) = assignment.filter(|(_, name)| faculty.assigned(name)) else {
+                continue;
+            };
+            if let Err(e) = state.storage.unassign(&campus.id, section).await {
+                return ApiError::from(e).into_response();
+            }
+            campus.record_assignment_change(&principal.id, &principal.role, section, Some(room.clone()), None);
+            released.push(json!({ "tenant": campus.id, "section": section, "room": room }));
+        }
+    }
+
+    let passkeys = state.passkeys.remove_faculty(&faculty.id);
+    tracing::info!(faculty_id = %faculty.id, by = %principal.id, sections_released = released.len(), passkeys_dropped = passkeys, "Faculty removed");
     Json(json!({
         "status": "success",
-        "message": format!("Access Granted to {}", payload.role),
-        "role": payload.role
+        "message": format!("{} removed", faculty.display_name()),
+        "unassigned_sections": released
     })).into_response()
 }
 
-fn log_denied(payload: &VerifyPayload, door: &Door, reason: &str) {
-    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let history = AccessHistory {
-        role: payload.role.clone(),
-        door_name: door.name.clone(),
-        section: payload.section.clone().unwrap_or_else(|| "N/A".to_string()),
-        timestamp,
-        status: format!("DENIED: {}", reason),
-        faculty_name: payload.faculty_name.clone(),
-        faculty_id: payload.faculty_id.clone(),
+// === Admins ===
+
+#[derive(Deserialize)]
+struct CreateAdminPayload {
+    id: String,
+    #[serde(default)]
+    name: String,
+    password: String,
+}
+
+impl Validate for CreateAdminPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("id", &self.id, validate::MAX_ID_LEN)?;
+        validate::text("name", &self.name, validate::MAX_NAME_LEN)?;
+        validate::secret("password", &self.password)?;
+        Ok(())
+    }
+}
+
+fn admin_json(a: &priv_access::admins::Admin) -> serde_json::Value {
+    json!({ "id": a.id, "name": a.display_name() })
+}
+
+// Admins are managed by admins only; an API key with delete rights isn't one
+fn require_admin_account(principal: &priv_access::rbac::Principal) -> Result<(), ApiError> {
+    if principal.role == "ADMIN" && priv_access::admins::find(&principal.id).is_some() {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("Only an admin account can manage admins"))
+    }
+}
+
+async fn api_list_admins(AdminAuth(principal): AdminAuth) -> impl IntoResponse {
+    if let Err(e) = require_admin_account(&principal) {
+        return e.into_response();
+    }
+    let admins: Vec<_> = priv_access::admins::list().iter().map(admin_json).collect();
+    Json(json!({ "admins": admins })).into_response()
+}
+
+async fn api_create_admin(
+    AdminAuth(principal): AdminAuth,
+    ValidJson(payload): ValidJson<CreateAdminPayload>,
+) -> impl IntoResponse {
+    if let Err(e) = require_admin_account(&principal) {
+        return e.into_response();
+    }
+    let admin = match tokio::task::spawn_blocking(move || priv_access::admins::add(&payload.id, &payload.name, &payload.password)).await {
+        Ok(Ok(a)) => a,
+        Ok(Err(e @ priv_access::admins::AdminError::DuplicateId(_))) => return ApiError::new(StatusCode::CONFLICT, e.to_string()).into_response(),
+        Ok(Err(e @ priv_access::admins::AdminError::Hash(_))) => return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Ok(Err(e)) => return ApiError::new(StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        Err(e) => return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    tracing::info!(admin_id = %admin.id, by = %principal.id, "Admin added");
+    (StatusCode::CREATED, Json(json!({
+        "status": "success",
+        "admin": admin_json(&admin)
+    }))).into_response()
+}
+
+async fn api_delete_admin(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::Path(admin_id): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if let Err(e) = require_admin_account(&principal) {
+        return e.into_response();
+    }
+    let admin = match priv_access::admins::remove(&admin_id) {
+        Ok(a) => a,
+        Err(e @ priv_access::admins::AdminError::NotFound) => return ApiError::new(StatusCode::NOT_FOUND, e.to_string()).into_response(),
+        Err(e) => return ApiError::new(StatusCode::CONFLICT, e.to_string()).into_response(),
+    };
+    tracing::info!(admin_id = %admin.id, by = %principal.id, "Admin removed");
+    Json(json!({
+        "status": "success",
+        "message": format!("{} removed", admin.display_name())
+    })).into_response()
+}
+
+// === Faculty passkeys ===
+
+// Passkeys are bound to the site: PRIVACCESS_WEBAUTHN_ORIGIN, or else this request's host.
+// Browsers only run WebAuthn on https, or plain http on localhost.
+fn relying_party(headers: &axum::http::HeaderMap) -> Result<RelyingParty, ApiError> {
+    let origin = match &priv_access::config::CONFIG.webauthn_origin {
+        Some(origin) => origin.clone(),
+        None => {
+            let host = headers.get("host").and_then(|h| h.to_str().ok()).unwrap_or("localhost");
+            let is_local = host.starts_with("localhost") || host.starts_with("127.0.0.1");
+            format!("{}://{}", if is_local { url_scheme() } else { "https" }, host)
+        }
+    };
+    RelyingParty::from_origin(&origin).ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "Cannot tell which site passkeys are for"))
+}
+
+fn webauthn_rejection(e: &WebAuthnError) -> ApiError {
+    let status = if e.is_bad_request() { StatusCode::BAD_REQUEST } else { StatusCode::UNAUTHORIZED };
+    let error = ApiError::coded(status, ErrorCode::PasskeyInvalid, e.to_string()).with_reason(e.code());
+    match e.field() {
+        Some(field) => error.with_field(field),
+        None => error,
+    }
+}
+
+// Passkey challenges share the /api/challenge store, scoped to the ceremony and faculty member
+// in place of a door so one can't answer for another
+fn passkey_scope(ceremony: &str, faculty_id: &str) -> String {
+    format!("webauthn-{}:{}", ceremony, faculty_id.trim().to_ascii_lowercase())
+}
+
+async fn issue_passkey_challenge(state: &AppState, tenant: &TenantState, scope: &str) -> Result<String, ApiError> {
+    let challenge = priv_access::webauthn::new_challenge();
+    state.storage.issue_challenge(&tenant.id, scope, &challenge, PASSKEY_CHALLENGE_TTL_SECS).await?;
+    Ok(challenge)
+}
+
+// The challenge a response answers, spent whether or not the rest of it verifies
+async fn take_passkey_challenge(state: &AppState, tenant: &TenantState, client_data_json: &str, scope: &str) -> Result<String, ApiError> {
+    let challenge = priv_access::webauthn::challenge_of(client_data_json).map_err(|e| webauthn_rejection(&e))?;
+    match state.storage.take_challenge(&tenant.id, &challenge).await? {
+        Some(issued_for) if issued_for == scope => Ok(challenge),
+        _ => Err(ApiError::coded(StatusCode::UNAUTHORIZED, ErrorCode::ChallengeInvalid, "Passkey challenge is unknown, expired or already used")),
+    }
+}
+
+// The faculty member a passkey assertion from /login/start proves, for /login/finish and /api/verify
+async fn passkey_principal(
+    state: &AppState,
+    tenant: &TenantState,
+    headers: &axum::http::HeaderMap,
+    faculty_id: &str,
+    assertion: &AssertionResponse,
+) -> Result<priv_access::rbac::Principal, ApiError> {
+    let rp = relying_party(headers)?;
+    let challenge = take_passkey_challenge(state, tenant, &assertion.response.client_data_json, &passkey_scope("login", faculty_id)).await?;
+    let (faculty, passkey) = priv_access::faculty::find(faculty_id.trim())
+        .and_then(|f| state.passkeys.find(&f.id, &assertion.id).map(|p| (f, p)))
+        .ok_or_else(|| ApiError::unauthorized("Invalid Faculty ID or passkey"))?;
+    let sign_count = rp.verify_assertion(assertion, &challenge, &passkey).map_err(|e| webauthn_rejection(&e))?;
+    state.passkeys.update_sign_count(&passkey.credential_id, sign_count);
+    Ok(priv_access::rbac::Principal { id: faculty.id, role: "FACULTY".to_string(), session_id: None, permissions: None })
+}
+
+// Registering needs the faculty member's existing credentials (PIN headers or their session)
+fn passkey_owner(headers: &axum::http::HeaderMap, tenant: &TenantState) -> Result<priv_access::faculty::Faculty, ApiError> {
+    let principal = authorize(headers, &tenant.id, Permission::Read)?;
+    if principal.role != "FACULTY" {
+        return Err(ApiError::forbidden("Only faculty can register passkeys"));
+    }
+    priv_access::faculty::find(&principal.id).ok_or_else(|| ApiError::unauthorized("Faculty account has been removed"))
+}
+
+// Options for navigator.credentials.create(), so a faculty member can add a passkey
+async fn api_passkey_register_start(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    let (faculty, rp) = match passkey_owner(&headers, &tenant).and_then(|f| relying_party(&headers).map(|rp| (f, rp))) {
+        Ok(v) => v,
+        Err(e) => return e.into_response(),
+    };
+    let challenge = match issue_passkey_challenge(&state, &tenant, &passkey_scope("register", &faculty.id)).await {
+        Ok(c) => c,
+        Err(e) => return e.into_response(),
+    };
+    let existing = state.passkeys.for_faculty(&faculty.id);
+    Json(json!({ "status": "success", "publicKey": rp.creation_options(&faculty, &challenge, &existing) })).into_response()
+}
+
+async fn api_passkey_register_finish(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    Json(credential): Json<RegistrationResponse>,
+) -> impl IntoResponse {
+    let (faculty, rp) = match passkey_owner(&headers, &tenant).and_then(|f| relying_party(&headers).map(|rp| (f, rp))) {
+        Ok(v) => v,
+        Err(e) => return e.into_response(),
+    };
+    let scope = passkey_scope("register", &faculty.id);
+    let challenge = match take_passkey_challenge(&state, &tenant, &credential.response.client_data_json, &scope).await {
+        Ok(c) => c,
+        Err(e) => return e.into_response(),
+    };
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let passkey = match rp.finish_registration(&credential, &challenge, &faculty.id, now) {
+        Ok(p) => p,
+        Err(e) => return webauthn_rejection(&e).into_response(),
+    };
+    if !state.passkeys.register(passkey.clone()) {
+        return ApiError::new(StatusCode::CONFLICT, "This passkey is already registered").into_response();
+    }
+    tracing::info!(faculty_id = %faculty.id, "Passkey registered");
+    (StatusCode::CREATED, Json(json!({
+        "status": "success",
+        "faculty_id": faculty.id,
+        "credential_id": passkey.credential_id
+    }))).into_response()
+}
+
+#[derive(Deserialize)]
+struct PasskeyLoginStart {
+    faculty_id: String,
+}
+
+impl Validate for PasskeyLoginStart {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+// Options for navigator.credentials.get() over the member's passkeys
+async fn api_passkey_login_start(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<PasskeyLoginStart>,
+) -> impl IntoResponse {
+    let rp = match relying_party(&headers) {
+        Ok(rp) => rp,
+        Err(e) => return e.into_response(),
     };
-    let mut logs = ACCESS_LOGS.lock().unwrap();
-    logs.push(history);
-    save_history(&logs);
+    // The same answer for unknown ids and members without a passkey
+    let passkeys = priv_access::faculty::find(payload.faculty_id.trim())
+        .map(|f| state.passkeys.for_faculty(&f.id))
+        .unwrap_or_default();
+    if passkeys.is_empty() {
+        return ApiError::new(StatusCode::NOT_FOUND, "No passkey registered for this Faculty ID").into_response();
+    }
+    let challenge = match issue_passkey_challenge(&state, &tenant, &passkey_scope("login", &payload.faculty_id)).await {
+        Ok(c) => c,
+        Err(e) => return e.into_response(),
+    };
+    Json(json!({ "status": "success", "publicKey": rp.request_options(&challenge, &passkeys) })).into_response()
+}
+
+#[derive(Deserialize)]
+struct PasskeyLoginFinish {
+    faculty_id: String,
+    credential: AssertionResponse,
+}
+
+impl Validate for PasskeyLoginFinish {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+// Trade an assertion for a faculty session token, as /mobile/setup does for a PIN
+async fn api_passkey_login_finish(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<PasskeyLoginFinish>,
+) -> impl IntoResponse {
+    let ip = priv_access::ratelimit::client_ip(&headers, peer);
+    if let Err(e) = state.limiter.check(&format!("setup:{}:FACULTY", ip), priv_access::config::CONFIG.rate_limit).await {
+        return e.into_response();
+    }
+    let principal = match passkey_principal(&state, &tenant, &headers, &payload.faculty_id, &payload.credential).await {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    let (token, claims) = priv_access::session::issue_token(&principal.id, &principal.role, &tenant.id);
+    Json(json!({
+        "status": "success",
+        "faculty_id": principal.id,
+        "token": token,
+        "expires_at": claims.exp
+    })).into_response()
+}
+
+// === API keys ===
+
+#[derive(Deserialize)]
+struct CreateKeyPayload {
+    name: String,
+    permissions: Vec<String>,
+}
+
+impl Validate for CreateKeyPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("name", &self.name, validate::MAX_NAME_LEN)?;
+        self.permissions.iter().try_for_each(|p| validate::text("permissions", p, validate::MAX_KEYWORD_LEN))?;
+        Ok(())
+    }
+}
+
+// Keys are managed by admins; the plaintext key is only ever shown in this response
+async fn api_create_key(
+    AdminAuth(principal): AdminAuth,
+    Tenant(tenant): Tenant,
+    ValidJson(payload): ValidJson<CreateKeyPayload>,
+) -> impl IntoResponse {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return ApiError::new(StatusCode::BAD_REQUEST, "Key name is required").into_response();
+    }
+    let mut permissions = Vec::new();
+    for p in &payload.permissions {
+        match Permission::parse(p) {
+            Some(p) => permissions.push(p.as_str().to_string()),
+            None => return ApiError::new(StatusCode::BAD_REQUEST, format!("Unknown permission '{}'", p)).into_response(),
+        }
+    }
+
+    let (key, secret) = priv_access::apikeys::create(&tenant.id, name, permissions, &principal.id);
+    tracing::info!(key_id = %key.id, name = %key.name, by = %principal.id, "API key created");
+    (StatusCode::CREATED, Json(json!({
+        "status": "success",
+        "key": secret,
+        "id": key.id,
+        "name": key.name,
+        "permissions": key.permissions
+    }))).into_response()
+}
+
+async fn api_list_keys(_admin: AdminAuth, Tenant(tenant): Tenant) -> impl IntoResponse {
+    let keys: Vec<_> = priv_access::apikeys::list(&tenant.id).into_iter()
+        .map(|k| json!({
+            "id": k.id,
+            "name": k.name,
+            "permissions": k.permissions,
+            "created_by": k.created_by,
+            "created_at": k.created_at,
+            "revoked": k.revoked
+        }))
+        .collect();
+    Json(json!({ "keys": keys })).into_response()
+}
+
+async fn api_revoke_key(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::Path(key_id): axum::extract::Path<String>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    if !priv_access::apikeys::revoke(&tenant.id, &key_id) {
+        return ApiError::new(StatusCode::NOT_FOUND, "API key not found").into_response();
+    }
+    tracing::info!(%key_id, by = %principal.id, "API key revoked");
+    Json(json!({
+        "status": "success",
+        "message": "API key revoked",
+        "id": key_id
+    })).into_response()
+}
+
+// === Key revocation ===
+
+#[derive(Deserialize)]
+struct RevokePayload {
+    public_key: String,
+}
+
+impl Validate for RevokePayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("public_key", &self.public_key, validate::MAX_TOKEN_LEN)?;
+        Ok(())
+    }
+}
+
+// Refuse a public key at /api/verify from now on, e.g. when a device is lost
+async fn api_revoke_public_key(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<RevokePayload>,
+) -> impl IntoResponse {
+    let public_key = payload.public_key.trim();
+    if public_key.is_empty() || public_key.len() > priv_access::config::CONFIG.max_proof_field_len {
+        return ApiError::new(StatusCode::BAD_REQUEST, "A public key is required").into_response();
+    }
+    let newly_revoked = state.revoked.revoke(public_key);
+    if newly_revoked {
+        tracing::info!(public_key = %public_key.chars().take(16).collect::<String>(), by = %principal.id, "Public key revoked");
+    }
+    Json(json!({
+        "status": "success",
+        "message": if newly_revoked { "Public key revoked" } else { "Public key was already revoked" },
+        "public_key": public_key
+    })).into_response()
+}
+
+async fn api_restore_public_key(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::Path(public_key): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if !state.revoked.restore(&public_key) {
+        return ApiError::new(StatusCode::NOT_FOUND, "Public key is not revoked").into_response();
+    }
+    tracing::info!(public_key = %public_key.chars().take(16).collect::<String>(), by = %principal.id, "Public key reinstated");
+    Json(json!({
+        "status": "success",
+        "message": "Public key reinstated",
+        "public_key": public_key
+    })).into_response()
+}
+
+// === ed25519 keys ===
+
+#[derive(Deserialize)]
+struct Ed25519KeyPayload {
+    public_key: String,
+    role: String,
+    // Required for FACULTY: the member whose PIN must come with the key's unlocks
+    faculty_id: Option<String>,
+}
+
+impl Validate for Ed25519KeyPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("public_key", &self.public_key, validate::MAX_TOKEN_LEN)?;
+        validate::text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::opt_text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+async fn api_list_ed25519_keys(
+    _admin: AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+) -> impl IntoResponse {
+    Json(json!({ "status": "success", "keys": state.ed25519_keys.list(&tenant.id) }))
+}
+
+// Allow an ed25519 key to stand in for a Schnorr proof for one role (and faculty member)
+async fn api_register_ed25519_key(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidJson(payload): ValidJson<Ed25519KeyPayload>,
+) -> impl IntoResponse {
+    if let Err(e) = priv_access::identity::check_public_key(&payload.public_key) {
+        return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MalformedProof, "public_key must be a 32-byte ed25519 key in hex")
+            .with_reason(e.code())
+            .with_field("public_key")
+            .into_response();
+    }
+    let role = payload.role.trim().to_uppercase();
+    let faculty_id = match (role.as_str(), payload.faculty_id.as_deref().map(str::trim)) {
+        ("STUDENT", _) => None,
+        ("FACULTY", Some(id)) => match priv_access::faculty::find(id) {
+            Some(faculty) => Some(faculty.id),
+            None => return ApiError::new(StatusCode::NOT_FOUND, "Faculty not found").into_response(),
+        },
+        ("FACULTY", None) => {
+            return ApiError::new(StatusCode::BAD_REQUEST, "FACULTY keys need a faculty_id").with_field("faculty_id").into_response()
+        }
+        _ => return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidRole, "Only STUDENT and FACULTY keys can be registered").into_response(),
+    };
+
+    let key = priv_access::identity::RegisteredKey {
+        public_key: payload.public_key.trim().to_ascii_lowercase(),
+        tenant: tenant.id.clone(),
+        role,
+        faculty_id,
+    };
+    if !state.ed25519_keys.register(key.clone()) {
+        return ApiError::new(StatusCode::CONFLICT, "This key is already registered").into_response();
+    }
+    tracing::info!(public_key = %key.public_key.chars().take(16).collect::<String>(), role = %key.role, by = %principal.id, "ed25519 key registered");
+    (StatusCode::CREATED, Json(json!({ "status": "success", "key": key }))).into_response()
+}
+
+async fn api_remove_ed25519_key(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    axum::extract::Path(public_key): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    if !state.ed25519_keys.remove(&tenant.id, &public_key) {
+        return ApiError::new(StatusCode::NOT_FOUND, "Key is not registered").into_response();
+    }
+    tracing::info!(public_key = %public_key.chars().take(16).collect::<String>(), by = %principal.id, "ed25519 key removed");
+    Json(json!({
+        "status": "success",
+        "message": "Key removed",
+        "public_key": public_key
+    })).into_response()
+}
+
+// === Simulation ===
+
+// Most synthetic attempts one /api/simulate call makes
+const MAX_SIMULATED_ATTEMPTS: u32 = 500;
+
+#[derive(Deserialize)]
+struct SimulatePayload {
+    count: u32,
+    role: Option<String>,
+    door_id: String,
+}
+
+impl Validate for SimulatePayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::opt_text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+// How a synthetic attempt goes wrong. Every fourth attempt takes the next fault in turn,
+// so a run is mostly grants with the common denials mixed in.
+#[derive(Clone, Copy, PartialEq)]
+enum SimulatedFault {
+    None,
+    // A proof whose response was altered, or a forged guest pass
+    BadProof,
+    StaleQr,
+    TooFar,
+}
+
+const SIMULATED_FAULTS: [SimulatedFault; 3] = [SimulatedFault::BadProof, SimulatedFault::StaleQr, SimulatedFault::TooFar];
+
+// What a phone would send after scanning the door's QR, built the same way the mobile app does
+async fn simulated_attempt(
+    state: &AppState,
+    tenant: &TenantState,
+    door_id: &str,
+    door: &Door,
+    role: &str,
+    section: Option<&str>,
+    fault: SimulatedFault,
+) -> Result<VerifyPayload, ApiError> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let geohash = priv_access::geo::encode(door.lat, door.lon, priv_access::zkp::CHALLENGE_GEOHASH_LEN)
+        .unwrap_or_else(|_| door.geohash_prefix.clone());
+    let mut body = json!({
+        "door_id": door_id,
+        "role": role,
+        "geohash": geohash,
+        "lat": if fault == SimulatedFault::TooFar { door.lat + 0.01 } else { door.lat },
+        "lon": door.lon,
+        "floor": door.floor,
+        "nonce": hex::encode(rand::random::<[u8; 8]>()),
+        "qr_timestamp": if fault == SimulatedFault::StaleQr { now - 60 } else { now },
+    });
+    if role == "GUEST" {
+        let (mut token, _) = priv_access::guest::issue(&tenant.id, door_id, now + CHALLENGE_TTL_SECS);
+        if fault == SimulatedFault::BadProof {
+            token.push('0');
+        }
+        body["guest_pass"] = json!(token);
+    } else {
+        let secret = tenant.role_secret(role).unwrap_or_else(get_random_secret);
+        let nonce = issue_door_challenge(state, tenant, door_id).await?;
+        let mut proof = priv_access::zkp::SchnorrProver::new(secret).generate_proof(geohash, &nonce);
+        if fault == SimulatedFault::BadProof {
+            let s: num_bigint::BigUint = proof.response.parse().unwrap_or_default();
+            proof.response = ((s + 1u32) % &*priv_access::crypto::Q).to_string();
+        }
+        body["proof"] = json!(proof);
+        body["section"] = json!(section);
+    }
+    serde_json::from_value(body).map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Serialize, Default)]
+struct SimulationSummary {
+    granted: u32,
+    denied: u32,
+    // Denials by reason, or by error code where there's no finer reason
+    reasons: std::collections::BTreeMap<String, u32>,
+}
+
+// Run `count` attempts through the real /api/verify checks, so the history they leave is what
+// real traffic would leave and the proofs are verified like any other
+async fn run_simulation(state: &Arc<AppState>, tenant: &Arc<TenantState>, door_id: &str, role: &str, count: u32) -> Result<SimulationSummary, ApiError> {
+    let door = tenant.door(door_id).ok_or_else(|| ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found"))?;
+    // Students get into a room through a section assigned to it; with none, every attempt is refused
+    let section = state.storage.room_sections(&tenant.id, door_id).await?.into_iter().next().map(|(section, _)| section);
+
+    let mut summary = SimulationSummary::default();
+    for i in 0..count {
+        let fault = if i % 4 == 3 { SIMULATED_FAULTS[(i / 4) as usize % SIMULATED_FAULTS.len()] } else { SimulatedFault::None };
+        let payload = simulated_attempt(state, tenant, door_id, &door, role, section.as_deref(), fault).await?;
+        // Each from its own documentation-range address, so the per-IP rate limit doesn't decide the outcome
+        let peer = std::net::SocketAddr::from(([198, 51, 100, (i % 254 + 1) as u8], 0));
+        let response = verify_access(state.clone(), peer, tenant.clone(), axum::http::HeaderMap::new(), payload).await;
+        if response.status().is_success() {
+            summary.granted += 1;
+            continue;
+        }
+        summary.denied += 1;
+        let body = axum::body::to_bytes(response.into_body(), MAX_BODY_BYTES).await.ok()
+            .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+            .unwrap_or_default();
+        let reason = body["reason"].as_str().or(body["error"]["code"].as_str()).unwrap_or("unknown").to_string();
+        *summary.reasons.entry(reason).or_default() += 1;
+    }
+    Ok(summary)
+}
+
+#[derive(Deserialize)]
+struct DevProvePayload {
+    role: String,
+    geohash: String,
+    // Answer a fresh challenge for this door, so the proof can go straight to /api/verify
+    door_id: Option<String>,
+}
+
+impl Validate for DevProvePayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::geohash("geohash", &self.geohash)?;
+        validate::opt_text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+// A ready-to-submit proof made with the role's own key, for driving /api/verify from curl
+// without a phone. Demo mode only: anyone who can call it can prove any role.
+async fn api_dev_prove(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidJson(payload): ValidJson<DevProvePayload>,
+) -> impl IntoResponse {
+    if !priv_access::config::CONFIG.demo_mode {
+        return ApiError::forbidden("Proof generation is only available with PRIVACCESS_DEMO_MODE=true").into_response();
+    }
+    let role = payload.role.trim().to_uppercase();
+    match dev_proof(&state, &tenant, &role, &payload.geohash, payload.door_id.as_deref()).await {
+        Ok(proof) => Json(json!({ "status": "success", "role": role, "proof": proof })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn dev_proof(state: &AppState, tenant: &TenantState, role: &str, geohash: &str, door_id: Option<&str>) -> Result<Proof, ApiError> {
+    let Some(secret) = tenant.role_secret(role) else {
+        return Err(ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidRole, "Unknown Role").with_field("role"));
+    };
+    let nonce = match door_id.map(str::trim) {
+        Some(door_id) if tenant.door(door_id).is_none() => {
+            return Err(ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found"));
+        }
+        Some(door_id) => issue_door_challenge(state, tenant, door_id).await?,
+        None => String::new(),
+    };
+    Ok(priv_access::zkp::SchnorrProver::new(secret).generate_proof(geohash.to_string(), &nonce))
+}
+
+// Fill the history with synthetic unlock attempts for demos and load tests. Demo mode only:
+// they're indistinguishable from real entries once logged.
+async fn api_simulate(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    ValidJson(payload): ValidJson<SimulatePayload>,
+) -> impl IntoResponse {
+    if !priv_access::config::CONFIG.demo_mode {
+        return ApiError::forbidden("Simulation is only available with PRIVACCESS_DEMO_MODE=true").into_response();
+    }
+    if payload.count == 0 || payload.count > MAX_SIMULATED_ATTEMPTS {
+        return ApiError::new(StatusCode::BAD_REQUEST, format!("count must be between 1 and {}", MAX_SIMULATED_ATTEMPTS))
+            .with_field("count")
+            .into_response();
+    }
+    let role = payload.role.as_deref().unwrap_or("STUDENT").trim().to_uppercase();
+    if !matches!(role.as_str(), "STUDENT" | "GUEST") {
+        return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidRole, "Only STUDENT and GUEST attempts can be simulated")
+            .with_field("role")
+            .into_response();
+    }
+    let door_id = payload.door_id.trim();
+    let summary = match run_simulation(&state, &tenant, door_id, &role, payload.count).await {
+        Ok(s) => s,
+        Err(e) => return e.into_response(),
+    };
+    tracing::info!(door_id = %door_id, %role, attempts = payload.count, granted = summary.granted, denied = summary.denied,
+        by = %principal.id, "Synthetic attempts simulated");
+    Json(json!({
+        "status": "success",
+        "door_id": door_id,
+        "role": role,
+        "attempts": payload.count,
+        "granted": summary.granted,
+        "denied": summary.denied,
+        "reasons": summary.reasons
+    })).into_response()
+}
+
+// === Admin TOTP ===
+
+// Side of the enrollment QR, in pixels
+const TOTP_QR_SIZE: u32 = 300;
+
+// How recently the dashboard must have logged in to enroll TOTP
+const TOTP_ENROLL_LOGIN_SECS: u64 = 5 * 60;
+
+// One-time enrollment of an admin's authenticator app; from then on the admin's password needs
+// its code everywhere. Binding the second factor takes a fresh dashboard login, so whoever holds
+// a leaked password or an old cookie can't enroll their own app first.
+async fn api_provision_totp(AdminAuth(principal): AdminAuth, Tenant(tenant): Tenant, headers: axum::http::HeaderMap) -> impl IntoResponse {
+    if principal.role != "ADMIN" {
+        return ApiError::forbidden("Only admins can enroll TOTP").into_response();
+    }
+    if !priv_access::session::fresh_admin_session(&headers, &principal.id, &tenant.id, TOTP_ENROLL_LOGIN_SECS) {
+        let message = format!("Log in to the dashboard again (within {} minutes) to enroll TOTP", TOTP_ENROLL_LOGIN_SECS / 60);
+        return ApiError::unauthorized(message).into_response();
+    }
+    let Some(url) = priv_access::rbac::provision_totp(&principal.id) else {
+        return ApiError::new(StatusCode::CONFLICT, "TOTP is already provisioned for this admin").into_response();
+    };
+    let qr_data_url = match qr_png_data_url(&url, TOTP_QR_SIZE) {
+        Ok(qr) => qr,
+        Err(e) => return qr_error(e).into_response(),
+    };
+    tracing::info!(admin_id = %principal.id, "TOTP provisioned");
+    Json(json!({
+        "status": "success",
+        "message": "Scan the QR code with an authenticator app; it won't be shown again",
+        "otpauth_url": url,
+        "qr_data_url": qr_data_url
+    })).into_response()
+}
+
+#[derive(Deserialize)]
+struct AdminLoginPayload {
+    password: String,
+    // The default admin if absent
+    admin_id: Option<String>,
+    // Needed once the admin has enrolled an authenticator app
+    totp_code: Option<String>,
+}
+
+impl Validate for AdminLoginPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::secret("password", &self.password)?;
+        validate::opt_text("admin_id", &self.admin_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("totp_code", &self.totp_code)?;
+        Ok(())
+    }
+}
+
+// Trade the admin password for a signed HttpOnly session cookie, so the dashboard doesn't resend
+// the password with every request. The session ends on logout or after PRIVACCESS_ADMIN_IDLE_SECS unused.
+async fn api_admin_login(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<AdminLoginPayload>,
+) -> impl IntoResponse {
+    let ip = priv_access::ratelimit::client_ip(&headers, peer);
+    let key = format!("admin_login:{}", ip);
+    if let Err(e) = state.limiter.check(&key, priv_access::config::CONFIG.rate_limit).await {
+        return e.into_response();
+    }
+
+    let credentials = admin_password(payload.admin_id, payload.password, payload.totp_code);
+    let admin = match authorize_credentials(Some(&credentials), &tenant.id, Permission::Delete) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    let jar = priv_access::session::start_admin_session(&admin.id, &tenant.id);
+    tracing::info!(admin_id = %admin.id, "Admin logged in");
+    (
+        jar,
+        Json(json!({
+            "status": "success",
+            "message": "Logged in",
+            "idle_timeout_secs": priv_access::config::CONFIG.admin_idle_secs
+        })),
+    ).into_response()
+}
+
+// Ends the session server-side too, so a copy of the cookie stops working
+async fn api_admin_logout(headers: axum::http::HeaderMap) -> impl IntoResponse {
+    let (ended, jar) = priv_access::session::end_admin_session(&headers);
+    (
+        jar,
+        Json(json!({
+            "status": "success",
+            "message": "Logged out",
+            "ended": ended
+        })),
+    )
+}
+
+// Side of a guest pass QR, in pixels
+const GUEST_QR_SIZE: u32 = 300;
+
+#[derive(Deserialize)]
+struct GuestPassRequest {
+    door_id: String,
+    valid_minutes: u64,
+}
+
+impl Validate for GuestPassRequest {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+// Mint a single-use pass a visitor can open one door with, without being provisioned
+async fn api_create_guest_pass(
+    AdminAuth(principal): AdminAuth,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(req): ValidJson<GuestPassRequest>,
+) -> impl IntoResponse {
+    let door_id = req.door_id.trim();
+    if tenant.door(door_id).is_none() {
+        return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response();
+    }
+    if req.valid_minutes == 0 || req.valid_minutes > priv_access::guest::MAX_GUEST_MINUTES {
+        let msg = format!("valid_minutes must be between 1 and {}", priv_access::guest::MAX_GUEST_MINUTES);
+        return ApiError::new(StatusCode::BAD_REQUEST, msg).with_field("valid_minutes").into_response();
+    }
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let expires_at = now + req.valid_minutes * 60;
+    let (token, _) = priv_access::guest::issue(&tenant.id, door_id, expires_at);
+    let url = format!("{}/mobile?role=GUEST&guest_pass={}", public_base_url(&state, &headers, &tenant), encode_url(&token));
+    let qr_data_url = match qr_png_data_url(&url, GUEST_QR_SIZE) {
+        Ok(qr) => qr,
+        Err(e) => return qr_error(e).into_response(),
+    };
+    tracing::info!(door_id = %door_id, by = %principal.id, valid_minutes = req.valid_minutes, "Guest pass issued");
+    Json(json!({
+        "status": "success",
+        "door_id": door_id,
+        "token": token,
+        "url": url,
+        "expires_at": expires_at,
+        "qr_data_url": qr_data_url
+    })).into_response()
+}
+
+// === Roles ===
+
+async fn api_list_roles() -> impl IntoResponse {
+    Json(json!({ "roles": priv_access::rbac::role_names() }))
+}
+
+async fn api_list_sections(axum::extract::State(state): axum::extract::State<Arc<AppState>>) -> impl IntoResponse {
+    Json(json!({ "sections": state.sections }))
+}
+
+async fn api_role_permissions(axum::extract::Path(role): axum::extract::Path<String>) -> impl IntoResponse {
+    let role = role.to_uppercase();
+    match priv_access::rbac::get_role_permissions(&role) {
+        Some(permissions) => Json(json!({
+            "role": role,
+            "permissions": permissions
+        })).into_response(),
+        None => ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::InvalidRole, "Unknown Role").into_response(),
+    }
+}
+
+// The JSON API, with paths relative to API_PREFIX. A breaking change goes in a new router
+// nested under /api/v2, leaving this one as it is.
+fn api_router(throttle: priv_access::ratelimit::IpThrottle) -> Router<Arc<AppState>> {
+    // Where credentials and proofs get checked, so where brute force would aim
+    let throttled = Router::new()
+        .route("/setup", get(mobile_setup))
+        .route("/admin/login", post(api_admin_login))
+        .route("/faculty/webauthn/login/start", post(api_passkey_login_start))
+        .route("/faculty/webauthn/login/finish", post(api_passkey_login_finish))
+        .route("/verify", post(api_verify))
+        .route("/challenge", get(api_challenge))
+        .route("/zkp/verify", post(verify_zkp))
+        .route("/verify_proof_only", post(api_verify_proof_only))
+        .route_layer(axum::middleware::from_fn_with_state(throttle, priv_access::ratelimit::throttle));
+
+    Router::new()
+        .route("/history", get(api_get_history))
+        .route("/history/export", get(api_export_history))
+        .route("/history/clear", post(api_archive_history))
+        .route("/history/merkle_root", get(api_history_merkle_root))
+        .route("/history/:id/merkle_proof", get(api_history_merkle_proof))
+        .route("/history/:id/proof", get(api_history_proof))
+        .route("/history/:id/reverify", post(api_reverify_history))
+        .route("/doors", get(api_list_doors))
+        .route("/doors/reload", post(api_reload_doors))
+        .route("/doors/:door_id", axum::routing::delete(api_delete_door))
+        .route("/room_qrs", get(api_room_qrs))
+        .route("/check_assignment", get(api_check_assignment))
+        .route("/my_room", get(api_my_room))
+        .route("/room_sections", get(api_room_sections))
+        .route("/unassign_section", post(api_unassign_section))
+        .route("/dynamic_qr/:door_id", get(api_dynamic_qr))
+        .route("/dynamic_qrs_all", get(api_dynamic_qrs_all))
+        .route("/notify_status", post(api_notify_status))
+        .route("/server_key", get(api_server_key))
+        .route("/params", get(api_params))
+        .route("/time", get(api_time))
+        .route("/roles", get(api_list_roles))
+        .route("/sections", get(api_list_sections))
+        .route("/roles/:role/permissions", get(api_role_permissions))
+        .route("/faculty", get(api_list_faculty).post(api_create_faculty))
+        .route("/faculty/:faculty_id", axum::routing::delete(api_delete_faculty))
+        .route("/admins", get(api_list_admins).post(api_create_admin))
+        .route("/admins/:admin_id", axum::routing::delete(api_delete_admin))
+        .route("/faculty/:faculty_id/stats", get(api_faculty_stats))
+        .route("/faculty/webauthn/register/start", post(api_passkey_register_start))
+        .route("/faculty/webauthn/register/finish", post(api_passkey_register_finish))
+        .route("/keys", get(api_list_keys).post(api_create_key))
+        .route("/keys/:key_id", axum::routing::delete(api_revoke_key))
+        .route("/admin/logout", post(api_admin_logout))
+        .route("/admin/totp/provision", get(api_provision_totp))
+        .route("/guest_pass", post(api_create_guest_pass))
+        .route("/simulate", post(api_simulate))
+        .route("/dev/prove", post(api_dev_prove))
+        .route("/revoke", post(api_revoke_public_key))
+        .route("/revoke/:public_key", axum::routing::delete(api_restore_public_key))
+        .route("/ed25519_keys", get(api_list_ed25519_keys).post(api_register_ed25519_key))
+        .route("/ed25519_keys/:public_key", axum::routing::delete(api_remove_ed25519_key))
+        .merge(throttled)
+        .merge(session_routes())
+}
+
+// Paths from before /api/v1, kept while clients move over: every /api/... path still reaches the
+// same handler as /api/v1/..., and the three API routes that lived outside /api keep their old
+// paths. New endpoints only need adding to `api_router`.
+fn legacy_api_routes(throttle: priv_access::ratelimit::IpThrottle) -> Router<Arc<AppState>> {
+    let throttled = Router::new()
+        .route("/mobile/setup", get(mobile_setup))
+        .route("/verify", post(verify_zkp))
+        .route_layer(axum::middleware::from_fn_with_state(throttle.clone(), priv_access::ratelimit::throttle));
+    Router::new()
+        .nest("/api", api_router(throttle))
+        .route("/history", get(api_get_history))
+        .merge(throttled)
+}
+
+// How long a browser may cache a preflight answer
+const CORS_MAX_AGE_SECS: u64 = 600;
+
+// Only origins listed in PRIVACCESS_CORS_ORIGINS get CORS headers; with none listed, same-origin only.
+// Any origin is allowed only in dev mode. The layer also answers OPTIONS preflights for the JSON POSTs.
+fn cors_layer(config: &priv_access::config::Config) -> Option<tower_http::cors::CorsLayer> {
+    use axum::http::{header, HeaderName, HeaderValue, Method};
+
+    let allow_origin = if config.cors_allow_any {
+        tower_http::cors::AllowOrigin::any()
+    } else {
+        let allowed: Vec<HeaderValue> = config.cors_origins.iter()
+            .filter_map(|o| HeaderValue::from_str(o).ok())
+            .collect();
+        if allowed.is_empty() {
+            return None;
+        }
+        tower_http::cors::AllowOrigin::list(allowed)
+    };
+    Some(
+        tower_http::cors::CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
+            .max_age(std::time::Duration::from_secs(CORS_MAX_AGE_SECS))
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::AUTHORIZATION,
+                HeaderName::from_static("x-api-key"),
+                HeaderName::from_static("x-admin-password"),
+                HeaderName::from_static("x-faculty-id"),
+                HeaderName::from_static("x-faculty-pin"),
+            ]),
+    )
+}
+
+fn session_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/session", get(api_current_session).delete(api_logout))
+        .route("/sessions/revoke_all", post(api_revoke_all_sessions))
+        .route("/token/refresh", post(api_refresh_token))
+        .route("/sessions/:session_id/revoke", post(api_revoke_session))
+        .route("/assignment_audit", get(api_assignment_audit))
+}
+
+// Who the bearer token belongs to, so a kiosk can check its session is still good
+async fn api_current_session(AuthedPrincipal(principal): AuthedPrincipal) -> impl IntoResponse {
+    Json(json!({
+        "status": "success",
+        "id": principal.id,
+        "role": principal.role,
+        "session_id": principal.session_id
+    }))
+}
+
+async fn api_logout(AuthedPrincipal(principal): AuthedPrincipal) -> impl IntoResponse {
+    if let Some(sid) = &principal.session_id {
+        priv_access::session::revoke_session(sid);
+        tracing::info!(principal = %principal.id, session_id = %sid, "Logged out");
+    }
+    Json(json!({"status": "success", "message": "Logged out"}))
+}
+
+#[derive(Deserialize)]
+struct RefreshPayload {
+    refresh_token: String,
+}
+
+impl Validate for RefreshPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("refresh_token", &self.refresh_token, validate::MAX_TOKEN_LEN)
+    }
+}
+
+// Swap a refresh token for a new access token, so a door terminal stays signed in without
+// holding a long-lived one
+async fn api_refresh_token(Tenant(tenant): Tenant, ValidJson(payload): ValidJson<RefreshPayload>) -> impl IntoResponse {
+    match priv_access::session::refresh(payload.refresh_token.trim(), &tenant.id) {
+        Ok((token, claims, refresh_token)) => Json(json!({
+            "status": "success",
+            "token": token,
+            "refresh_token": refresh_token,
+            "expires_at": claims.exp
+        })).into_response(),
+        Err(e) => ApiError::unauthorized(e.message()).into_response(),
+    }
+}
+
+async fn api_revoke_all_sessions(AdminAuth(principal): AdminAuth, Tenant(tenant): Tenant) -> impl IntoResponse {
+    let epoch = priv_access::session::revoke_all(&tenant.id);
+    tracing::warn!(tenant = %tenant.id, by = %principal.id, epoch, "All sessions revoked");
+    Json(json!({
+        "status": "success",
+        "message": "All sessions revoked",
+        "epoch": epoch
+    })).into_response()
+}
+
+async fn api_revoke_session(
+    axum::extract::Path(session_id): axum::extract::Path<String>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    let principal = match authorize(&headers, &tenant.id, Permission::Read) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    // Admins may revoke any session, everyone else only their own
+    let own_session = principal.session_id.as_deref() == Some(session_id.as_str());
+    if !own_session && !principal.allows(Permission::Delete) {
+        return ApiError::forbidden("Cannot revoke another user's session").into_response();
+    }
+    priv_access::session::revoke_session(&session_id);
+    tracing::info!(%session_id, by = %principal.id, "Session revoked");
+    Json(json!({
+        "status": "success",
+        "message": "Session revoked",
+        "session_id": session_id
+    })).into_response()
+}
+
+#[derive(Deserialize)]
+struct AssignmentAuditParams {
+    section: Option<String>,
+}
+
+impl Validate for AssignmentAuditParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::section("section", &self.section)?;
+        Ok(())
+    }
+}
+
+async fn api_assignment_audit(
+    Tenant(tenant): Tenant,
+    ValidQuery(params): ValidQuery<AssignmentAuditParams>,
+    headers: axum::http::HeaderMap,
+) -> impl IntoResponse {
+    if let Err(e) = authorize(&headers, &tenant.id, Permission::Write) {
+        return e.into_response();
+    }
+    let audit = tenant.assignment_audit.lock().unwrap();
+    let entries: Vec<&AssignmentAudit> = audit.iter()
+        .filter(|e| params.section.as_deref().is_none_or(|s| e.section.eq_ignore_ascii_case(s)))
+        .collect();
+    Json(json!(entries)).into_response()
+}
+
+// === 3. Verification ===
+#[derive(Deserialize, Debug)]
+struct VerifyPayload {
+    door_id: String,
+    role: String,
+    // Schnorr proof; the default `identity`
+    proof: Option<Proof>,
+    // "schnorr" (default) or "ed25519", which sends a signature from a registered key instead
+    #[serde(default)]
+    identity: IdentityKind,
+    ed25519: Option<Ed25519Proof>,
+    geohash: String,
+    password: Option<String>,
+    // Which admin `password` belongs to; the default admin if absent
+    admin_id: Option<String>,
+    // Admins who have enrolled an authenticator app send its current code
+    totp_code: Option<String>,
+    // Signed single-use pass from /api/guest_pass, for the GUEST role
+    guest_pass: Option<String>,
+    pin: Option<String>,
+    // A passkey assertion from /api/faculty/webauthn/login/start, in place of `pin`
+    webauthn: Option<AssertionResponse>,
+    section: Option<String>,
+    faculty_name: Option<String>,
+    faculty_id: Option<String>,
+    // Raw GPS fix, when the client has one
+    lat: Option<f64>,
+    lon: Option<f64>,
+    // The door's BLE beacon as the phone hears it: its id and signal strength in dBm
+    beacon_id: Option<String>,
+    beacon_rssi: Option<i32>,
+    ip_city: Option<String>,
+    ip_region: Option<String>,
+    ip_country: Option<String>,
+    nonce: Option<String>,
+    qr_timestamp: Option<u64>,
+    floor: Option<i32>,
+}
+
+impl Validate for VerifyPayload {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        validate::text("role", &self.role, validate::MAX_KEYWORD_LEN)?;
+        validate::geohash("geohash", &self.geohash)?;
+        validate::opt_secret("password", &self.password)?;
+        validate::opt_text("admin_id", &self.admin_id, validate::MAX_ID_LEN)?;
+        validate::opt_secret("totp_code", &self.totp_code)?;
+        validate::opt_text("guest_pass", &self.guest_pass, validate::MAX_TOKEN_LEN)?;
+        validate::opt_secret("pin", &self.pin)?;
+        validate::section("section", &self.section)?;
+        validate::opt_text("faculty_name", &self.faculty_name, validate::MAX_NAME_LEN)?;
+        validate::opt_text("faculty_id", &self.faculty_id, validate::MAX_ID_LEN)?;
+        validate::opt_text("beacon_id", &self.beacon_id, validate::MAX_ID_LEN)?;
+        validate::opt_text("ip_city", &self.ip_city, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("ip_region", &self.ip_region, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("ip_country", &self.ip_country, validate::MAX_TEXT_LEN)?;
+        validate::opt_text("nonce", &self.nonce, validate::MAX_TEXT_LEN)?;
+        Ok(())
+    }
+}
+
+impl VerifyPayload {
+    // The proof of the kind `identity` names, if it was sent
+    fn identity_proof(&self) -> Option<IdentityProof<'_>> {
+        match self.identity {
+            IdentityKind::Schnorr => self.proof.as_ref().map(IdentityProof::Schnorr),
+            IdentityKind::Ed25519 => self.ed25519.as_ref().map(IdentityProof::Ed25519),
+        }
+    }
+}
+
+// Admins and guests hold no role key, so they send a placeholder proof that is never checked
+async fn api_verify(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidJson(payload): ValidJson<VerifyPayload>,
+) -> axum::response::Response {
+    let started = std::time::Instant::now();
+    let door_id = payload.door_id.trim().to_string();
+    let response = verify_access(state, peer, tenant, headers, payload).await;
+    let response = if priv_access::config::CONFIG.sign_responses {
+        sign_response(response, &door_id).await
+    } else {
+        response
+    };
+    priv_access::metrics::observe_verify_latency(started.elapsed());
+    response
+}
+
+// Public half of the key /api/verify responses are signed with
+async fn api_server_key() -> impl IntoResponse {
+    Json(json!({
+        "status": "success",
+        "algorithm": "schnorr-sha256",
+        "public_key": priv_access::zkp::SERVER_SIGNER.public_key().to_string(),
+        "signing_enabled": priv_access::config::CONFIG.sign_responses
+    }))
+}
+
+#[derive(Deserialize)]
+struct ChallengeParams {
+    door_id: String,
+}
+
+impl Validate for ChallengeParams {
+    fn validate(&self) -> Result<(), FieldError> {
+        validate::text("door_id", &self.door_id, validate::MAX_ID_LEN)?;
+        Ok(())
+    }
+}
+
+// A single-use nonce for a proof at `door_id`, remembered for CHALLENGE_TTL_SECS
+async fn issue_door_challenge(state: &AppState, tenant: &TenantState, door_id: &str) -> Result<String, ApiError> {
+    let mut bytes = [0u8; priv_access::zkp::CHALLENGE_NONCE_LEN / 2];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut bytes);
+    let nonce = hex::encode(bytes);
+    state.storage.issue_challenge(&tenant.id, door_id, &nonce, CHALLENGE_TTL_SECS).await?;
+    Ok(nonce)
+}
+
+// Fresh single-use nonce for a proof at one door; /api/verify consumes it
+async fn api_challenge(
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    Tenant(tenant): Tenant,
+    headers: axum::http::HeaderMap,
+    ValidQuery(params): ValidQuery<ChallengeParams>,
+) -> impl IntoResponse {
+    // Every unlock needs one, so the same cap as unlocks keeps anyone from flooding the store
+    let ip = priv_access::ratelimit::client_ip(&headers, peer);
+    let key = format!("challenge:{}", ip);
+    if let Err(e) = state.limiter.check(&key, priv_access::config::CONFIG.rate_limit).await {
+        return e.into_response();
+    }
+
+    let door_id = params.door_id.trim();
+    if tenant.door(door_id).is_none() {
+        return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response();
+    }
+
+    let nonce = match issue_door_challenge(&state, &tenant, door_id).await {
+        Ok(nonce) => nonce,
+        Err(e) => return e.into_response(),
+    };
+
+    Json(json!({
+        "status": "success",
+        "door_id": door_id,
+        "nonce": nonce,
+        "expires_in": CHALLENGE_TTL_SECS
+    })).into_response()
+}
+
+// The server's clock, for clients whose own is off. A client takes `unix_time` minus its local
+// Unix time as its offset and applies it to the timestamp before `generate_proof` binds it in
+// (`SchnorrProver::generate_proof_at` in Rust, `setClockOffset` in zkp.js); proofs stamped
+// more than `max_clock_skew_secs` off the server's clock are refused.
+async fn api_time() -> impl IntoResponse {
+    Json(json!({
+        "status": "success",
+        "unix_time": priv_access::zkp::unix_now(),
+        "max_clock_skew_secs": priv_access::config::CONFIG.max_clock_skew_secs
+    }))
+}
+
+// Everything a prover needs to build proofs this server accepts, so clients don't hardcode it
+async fn api_params(axum::extract::State(state): axum::extract::State<Arc<AppState>>) -> impl IntoResponse {
+    Json(json!({
+        "status": "success",
+        "group": {
+            "p": P.to_string(),
+            "g": G.to_string(),
+            "q": priv_access::crypto::Q.to_string(),
+            "bits": P.bits()
+        },
+        "challenge": {
+            "hash": "sha256",
+            "input": "decimal(R) || decimal(Y) || geohash prefix || nonce || decimal(timestamp)",
+            "geohash_precision": priv_access::zkp::CHALLENGE_GEOHASH_LEN,
+            "nonce": "single-use, from GET /api/challenge?door_id=; send it back as proof.nonce",
+            "nonce_ttl_secs": CHALLENGE_TTL_SECS,
+            "timestamp": "unix seconds when the proof was made; send it back as proof.timestamp",
+            "max_age_secs": state.proof_window.max_age_secs,
+            "max_skew_secs": state.proof_window.max_skew_secs
+        },
+        "schemes": {
+            "modp": "default; public_key, commitment and response as decimal strings",
+            "secp256k1": "public_key and commitment as compressed SEC1 hex, response as 32-byte hex; c = sha256(hex(R) || hex(Y) || geohash prefix || nonce || decimal(timestamp)) mod n"
+        },
+        "identity": {
+            "schnorr": "default; send the proof as `proof`",
+            "ed25519": "send identity=ed25519 and `ed25519` {public_key, signature, nonce} as hex; the signature covers door_id || nonce || geohash, and the key must be registered for the role"
+        },
+        "max_proof_field_len": priv_access::config::CONFIG.max_proof_field_len,
+        "server_key": {
+            "algorithm": "schnorr-sha256",
+            "public_key": priv_access::zkp::SERVER_SIGNER.public_key().to_string(),
+            "signing_enabled": priv_access::config::CONFIG.sign_responses
+        }
+    }))
+}
+
+// Adds `door_id`, `signed_at` and a `signature` over the rest of the body. The signed message is
+// the body without `signature`, as compact JSON with sorted keys.
+async fn sign_response(response: axum::response::Response, door_id: &str) -> axum::response::Response {
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(_) => return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to sign response").into_response(),
+    };
+    let mut value = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(v @ serde_json::Value::Object(_)) => v,
+        _ => return axum::response::Response::from_parts(parts, axum::body::Body::from(bytes)),
+    };
+    value["door_id"] = json!(door_id);
+    value["signed_at"] = json!(chrono::Utc::now().timestamp());
+    let signature = priv_access::zkp::SERVER_SIGNER.sign(value.to_string().as_bytes());
+    value["signature"] = json!(signature);
+
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    axum::response::Response::from_parts(parts, axum::body::Body::from(value.to_string()))
+}
+
+async fn verify_access(
+    state: Arc<AppState>,
+    peer: std::net::SocketAddr,
+    tenant: Arc<TenantState>,
+    headers: axum::http::HeaderMap,
+    payload: VerifyPayload,
+) -> axum::response::Response {
+    let door_id = payload.door_id.trim();
+    tracing::info!(door_id = %door_id, role = %payload.role, "Access request received");
+
+    let ip = priv_access::ratelimit::client_ip(&headers, peer);
+    let key = format!("verify:{}:{}", ip, payload.role);
+    if let Err(e) = state.limiter.check(&key, priv_access::config::CONFIG.rate_limit).await {
+        return e.into_response();
+    }
+
+    if let Some(proof) = &payload.proof {
+        if let Err(e) = proof.check_field_sizes(priv_access::config::CONFIG.max_proof_field_len) {
+            tracing::info!(door_id = %door_id, role = %payload.role, result = "denied", reason = e.code(), "{}", e);
+            return proof_rejection(&e);
+        }
+    }
+
+    // A proof that doesn't even parse is a 400 before any QR nonce or challenge is spent on it
+    if proves_identity(&payload.role) {
+        let format = match payload.identity_proof() {
+            Some(IdentityProof::Schnorr(proof)) => proof.check_format().map_err(|e| proof_rejection(&e)),
+            Some(IdentityProof::Ed25519(proof)) => proof.check_format().map_err(|e| ed25519_rejection(&e)),
+            None => Err(ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MissingProof, "Missing identity proof").into_response()),
+        };
+        if let Err(response) = format {
+            tracing::info!(door_id = %door_id, role = %payload.role, result = "denied", status = response.status().as_u16(), "Identity proof rejected");
+            return response;
+        }
+    }
+
+    // 1. Check Door Existence
+    let door = match tenant.door(door_id) {
+        Some(d) => d,
+        None => {
+            return ApiError::coded(StatusCode::NOT_FOUND, ErrorCode::DoorNotFound, "Door Not Found").into_response()
+        },
+    };
+
+    if !matches!(payload.role.as_str(), "ADMIN" | "FACULTY" | "STUDENT" | "GUEST") {
+        return ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidRole, "Invalid Role").into_response();
+    }
+
+    // 2. Gather what the access policy weighs. Checks run in the order it weighs them and stop at
+    // the first failure, so a request that's already refused spends no QR nonce, TOTP code, guest
+    // pass or challenge
+    let now = chrono::Local::now();
+    let mut request = AccessRequest {
+        role: &payload.role,
+        door: &door,
+        now: now.naive_local(),
+        proximity_required: state.requires_proximity(&payload.role),
+        location: None,
+        credentials: None,
+        section: SectionState::Missing,
+        proof: None,
+    };
+    let mut failed = None;
+    // The account that authenticated, recorded with the grant so admin unlocks are attributable
+    let mut principal = None;
+    // The section's current (room, assigned by), if it has one
+    let mut assignment = None;
+    if within_schedule(&door, &payload.role, request.now) {
+        if request.proximity_required {
+            let check = match check_location(&state, &tenant, &payload, &door).await {
+                Ok(check) => check,
+                Err(response) => return response,
+            };
+            request.location = Some(outcome(check, &mut failed));
+        }
+        if failed.is_none() {
+            let check = match check_credentials(&state, &tenant, &headers, &payload, request.proximity_required).await {
+                Ok(check) => check,
+                Err(response) => return response,
+            };
+            request.credentials = Some(outcome(check.map(|found| principal = found), &mut failed));
+        }
+        if failed.is_none() {
+            (request.section, assignment) = match section_state(&state, &tenant, &payload, principal.as_ref()).await {
+                Ok(found) => found,
+                Err(response) => return response,
+            };
+        }
+        if failed.is_none() && section_allows(&payload.role, request.section).is_ok() && proves_identity(&payload.role) {
+            let check = match check_proof(&state, &tenant, &payload).await {
+                Ok(check) => check,
+                Err(response) => return response,
+            };
+            request.proof = Some(outcome(check, &mut failed));
+        }
+    }
+
+    // 3. Decide. A failed check answers with its own denial; the policy's own rules are worded here
+    if let AccessDecision::Deny(reason) = priv_access::rbac::evaluate_access(request) {
+        let denial = match failed {
+            Some(denial) if denial.reason == reason => denial,
+            _ => policy_denial(reason, &payload, &door, now, assignment.as_ref()),
+        };
+        let actor_id = principal.map(|p| p.id);
+        log_denied(&state, &tenant, &payload, &door, actor_id, denial.reason, &denial.log).await;
+        return denial.response;
+    }
+
+    // A section a faculty member names moves to this room with them
+    let section = payload.section.as_deref().unwrap_or("").trim();
+    if let (Some(faculty), false, "FACULTY") = (&principal, section.is_empty(), payload.role.as_str()) {
+        // Recorded under the account's own name, not one from the request, so nobody can pass as
+        // the member who holds a section
+        let faculty_name = priv_access::faculty::find(&faculty.id).map_or_else(|| faculty.id.clone(), |f| f.display_name().to_string());
+        let previous = match state.storage.assign(&tenant.id, section, (door_id.to_string(), faculty_name.clone())).await {
+            Ok(p) => p,
+            Err(e) => return ApiError::from(e).into_response(),
+        };
+        tracing::info!(%section, door_id = %door_id, faculty = %faculty_name, "Section assigned to room");
+        let old_room = previous.map(|(room, _)| room);
+        if old_room.as_deref() != Some(door_id) {
+            // A section is in one room at a time, so assigning it here took it out of the old room's set
+            if let Some(old) = &old_room {
+                tracing::info!(%section, door_id = %old, "Section removed from room");
+            }
+            tenant.record_assignment_change(&faculty.id, &faculty.role, section, old_room, Some(door_id.to_string()));
+        }
+    }
+    // Recorded as the account that authenticated; a name or id from the request body is never trusted
+    let (faculty_id, faculty_name) = logged_identity(principal.as_ref());
+    let actor_id = principal.map(|p| p.id);
+
+    // 4. Log Success
+    let timestamp = log_timestamp();
+    let history = AccessHistory {
+        id: 0,
+        role: payload.role.clone(),
+        door_name: door.name.clone(),
+        section: payload.section.clone().unwrap_or_else(|| "N/A".to_string()),
+        timestamp,
+        status: "GRANTED".to_string(),
+        faculty_name,
+        faculty_id,
+        actor_id,
+        denial_reason: None,
+    };
+    let log_id = record_access(&state, &tenant, history).await;
+
+    // Admins and guests skip the proof check, so there's nothing worth keeping for them. The proof
+    // store holds Schnorr proofs only, so ed25519 signatures aren't kept either
+    if priv_access::config::CONFIG.store_proofs && proves_identity(&payload.role) {
+        if let (Some(log_id), Some(IdentityProof::Schnorr(proof))) = (log_id, payload.identity_proof()) {
+            if let Err(e) = state.storage.save_proof(&tenant.id, log_id, proof).await {
+                tracing::error!(log_id, error = %e.0, "Failed to store proof");
+            }
+        }
+    }
+
+    priv_access::door_status::notify(&tenant, door_id, "unlocked");
+    priv_access::metrics::record_granted(&payload.role, door_id);
+    tracing::info!(door_id = %door_id, role = %payload.role, result = "granted", "Access granted");
+
+    Json(json!({
+        "status": "success",
+        "message": format!("Access Granted to {}", payload.role),
+        "role": payload.role
+    })).into_response()
+}
+
+// A failed check: the reason the access policy is told, the log line, and the client's response
+struct Denial {
+    reason: DenialReason,
+    log: String,
+    response: axum::response::Response,
+}
+
+impl Denial {
+    fn new(reason: DenialReason, log: impl Into<String>, response: impl IntoResponse) -> Self {
+        Denial { reason, log: log.into(), response: response.into_response() }
+    }
+}
+
+// Ok is how the check went; Err is a response that ends the request with no decision, for bad
+// input or a storage failure
+type CheckResult<T = ()> = Result<Result<T, Denial>, axum::response::Response>;
+
+// What the policy is told about a check, keeping the denial to answer with if it failed
+fn outcome(check: Result<(), Denial>, failed: &mut Option<Denial>) -> Result<(), DenialReason> {
+    check.map_err(|denial| {
+        let reason = denial.reason;
+        *failed = Some(denial);
+        reason
+    })
+}
+
+// Dynamic QR (anti-replay), floor, and beacon or GPS
+async fn check_location(state: &AppState, tenant: &TenantState, payload: &VerifyPayload, door: &Door) -> CheckResult {
+    let door_id = payload.door_id.trim();
+    let ts = payload.qr_timestamp.unwrap_or(0);
+    let current_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    // Same allowance for clock drift as proof timestamps get; anything further ahead wasn't drawn by this server
+    if ts > current_time.saturating_add(priv_access::config::CONFIG.max_clock_skew_secs) {
+        let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::QrInvalid, "Location Check Failed: QR timestamp is in the future");
+        return Ok(Err(Denial::new(DenialReason::QrInvalid, "QR From Future", error)));
+    }
+    if current_time > ts.saturating_add(QR_LIFETIME_SECS) {
+        let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::QrExpired, "Location Check Failed: QR Code Expired (Took more than 15s).");
+        return Ok(Err(Denial::new(DenialReason::QrExpired, "QR Expired", error)));
+    }
+
+    let nonce = payload.nonce.clone().unwrap_or_default();
+    if nonce.is_empty() {
+        let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::QrInvalid, "Location Check Failed: Invalid QR format");
+        return Ok(Err(Denial::new(DenialReason::QrInvalid, "Missing QR Nonce", error)));
+    }
+
+    match state.storage.claim_nonce(&tenant.id, &nonce, NONCE_TTL_SECS).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::QrReused, "Location Check Failed: QR Code already used");
+            return Ok(Err(Denial::new(DenialReason::QrReused, "QR Reused", error)));
+        }
+        Err(e) => return Err(ApiError::from(e).into_response()),
+    }
+
+    let sent_floor = payload.floor.unwrap_or(-1);
+    if sent_floor != door.floor {
+        let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::LocationMismatch, "Location Check Failed: Wrong Floor");
+        return Ok(Err(Denial::new(DenialReason::Location, "Floor Mismatch", error)));
+    }
+
+    if payload.beacon_id.is_some() != payload.beacon_rssi.is_some() {
+        let missing = if payload.beacon_id.is_some() { "beacon_rssi" } else { "beacon_id" };
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "beacon_id and beacon_rssi must be sent together").with_field(missing).into_response());
+    }
+
+    // Indoors, hearing the door's own beacon places the client better than GPS can, so a beacon
+    // reading for a door that has one decides; otherwise a shared GPS fix must put the client
+    // within the door's radius
+    let beacon = match (&door.beacon_id, payload.beacon_id.as_deref(), payload.beacon_rssi) {
+        (Some(_), Some(beacon_id), Some(rssi)) => Some(door.check_beacon(beacon_id, rssi).map(|()| rssi)),
+        _ => None,
+    };
+    if let Some(result) = beacon {
+        match result {
+            Ok(rssi) => tracing::debug!(door_id = %door_id, rssi, "Door beacon heard"),
+            Err(e) => {
+                let msg = format!("Location Check Failed: {}", e);
+                let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::LocationMismatch, msg).with_reason(e.code());
+                return Ok(Err(Denial::new(DenialReason::Location, e.to_string(), error)));
+            }
+        }
+    } else if let (Some(lat), Some(lon)) = (payload.lat, payload.lon) {
+        let distance = door.distance_to(lat, lon);
+        if let (Ok(d), Ok(cell)) = (&distance, priv_access::geo::encode(lat, lon, priv_access::zkp::CHALLENGE_GEOHASH_LEN)) {
+            tracing::debug!(door_id = %door_id, geohash = %cell, distance_m = d, "GPS fix checked");
+        }
+        match distance {
+            Ok(d) if door.radius_meters == 0 || d <= door.radius_meters as f64 => {}
+            Ok(d) => {
+                let msg = format!("Location Check Failed: {:.0}m from the door (limit {}m)", d, door.radius_meters);
+                let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::LocationMismatch, msg);
+                return Ok(Err(Denial::new(DenialReason::Location, "Too Far From Door", error)));
+            }
+            Err(e) => {
+                let error = ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidLocation, e.to_string()).with_reason(e.code());
+                return Ok(Err(Denial::new(DenialReason::Location, e.to_string(), error)));
+            }
+        }
+    }
+    Ok(Ok(()))
+}
+
+// Admin password and TOTP, faculty PIN or passkey, or a guest pass. Gives the account that
+// authenticated, if the role has accounts
+async fn check_credentials(
+    state: &AppState,
+    tenant: &TenantState,
+    headers: &axum::http::HeaderMap,
+    payload: &VerifyPayload,
+    proximity_required: bool,
+) -> CheckResult<Option<priv_access::rbac::Principal>> {
+    let door_id = payload.door_id.trim();
+    match payload.role.as_str() {
+        "ADMIN" => {
+            let credentials = admin_body_credentials(payload.password.clone(), payload.admin_id.clone(), payload.totp_code.clone(), headers);
+            // A dashboard session already passed TOTP when it logged in; a password needs the code too
+            let admin = match authorize_credentials(credentials.as_ref(), &tenant.id, Permission::Read) {
+                Ok(principal) => principal,
+                Err(e) if e.code == ErrorCode::AccountLocked => return Err(e.into_response()),
+                Err(e) if e.reason.is_some_and(|r| r.starts_with("totp_")) => {
+                    return Ok(Err(Denial::new(DenialReason::InvalidCredentials, "Invalid TOTP Code", e)));
+                }
+                Err(e) => return Ok(Err(Denial::new(DenialReason::WrongPassword, "Incorrect Admin Password", e))),
+            };
+            if !proximity_required {
+                tracing::info!(door_id = %door_id, role = "ADMIN", remote = true, "Admin access");
+            } else {
+                tracing::info!(door_id = %door_id, role = "ADMIN", remote = false, "Admin access (remote access disabled)");
+            }
+            Ok(Ok(Some(admin)))
+        },
+        "FACULTY" => {
+            let fac_id = payload.faculty_id.as_deref().unwrap_or("").trim();
+            // A passkey stands in for the PIN; the PIN still works on devices without one. Only
+            // PINs can be guessed, so a locked-out account can still get in with its passkey
+            let authenticated = match &payload.webauthn {
+                Some(assertion) => passkey_principal(state, tenant, headers, fac_id, assertion).await,
+                None => {
+                    let credentials = Credentials::FacultyPin {
+                        id: fac_id.to_string(),
+                        pin: payload.pin.clone().unwrap_or_default(),
+                    };
+                    authorize_credentials(Some(&credentials), &tenant.id, Permission::Read)
+                }
+            };
+            match authenticated {
+                Ok(principal) => Ok(Ok(Some(principal))),
+                Err(e) if e.code == ErrorCode::AccountLocked => Err(e.into_response()),
+                Err(e) => {
+                    tracing::debug!(door_id = %door_id, faculty_id = %fac_id, "Faculty login failed");
+                    Ok(Err(Denial::new(DenialReason::InvalidCredentials, "Invalid Faculty Credentials", e)))
+                }
+            }
+        },
+        "GUEST" => {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+            let token = payload.guest_pass.as_deref().unwrap_or("");
+            let pass = match priv_access::guest::validate(token, &tenant.id, door_id, now) {
+                Ok(pass) => pass,
+                Err(e) => {
+                    let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::GuestPassInvalid, e.message()).with_reason(e.code());
+                    return Ok(Err(Denial::new(DenialReason::GuestPass, e.message(), error)));
+                }
+            };
+            // Only remembered until the pass expires; after that it's refused as expired anyway
+            match state.storage.claim_nonce(&tenant.id, &format!("guest:{}", pass.nonce), pass.expires_at - now).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::GuestPassInvalid, "Guest pass already used").with_reason("guest_pass_reused");
+                    return Ok(Err(Denial::new(DenialReason::GuestPass, "Guest Pass Reused", error)));
+                }
+                Err(e) => return Err(ApiError::from(e).into_response()),
+            }
+            tracing::info!(door_id = %door_id, role = "GUEST", expires_at = pass.expires_at, "Guest pass accepted");
+            Ok(Ok(None))
+        },
+        // Students are who their role key proves them to be
+        _ => Ok(Ok(None)),
+    }
+}
+
+// Where the section named in the request stands, and its current (room, assigned by)
+async fn section_state(
+    state: &AppState,
+    tenant: &TenantState,
+    payload: &VerifyPayload,
+    principal: Option<&priv_access::rbac::Principal>,
+) -> Result<(SectionState, Option<(String, String)>), axum::response::Response> {
+    let door_id = payload.door_id.trim();
+    let section = payload.section.as_deref().unwrap_or("").trim();
+    if !matches!(payload.role.as_str(), "FACULTY" | "STUDENT") || section.is_empty() {
+        return Ok((SectionState::Missing, None));
+    }
+    if priv_access::rbac::find_section(&state.sections, section).is_none() {
+        return Ok((SectionState::Invalid, None));
+    }
+
+    // A room admits every section assigned to it
+    if payload.role == "STUDENT" {
+        let room_sections = state.storage.room_sections(&tenant.id, door_id).await.map_err(|e| ApiError::from(e).into_response())?;
+        if room_sections.iter().any(|(s, _)| s == section) {
+            return Ok((SectionState::Here, None));
+        }
+    }
+    let assignment = state.storage.assignment(&tenant.id, section).await.map_err(|e| ApiError::from(e).into_response())?;
+    let state = match &assignment {
+        None => SectionState::Unassigned,
+        Some((room, _)) if payload.role == "FACULTY" && room == door_id => SectionState::Here,
+        Some((_, owner)) => {
+            // Only whoever assigned a section can move it; anyone else has to wait for it to be released
+            let profile = principal.and_then(|p| priv_access::faculty::find(&p.id));
+            SectionState::Elsewhere { own: profile.is_some_and(|f| f.assigned(owner)) }
+        }
+    };
+    Ok((state, assignment))
+}
+
+// Challenge, revocation, then the Schnorr or ed25519 proof itself
+async fn check_proof(state: &AppState, tenant: &TenantState, payload: &VerifyPayload) -> CheckResult {
+    let door_id = payload.door_id.trim();
+    let Some(identity) = payload.identity_proof() else {
+        let error = ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MissingProof, "Missing identity proof");
+        return Ok(Err(Denial::new(DenialReason::MalformedProof, "Missing Proof", error)));
+    };
+
+    // The proof must answer a challenge issued for this door and not used before
+    let rejection = match state.storage.take_challenge(&tenant.id, identity.nonce()).await {
+        Ok(Some(issued_for)) if issued_for == door_id => None,
+        _ if identity.nonce().is_empty() => Some(("challenge_missing", "Proof has no challenge nonce; fetch one from /api/challenge")),
+        Ok(Some(_)) => Some(("challenge_wrong_door", "Challenge was issued for another door")),
+        Ok(None) => Some(("challenge_invalid", "Challenge expired or already used")),
+        Err(e) => return Err(ApiError::from(e).into_response()),
+    };
+    if let Some((reason, message)) = rejection {
+        tracing::debug!(door_id = %door_id, %reason, "Proof rejected: {}", message);
+        let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::ChallengeInvalid, message).with_reason(reason);
+        return Ok(Err(Denial::new(DenialReason::ChallengeInvalid, message, error)));
+    }
+
+    if state.revoked.is_revoked(identity.public_key()) {
+        let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::KeyRevoked, "This key has been revoked").with_reason("revoked_key");
+        return Ok(Err(Denial::new(DenialReason::RevokedKey, "Revoked Key", error)));
+    }
+
+    match identity {
+        IdentityProof::Schnorr(proof) => {
+            // A valid proof only counts if it's for this campus's key for the claimed role; the
+            // server holds no secp256k1 role keys, so those never match
+            let role_key = tenant.role_public_key(&payload.role);
+            let claimed = num_bigint::BigUint::parse_bytes(proof.public_key.as_bytes(), 10);
+            if proof.scheme != priv_access::zkp::ProofScheme::Modp || role_key.is_none() || claimed != role_key {
+                let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::InvalidProof, "This key does not hold the role")
+                    .with_reason("wrong_role_key");
+                return Ok(Err(Denial::new(DenialReason::InvalidProof, "Wrong Role Key", error)));
+            }
+            if let Err(e) = SchnorrVerifier::verify_proof_within(proof, &state.proof_window) {
+                tracing::debug!(door_id = %door_id, reason = e.code(), "Proof rejected: {}", e);
+                return Ok(Err(Denial::new(DenialReason::from(&e), e.to_string(), proof_rejection(&e))));
+            }
+        }
+        IdentityProof::Ed25519(proof) => {
+            // Any key can sign, so only ones an admin registered for this role count
+            if !state.ed25519_keys.allows(&tenant.id, &proof.public_key, &payload.role, payload.faculty_id.as_deref()) {
+                let error = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::InvalidProof, "This key is not registered for this role")
+                    .with_reason("key_not_registered");
+                return Ok(Err(Denial::new(DenialReason::InvalidCredentials, "Unregistered ed25519 Key", error)));
+            }
+            if let Err(e) = proof.verify(door_id, &payload.geohash) {
+                tracing::debug!(door_id = %door_id, reason = e.code(), "Proof rejected: {}", e);
+                return Ok(Err(Denial::new(DenialReason::InvalidProof, e.to_string(), ed25519_rejection(&e))));
+            }
+        }
+    }
+    Ok(Ok(()))
+}
+
+// Denials that come from the policy's own rules rather than a failed check
+fn policy_denial(
+    reason: DenialReason,
+    payload: &VerifyPayload,
+    door: &Door,
+    now: chrono::DateTime<chrono::Local>,
+    assignment: Option<&(String, String)>,
+) -> Denial {
+    let section = payload.section.as_deref().unwrap_or("").trim();
+    match (reason, assignment) {
+        (DenialReason::OutsideSchedule, _) => {
+            // A local time skipped by a DST change has no instant; leave the next opening out then
+            let next_open = door.schedule.as_ref()
+                .and_then(|s| s.next_open(now.naive_local()))
+                .and_then(|t| t.and_local_timezone(chrono::Local).earliest());
+            let msg = match next_open {
+                Some(t) => format!("{} is closed now; it next opens {}", door.name, t.format("%a %-d %b at %H:%M")),
+                None => format!("{} is closed to {} at all times", door.name, payload.role),
+            };
+            let mut body = ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::OutsideSchedule, msg).to_json();
+            if let Some(t) = next_open {
+                body["next_open"] = json!(t.to_rfc3339_opts(chrono::SecondsFormat::Secs, false));
+            }
+            Denial::new(reason, "Outside Schedule", (StatusCode::FORBIDDEN, Json(body)))
+        }
+        (DenialReason::InvalidSection, _) => {
+            let error = ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::InvalidSection, "Invalid Section Selected");
+            Denial::new(reason, format!("Invalid Section: {}", section), error)
+        }
+        (DenialReason::WrongRoom, Some((room, faculty))) => {
+            let msg = format!("Access Denied: Your section is assigned to {} by {}", room, faculty);
+            Denial::new(reason, msg.clone(), ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::WrongRoom, msg))
+        }
+        (DenialReason::NoRoomAssigned, _) => {
+            let msg = "No room is being alloted for ur section";
+            Denial::new(reason, msg, ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::NoRoomAssigned, msg))
+        }
+        (DenialReason::SectionConflict, Some((room, owner))) => {
+            let msg = format!("Section {} is already assigned to {} by {}", section, room, owner);
+            Denial::new(reason, msg.clone(), ApiError::coded(StatusCode::CONFLICT, ErrorCode::SectionConflict, msg))
+        }
+        // A check the role needs that never ran; gathering stops at a failure, so this isn't expected
+        _ => Denial::new(reason, format!("Check Not Run ({})", reason), ApiError::forbidden("Access Denied")),
+    }
+}
+
+// Self-check for clients: runs only the Schnorr check on a proof and reports the challenge the
+// server derives. No door, role, nonce or time window is involved and nothing is logged or
+// unlocked; `/api/verify` is the only route that grants access.
+async fn api_verify_proof_only(Json(proof): Json<Proof>) -> impl IntoResponse {
+    let challenge = SchnorrVerifier::challenge(&proof).ok();
+    match SchnorrVerifier::verify_proof(&proof) {
+        Ok(()) => Json(json!({"status": "success", "valid": true, "challenge": challenge})),
+        Err(e) => Json(json!({
+            "status": "success",
+            "valid": false,
+            "challenge": challenge,
+            "reason": e.code(),
+            "message": e.to_string()
+        })),
+    }
+}
+
+fn proof_rejection(e: &VerifyError) -> axum::response::Response {
+    let status = match e {
+        // Startup refuses a bad group, so this is the server's fault if it ever happens
+        VerifyError::Params(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ if e.is_bad_request() => StatusCode::BAD_REQUEST,
+        _ => StatusCode::FORBIDDEN,
+    };
+    let code = match e {
+        VerifyError::EquationMismatch => ErrorCode::InvalidProof,
+        VerifyError::Expired { .. } | VerifyError::FromFuture { .. } => ErrorCode::ProofExpired,
+        VerifyError::Geohash(_) => ErrorCode::InvalidLocation,
+        VerifyError::FieldTooLong { .. } | VerifyError::Malformed { .. } | VerifyError::OutOfRange { .. } | VerifyError::NotInSubgroup { .. } => ErrorCode::MalformedProof,
+        VerifyError::Params(_) => ErrorCode::Internal,
+    };
+    let error = ApiError::coded(status, code, e.to_string()).with_reason(e.code());
+    match e.field() {
+        Some(field) => error.with_field(field),
+        None => error,
+    }.into_response()
+}
+
+fn ed25519_rejection(e: &Ed25519Error) -> axum::response::Response {
+    match e.field() {
+        Some(field) => ApiError::coded(StatusCode::BAD_REQUEST, ErrorCode::MalformedProof, e.to_string()).with_reason(e.code()).with_field(field),
+        None => ApiError::coded(StatusCode::FORBIDDEN, ErrorCode::InvalidProof, e.to_string()).with_reason(e.code()),
+    }.into_response()
+}
+
+// Access log times are UTC RFC 3339 ("2026-03-02T09:15:00Z"): text order is time order on every backend
+fn log_timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+// A failed log write shouldn't turn a decision into an error, so it's only reported. Logged
+// decisions also go out as access events (and to the webhook, if one is set).
+async fn record_access(state: &AppState, tenant: &TenantState, entry: AccessHistory) -> Option<u64> {
+    let mut event = entry.clone();
+    match state.storage.append_log(&tenant.id, entry).await {
+        Ok(id) => {
+            event.id = id;
+            priv_access::webhook::publish(&tenant.id, &event);
+            Some(id)
+        }
+        Err(e) => {
+            tracing::error!(error = %e.0, "Failed to record access log");
+            None
+        }
+    }
+}
+
+// The id and display name a granted entry is logged under: the faculty member's or admin's own
+// account. Other roles have no account to name
+fn logged_identity(principal: Option<&priv_access::rbac::Principal>) -> (Option<String>, Option<String>) {
+    let Some(principal) = principal else { return (None, None) };
+    let name = match principal.role.as_str() {
+        "FACULTY" => priv_access::faculty::find(&principal.id).map(|f| f.display_name().to_string()),
+        "ADMIN" => priv_access::admins::find(&principal.id).map(|a| a.display_name().to_string()),
+        _ => return (None, None),
+    };
+    (Some(principal.id.clone()), Some(name.unwrap_or_else(|| principal.id.clone())))
+}
+
+// `reason` is the message kept in the log's status; `kind` is its structured counterpart, stored
+// alongside it and counted in metrics. `actor_id` is the account, if its credentials checked out
+// before the denial.
+async fn log_denied(
+    state: &AppState,
+    tenant: &TenantState,
+    payload: &VerifyPayload,
+    door: &Door,
+    actor_id: Option<String>,
+    kind: DenialReason,
+    reason: &str,
+) {
+    priv_access::metrics::record_denied(kind);
+    tracing::info!(door_id = %payload.door_id.trim(), role = %payload.role, result = "denied", reason = kind.as_str(), "{}", reason);
+    let timestamp = log_timestamp();
+    let history = AccessHistory {
+        id: 0,
+        role: payload.role.clone(),
+        door_name: door.name.clone(),
+        section: payload.section.clone().unwrap_or_else(|| "N/A".to_string()),
+        timestamp,
+        status: format!("DENIED: {}", reason),
+        faculty_name: payload.faculty_name.clone(),
+        faculty_id: payload.faculty_id.clone(),
+        actor_id,
+        denial_reason: Some(kind),
+    };
+    record_access(state, tenant, history).await;
+}
+ 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use priv_access::storage::MemoryStorage;
+    use priv_access::tenant::{get_tenant, DEFAULT_TENANT};
+    use priv_access::zkp::SchnorrProver;
+    use tower::ServiceExt;
+
+    const GEOHASH: &str = "t1q7hk9vj";
+
+    static SANDBOX: std::sync::Once = std::sync::Once::new();
+
+    // A second campus, for checking that nothing leaks between tenants
+    const OTHER_TENANT: &str = "test-north";
+
+    // The memory store and the faculty and revocation registries write JSON files into the working
+    // directory. Load the repo's faculty first, then move into a scratch directory so tests never
+    // touch the real files. Campuses load there too, so the second one's doors file isn't written
+    // into the checkout.
+    fn sandbox() {
+        SANDBOX.call_once(|| {
+            std::env::set_var("PRIVACCESS_TENANTS", OTHER_TENANT);
+            let _ = priv_access::faculty::list();
+            let dir = std::env::temp_dir().join(format!("privaccess-tests-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::env::set_current_dir(&dir).unwrap();
+            let _ = get_tenant(DEFAULT_TENANT);
+            // Just the default admin, whatever admins.json the checkout has
+            let _ = priv_access::admins::list();
+        });
+    }
+
+    fn test_state() -> Arc<AppState> {
+        sandbox();
+        Arc::new(AppState {
+            tera: Tera::default(),
+            storage: Arc::new(MemoryStorage::default()),
+            limiter: priv_access::ratelimit::RateLimiter::local(),
+            throttle: priv_access::ratelimit::IpThrottle::new(0, 0),
+            proof_window: priv_access::zkp::ProofWindow { max_age_secs: 60, max_skew_secs: 10 },
+            groth16: None,
+            revoked: priv_access::revocation::RevocationList::load(),
+            ed25519_keys: priv_access::identity::KeyRegistry::load(),
+            passkeys: priv_access::webauthn::PasskeyRegistry::load(),
+            lan_ip: std::sync::RwLock::new("127.0.0.1".to_string()),
+            sections: priv_access::config::CONFIG.sections.clone(),
+            require_proximity: priv_access::config::CONFIG.require_proximity.clone(),
+        })
+    }
+
+    fn tenant() -> Arc<TenantState> {
+        sandbox();
+        get_tenant(DEFAULT_TENANT).unwrap()
+    }
+
+    // A door of its own per test, so tests running in parallel don't share QR or section state
+    fn seed_door(door_id: &str) -> Door {
+        let door = Door::new(door_id, "secret", GEOHASH, 1).unwrap().with_radius(50);
+        tenant().doors.write().unwrap().insert(door_id.to_string(), door.clone());
+        door
+    }
+
+    async fn seed_section(state: &AppState, section: &str, door_id: &str) {
+        state.storage.assign(DEFAULT_TENANT, section, (door_id.to_string(), "Dr. Test".to_string())).await.unwrap();
+    }
+
+    // A proof for `role`'s key that answers a fresh challenge for the door
+    async fn role_proof(state: &AppState, role: &str, door_id: &str) -> Proof {
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        state.storage.issue_challenge(DEFAULT_TENANT, door_id, &nonce, CHALLENGE_TTL_SECS).await.unwrap();
+        SchnorrProver::new(tenant().role_secret(role).unwrap()).generate_proof(GEOHASH.to_string(), &nonce)
+    }
+
+    // What the phone sends after scanning the door's dynamic QR from where the door is
+    fn scan_payload(door: &Door, door_id: &str, role: &str, proof: &Proof) -> serde_json::Value {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        json!({
+            "door_id": door_id,
+            "role": role,
+            "proof": proof,
+            "geohash": GEOHASH,
+            "lat": door.lat,
+            "lon": door.lon,
+            "nonce": hex::encode(rand::random::<[u8; 8]>()),
+            "qr_timestamp": now,
+            "floor": door.floor
+        })
+    }
+
+    async fn verify(state: Arc<AppState>, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        call(state, "POST", "/api/verify", &[], Some(body)).await
+    }
+
+    // One request through the whole router, campus prefix included, from a LAN client; the body is
+    // JSON, or null if empty
+    async fn call(
+        state: Arc<AppState>,
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut req = axum::http::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let mut req = match body {
+            Some(body) => req.header("content-type", "application/json").body(Body::from(body.to_string())),
+            None => req.body(Body::empty()),
+        }
+        .unwrap();
+        req.extensions_mut().insert(ConnectInfo(std::net::SocketAddr::from(([127, 0, 0, 1], 40000))));
+        let app = tower::Layer::layer(&axum::middleware::from_fn(priv_access::tenant::resolve_tenant), build_router(state));
+        let res = app.oneshot(req).await.unwrap();
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn destructive_endpoints_need_the_delete_permission() {
+        let state = test_state();
+        seed_door("test-undeletable");
+        priv_access::faculty::add("TEST-NODELETE", "", "1357").unwrap();
+        let faculty = [("x-faculty-id", "TEST-NODELETE"), ("x-faculty-pin", "1357")];
+
+        let (status, res) = call(state.clone(), "DELETE", "/api/v1/doors/test-undeletable", &faculty, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["error"]["code"], "FORBIDDEN");
+        // The live log only empties through the confirmed archive, never a bare DELETE
+        let admin = [("x-admin-password", "Admin@1234")];
+        for uri in ["/api/v1/history", "/api/history", "/history"] {
+            let (status, _) = call(state.clone(), "DELETE", uri, &admin, None).await;
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "DELETE {}", uri);
+        }
+        // Without credentials it's a 401, not a 403
+        let (status, _) = call(state.clone(), "DELETE", "/api/v1/doors/test-undeletable", &[], None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(tenant().door("test-undeletable").is_some());
+    }
+
+    #[tokio::test]
+    async fn role_permissions_are_served_with_inheritance_resolved() {
+        let state = test_state();
+        let (status, res) = call(state.clone(), "GET", "/api/v1/roles/ADMIN/permissions", &[], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res["permissions"], json!(["read", "write", "delete"]));
+        let (_, res) = call(state.clone(), "GET", "/api/roles/student/permissions", &[], None).await;
+        assert_eq!((res["role"].as_str(), &res["permissions"]), (Some("STUDENT"), &json!(["read"])));
+
+        let (status, res) = call(state.clone(), "GET", "/api/v1/roles/JANITOR/permissions", &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(res["error"]["code"], "INVALID_ROLE");
+        let (_, res) = call(state, "GET", "/api/v1/roles", &[], None).await;
+        assert_eq!(res["roles"], json!(["ADMIN", "FACULTY", "STUDENT"]));
+    }
+
+    #[tokio::test]
+    async fn the_scan_page_leaves_out_controls_the_role_cannot_use() {
+        let mut state = Arc::try_unwrap(test_state()).ok().unwrap();
+        state.tera = Tera::new(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/**/*")).unwrap();
+        let app = build_router(Arc::new(state));
+        let page = |role: &str| {
+            let req = axum::http::Request::get(format!("/mobile/scan?role={}", role)).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                assert_eq!(res.status(), StatusCode::OK);
+                String::from_utf8(axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+            }
+        };
+
+        assert!(page("FACULTY").await.contains(r#"id="faculty-pin""#));
+        let student = page("STUDENT").await;
+        assert!(!student.contains(r#"id="faculty-pin""#));
+        assert!(student.contains(r#"const ROLE_PERMISSIONS = ["read"]"#));
+    }
+
+    #[tokio::test]
+    async fn dev_proofs_answer_a_challenge_and_are_off_outside_demo_mode() {
+        let state = test_state();
+        seed_door("test-dev-prove");
+        let proof = dev_proof(&state, &tenant(), "STUDENT", GEOHASH, Some("test-dev-prove")).await.unwrap();
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Ok(()));
+        assert_eq!(state.storage.take_challenge(DEFAULT_TENANT, &proof.nonce).await.unwrap().as_deref(), Some("test-dev-prove"));
+        assert_eq!(dev_proof(&state, &tenant(), "JANITOR", GEOHASH, None).await.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+        // Tests run without PRIVACCESS_DEMO_MODE
+        let req = axum::http::Request::post("/api/dev/prove")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "role": "STUDENT", "geohash": GEOHASH }).to_string()))
+            .unwrap();
+        assert_eq!(build_router(state).oneshot(req).await.unwrap().status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn groth16_proofs_without_a_verifier_are_unavailable_not_denied() {
+        let req = axum::http::Request::post("/verify")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "proof": {}, "publicSignals": ["1"] }).to_string()))
+            .unwrap();
+        let res = build_router(test_state()).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = axum::body::to_bytes(res.into_body(), MAX_BODY_BYTES).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"]["code"], "VERIFIER_UNAVAILABLE");
+
+        let missing = priv_access::groth16::Groth16Verifier::load("no-such-vkey.json").err().unwrap();
+        assert_eq!(missing.code(), "vkey_missing");
+    }
+
+    #[tokio::test]
+    async fn admin_with_wrong_password_is_unauthorized() {
+        let state = test_state();
+        seed_door("test-admin");
+        let body = json!({
+            "door_id": "test-admin",
+            "role": "ADMIN",
+            "proof": {"public_key": "0", "commitment": "0", "response": "0", "geohash": "0"},
+            "geohash": "0",
+            "password": "not-the-password"
+        });
+        let (status, res) = verify(state, body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res["error"]["code"], "UNAUTHORIZED");
+    }
+
+    #[tokio::test]
+    async fn admin_unlocks_are_attributed_to_the_admin() {
+        let state = test_state();
+        let door = seed_door("test-admins");
+        let req = axum::http::Request::post("/api/admins")
+            .header("content-type", "application/json")
+            .header("x-admin-password", "Admin@1234")
+            .body(Body::from(json!({ "id": "test-ops", "name": "Night Shift", "password": "night-shift-1" }).to_string()))
+            .unwrap();
+        assert_eq!(build_router(state.clone()).oneshot(req).await.unwrap().status(), StatusCode::CREATED);
+
+        let unlock = |password: &str| json!({
+            "door_id": "test-admins",
+            "role": "ADMIN",
+            "geohash": "0",
+            "admin_id": "test-ops",
+            "password": password
+        });
+        let (status, res) = verify(state.clone(), unlock("night-shift-1")).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        let granted = logs.iter().rev().find(|l| l.door_name == door.name).unwrap();
+        assert_eq!(granted.actor_id.as_deref(), Some("test-ops"));
+
+        // The default admin's password isn't theirs
+        let (status, _) = verify(state.clone(), unlock("Admin@1234")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let req = axum::http::Request::delete("/api/admins/test-ops")
+            .header("x-admin-password", "Admin@1234")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(build_router(state.clone()).oneshot(req).await.unwrap().status(), StatusCode::OK);
+        let (status, _) = verify(state, unlock("night-shift-1")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn qr_timestamps_must_be_recent_and_not_ahead_of_the_clock() {
+        let state = test_state();
+        let door = seed_door("test-qr-clock");
+        seed_section(&state, "G", "test-qr-clock").await;
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let skew = priv_access::config::CONFIG.max_clock_skew_secs;
+        for (ts, code) in [(now - 60, "QR_EXPIRED"), (now + skew + 60, "QR_INVALID"), (u64::MAX, "QR_INVALID")] {
+            let proof = role_proof(&state, "STUDENT", "test-qr-clock").await;
+            let mut body = scan_payload(&door, "test-qr-clock", "STUDENT", &proof);
+            body["section"] = json!("G");
+            body["qr_timestamp"] = json!(ts);
+            let (status, res) = verify(state.clone(), body).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}: {}", ts, res);
+            assert_eq!(res["error"]["code"], code, "{}: {}", ts, res);
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_geohashes_are_refused_cleanly() {
+        let state = test_state();
+        let door = seed_door("test-geohash");
+        seed_section(&state, "A", "test-geohash").await;
+        for geohash in ["", "tdr1a", "tdr1 wxyz", "é", "0123456789bcd"] {
+            // An empty geohash just means the request doesn't use one; the proof always does
+            if !geohash.is_empty() {
+                let proof = role_proof(&state, "STUDENT", "test-geohash").await;
+                let mut body = scan_payload(&door, "test-geohash", "STUDENT", &proof);
+                body["section"] = json!("A");
+                body["geohash"] = json!(geohash);
+                let (status, res) = verify(state.clone(), body).await;
+                assert_eq!(status, StatusCode::BAD_REQUEST, "{:?}: {}", geohash, res);
+                assert_eq!(res["error"]["code"], "INVALID_LOCATION");
+                assert_eq!(res["error"]["field"], "geohash");
+            }
+
+            let proof = Proof { geohash: geohash.to_string(), ..role_proof(&state, "STUDENT", "test-geohash").await };
+            let mut body = scan_payload(&door, "test-geohash", "STUDENT", &proof);
+            body["section"] = json!("A");
+            let (status, res) = verify(state.clone(), body).await;
+            assert!(status.is_client_error(), "{:?}: {} {}", geohash, status, res);
+            assert_eq!(res["error"]["code"], "INVALID_LOCATION", "{:?}: {}", geohash, res);
+            assert!(res["reason"].as_str().unwrap_or("").starts_with("geohash_"), "{:?}: {}", geohash, res);
+        }
+    }
+
+    #[tokio::test]
+    async fn untrusted_fields_are_checked_before_the_handler_runs() {
+        let state = test_state();
+        let door = seed_door("test-validation");
+        let proof = role_proof(&state, "FACULTY", "test-validation").await;
+        let body = scan_payload(&door, "test-validation", "FACULTY", &proof);
+
+        let mut forged = body.clone();
+        forged["faculty_name"] = json!("Dr. Test\nGRANTED");
+        let (status, res) = verify(state.clone(), forged).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(res["error"]["field"], "faculty_name");
+
+        let mut unknown = body.clone();
+        unknown["section"] = json!("Z");
+        let (status, res) = verify(state.clone(), unknown).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(res["error"]["code"], "INVALID_SECTION");
+        assert_eq!(res["error"]["field"], "section");
+
+        let mut oversized = body;
+        oversized["beacon_id"] = json!("b".repeat(validate::MAX_ID_LEN + 1));
+        let (status, res) = verify(state.clone(), oversized).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(res["error"]["field"], "beacon_id");
+
+        // Nothing was logged for any of them
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        assert!(!logs.iter().any(|l| l.door_name == door.name));
+    }
+
+    #[tokio::test]
+    async fn scheduled_door_is_closed_to_students_but_not_admins() {
+        let state = test_state();
+        // Open for an hour tomorrow only, so never right now
+        let tomorrow = (chrono::Local::now() + chrono::Duration::days(1)).format("%a").to_string().to_lowercase();
+        let schedule = toml::from_str(&format!("{} = [\"08:00-09:00\"]", tomorrow)).unwrap();
+        let door = Door::new("test-schedule", "secret", GEOHASH, 1).unwrap().with_schedule(schedule);
+        tenant().doors.write().unwrap().insert("test-schedule".to_string(), door.clone());
+
+        let proof = role_proof(&state, "STUDENT", "test-schedule").await;
+        let (status, res) = verify(state.clone(), scan_payload(&door, "test-schedule", "STUDENT", &proof)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["error"]["code"], "OUTSIDE_SCHEDULE");
+        assert!(res["next_open"].as_str().unwrap().contains("T08:00:00"), "{}", res);
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        let denied = logs.iter().rev().find(|l| l.door_name == door.name).unwrap();
+        assert_eq!(denied.denial_reason, Some(DenialReason::OutsideSchedule));
+
+        let (status, res) = verify(state, json!({
+            "door_id": "test-schedule",
+            "role": "ADMIN",
+            "geohash": "0",
+            "password": "Admin@1234"
+        })).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+    }
+
+    #[tokio::test]
+    async fn faculty_far_from_the_door_is_forbidden() {
+        let state = test_state();
+        let door = seed_door("test-faculty");
+        priv_access::faculty::add("TEST-FAC", "Dr. Test", "4321").unwrap();
+        let proof = role_proof(&state, "FACULTY", "test-faculty").await;
+        let mut body = scan_payload(&door, "test-faculty", "FACULTY", &proof);
+        body["faculty_id"] = json!("TEST-FAC");
+        body["pin"] = json!("4321");
+        body["section"] = json!("A");
+        // About 1km north of the door
+        body["lat"] = json!(door.lat + 0.01);
+
+        let (status, res) = verify(state.clone(), body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["error"]["code"], "LOCATION_MISMATCH");
+        // Refused before the faculty member could claim a room
+        assert_eq!(state.storage.assignment(DEFAULT_TENANT, "A").await.unwrap(), None);
+    }
+
+    // The test state, with `role` made to show it's at the door or not
+    fn state_with_proximity(role: &str, required: bool) -> Arc<AppState> {
+        let mut state = Arc::try_unwrap(test_state()).ok().unwrap();
+        state.require_proximity.insert(role.to_string(), required);
+        Arc::new(state)
+    }
+
+    #[tokio::test]
+    async fn admins_may_unlock_remotely_unless_told_to_be_at_the_door() {
+        let door = seed_door("test-remote-admin");
+        let far_away = || {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+            let mut body = json!({
+                "door_id": "test-remote-admin",
+                "role": "ADMIN",
+                "geohash": GEOHASH,
+                "lon": door.lon,
+                "nonce": hex::encode(rand::random::<[u8; 8]>()),
+                "qr_timestamp": now,
+                "floor": door.floor
+            });
+            body["password"] = json!("Admin@1234");
+            // About 1km north of the door
+            body["lat"] = json!(door.lat + 0.01);
+            body
+        };
+
+        let (status, res) = verify(test_state(), far_away()).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+
+        let (status, res) = verify(state_with_proximity("ADMIN", true), far_away()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["error"]["code"], "LOCATION_MISMATCH");
+    }
+
+    #[tokio::test]
+    async fn a_role_let_off_proximity_skips_the_location_check() {
+        let door = seed_door("test-proximity-flag");
+        let attempt = |state: Arc<AppState>| {
+            let door = door.clone();
+            async move {
+                seed_section(&state, "D", "test-proximity-flag").await;
+                let proof = role_proof(&state, "STUDENT", "test-proximity-flag").await;
+                let mut body = scan_payload(&door, "test-proximity-flag", "STUDENT", &proof);
+                body["section"] = json!("D");
+                body["lat"] = json!(door.lat + 0.01);
+                verify(state, body).await
+            }
+        };
+
+        let (status, res) = attempt(test_state()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["error"]["code"], "LOCATION_MISMATCH");
+
+        let (status, res) = attempt(state_with_proximity("STUDENT", false)).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+    }
+
+    #[tokio::test]
+    async fn repeated_wrong_pins_lock_the_faculty_account() {
+        let state = test_state();
+        let door = seed_door("test-lockout");
+        priv_access::faculty::add("TEST-LOCKED", "", "2468").unwrap();
+        let proof = role_proof(&state, "FACULTY", "test-lockout").await;
+        let attempt = |pin: &str| {
+            let mut body = scan_payload(&door, "test-lockout", "FACULTY", &proof);
+            body["faculty_id"] = json!("TEST-LOCKED");
+            body["pin"] = json!(pin);
+            verify(state.clone(), body)
+        };
+
+        // A correct PIN in between starts the count again
+        let limit = priv_access::config::CONFIG.lockout_attempts as usize;
+        let pins = [vec!["0000"; limit - 1], vec!["2468"], vec!["0000"; limit - 1]].concat();
+        for pin in pins {
+            let (status, res) = attempt(pin).await;
+            assert_ne!(status, StatusCode::TOO_MANY_REQUESTS, "{}", res);
+        }
+        let (status, _) = attempt("0000").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Locked now, even with the right PIN
+        let (status, res) = attempt("2468").await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res["error"]["code"], "ACCOUNT_LOCKED");
+
+        // The lock is on the account, not the endpoint or the campus the guesses came through
+        let faculty = [("x-faculty-id", "test-locked"), ("x-faculty-pin", "2468")];
+        let (status, res) = call(state.clone(), "GET", "/api/v1/faculty/TEST-LOCKED/stats", &faculty, None).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res["error"]["code"], "ACCOUNT_LOCKED");
+        let uri = format!("/t/{}/api/v1/faculty/TEST-LOCKED/stats", OTHER_TENANT);
+        let (status, _) = call(state, "GET", &uri, &faculty, None).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn wrong_admin_passwords_at_login_lock_remote_unlocks_too() {
+        let state = test_state();
+        seed_door("test-admin-lockout");
+        let admin = [("x-admin-password", "Admin@1234")];
+        let body = json!({ "id": "test-guessed", "name": "Guessed", "password": "guessed-pass-1" });
+        let (status, _) = call(state.clone(), "POST", "/api/v1/admins", &admin, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let login = |password: &str| json!({ "admin_id": "test-guessed", "password": password });
+        for _ in 0..priv_access::config::CONFIG.lockout_attempts {
+            let (status, res) = call(state.clone(), "POST", "/api/v1/admin/login", &[], Some(login("wrong-pass-1"))).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", res);
+        }
+        let (status, _) = call(state.clone(), "POST", "/api/v1/admin/login", &[], Some(login("guessed-pass-1"))).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        let unlock = json!({ "door_id": "test-admin-lockout", "role": "ADMIN", "geohash": "0", "admin_id": "TEST-GUESSED", "password": "guessed-pass-1" });
+        let (status, res) = verify(state.clone(), unlock).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res["error"]["code"], "ACCOUNT_LOCKED");
+        // Other admins aren't held up
+        let (status, _) = call(state, "GET", "/api/v1/admins", &admin, None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn enrolled_admins_need_their_code_alongside_the_password() {
+        let state = test_state();
+        seed_door("test-totp");
+        let admin = [("x-admin-password", "Admin@1234")];
+        let body = json!({ "id": "test-totp-admin", "name": "Two Factor", "password": "two-factor-1" });
+        let (status, _) = call(state.clone(), "POST", "/api/v1/admins", &admin, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let password = [("x-admin-id", "test-totp-admin"), ("x-admin-password", "two-factor-1")];
+
+        // A password alone can't bind an authenticator app; a fresh dashboard login can
+        let (status, _) = call(state.clone(), "GET", "/api/v1/admin/totp/provision", &password, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let mut login = axum::http::Request::post("/api/v1/admin/login")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "admin_id": "test-totp-admin", "password": "two-factor-1" }).to_string()))
+            .unwrap();
+        login.extensions_mut().insert(ConnectInfo(std::net::SocketAddr::from(([127, 0, 0, 1], 40000))));
+        let res = build_router(state.clone()).oneshot(login).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let set_cookie = res.headers()[axum::http::header::SET_COOKIE].to_str().unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+        let (status, res) = call(state.clone(), "GET", "/api/v1/admin/totp/provision", &[("cookie", &cookie)], None).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        let totp = totp_rs::Totp::from_url(res["otpauth_url"].as_str().unwrap()).unwrap();
+
+        // From now on the password needs the code on admin endpoints and remote unlocks alike
+        let (status, res) = call(state.clone(), "GET", "/api/v1/admins", &password, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res["reason"], "totp_required");
+        let unlock = json!({ "door_id": "test-totp", "role": "ADMIN", "geohash": "0", "admin_id": "test-totp-admin", "password": "two-factor-1" });
+        let (status, res) = verify(state.clone(), unlock).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res["reason"], "totp_required");
+
+        let code = totp.generate_current().to_string();
+        let with_code = [password[0], password[1], ("x-admin-totp", code.as_str())];
+        let (status, res) = call(state.clone(), "GET", "/api/v1/admins", &with_code, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        // Each code works once
+        let (status, res) = call(state, "GET", "/api/v1/admins", &with_code, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res["reason"], "totp_reused");
+    }
+
+    #[tokio::test]
+    async fn student_without_an_assigned_room_is_forbidden() {
+        let state = test_state();
+        let door = seed_door("test-unassigned");
+        let proof = role_proof(&state, "STUDENT", "test-unassigned").await;
+        let mut body = scan_payload(&door, "test-unassigned", "STUDENT", &proof);
+        body["section"] = json!("B");
+
+        let (status, res) = verify(state.clone(), body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["error"]["code"], "NO_ROOM_ASSIGNED");
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        let denied = logs.iter().rev().find(|l| l.door_name == door.name).unwrap();
+        assert!(denied.status.starts_with("DENIED"));
+        assert_eq!(denied.denial_reason, Some(DenialReason::NoRoomAssigned));
+    }
+
+    #[tokio::test]
+    async fn student_in_the_right_room_with_a_valid_proof_is_granted() {
+        let state = test_state();
+        let door = seed_door("test-granted");
+        seed_section(&state, "C", "test-granted").await;
+        let proof = role_proof(&state, "STUDENT", "test-granted").await;
+        let mut body = scan_payload(&door, "test-granted", "STUDENT", &proof);
+        body["section"] = json!("C");
+        let logged_before = state.storage.logs(DEFAULT_TENANT).await.unwrap().len();
+
+        let (status, res) = verify(state.clone(), body).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        assert_eq!(res["status"], "success");
+
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        assert_eq!(logs.len(), logged_before + 1);
+        let entry = logs.last().unwrap();
+        assert_eq!((entry.role.as_str(), entry.section.as_str(), entry.status.as_str()), ("STUDENT", "C", "GRANTED"));
+        assert_eq!(entry.door_name, "test-granted");
+    }
+
+    #[tokio::test]
+    async fn granted_entries_name_the_account_not_the_request() {
+        let state = test_state();
+        let door = seed_door("test-named");
+        priv_access::faculty::add("TEST-NAMED", "Dr. Named", "4321").unwrap();
+        let proof = role_proof(&state, "FACULTY", "test-named").await;
+        let mut body = scan_payload(&door, "test-named", "FACULTY", &proof);
+        body["faculty_id"] = json!("TEST-NAMED");
+        body["pin"] = json!("4321");
+        body["faculty_name"] = json!("Dr. Someone Else");
+        let (status, res) = verify(state.clone(), body).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+
+        seed_section(&state, "H", "test-named").await;
+        let proof = role_proof(&state, "STUDENT", "test-named").await;
+        let mut body = scan_payload(&door, "test-named", "STUDENT", &proof);
+        body["section"] = json!("H");
+        body["faculty_id"] = json!("TEST-NAMED");
+        body["faculty_name"] = json!("Dr. Named");
+        let (status, res) = verify(state.clone(), body).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        let logged: Vec<_> = logs.iter().filter(|l| l.door_name == "test-named").map(|l| (l.role.as_str(), l.faculty_id.as_deref(), l.faculty_name.as_deref())).collect();
+        assert_eq!(logged, [("FACULTY", Some("TEST-NAMED"), Some("Dr. Named")), ("STUDENT", None, None)]);
+    }
+
+    #[tokio::test]
+    async fn faculty_cannot_take_a_section_another_one_holds() {
+        let state = test_state();
+        let door = seed_door("test-rival");
+        priv_access::faculty::add("TEST-OWNER", "", "1111").unwrap();
+        priv_access::faculty::add("TEST-RIVAL", "", "2222").unwrap();
+        state.storage.assign(DEFAULT_TENANT, "E", ("test-owned".to_string(), "TEST-OWNER".to_string())).await.unwrap();
+
+        let proof = role_proof(&state, "FACULTY", "test-rival").await;
+        let mut body = scan_payload(&door, "test-rival", "FACULTY", &proof);
+        body["faculty_id"] = json!("TEST-RIVAL");
+        body["pin"] = json!("2222");
+        body["section"] = json!("E");
+        let (status, res) = verify(state.clone(), body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(res["error"]["code"], "SECTION_CONFLICT");
+
+        let unassign = |id: &str, pin: &str| {
+            let req = axum::http::Request::post("/api/unassign_section")
+                .header("content-type", "application/json")
+                .header("x-faculty-id", id)
+                .header("x-faculty-pin", pin)
+                .body(Body::from(json!({ "section": "e" }).to_string()))
+                .unwrap();
+            build_router(state.clone()).oneshot(req)
+        };
+        assert_eq!(unassign("TEST-RIVAL", "2222").await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert_eq!(unassign("TEST-OWNER", "1111").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(state.storage.assignment(DEFAULT_TENANT, "E").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn door_beacon_reading_decides_over_gps() {
+        let state = test_state();
+        let door = seed_door("test-beacon").with_beacon("B1", -70);
+        tenant().doors.write().unwrap().insert("test-beacon".to_string(), door.clone());
+        seed_section(&state, "F", "test-beacon").await;
+        let scan = |rssi: i32| {
+            let state = state.clone();
+            let door = door.clone();
+            async move {
+                let proof = role_proof(&state, "STUDENT", "test-beacon").await;
+                let mut body = scan_payload(&door, "test-beacon", "STUDENT", &proof);
+                body["section"] = json!("F");
+                // GPS puts the phone about 1km away, as it can indoors
+                body["lat"] = json!(door.lat + 0.01);
+                body["beacon_id"] = json!("b1");
+                body["beacon_rssi"] = json!(rssi);
+                body
+            }
+        };
+
+        let (status, res) = verify(state.clone(), scan(-85).await).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["reason"], "beacon_too_weak");
+
+        let (status, res) = verify(state.clone(), scan(-60).await).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+    }
+
+    #[tokio::test]
+    async fn a_display_connecting_after_an_unlock_still_hears_of_it() {
+        let state = test_state();
+        seed_door("test-late-display");
+        // Nobody is watching the door when it opens
+        priv_access::door_status::notify(&tenant(), "test-late-display", "unlocked");
+
+        let req = axum::http::Request::get("/door/test-late-display/status").body(Body::empty()).unwrap();
+        let res = build_router(state).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut events = res.into_body().into_data_stream();
+        let first = tokio::time::timeout(std::time::Duration::from_secs(5), futures::StreamExt::next(&mut events)).await.unwrap().unwrap().unwrap();
+        assert_eq!(std::str::from_utf8(&first).unwrap().trim(), "data: unlocked");
+    }
+
+    #[tokio::test]
+    async fn only_listed_origins_may_call_the_api_cross_origin() {
+        let state = test_state();
+        let mut config = priv_access::config::CONFIG.clone();
+        config.cors_allow_any = false;
+        config.cors_origins = Vec::new();
+        assert!(cors_layer(&config).is_none());
+        config.cors_origins = vec!["https://app.example".to_string()];
+        let allowed_origin = |origin: &str, method: &str, uri: &str| {
+            let req = axum::http::Request::builder().method(method).uri(uri)
+                .header("origin", origin)
+                .header("access-control-request-method", "GET")
+                .body(Body::empty())
+                .unwrap();
+            let app = build_router_with_cors(state.clone(), cors_layer(&config));
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                res.headers().get("access-control-allow-origin").map(|v| v.to_str().unwrap().to_string())
+            }
+        };
+
+        assert_eq!(allowed_origin("https://app.example", "GET", "/api/v1/sections").await.as_deref(), Some("https://app.example"));
+        assert_eq!(allowed_origin("https://app.example", "OPTIONS", "/api/v1/sections").await.as_deref(), Some("https://app.example"));
+        assert_eq!(allowed_origin("https://app.example", "GET", "/api/sections").await.as_deref(), Some("https://app.example"));
+        assert_eq!(allowed_origin("https://elsewhere.example", "GET", "/api/v1/sections").await, None);
+        // Pages aren't part of the API
+        assert_eq!(allowed_origin("https://app.example", "GET", "/healthz").await, None);
+    }
+
+    #[tokio::test]
+    async fn api_keys_work_until_revoked_and_only_on_their_campus() {
+        let state = test_state();
+        let admin = [("x-admin-password", "Admin@1234")];
+        let create = |permissions: serde_json::Value| {
+            let body = json!({ "name": "test kiosk", "permissions": permissions });
+            call(state.clone(), "POST", "/api/v1/keys", &admin, Some(body))
+        };
+        let (status, kiosk) = create(json!(["write"])).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", kiosk);
+        let (_, reader) = create(json!(["read"])).await;
+        let report = |key: String, uri: &'static str| {
+            let body = json!({ "door_id": "test-kiosk", "status": "connected" });
+            let state = state.clone();
+            async move { call(state, "POST", uri, &[("x-api-key", &key)], Some(body)).await.0 }
+        };
+        let key = |created: &serde_json::Value| created["key"].as_str().unwrap().to_string();
+
+        assert_eq!(report(key(&kiosk), "/api/v1/notify_status").await, StatusCode::OK);
+        assert_eq!(report(key(&reader), "/api/v1/notify_status").await, StatusCode::FORBIDDEN);
+        assert_eq!(report(key(&kiosk), "/t/test-north/api/v1/notify_status").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(report("pak_not-a-key".to_string(), "/api/v1/notify_status").await, StatusCode::UNAUTHORIZED);
+
+        let revoke = format!("/api/v1/keys/{}", kiosk["id"].as_str().unwrap());
+        assert_eq!(call(state.clone(), "DELETE", &revoke, &admin, None).await.0, StatusCode::OK);
+        assert_eq!(report(key(&kiosk), "/api/v1/notify_status").await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn v1_paths_and_their_old_aliases_reach_the_same_handlers() {
+        let state = test_state();
+        let status = |method: &str, uri: &str| {
+            let mut req = axum::http::Request::builder().method(method).uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "proof": {}, "publicSignals": ["1"] }).to_string()))
+                .unwrap();
+            req.extensions_mut().insert(ConnectInfo(std::net::SocketAddr::from(([127, 0, 0, 1], 40000))));
+            let app = build_router(state.clone());
+            async move { app.oneshot(req).await.unwrap().status() }
+        };
+        for (v1, old) in [("/api/v1/sections", "/api/sections"), ("/api/v1/history", "/history"), ("/api/v1/setup", "/mobile/setup")] {
+            assert_eq!(status("GET", v1).await, StatusCode::OK, "{}", v1);
+            assert_eq!(status("GET", old).await, StatusCode::OK, "{}", old);
+        }
+        assert_eq!(status("POST", "/api/v1/zkp/verify").await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status("POST", "/verify").await, StatusCode::SERVICE_UNAVAILABLE);
+        // Pages aren't part of the API
+        assert_eq!(status("GET", "/api/v1/mobile/scan").await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn sections_come_from_the_state_not_a_fixed_list() {
+        let mut state = Arc::into_inner(test_state()).unwrap();
+        state.sections = vec!["CS-1".to_string(), "CS-2".to_string()];
+        let state = Arc::new(state);
+        let get = |uri: &str| {
+            let req = axum::http::Request::get(uri).body(Body::empty()).unwrap();
+            let app = build_router(state.clone());
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let bytes = axum::body::to_bytes(res.into_body(), MAX_BODY_BYTES).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, body) = get("/api/sections").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["sections"], json!(["CS-1", "CS-2"]));
+
+        let (status, body) = get("/api/my_room?section=cs-2").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["section"], "CS-2");
+        let (status, body) = get("/api/my_room?section=E").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "INVALID_SECTION");
+    }
+
+    #[tokio::test]
+    async fn door_list_hides_secrets_and_private_doors_from_non_admins() {
+        let state = test_state();
+        let mut door = seed_door("test-private");
+        door.private = true;
+        tenant().doors.write().unwrap().insert("test-private".to_string(), door);
+        let list = |password: Option<&str>| {
+            let mut req = axum::http::Request::get("/api/doors");
+            if let Some(password) = password {
+                req = req.header("x-admin-password", password);
+            }
+            let app = build_router(state.clone());
+            async move {
+                let res = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+                let bytes = axum::body::to_bytes(res.into_body(), MAX_BODY_BYTES).await.unwrap();
+                serde_json::from_slice::<Vec<serde_json::Value>>(&bytes).unwrap()
+            }
+        };
+
+        let public = list(None).await;
+        assert!(public.iter().any(|d| d["id"] == "room101"));
+        assert!(public.iter().all(|d| d["id"] != "test-private" && d.get("secret_qr").is_none()));
+        let admin = list(Some("Admin@1234")).await;
+        assert!(admin.iter().any(|d| d["id"] == "test-private" && d["private"] == true));
+    }
+
+    // A door on the second campus
+    fn seed_other_door(door_id: &str) -> Door {
+        sandbox();
+        let door = Door::new(door_id, "secret", GEOHASH, 1).unwrap().with_radius(50);
+        get_tenant(OTHER_TENANT).unwrap().doors.write().unwrap().insert(door_id.to_string(), door.clone());
+        door
+    }
+
+    #[tokio::test]
+    async fn a_door_on_one_campus_is_out_of_reach_from_another() {
+        let state = test_state();
+        let door = seed_other_door("test-north-wing");
+        let admin = [("x-admin-password", "Admin@1234")];
+
+        let (_, north) = call(state.clone(), "GET", "/t/test-north/api/v1/doors", &admin, None).await;
+        assert!(north.as_array().unwrap().iter().any(|d| d["id"] == "test-north-wing"));
+        let (_, here) = call(state.clone(), "GET", "/api/v1/doors", &admin, None).await;
+        assert!(here.as_array().unwrap().iter().all(|d| d["id"] != "test-north-wing"));
+
+        // A scan that would be valid on its own campus names no door here
+        let proof = role_proof(&state, "STUDENT", "test-north-wing").await;
+        let (status, res) = verify(state.clone(), scan_payload(&door, "test-north-wing", "STUDENT", &proof)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", res);
+        assert_eq!(res["error"]["code"], "DOOR_NOT_FOUND");
+        let (status, _) = call(state, "GET", "/api/v1/dynamic_qr/test-north-wing", &[], None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn scan_redirects_keep_query_values_inside_their_parameter() {
+        let state = test_state();
+        seed_door("test-redirect");
+        let req = axum::http::Request::get("/s/test-redirect?nonce=n%26role%3DADMIN&role=STUDENT%23x").body(Body::empty()).unwrap();
+        let app = tower::Layer::layer(&axum::middleware::from_fn(priv_access::tenant::resolve_tenant), build_router(state));
+        let res = app.oneshot(req).await.unwrap();
+        assert!(res.status().is_redirection());
+        assert_eq!(res.headers()["location"], "/mobile/scan?door=test-redirect&nonce=n%26role%3DADMIN&role=STUDENT%23x");
+    }
+
+    #[tokio::test]
+    async fn the_same_door_id_on_two_campuses_never_crosses_over() {
+        let state = test_state();
+        let door = seed_door("test-lab");
+        seed_other_door("test-lab");
+
+        let (_, here) = call(state.clone(), "GET", "/api/v1/dynamic_qr/test-lab", &[], None).await;
+        let (_, north) = call(state.clone(), "GET", "/t/test-north/api/v1/dynamic_qr/test-lab", &[], None).await;
+        assert!(north["url"].as_str().unwrap().contains("/t/test-north/s/test-lab?"), "{}", north);
+        assert!(!here["url"].as_str().unwrap().contains("/t/"), "{}", here);
+
+        // Scanning the other campus's QR lands on that campus's page
+        let req = axum::http::Request::get("/t/test-north/s/test-lab").body(Body::empty()).unwrap();
+        let app = tower::Layer::layer(&axum::middleware::from_fn(priv_access::tenant::resolve_tenant), build_router(state.clone()));
+        let res = app.oneshot(req).await.unwrap();
+        assert!(res.status().is_redirection());
+        assert_eq!(res.headers()["location"], "/t/test-north/mobile/scan?door=test-lab");
+
+        // Each campus has its own role keys, so a proof made with the other campus's key is refused
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        state.storage.issue_challenge(DEFAULT_TENANT, "test-lab", &nonce, CHALLENGE_TTL_SECS).await.unwrap();
+        let north_key = get_tenant(OTHER_TENANT).unwrap().role_secret("STUDENT").unwrap();
+        let proof = SchnorrProver::new(north_key).generate_proof(GEOHASH.to_string(), &nonce);
+        seed_section(&state, "E", "test-lab").await;
+        let mut body = scan_payload(&door, "test-lab", "STUDENT", &proof);
+        body["section"] = json!("E");
+        let (status, res) = verify(state, body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", res);
+        assert_eq!(res["reason"], "wrong_role_key");
+    }
+
+    #[tokio::test]
+    async fn faculty_stats_count_only_attempts_made_with_their_credentials() {
+        let state = test_state();
+        let door = seed_door("test-stats");
+        priv_access::faculty::add("TEST-STATS", "", "8642").unwrap();
+        priv_access::faculty::add("TEST-NOSY", "", "9753").unwrap();
+        let attempt = |door: &str, status: &str, actor: Option<&str>, reason: Option<DenialReason>| AccessHistory {
+            id: 0,
+            role: "FACULTY".to_string(),
+            door_name: door.to_string(),
+            section: "A".to_string(),
+            timestamp: log_timestamp(),
+            status: status.to_string(),
+            faculty_name: None,
+            faculty_id: Some("TEST-STATS".to_string()),
+            actor_id: actor.map(str::to_string),
+            denial_reason: reason,
+        };
+        for entry in [
+            attempt("test-stats-lab", "GRANTED", Some("TEST-STATS"), None),
+            attempt("test-stats-lab", "GRANTED", Some("TEST-STATS"), None),
+            attempt("test-stats-office", "GRANTED", Some("TEST-STATS"), None),
+            attempt("test-stats-lab", "DENIED: Wrong Room", Some("TEST-STATS"), Some(DenialReason::WrongRoom)),
+        ] {
+            state.storage.append_log(DEFAULT_TENANT, entry).await.unwrap();
+        }
+        // Someone else naming them with a wrong PIN isn't their attempt
+        let proof = role_proof(&state, "FACULTY", "test-stats").await;
+        let mut body = scan_payload(&door, "test-stats", "FACULTY", &proof);
+        body["faculty_id"] = json!("TEST-STATS");
+        body["pin"] = json!("0000");
+        assert_eq!(verify(state.clone(), body).await.0, StatusCode::UNAUTHORIZED);
+
+        let own = [("x-faculty-id", "TEST-STATS"), ("x-faculty-pin", "8642")];
+        let (status, stats) = call(state.clone(), "GET", "/api/v1/faculty/TEST-STATS/stats", &own, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", stats);
+        assert_eq!((stats["total_unlocks"].as_u64(), stats["total_denials"].as_u64()), (Some(3), Some(1)));
+        assert_eq!(stats["denials_by_reason"], json!({ "wrong_room": 1 }));
+        assert_eq!(stats["doors"], json!([
+            { "door_name": "test-stats-lab", "unlocks": 2, "denials": 1 },
+            { "door_name": "test-stats-office", "unlocks": 1, "denials": 0 },
+        ]));
+        assert_eq!(stats["busiest_hours"][0]["unlocks"], 3);
+
+        let nosy = [("x-faculty-id", "TEST-NOSY"), ("x-faculty-pin", "9753")];
+        let (status, _) = call(state, "GET", "/api/v1/faculty/TEST-STATS/stats", &nosy, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn reverifying_a_stored_proof_catches_tampering() {
+        let state = test_state();
+        let admin = [("x-admin-password", "Admin@1234")];
+        let proof = role_proof(&state, "STUDENT", "test-reverify").await;
+        let mut corrupted = proof.clone();
+        let s: num_bigint::BigUint = corrupted.response.parse().unwrap();
+        corrupted.response = ((s + 1u32) % &*priv_access::crypto::Q).to_string();
+
+        let mut ids = Vec::new();
+        for proof in [&proof, &corrupted] {
+            let entry = AccessHistory {
+                id: 0,
+                role: "STUDENT".to_string(),
+                door_name: "test-reverify".to_string(),
+                section: "A".to_string(),
+                timestamp: log_timestamp(),
+                status: "GRANTED".to_string(),
+                faculty_name: None,
+                faculty_id: None,
+                actor_id: None,
+                denial_reason: None,
+            };
+            let id = state.storage.append_log(DEFAULT_TENANT, entry).await.unwrap();
+            state.storage.save_proof(DEFAULT_TENANT, id, proof).await.unwrap();
+            ids.push(id);
+        }
+
+        let uri = |id: u64| format!("/api/v1/history/{}/reverify", id);
+        let (status, res) = call(state.clone(), "POST", &uri(ids[0]), &admin, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        assert_eq!(res["valid"], true);
+        let (status, res) = call(state, "POST", &uri(ids[1]), &admin, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        assert_eq!((res["valid"].as_bool(), res["reason"].as_str()), (Some(false), Some("proof_mismatch")));
+    }
+
+    #[tokio::test]
+    async fn params_publish_the_group_proofs_are_checked_in() {
+        let (status, res) = call(test_state(), "GET", "/api/v1/params", &[], None).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        let number = |field: &str| num_bigint::BigUint::parse_bytes(res["group"][field].as_str().unwrap().as_bytes(), 10).unwrap();
+        assert_eq!(number("p"), *P);
+        assert_eq!(number("g"), *G);
+        assert_eq!(number("q"), (&*P - 1u32) / 2u32);
+        assert_eq!(res["group"]["bits"], P.bits());
+        assert_eq!(res["server_key"]["public_key"], priv_access::zkp::SERVER_SIGNER.public_key().to_string());
+    }
+
+    #[tokio::test]
+    async fn signed_responses_verify_only_as_sent() {
+        let genuine = Json(json!({ "success": false, "message": "Access Denied" })).into_response();
+        let signed = sign_response(genuine, "test-lab").await;
+        let bytes = axum::body::to_bytes(signed.into_body(), MAX_BODY_BYTES).await.unwrap();
+        let mut body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["door_id"], "test-lab");
+
+        // Clients check the signature over the body without it, as compact JSON with sorted keys
+        let signature: priv_access::zkp::Signature = serde_json::from_value(body["signature"].take()).unwrap();
+        body.as_object_mut().unwrap().remove("signature");
+        let key = priv_access::zkp::SERVER_SIGNER.public_key();
+        assert!(SchnorrVerifier::verify_signature(key, body.to_string().as_bytes(), &signature));
+
+        body["success"] = json!(true);
+        body["message"] = json!("Access Granted");
+        assert!(!SchnorrVerifier::verify_signature(key, body.to_string().as_bytes(), &signature));
+    }
+
+    #[tokio::test]
+    async fn history_exports_as_a_json_array() {
+        let state = test_state();
+        let admin = [("x-admin-password", "Admin@1234")];
+        for section in ["A", "B", "C"] {
+            let entry = AccessHistory {
+                id: 0,
+                role: "STUDENT".to_string(),
+                door_name: "test-export".to_string(),
+                section: section.to_string(),
+                timestamp: log_timestamp(),
+                status: "GRANTED".to_string(),
+                faculty_name: None,
+                faculty_id: None,
+                actor_id: None,
+                denial_reason: None,
+            };
+            state.storage.append_log(DEFAULT_TENANT, entry).await.unwrap();
+        }
+        let (status, res) = call(state, "GET", "/api/v1/history/export?format=json", &admin, None).await;
+        assert_eq!(status, StatusCode::OK);
+        // Other tests log to the same in-memory history, so only look at this test's door
+        let mut sections: Vec<_> = res.as_array().unwrap().iter()
+            .filter(|e| e["door_name"] == "test-export")
+            .map(|e| e["section"].as_str().unwrap())
+            .collect();
+        sections.sort();
+        assert_eq!(sections, ["A", "B", "C"]);
+    }
+
+    #[tokio::test]
+    async fn csv_export_reads_every_page_in_order_and_defuses_formulas() {
+        let state = test_state();
+        let pages = EXPORT_PAGE_SIZE * 2 + 1;
+        for i in 0..pages {
+            let entry = AccessHistory {
+                id: 0,
+                role: "TEST-PAGED".to_string(),
+                door_name: "=1+1".to_string(),
+                section: i.to_string(),
+                timestamp: log_timestamp(),
+                status: "GRANTED".to_string(),
+                faculty_name: Some("@SUM(A1)".to_string()),
+                faculty_id: None,
+                actor_id: None,
+                denial_reason: None,
+            };
+            state.storage.append_log(DEFAULT_TENANT, entry).await.unwrap();
+        }
+        let mut req = axum::http::Request::get("/api/v1/history/export?format=csv&role=test-paged")
+            .header("x-admin-password", "Admin@1234")
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut().insert(ConnectInfo(std::net::SocketAddr::from(([127, 0, 0, 1], 40000))));
+        let res = build_router(state).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let csv = String::from_utf8(axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+
+        let mut lines = csv.split_terminator("\r\n");
+        assert_eq!(lines.next(), Some(EXPORT_COLUMNS));
+        let rows: Vec<Vec<&str>> = lines.map(|l| l.split(',').collect()).collect();
+        assert_eq!(rows.len(), pages);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!((row[3], row[4], row[7]), ("'=1+1", i.to_string().as_str(), "'@SUM(A1)"));
+        }
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("+a,b"), "\"'+a,b\"");
+    }
+
+    #[tokio::test]
+    async fn history_pages_back_from_the_newest_entries() {
+        let state = test_state();
+        for i in 0..5 {
+            let entry = AccessHistory {
+                id: 0,
+                role: "STUDENT".to_string(),
+                door_name: "test-history".to_string(),
+                section: format!("{}", i),
+                timestamp: log_timestamp(),
+                status: if i % 2 == 0 { "GRANTED".to_string() } else { "DENIED: Test".to_string() },
+                faculty_name: None,
+                faculty_id: None,
+                actor_id: None,
+                denial_reason: None,
+            };
+            state.storage.append_log(DEFAULT_TENANT, entry).await.unwrap();
+        }
+        let page = |query: &str| {
+            let req = axum::http::Request::get(format!("/history?door=test-history&{}", query)).body(Body::empty()).unwrap();
+            let app = build_router(state.clone());
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let bytes = axum::body::to_bytes(res.into_body(), MAX_BODY_BYTES).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+            }
+        };
+        let sections = |res: &serde_json::Value| -> Vec<String> {
+            res["items"].as_array().unwrap().iter().map(|e| e["section"].as_str().unwrap().to_string()).collect()
+        };
+
+        let res = page("limit=2&offset=1").await;
+        assert_eq!((res["total"].as_u64(), res["limit"].as_u64(), res["offset"].as_u64()), (Some(5), Some(2), Some(1)));
+        assert_eq!(sections(&res), ["2", "3"]);
+
+        let res = page("status=denied").await;
+        assert_eq!(res["total"], 2);
+        assert_eq!(sections(&res), ["1", "3"]);
+    }
+
+    #[tokio::test]
+    async fn ed25519_signature_is_granted_only_from_a_registered_key() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let state = test_state();
+        let door = seed_door("test-ed25519");
+        seed_section(&state, "D", "test-ed25519").await;
+        let key = SigningKey::from_bytes(&rand::random::<[u8; 32]>());
+        let public_key = hex::encode(key.verifying_key().to_bytes());
+        // Only there to fill in the scan fields; it's dropped from the body
+        let placeholder = role_proof(&state, "STUDENT", "test-ed25519").await;
+        let signed_body = || async {
+            let nonce = hex::encode(rand::random::<[u8; 16]>());
+            state.storage.issue_challenge(DEFAULT_TENANT, "test-ed25519", &nonce, CHALLENGE_TTL_SECS).await.unwrap();
+            let signature = key.sign(priv_access::identity::message("test-ed25519", &nonce, GEOHASH).as_bytes());
+            let mut body = scan_payload(&door, "test-ed25519", "STUDENT", &placeholder);
+            body.as_object_mut().unwrap().remove("proof");
+            body["identity"] = json!("ed25519");
+            body["ed25519"] = json!({ "public_key": public_key, "signature": hex::encode(signature.to_bytes()), "nonce": nonce });
+            body["section"] = json!("D");
+            body
+        };
+
+        let (status, res) = verify(state.clone(), signed_body().await).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["reason"], "key_not_registered");
+
+        state.ed25519_keys.register(priv_access::identity::RegisteredKey {
+            public_key: public_key.clone(),
+            tenant: DEFAULT_TENANT.to_string(),
+            role: "STUDENT".to_string(),
+            faculty_id: None,
+        });
+        let (status, res) = verify(state.clone(), signed_body().await).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        assert_eq!(res["status"], "success");
+    }
+
+    #[tokio::test]
+    async fn faculty_passkey_stands_in_for_the_pin() {
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        use base64::Engine;
+        use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+        use sha2::{Digest, Sha256};
+
+        let state = test_state();
+        let door = seed_door("test-passkey");
+        priv_access::faculty::add("TEST-PASSKEY", "", "4444").unwrap();
+        let call = |path: &'static str, pin: Option<&'static str>, body: serde_json::Value| {
+            let state = state.clone();
+            async move {
+                let mut req = axum::http::Request::post(path).header("content-type", "application/json");
+                if let Some(pin) = pin {
+                    req = req.header("x-faculty-id", "TEST-PASSKEY").header("x-faculty-pin", pin);
+                }
+                let mut req = req.body(Body::from(body.to_string())).unwrap();
+                req.extensions_mut().insert(ConnectInfo(std::net::SocketAddr::from(([127, 0, 0, 1], 40000))));
+                let res = build_router(state).oneshot(req).await.unwrap();
+                let status = res.status();
+                let bytes = axum::body::to_bytes(res.into_body(), MAX_BODY_BYTES).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        // A platform authenticator answering for http://localhost, the origin without a Host header
+        let key = SigningKey::from_bytes(&[11u8; 32].into()).unwrap();
+        let client_data = |ceremony: &str, options: &serde_json::Value| {
+            URL_SAFE_NO_PAD.encode(json!({ "type": ceremony, "challenge": options["publicKey"]["challenge"], "origin": "http://localhost" }).to_string())
+        };
+        // User present and verified
+        let auth_data = |flags: u8, sign_count: u32| [Sha256::digest(b"localhost").as_slice(), &[0x05 | flags], &sign_count.to_be_bytes()].concat();
+        let assertion = |options: &serde_json::Value, sign_count: u32| {
+            let client_data_json = client_data("webauthn.get", options);
+            let authenticator_data = auth_data(0, sign_count);
+            let client_data_hash = Sha256::digest(URL_SAFE_NO_PAD.decode(&client_data_json).unwrap());
+            let signature: Signature = key.sign(&[authenticator_data.as_slice(), client_data_hash.as_slice()].concat());
+            json!({
+                "id": URL_SAFE_NO_PAD.encode(b"passkey-1"),
+                "response": {
+                    "clientDataJSON": client_data_json,
+                    "authenticatorData": URL_SAFE_NO_PAD.encode(authenticator_data),
+                    "signature": URL_SAFE_NO_PAD.encode(signature.to_der().as_bytes())
+                }
+            })
+        };
+
+        // Registering takes the PIN
+        let (status, _) = call("/api/faculty/webauthn/register/start", None, json!({})).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, options) = call("/api/faculty/webauthn/register/start", Some("4444"), json!({})).await;
+        assert_eq!(status, StatusCode::OK, "{}", options);
+        assert_eq!(options["publicKey"]["rp"]["id"], "localhost");
+
+        let point = key.verifying_key().to_encoded_point(false);
+        let int = |i: i64| ciborium::Value::Integer(i.into());
+        let cose = ciborium::Value::Map(vec![
+            (int(1), int(2)),
+            (int(3), int(-7)),
+            (int(-1), int(1)),
+            (int(-2), ciborium::Value::Bytes(point.x().unwrap().to_vec())),
+            (int(-3), ciborium::Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        let mut authenticator_data = [auth_data(0x40, 0), vec![0; 16], 9u16.to_be_bytes().to_vec(), b"passkey-1".to_vec()].concat();
+        ciborium::into_writer(&cose, &mut authenticator_data).unwrap();
+        let mut attestation_object = Vec::new();
+        let attestation = ciborium::Value::Map(vec![
+            (ciborium::Value::Text("fmt".into()), ciborium::Value::Text("none".into())),
+            (ciborium::Value::Text("attStmt".into()), ciborium::Value::Map(vec![])),
+            (ciborium::Value::Text("authData".into()), ciborium::Value::Bytes(authenticator_data)),
+        ]);
+        ciborium::into_writer(&attestation, &mut attestation_object).unwrap();
+        let registration = json!({
+            "id": URL_SAFE_NO_PAD.encode(b"passkey-1"),
+            "response": {
+                "clientDataJSON": client_data("webauthn.create", &options),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object)
+            }
+        });
+        let (status, res) = call("/api/faculty/webauthn/register/finish", Some("4444"), registration).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", res);
+
+        // At the door the assertion replaces the PIN, once per challenge
+        let (status, options) = call("/api/faculty/webauthn/login/start", None, json!({ "faculty_id": "test-passkey" })).await;
+        assert_eq!(status, StatusCode::OK, "{}", options);
+        let door_scan = |webauthn: serde_json::Value| {
+            let (state, door) = (state.clone(), door.clone());
+            async move {
+                let proof = role_proof(&state, "FACULTY", "test-passkey").await;
+                let mut body = scan_payload(&door, "test-passkey", "FACULTY", &proof);
+                body["faculty_id"] = json!("TEST-PASSKEY");
+                body["webauthn"] = webauthn;
+                body
+            }
+        };
+        let signed_in = assertion(&options, 1);
+        let (status, res) = verify(state.clone(), door_scan(signed_in.clone()).await).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        let (status, res) = verify(state.clone(), door_scan(signed_in).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res["error"]["code"], "CHALLENGE_INVALID");
+
+        // Or is traded for a session
+        let (_, options) = call("/api/faculty/webauthn/login/start", None, json!({ "faculty_id": "TEST-PASSKEY" })).await;
+        let (status, res) = call("/api/faculty/webauthn/login/finish", None, json!({ "faculty_id": "TEST-PASSKEY", "credential": assertion(&options, 2) })).await;
+        assert_eq!(status, StatusCode::OK, "{}", res);
+        assert!(res["token"].is_string());
+    }
+
+    #[tokio::test]
+    async fn simulated_attempts_go_through_verification() {
+        let state = test_state();
+        let door = seed_door("test-simulate");
+        seed_section(&state, "G", "test-simulate").await;
+
+        // Every fourth attempt is faulty: first a proof that doesn't verify, then a stale QR
+        let summary = run_simulation(&state, &tenant(), "test-simulate", "STUDENT", 8).await.unwrap();
+        assert_eq!((summary.granted, summary.denied), (6, 2));
+        assert_eq!(summary.reasons.get("proof_mismatch"), Some(&1));
+        assert_eq!(summary.reasons.get("QR_EXPIRED"), Some(&1));
+        let logs = state.storage.logs(DEFAULT_TENANT).await.unwrap();
+        assert_eq!(logs.iter().filter(|l| l.door_name == door.name).count(), 8);
+
+        // Off outside demo mode
+        let req = axum::http::Request::post("/api/simulate")
+            .header("content-type", "application/json")
+            .header("x-admin-password", "Admin@1234")
+            .body(Body::from(json!({ "count": 5, "door_id": "test-simulate" }).to_string()))
+            .unwrap();
+        assert_eq!(build_router(state).oneshot(req).await.unwrap().status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn student_looks_up_their_room_by_section() {
+        let state = test_state();
+        let door = seed_door("test-my-room");
+        let lookup = |section: &'static str| {
+            let state = state.clone();
+            async move {
+                let req = axum::http::Request::get(format!("/api/my_room?section={}", section)).body(Body::empty()).unwrap();
+                let res = build_router(state).oneshot(req).await.unwrap();
+                let status = res.status();
+                let bytes = axum::body::to_bytes(res.into_body(), MAX_BODY_BYTES).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+
+        let (status, res) = lookup("h").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res["assigned"], false);
+        assert_eq!(res["state"], "not_assigned");
+
+        seed_section(&state, "H", "test-my-room").await;
+        let (_, res) = lookup("h").await;
+        assert_eq!(res["assigned"], true);
+        assert_eq!(res["room_name"], door.name);
+        assert_eq!(res["faculty_name"], "Dr. Test");
+        assert_eq!(res["scan_url"], "/mobile/scan?door=test-my-room&role=STUDENT&section=H");
+
+        let (status, res) = lookup("Z").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(res["error"]["code"], "INVALID_SECTION");
+    }
 }
- 
\ No newline at end of file
@@ -0,0 +1,120 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+// Leaves and inner nodes are hashed with different prefixes so a node can never pass as a leaf
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub type Hash = [u8; 32];
+
+/// One sibling on the path from a leaf to the root. `side` says where the sibling sits,
+/// so a verifier hashes `sibling || current` for "left" and `current || sibling` for "right".
+#[derive(Serialize, Debug)]
+pub struct ProofStep {
+    pub hash: String,
+    pub side: &'static str,
+}
+
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// An odd node out is carried up unchanged rather than paired with itself
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level.chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Root over the leaves in order. The empty tree's root is SHA-256 of nothing.
+pub fn root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Sha256::digest([]).into();
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Siblings needed to rebuild the root from `leaves[index]`, leaf level first.
+pub fn inclusion_proof(leaves: &[Hash], mut index: usize) -> Vec<ProofStep> {
+    let mut path = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            let side = if sibling < index { "left" } else { "right" };
+            path.push(ProofStep { hash: hex::encode(level[sibling]), side });
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What a verifier does with a proof: hash its way up from the leaf
+    fn fold(leaf: Hash, path: &[ProofStep]) -> Hash {
+        path.iter().fold(leaf, |current, step| {
+            let sibling: Hash = hex::decode(&step.hash).unwrap().try_into().unwrap();
+            match step.side {
+                "left" => node_hash(&sibling, &current),
+                _ => node_hash(&current, &sibling),
+            }
+        })
+    }
+
+    fn leaves(n: usize) -> Vec<Hash> {
+        (0..n).map(|i| leaf_hash(format!("entry {}", i).as_bytes())).collect()
+    }
+
+    #[test]
+    fn every_leaf_proves_its_way_to_the_root() {
+        // Odd sizes carry a node up unpaired, so try a few of each
+        for n in 1..=9 {
+            let leaves = leaves(n);
+            let root = root(&leaves);
+            for (i, leaf) in leaves.iter().enumerate() {
+                assert_eq!(fold(*leaf, &inclusion_proof(&leaves, i)), root, "leaf {} of {}", i, n);
+            }
+        }
+    }
+
+    #[test]
+    fn a_proof_holds_only_for_its_own_leaf() {
+        let leaves = leaves(6);
+        let root = root(&leaves);
+        let path = inclusion_proof(&leaves, 2);
+        assert_ne!(fold(leaf_hash(b"entry 2, edited"), &path), root);
+        assert_ne!(fold(leaves[3], &path), root);
+        // Leaves are hashed apart from nodes, so an inner node can't be passed off as an entry
+        let inner = node_hash(&leaves[0], &leaves[1]);
+        assert_ne!(leaf_hash(&[leaves[0], leaves[1]].concat()), inner);
+    }
+
+    #[test]
+    fn an_empty_log_has_the_hash_of_nothing_as_its_root() {
+        assert_eq!(hex::encode(root(&[])), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert!(inclusion_proof(&leaves(1), 0).is_empty());
+    }
+}
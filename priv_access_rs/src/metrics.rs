@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::zkp::VerifyError;
+
+const GRANTED: &str = "access_granted_total";
+const DENIED: &str = "access_denied_total";
+const VERIFY_LATENCY: &str = "verify_latency_seconds";
+const QR_CACHE: &str = "qr_cache_requests_total";
+
+// Proof checks dominate: a modp verify is tens of milliseconds, a slow storage backend adds more
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+// Drains histogram samples into their buckets between scrapes
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+static HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// Why an access request was denied: the `reason` label on `access_denied_total` and the
+/// `denial_reason` stored with each denied log entry. A fixed set so both can be aggregated,
+/// unlike the free-text message the client is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DenialReason {
+    QrExpired,
+    QrInvalid,
+    QrReused,
+    /// Wrong floor, too far from the door, or an unusable GPS fix
+    Location,
+    /// Wrong admin password, or no such admin
+    WrongPassword,
+    /// Bad faculty PIN, TOTP code or unregistered key
+    InvalidCredentials,
+    InvalidSection,
+    /// The student's section is assigned to another room
+    WrongRoom,
+    /// The student's section has no room yet
+    NoRoomAssigned,
+    /// A faculty member tried to take a section another one holds
+    SectionConflict,
+    /// The challenge nonce is missing, used, expired or for another door
+    ChallengeInvalid,
+    MalformedProof,
+    InvalidProof,
+    ProofExpired,
+    RevokedKey,
+    /// Missing, forged, expired or reused guest pass
+    GuestPass,
+    /// The door's schedule has it closed for this role right now
+    OutsideSchedule,
+}
+
+impl DenialReason {
+    pub const ALL: [DenialReason; 17] = [
+        DenialReason::QrExpired,
+        DenialReason::QrInvalid,
+        DenialReason::QrReused,
+        DenialReason::Location,
+        DenialReason::WrongPassword,
+        DenialReason::InvalidCredentials,
+        DenialReason::InvalidSection,
+        DenialReason::WrongRoom,
+        DenialReason::NoRoomAssigned,
+        DenialReason::SectionConflict,
+        DenialReason::ChallengeInvalid,
+        DenialReason::MalformedProof,
+        DenialReason::InvalidProof,
+        DenialReason::ProofExpired,
+        DenialReason::RevokedKey,
+        DenialReason::GuestPass,
+        DenialReason::OutsideSchedule,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DenialReason::QrExpired => "qr_expired",
+            DenialReason::QrInvalid => "qr_invalid",
+            DenialReason::QrReused => "qr_reused",
+            DenialReason::Location => "location",
+            DenialReason::WrongPassword => "wrong_password",
+            DenialReason::InvalidCredentials => "invalid_credentials",
+            DenialReason::InvalidSection => "invalid_section",
+            DenialReason::WrongRoom => "wrong_room",
+            DenialReason::NoRoomAssigned => "no_room_assigned",
+            DenialReason::SectionConflict => "section_conflict",
+            DenialReason::ChallengeInvalid => "challenge_invalid",
+            DenialReason::MalformedProof => "malformed_proof",
+            DenialReason::InvalidProof => "invalid_proof",
+            DenialReason::ProofExpired => "proof_expired",
+            DenialReason::RevokedKey => "revoked_key",
+            DenialReason::GuestPass => "guest_pass",
+            DenialReason::OutsideSchedule => "outside_schedule",
+        }
+    }
+}
+
+impl std::fmt::Display for DenialReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DenialReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DenialReason::ALL.into_iter()
+            .find(|r| r.as_str() == s)
+            .ok_or_else(|| format!("Unknown denial reason '{}'", s))
+    }
+}
+
+impl From<&VerifyError> for DenialReason {
+    fn from(e: &VerifyError) -> Self {
+        match e {
+            VerifyError::EquationMismatch | VerifyError::Params(_) => DenialReason::InvalidProof,
+            VerifyError::Expired { .. } | VerifyError::FromFuture { .. } => DenialReason::ProofExpired,
+            VerifyError::Geohash(_) => DenialReason::Location,
+            VerifyError::FieldTooLong { .. } | VerifyError::Malformed { .. } | VerifyError::OutOfRange { .. } | VerifyError::NotInSubgroup { .. } => DenialReason::MalformedProof,
+        }
+    }
+}
+
+/// Install the Prometheus recorder and keep its histograms drained. Call once at startup,
+/// from within the runtime; until then the counters below are no-ops.
+pub fn install() -> Result<(), String> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(VERIFY_LATENCY.to_string()), LATENCY_BUCKETS)
+        .and_then(|builder| builder.install_recorder())
+        .map_err(|e| e.to_string())?;
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    HANDLE.set(handle).map_err(|_| "metrics recorder already installed".to_string())
+}
+
+/// Everything recorded so far in the Prometheus text format; empty if `install` wasn't called.
+pub fn render() -> String {
+    HANDLE.get().map(PrometheusHandle::render).unwrap_or_default()
+}
+
+pub fn record_granted(role: &str, door_id: &str) {
+    ::metrics::counter!(GRANTED, "role" => role.to_string(), "door" => door_id.to_string()).increment(1);
+}
+
+pub fn record_denied(reason: DenialReason) {
+    ::metrics::counter!(DENIED, "reason" => reason.as_str()).increment(1);
+}
+
+/// Time one `/api/verify` request took end to end, whatever the outcome.
+pub fn observe_verify_latency(elapsed: Duration) {
+    ::metrics::histogram!(VERIFY_LATENCY).record(elapsed.as_secs_f64());
+}
+
+/// One lookup in the rendered-QR cache; the hit rate is the `result="hit"` share of the total.
+pub fn record_qr_cache(hit: bool) {
+    ::metrics::counter!(QR_CACHE, "result" => if hit { "hit" } else { "miss" }).increment(1);
+}
@@ -0,0 +1,325 @@
+//! Access tokens for doors without a network. The phone shows a QR holding its role, an expiry
+//! and a Schnorr proof; the door's terminal scans it and decides on its own, with the role
+//! public keys it was provisioned with.
+//!
+//! ```no_run
+//! use priv_access::offline::{encode_access_qr, AccessToken, OfflineVerifier};
+//!
+//! # let secret = num_bigint::BigUint::from(42u32);
+//! # let now = priv_access::zkp::unix_now();
+//! let token = AccessToken::new("STUDENT", secret, "t1q7hk9vj".to_string(), now + 120);
+//! let qr = encode_access_qr(&token);
+//!
+//! // On the terminal, with the role keys from /mobile/setup
+//! # let role_keys = std::collections::HashMap::new();
+//! let decision = OfflineVerifier::new(role_keys, "t1q7hk9").decode_and_verify(&qr, now);
+//! ```
+//!
+//! The QR payload is binary, base45 encoded (RFC 9285) so it fits the QR alphanumeric mode:
+//!
+//! ```text
+//! version (1) | scheme (1) | timestamp (8) | expires_at (8) | role | geohash | public_key | commitment | response
+//! ```
+//!
+//! with integers big-endian and every variable field prefixed by its length as a u16. The modp
+//! numbers are big-endian unsigned bytes, the secp256k1 ones their usual encodings. A modp
+//! token is about 1,200 characters, within what phone cameras read reliably; a secp256k1
+//! token is about 230. The nonce isn't sent: it's derived from the role and expiry, so the
+//! proof signs both of them.
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+
+use crate::zkp::{Proof, ProofScheme, SchnorrProver, SchnorrVerifier, VerifyError};
+
+const FORMAT_VERSION: u8 = 1;
+
+/// Longest a token may be valid for, from the proof's timestamp to its expiry.
+pub const DEFAULT_MAX_VALIDITY_SECS: u64 = 5 * 60;
+
+/// A role's claim to open a door offline until `expires_at`.
+#[derive(Debug, Clone)]
+pub struct AccessToken {
+    pub role: String,
+    /// Unix seconds
+    pub expires_at: u64,
+    /// Its nonce must be `AccessToken::nonce(role, expires_at)`
+    pub proof: Proof,
+}
+
+impl AccessToken {
+    /// Prove `secret` at `geohash` now, for `role` until `expires_at`.
+    pub fn new(role: &str, secret: BigUint, geohash: String, expires_at: u64) -> Self {
+        let role = role.trim().to_uppercase();
+        let proof = SchnorrProver::new(secret).generate_proof(geohash, &Self::nonce(&role, expires_at));
+        AccessToken { role, expires_at, proof }
+    }
+
+    /// The challenge nonce an offline proof answers. It stands in for the one `/api/challenge`
+    /// would issue and binds the role and expiry into the proof.
+    pub fn nonce(role: &str, expires_at: u64) -> String {
+        format!("offline:{}:{}", role, expires_at)
+    }
+}
+
+/// Why a scanned token was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OfflineQrError {
+    /// Not base45, or not a token of a version this build reads
+    Malformed,
+    UnsupportedVersion(u8),
+    Expired,
+    /// The expiry is further from the proof's timestamp than the terminal allows
+    ValidTooLong { secs: u64, max_secs: u64 },
+    /// The proof was made ahead of the terminal's clock by more than the allowed skew
+    FromFuture,
+    UnknownRole,
+    /// The proof is valid but not by the role's key
+    WrongKey,
+    /// The proof places the phone somewhere other than this door
+    WrongLocation,
+    Proof(VerifyError),
+}
+
+impl OfflineQrError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OfflineQrError::Malformed => "offline_qr_malformed",
+            OfflineQrError::UnsupportedVersion(_) => "offline_qr_unsupported_version",
+            OfflineQrError::Expired => "offline_qr_expired",
+            OfflineQrError::ValidTooLong { .. } => "offline_qr_valid_too_long",
+            OfflineQrError::FromFuture => "offline_qr_from_future",
+            OfflineQrError::UnknownRole => "offline_qr_unknown_role",
+            OfflineQrError::WrongKey => "offline_qr_wrong_key",
+            OfflineQrError::WrongLocation => "offline_qr_wrong_location",
+            OfflineQrError::Proof(e) => e.code(),
+        }
+    }
+}
+
+impl std::fmt::Display for OfflineQrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OfflineQrError::Malformed => write!(f, "Not an access QR"),
+            OfflineQrError::UnsupportedVersion(v) => write!(f, "Access QR version {} is not supported", v),
+            OfflineQrError::Expired => write!(f, "Access QR expired"),
+            OfflineQrError::ValidTooLong { secs, max_secs } => write!(f, "Access QR is valid for {}s, more than the {}s allowed", secs, max_secs),
+            OfflineQrError::FromFuture => write!(f, "Access QR was made ahead of this terminal's clock"),
+            OfflineQrError::UnknownRole => write!(f, "Role is not allowed at this door"),
+            OfflineQrError::WrongKey => write!(f, "Proof is not by the role's key"),
+            OfflineQrError::WrongLocation => write!(f, "Proof is for another location"),
+            OfflineQrError::Proof(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OfflineQrError {}
+
+/// A granted offline unlock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessDecision {
+    pub role: String,
+    pub geohash: String,
+    pub expires_at: u64,
+}
+
+fn put_field(out: &mut Vec<u8>, bytes: &[u8]) {
+    // Every field is far below 64KB; a longer one couldn't be in a valid proof anyway
+    let len = u16::try_from(bytes.len()).unwrap_or(u16::MAX);
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&bytes[..len as usize]);
+}
+
+// A proof number as bytes: big-endian for modp, the decoded hex for secp256k1
+fn number_bytes(scheme: ProofScheme, value: &str) -> Vec<u8> {
+    match scheme {
+        ProofScheme::Modp => value.parse::<BigUint>().map(|n| n.to_bytes_be()).unwrap_or_default(),
+        ProofScheme::Secp256k1 => hex::decode(value).unwrap_or_default(),
+    }
+}
+
+fn number_string(scheme: ProofScheme, bytes: &[u8]) -> String {
+    match scheme {
+        ProofScheme::Modp => BigUint::from_bytes_be(bytes).to_string(),
+        ProofScheme::Secp256k1 => hex::encode(bytes),
+    }
+}
+
+/// The QR text for `token`: its binary form, base45 encoded.
+pub fn encode_access_qr(token: &AccessToken) -> String {
+    let proof = &token.proof;
+    let mut out = vec![FORMAT_VERSION, match proof.scheme {
+        ProofScheme::Modp => 0,
+        ProofScheme::Secp256k1 => 1,
+    }];
+    out.extend_from_slice(&proof.timestamp.to_be_bytes());
+    out.extend_from_slice(&token.expires_at.to_be_bytes());
+    put_field(&mut out, token.role.as_bytes());
+    put_field(&mut out, proof.geohash.as_bytes());
+    for number in [&proof.public_key, &proof.commitment, &proof.response] {
+        put_field(&mut out, &number_bytes(proof.scheme, number));
+    }
+    base45::encode(out)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], OfflineQrError> {
+        if self.0.len() < n {
+            return Err(OfflineQrError::Malformed);
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64, OfflineQrError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn field(&mut self) -> Result<&'a [u8], OfflineQrError> {
+        let len = u16::from_be_bytes(self.take(2)?.try_into().unwrap());
+        self.take(len as usize)
+    }
+
+    fn text(&mut self) -> Result<String, OfflineQrError> {
+        String::from_utf8(self.field()?.to_vec()).map_err(|_| OfflineQrError::Malformed)
+    }
+}
+
+/// Read a token back from QR text without checking it.
+pub fn decode_access_qr(qr: &str) -> Result<AccessToken, OfflineQrError> {
+    let bytes = base45::decode(qr.trim()).map_err(|_| OfflineQrError::Malformed)?;
+    let mut reader = Reader(&bytes);
+    let version = reader.take(1)?[0];
+    if version != FORMAT_VERSION {
+        return Err(OfflineQrError::UnsupportedVersion(version));
+    }
+    let scheme = match reader.take(1)?[0] {
+        0 => ProofScheme::Modp,
+        1 => ProofScheme::Secp256k1,
+        _ => return Err(OfflineQrError::Malformed),
+    };
+    let timestamp = reader.u64()?;
+    let expires_at = reader.u64()?;
+    let role = reader.text()?;
+    let geohash = reader.text()?;
+    let public_key = number_string(scheme, reader.field()?);
+    let commitment = number_string(scheme, reader.field()?);
+    let response = number_string(scheme, reader.field()?);
+    if !reader.0.is_empty() {
+        return Err(OfflineQrError::Malformed);
+    }
+    let nonce = AccessToken::nonce(&role, expires_at);
+    Ok(AccessToken {
+        role,
+        expires_at,
+        proof: Proof { scheme, public_key, commitment, response, geohash, nonce, timestamp },
+    })
+}
+
+/// What a door terminal checks scanned tokens against.
+#[derive(Debug, Clone)]
+pub struct OfflineVerifier {
+    /// Role -> its public key, as in proofs (decimal for modp, compressed hex for secp256k1).
+    /// Roles not listed are refused.
+    pub role_keys: HashMap<String, String>,
+    /// The door's geohash cell; a token's geohash must fall inside it
+    pub geohash_prefix: String,
+    pub max_validity_secs: u64,
+    /// How far ahead of the terminal's clock a phone's may run
+    pub max_skew_secs: u64,
+}
+
+impl OfflineVerifier {
+    pub fn new(role_keys: HashMap<String, String>, geohash_prefix: &str) -> Self {
+        OfflineVerifier {
+            role_keys,
+            geohash_prefix: geohash_prefix.trim().to_lowercase(),
+            max_validity_secs: DEFAULT_MAX_VALIDITY_SECS,
+            max_skew_secs: crate::config::CONFIG.max_clock_skew_secs,
+        }
+    }
+
+    /// Decode a scanned QR and decide on it at `now`, all without a network.
+    pub fn decode_and_verify(&self, qr: &str, now: u64) -> Result<AccessDecision, OfflineQrError> {
+        let token = decode_access_qr(qr)?;
+        let proof = &token.proof;
+        if now >= token.expires_at {
+            return Err(OfflineQrError::Expired);
+        }
+        if proof.timestamp > now.saturating_add(self.max_skew_secs) {
+            return Err(OfflineQrError::FromFuture);
+        }
+        let validity = token.expires_at.saturating_sub(proof.timestamp);
+        if validity > self.max_validity_secs {
+            return Err(OfflineQrError::ValidTooLong { secs: validity, max_secs: self.max_validity_secs });
+        }
+        let role_key = self.role_keys.get(&token.role).ok_or(OfflineQrError::UnknownRole)?;
+        if !proof.geohash.to_lowercase().starts_with(&self.geohash_prefix) {
+            return Err(OfflineQrError::WrongLocation);
+        }
+        // Signs the role and expiry too, through the nonce they were rebuilt into
+        SchnorrVerifier::verify_proof(proof).map_err(OfflineQrError::Proof)?;
+        if !proof.public_key.eq_ignore_ascii_case(role_key) {
+            return Err(OfflineQrError::WrongKey);
+        }
+        Ok(AccessDecision { role: token.role, geohash: token.proof.geohash, expires_at: token.expires_at })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{power_mod, G, P};
+    use crate::zkp::{unix_now, EccSchnorr};
+
+    fn verifier() -> OfflineVerifier {
+        let student = power_mod(&G, &BigUint::from(42u32), &P).to_string();
+        OfflineVerifier::new(HashMap::from([("STUDENT".to_string(), student)]), "t1q7hk9")
+    }
+
+    #[test]
+    fn token_round_trips_through_a_qr() {
+        let now = unix_now();
+        let token = AccessToken::new("student", BigUint::from(42u32), "t1q7hk9vj".to_string(), now + 120);
+        let qr = encode_access_qr(&token);
+        // Alphanumeric mode holds 4,296 characters at most
+        assert!(qr.len() < 1_300, "{} chars", qr.len());
+        assert!(qrcode::QrCode::new(qr.as_bytes()).is_ok());
+
+        let decision = verifier().decode_and_verify(&qr, now).unwrap();
+        assert_eq!(decision, AccessDecision { role: "STUDENT".to_string(), geohash: "t1q7hk9vj".to_string(), expires_at: now + 120 });
+        assert_eq!(verifier().decode_and_verify(&qr, now + 120), Err(OfflineQrError::Expired));
+    }
+
+    #[test]
+    fn role_and_expiry_are_signed() {
+        let now = unix_now();
+        let token = AccessToken::new("STUDENT", BigUint::from(42u32), "t1q7hk9vj".to_string(), now + 60);
+        let extended = AccessToken { expires_at: now + 240, ..token.clone() };
+        let err = verifier().decode_and_verify(&encode_access_qr(&extended), now).unwrap_err();
+        assert_eq!(err, OfflineQrError::Proof(VerifyError::EquationMismatch));
+
+        let too_long = AccessToken::new("STUDENT", BigUint::from(42u32), "t1q7hk9vj".to_string(), now + 3600);
+        assert!(matches!(verifier().decode_and_verify(&encode_access_qr(&too_long), now), Err(OfflineQrError::ValidTooLong { .. })));
+        let elsewhere = AccessToken::new("STUDENT", BigUint::from(42u32), "u4pruydqq".to_string(), now + 60);
+        assert_eq!(verifier().decode_and_verify(&encode_access_qr(&elsewhere), now), Err(OfflineQrError::WrongLocation));
+        let impostor = AccessToken::new("STUDENT", BigUint::from(43u32), "t1q7hk9vj".to_string(), now + 60);
+        assert_eq!(verifier().decode_and_verify(&encode_access_qr(&impostor), now), Err(OfflineQrError::WrongKey));
+        assert_eq!(verifier().decode_and_verify("not a token", now), Err(OfflineQrError::Malformed));
+    }
+
+    #[test]
+    fn secp256k1_tokens_are_compact() {
+        let now = unix_now();
+        let secret = k256::Scalar::from(42u64);
+        let proof = EccSchnorr::prove(&secret, "t1q7hk9vj", &AccessToken::nonce("STUDENT", now + 60));
+        let verifier = OfflineVerifier::new(HashMap::from([("STUDENT".to_string(), proof.public_key.clone())]), "t1q7hk9");
+        let qr = encode_access_qr(&AccessToken { role: "STUDENT".to_string(), expires_at: now + 60, proof });
+        assert!(qr.len() < 250, "{} chars", qr.len());
+        assert_eq!(verifier.decode_and_verify(&qr, now).unwrap().role, "STUDENT");
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use base64::{engine::general_purpose, Engine as _};
+use once_cell::sync::Lazy;
+use qrcode::render::svg;
+use qrcode::types::QrError;
+use qrcode::QrCode;
+use serde::Deserialize;
+
+// Smallest side of a rendered SVG, in user units; it scales freely from there
+const SVG_MIN_SIZE: u32 = 320;
+
+// Rendered codes kept at most; a door's static URL takes one PNG and one SVG entry
+const CACHE_CAPACITY: usize = 512;
+
+// (format, PNG size or 0, encoded data) -> (rendered code, last use)
+type CacheKey = (QrFormat, u32, String);
+
+struct QrCache {
+    entries: HashMap<CacheKey, (String, u64)>,
+    // Bumped on every lookup, so the smallest stamp is the least recently used entry
+    clock: u64,
+}
+
+static CACHE: Lazy<Mutex<QrCache>> = Lazy::new(|| Mutex::new(QrCache { entries: HashMap::new(), clock: 0 }));
+
+/// How a QR-producing endpoint should render its codes, from `?format=`.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    /// Base64 PNG data URL, what door displays have always been given
+    #[default]
+    Png,
+    /// Inline `<svg>` markup that stays sharp at any size
+    Svg,
+}
+
+/// `data` as a standalone SVG document, dark modules on white with the standard quiet zone.
+/// Fails if `data` is too long to fit in a QR code.
+pub fn render_qr_svg(data: &str) -> Result<String, QrError> {
+    let code = QrCode::new(data.as_bytes())?;
+    Ok(code.render::<svg::Color>()
+        .min_dimensions(SVG_MIN_SIZE, SVG_MIN_SIZE)
+        .dark_color(svg::Color("#000000"))
+        .light_color(svg::Color("#ffffff"))
+        .build())
+}
+
+/// `data` as a `data:image/png;base64,...` URL of a `size` x `size` greyscale PNG.
+/// Fails if `data` is too long to fit in a QR code.
+pub fn qr_png_data_url(data: &str, size: u32) -> Result<String, QrError> {
+    // Drawn module by module to avoid the qrcode/image trait version mismatch
+    let code = QrCode::new(data.as_bytes())?;
+    let width = code.width();
+    let mut img = image::GrayImage::new(width as u32, width as u32);
+
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        let x = (i % width) as u32;
+        let y = (i / width) as u32;
+        let pixel = if color == qrcode::Color::Dark {
+            image::Luma([0u8])
+        } else {
+            image::Luma([255u8])
+        };
+        img.put_pixel(x, y, pixel);
+    }
+
+    // Nearest keeps the module edges sharp
+    let upscaled = image::imageops::resize(&img, size, size, image::imageops::FilterType::Nearest);
+
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageLuma8(upscaled)
+        .write_to(&mut buffer, image::ImageFormat::Png)
+        .expect("PNG encoding into memory can't fail");
+    Ok(format!("data:image/png;base64,{}", general_purpose::STANDARD.encode(buffer.into_inner())))
+}
+
+fn cached(key: CacheKey, render: impl FnOnce(&str) -> Result<String, QrError>) -> Result<String, QrError> {
+    {
+        let mut cache = CACHE.lock().unwrap();
+        cache.clock += 1;
+        let now = cache.clock;
+        if let Some((code, last_used)) = cache.entries.get_mut(&key) {
+            *last_used = now;
+            crate::metrics::record_qr_cache(true);
+            return Ok(code.clone());
+        }
+    }
+    crate::metrics::record_qr_cache(false);
+
+    // Rendered without the lock, so a slow PNG doesn't hold up every other door's lookup; two
+    // requests missing on the same key at once both render, and the later insert wins
+    let code = render(&key.2)?;
+
+    let mut cache = CACHE.lock().unwrap();
+    cache.clock += 1;
+    let now = cache.clock;
+    if !cache.entries.contains_key(&key) && cache.entries.len() >= CACHE_CAPACITY {
+        let oldest = cache.entries.iter().min_by_key(|(_, (_, last_used))| *last_used).map(|(k, _)| k.clone());
+        if let Some(oldest) = oldest {
+            cache.entries.remove(&oldest);
+        }
+    }
+    cache.entries.insert(key, (code.clone(), now));
+    Ok(code)
+}
+
+/// `qr_png_data_url`, remembered per `data` and `size`. For URLs that stay the same between
+/// requests, like a door's `/s/` link; one-off codes (nonces, tokens) would only evict them.
+pub fn cached_png_data_url(data: &str, size: u32) -> Result<String, QrError> {
+    cached((QrFormat::Png, size, data.to_string()), |data| qr_png_data_url(data, size))
+}
+
+/// `render_qr_svg`, remembered per `data`, on the same terms as `cached_png_data_url`.
+pub fn cached_qr_svg(data: &str) -> Result<String, QrError> {
+    cached((QrFormat::Svg, 0, data.to_string()), render_qr_svg)
+}
+
+/// Drop every cached code, e.g. when the LAN IP door URLs embed has changed and phones can no
+/// longer reach the addresses they point at.
+pub fn clear_cache() {
+    CACHE.lock().unwrap().entries.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_data_url_is_stable() {
+        let url = qr_png_data_url("http://localhost:3000/s/room101", 400).unwrap();
+        // base64 of the PNG signature and the start of the IHDR chunk
+        assert!(url.starts_with("data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAZAAAAGQ"), "{}", &url[..60]);
+        assert_eq!(url, qr_png_data_url("http://localhost:3000/s/room101", 400).unwrap());
+    }
+
+    #[test]
+    fn too_long_input_is_an_error() {
+        let data = "x".repeat(8000);
+        assert_eq!(qr_png_data_url(&data, 400), Err(QrError::DataTooLong));
+        assert_eq!(render_qr_svg(&data), Err(QrError::DataTooLong));
+        assert_eq!(cached_qr_svg(&data), Err(QrError::DataTooLong));
+    }
+
+    #[test]
+    fn cached_codes_match_fresh_ones_until_cleared() {
+        let url = "http://10.0.0.7:3000/s/cache-test";
+        assert_eq!(cached_png_data_url(url, 200), qr_png_data_url(url, 200));
+        assert_eq!(cached_png_data_url(url, 200), qr_png_data_url(url, 200));
+        assert_eq!(cached_qr_svg(url), render_qr_svg(url));
+        let key = (QrFormat::Png, 200, url.to_string());
+        assert!(CACHE.lock().unwrap().entries.contains_key(&key));
+
+        clear_cache();
+        assert!(!CACHE.lock().unwrap().entries.contains_key(&key));
+    }
+
+    #[test]
+    fn rendering_happens_outside_the_cache_lock() {
+        // A render that itself goes through the cache would deadlock if the lock were held
+        let outer = "http://10.0.0.7:3000/s/lock-outer";
+        let inner = "http://10.0.0.7:3000/s/lock-inner";
+        let code = cached((QrFormat::Svg, 0, outer.to_string()), |data| {
+            cached_qr_svg(inner)?;
+            render_qr_svg(data)
+        });
+        assert_eq!(code, render_qr_svg(outer));
+        assert_eq!(cached_qr_svg(outer), render_qr_svg(outer));
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::error::{ApiError, ErrorCode};
+
+// Fixed window every limit is counted over
+pub const WINDOW_SECS: u64 = 60;
+
+/// Fixed-window attempt counter. With Redis every replica shares the same counters,
+/// so spreading requests over instances doesn't buy extra attempts.
+pub enum RateLimiter {
+    // key -> (count, window end)
+    Local(Mutex<HashMap<String, (u64, u64)>>),
+    Redis(redis::aio::MultiplexedConnection),
+}
+
+impl RateLimiter {
+    pub fn local() -> Self {
+        RateLimiter::Local(Mutex::new(HashMap::new()))
+    }
+
+    pub async fn redis(url: &str) -> redis::RedisResult<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(RateLimiter::Redis(client.get_multiplexed_async_connection().await?))
+    }
+
+    /// Count one attempt against `key`; returns the attempts made in the current window.
+    pub async fn hit(&self, key: &str) -> u64 {
+        match self {
+            RateLimiter::Local(counters) => {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+                let mut counters = counters.lock().unwrap();
+                counters.retain(|_, (_, window_end)| *window_end > now);
+                let entry = counters.entry(key.to_string()).or_insert((0, now + WINDOW_SECS));
+                entry.0 += 1;
+                entry.0
+            }
+            RateLimiter::Redis(conn) => {
+                let mut conn = conn.clone();
+                let key = format!("privaccess:ratelimit:{}", key);
+                // INCR and the TTL go together so a crash can't leave a counter without expiry
+                let result: redis::RedisResult<(u64, i64)> = redis::pipe()
+                    .atomic()
+                    .cmd("INCR").arg(&key)
+                    .cmd("EXPIRE").arg(&key).arg(WINDOW_SECS).arg("NX")
+                    .query_async(&mut conn)
+                    .await;
+                match result {
+                    Ok((count, _)) => count,
+                    Err(e) => {
+                        // Don't lock everyone out because Redis hiccuped
+                        tracing::warn!(error = %e, "Redis unavailable, not counting attempt");
+                        0
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reject with 429 once `key` exceeds `limit` attempts per window. A limit of 0 disables the check.
+    pub async fn check(&self, key: &str, limit: u64) -> Result<(), ApiError> {
+        if limit == 0 {
+            return Ok(());
+        }
+        if self.hit(key).await > limit {
+            tracing::info!(%key, limit, window_secs = WINDOW_SECS, "Rate limit exceeded");
+            return Err(ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many attempts, please wait a minute and try again"));
+        }
+        Ok(())
+    }
+}
+
+/// Address to rate limit on. Behind a load balancer every request comes from the balancer,
+/// so X-Forwarded-For is honoured when PRIVACCESS_TRUST_PROXY is set.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    if crate::config::CONFIG.trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.split(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    peer.ip()
+}
+
+// How often buckets that have filled back up are dropped
+const EVICT_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-IP token bucket in front of the verification and setup routes. Where `RateLimiter` caps
+/// attempts per minute, this caps the request rate itself: `burst` requests at once, then
+/// `rps` per second. Buckets live in this process only.
+#[derive(Clone)]
+pub struct IpThrottle {
+    rps: f64,
+    burst: f64,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl IpThrottle {
+    /// A rate of 0 lets everything through.
+    pub fn new(rps: u64, burst: u64) -> Self {
+        IpThrottle { rps: rps as f64, burst: burst.max(1) as f64, buckets: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// Take a token for `ip`, or say how long until one is available.
+    pub fn take(&self, ip: IpAddr) -> Result<(), Duration> {
+        if self.rps == 0.0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(ip).or_insert(Bucket { tokens: self.burst, updated: now });
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * self.rps;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rps))
+        }
+    }
+
+    // A bucket that would be full again is the same as no bucket at all
+    fn evict_idle(&self) {
+        let now = Instant::now();
+        let full_after = self.burst / self.rps.max(f64::MIN_POSITIVE);
+        self.buckets.lock().unwrap()
+            .retain(|_, b| now.duration_since(b.updated).as_secs_f64() < full_after);
+    }
+
+    /// Drop idle buckets every minute so one-off clients don't pile up in memory.
+    pub fn start_eviction(&self) {
+        let throttle = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EVICT_INTERVAL);
+            loop {
+                interval.tick().await;
+                throttle.evict_idle();
+            }
+        });
+    }
+}
+
+/// Middleware for `IpThrottle`: 429 with `Retry-After` once a client runs out of tokens.
+pub async fn throttle(
+    axum::extract::State(throttle): axum::extract::State<IpThrottle>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0);
+    if let Some(peer) = peer {
+        let ip = client_ip(req.headers(), peer);
+        if let Err(wait) = throttle.take(ip) {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            tracing::info!(%ip, path = %req.uri().path(), retry_after_secs = retry_after, "Client throttled");
+            let mut response = ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many requests, please slow down").into_response();
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after.into());
+            return response;
+        }
+    }
+    next.run(req).await
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Per-account lockout for faculty PINs and admin passwords. IP limits don't stop a guesser
+/// spreading attempts over many addresses, so this counts failures against the account itself:
+/// `max_failures` in a row locks it for `duration`, and a success starts the count again.
+#[derive(Clone)]
+pub struct AccountLockout {
+    max_failures: u32,
+    duration: Duration,
+    accounts: Arc<Mutex<HashMap<String, Failures>>>,
+}
+
+impl AccountLockout {
+    /// A limit of 0 never locks anyone out.
+    pub fn new(max_failures: u32, duration: Duration) -> Self {
+        AccountLockout { max_failures, duration, accounts: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// How much longer `account` stays locked, if it is.
+    pub fn locked(&self, account: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let mut accounts = self.accounts.lock().unwrap();
+        let until = accounts.get(account)?.locked_until?;
+        if until > now {
+            return Some(until - now);
+        }
+        // Served its time; the next guess starts from zero
+        accounts.remove(account);
+        None
+    }
+
+    /// Count a failed login. Returns the lockout if this failure started one.
+    pub fn record_failure(&self, account: &str) -> Option<Duration> {
+        if self.max_failures == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let mut accounts = self.accounts.lock().unwrap();
+        // Failures older than a lockout no longer count as "in a row"; dropping them also keeps
+        // guesses at made-up ids from piling up
+        accounts.retain(|_, f| f.locked_until.map_or(now.duration_since(f.last) < self.duration, |until| until > now));
+        let failures = accounts.entry(account.to_string()).or_insert(Failures { count: 0, last: now, locked_until: None });
+        failures.count += 1;
+        failures.last = now;
+        if failures.count >= self.max_failures {
+            failures.locked_until = Some(now + self.duration);
+            return Some(self.duration);
+        }
+        None
+    }
+
+    pub fn record_success(&self, account: &str) {
+        self.accounts.lock().unwrap().remove(account);
+    }
+}
+
+/// 429 `ACCOUNT_LOCKED` with `Retry-After` set to the time left on the lock.
+pub fn locked_error(remaining: Duration) -> ApiError {
+    let secs = remaining.as_secs_f64().ceil().max(1.0) as u64;
+    let message = format!("Too many failed attempts, this account is locked for {} more seconds", secs);
+    ApiError::coded(StatusCode::TOO_MANY_REQUESTS, ErrorCode::AccountLocked, message).with_retry_after(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::redis_url;
+
+    #[tokio::test]
+    async fn attempts_past_the_limit_are_refused() {
+        let limiter = RateLimiter::local();
+        for _ in 0..3 {
+            assert!(limiter.check("test:local", 3).await.is_ok());
+        }
+        assert!(limiter.check("test:local", 3).await.is_err());
+        assert!(limiter.check("test:elsewhere", 3).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at PRIVACCESS_TEST_REDIS_URL"]
+    async fn replicas_count_against_the_same_redis_limit() {
+        let url = redis_url();
+        let (a, b) = (RateLimiter::redis(&url).await.unwrap(), RateLimiter::redis(&url).await.unwrap());
+        let key = format!("test:shared:{}", rand::random::<u32>());
+        // Spread over both, the attempts still add up to one count
+        for limiter in [&a, &b, &a] {
+            assert!(limiter.check(&key, 3).await.is_ok());
+        }
+        assert!(b.check(&key, 3).await.is_err());
+        assert!(a.check(&key, 3).await.is_err());
+    }
+}
@@ -1,8 +1,23 @@
 use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+use argon2::password_hash::{rand_core::OsRng, PasswordHasher, SaltString};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::http::HeaderMap;
+use chrono::NaiveDateTime;
 use num_bigint::BigUint;
 use num_traits::Num;
 use once_cell::sync::Lazy;
-use serde::Serialize;
+use sha2::{Digest, Sha256};
+use totp_rs::{Builder, Secret, Totp};
+
+use crate::doors::Door;
+use crate::error::ApiError;
+use crate::faculty::Faculty;
+use crate::metrics::DenialReason;
+use crate::ratelimit::AccountLockout;
+use crate::zkp::{Proof, SchnorrProver};
 
 pub static ROLES: Lazy<HashMap<String, BigUint>> = Lazy::new(|| {
     let mut m = HashMap::new();
@@ -12,38 +27,681 @@ pub static ROLES: Lazy<HashMap<String, BigUint>> = Lazy::new(|| {
     m
 });
 
-pub const ADMIN_PASSWORD: &str = "Admin@1234";
+// Credentials are stored as Argon2 PHC strings; generate new ones with `cargo run --bin hash_secret`.
+// Admin passwords live in `crate::admins`.
+
+// Checked against when the faculty or admin id is unknown, so a miss costs the same as a wrong PIN
+const DUMMY_PIN_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$Agcg97dUuoWPbR/d30On1g$DfSaLhCNZ//h6KV1ywvAoHGuM0jJGuEzN2/4/Ev6cnM";
+
+/// Check `candidate` against an Argon2 PHC string. The argon2 verifier compares the digests in
+/// constant time; a malformed stored hash never matches.
+pub fn verify_password(stored_hash: &str, candidate: &str) -> bool {
+    match PasswordHash::new(stored_hash) {
+        Ok(hash) => Argon2::default().verify_password(candidate.as_bytes(), &hash).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn verify_pin(faculty: &Faculty, candidate: &str) -> bool {
+    verify_password(faculty.pin_hash(), candidate)
+}
+
+/// Argon2 PHC string for a new password or PIN, with a fresh random salt.
+pub fn hash_password(secret: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(secret.as_bytes(), &salt)?.to_string())
+}
+
+// Admin id -> base32 TOTP secret, written once by `provision_totp`
+const ADMIN_TOTP_PATH: &str = "admin_totp.json";
+// Shown as the account's label in the authenticator app
+const TOTP_ISSUER: &str = "PrivAccess";
+
+static ADMIN_TOTP: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| {
+    let secrets = fs::read_to_string(ADMIN_TOTP_PATH)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    Mutex::new(secrets)
+});
+
+// Last time step accepted per admin, so an observed code can't be replayed within its window
+static LAST_TOTP_STEP: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Wrong passwords, PINs and TOTP codes in a row per account, whichever endpoint they came in on
+static LOGIN_LOCKOUT: Lazy<AccountLockout> = Lazy::new(|| {
+    let config = &crate::config::CONFIG;
+    AccountLockout::new(config.lockout_attempts, Duration::from_secs(config.lockout_secs))
+});
+
+// Accounts aren't per campus and ids match case-insensitively, so the count is kept the same way
+fn lockout_account(role: &str, id: &str) -> String {
+    format!("{}:{}", role, id.trim().to_lowercase())
+}
+
+fn check_not_locked(account: &str) -> Result<(), ApiError> {
+    match LOGIN_LOCKOUT.locked(account) {
+        Some(remaining) => Err(crate::ratelimit::locked_error(remaining)),
+        None => Ok(()),
+    }
+}
+
+fn record_login_failure(account: &str) {
+    if let Some(duration) = LOGIN_LOCKOUT.record_failure(account) {
+        tracing::warn!(%account, lockout_secs = duration.as_secs(), "Account locked after repeated failed logins");
+    }
+}
+
+// RFC 6238 defaults (SHA-1, 6 digits, 30s steps), which every authenticator app supports,
+// accepting the previous and next step for clock drift
+fn admin_totp(admin_id: &str, secret: Secret) -> Option<Totp> {
+    Builder::new()
+        .with_secret(secret)
+        .with_skew(1)
+        .with_account_name(admin_id)
+        .with_issuer(Some(TOTP_ISSUER))
+        .build()
+        .ok()
+}
+
+pub fn totp_enrolled(admin_id: &str) -> bool {
+    ADMIN_TOTP.lock().unwrap().contains_key(admin_id)
+}
+
+/// Enroll `admin_id` with a fresh TOTP secret and return its `otpauth://` URL for an
+/// authenticator app. None if the admin is already enrolled; the secret is only handed out once.
+pub fn provision_totp(admin_id: &str) -> Option<String> {
+    let mut secrets = ADMIN_TOTP.lock().unwrap();
+    if secrets.contains_key(admin_id) {
+        return None;
+    }
+    let secret = Secret::generate();
+    let url = admin_totp(admin_id, secret.clone())?.to_url().ok()?;
+    secrets.insert(admin_id.to_string(), secret.to_base32());
+    if let Ok(content) = serde_json::to_string_pretty(&*secrets) {
+        let _ = fs::write(ADMIN_TOTP_PATH, content);
+    }
+    Some(url)
+}
+
+/// Second factor for an admin who has enrolled TOTP: `code` must be current, give or take one
+/// step, and not already used. Admins who haven't enrolled yet pass on their password alone.
+/// Wrong codes count towards the admin's lockout like wrong passwords do.
+pub fn check_admin_totp(admin_id: &str, code: Option<&str>) -> Result<(), ApiError> {
+    let Some(secret) = ADMIN_TOTP.lock().unwrap().get(admin_id).cloned() else {
+        return Ok(());
+    };
+    let account = lockout_account("ADMIN", admin_id);
+    let result = check_totp_code(admin_id, &secret, code);
+    match &result {
+        Ok(()) => LOGIN_LOCKOUT.record_success(&account),
+        Err(_) => record_login_failure(&account),
+    }
+    result
+}
+
+fn check_totp_code(admin_id: &str, secret: &str, code: Option<&str>) -> Result<(), ApiError> {
+    let code = code.map(str::trim).filter(|c| !c.is_empty())
+        .ok_or_else(|| ApiError::unauthorized("TOTP code required").with_reason("totp_required"))?;
+    let step = Secret::try_from_base32(secret).ok()
+        .and_then(|secret| admin_totp(admin_id, secret))
+        .and_then(|totp| totp.check_current(code))
+        .ok_or_else(|| ApiError::unauthorized("Invalid or expired TOTP code").with_reason("totp_invalid"))?;
 
-#[derive(Debug, Serialize, Clone)]
-pub struct Faculty {
-    pub id: &'static str,
-    pub pin: &'static str,
+    let mut last_steps = LAST_TOTP_STEP.lock().unwrap();
+    if last_steps.get(admin_id).is_some_and(|&last| step <= last) {
+        return Err(ApiError::unauthorized("TOTP code already used").with_reason("totp_reused"));
+    }
+    last_steps.insert(admin_id.to_string(), step);
+    Ok(())
 }
 
-pub const FACULTIES: &[Faculty] = &[
-    Faculty { id: "Fac1", pin: "1234" },
-    Faculty { id: "Fac2", pin: "5678" },
-    Faculty { id: "Fac3", pin: "9876" },
-    Faculty { id: "Fac4", pin: "5432" },
-];
+/// Sections when `PRIVACCESS_SECTIONS` isn't set.
+pub const DEFAULT_SECTIONS: &[&str] = &["A", "B", "C", "D", "E", "F", "G", "H"];
 
-pub const SECTIONS: &[&str] = &["A", "B", "C", "D", "E", "F", "G", "H"];
+/// A comma-separated section list, e.g. `"A,B,C"` or `"CS-1,CS-2,EE-1"`. Names are kept as
+/// written but must differ other than by case, since sections are matched case-insensitively,
+/// and they go in URLs and logs, so only letters, digits, `-` and `_` are allowed.
+pub fn parse_sections(spec: &str) -> Result<Vec<String>, String> {
+    let mut sections: Vec<String> = Vec::new();
+    for name in spec.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        if name.len() > crate::validate::MAX_KEYWORD_LEN || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("invalid section name '{}'", name));
+        }
+        if sections.iter().any(|s| s.eq_ignore_ascii_case(name)) {
+            return Err(format!("section '{}' is listed twice", name));
+        }
+        sections.push(name.to_string());
+    }
+    if sections.is_empty() {
+        return Err("no sections listed".to_string());
+    }
+    Ok(sections)
+}
 
-#[allow(dead_code)]
+/// `name` as it's written in `sections`, if it's one of them (in any case).
+pub fn find_section<'a>(sections: &'a [String], name: &str) -> Option<&'a str> {
+    let name = name.trim();
+    sections.iter().map(String::as_str).find(|s| s.eq_ignore_ascii_case(name))
+}
+
+/// What each role is granted itself, on top of what it inherits through `ROLE_PARENTS`.
 pub static ROLE_PERMISSIONS: Lazy<HashMap<String, Vec<&'static str>>> = Lazy::new(|| {
     let mut m = HashMap::new();
-    m.insert("ADMIN".to_string(), vec!["read", "write", "delete"]);
-    m.insert("FACULTY".to_string(), vec!["read", "write"]);
+    m.insert("ADMIN".to_string(), vec!["delete"]);
+    m.insert("FACULTY".to_string(), vec!["write"]);
     m.insert("STUDENT".to_string(), vec!["read"]);
     m
 });
 
+/// Role -> the role it inherits every permission from, so ADMIN ⊇ FACULTY ⊇ STUDENT.
+pub static ROLE_PARENTS: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+    m.insert("ADMIN".to_string(), "FACULTY".to_string());
+    m.insert("FACULTY".to_string(), "STUDENT".to_string());
+    m
+});
+
 pub fn get_role_secret(role_name: &str) -> Option<BigUint> {
     ROLES.get(role_name).cloned()
 }
 
-#[allow(dead_code)]
+/// Why `build_proof_for_role` couldn't build a proof.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoleProofError {
+    UnknownRole(String),
+    /// The secret isn't a decimal number in [2, Q)
+    InvalidSecret,
+}
+
+impl std::fmt::Display for RoleProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoleProofError::UnknownRole(role) => write!(f, "Unknown role '{}'", role),
+            RoleProofError::InvalidSecret => write!(f, "Secret must be a decimal number in [2, Q)"),
+        }
+    }
+}
+
+impl std::error::Error for RoleProofError {}
+
+/// Client-side shortcut: build the proof a `role` member sends to `/api/verify`, from the decimal
+/// `secret` that `/mobile/setup` returns and the `nonce` from `/api/challenge`.
+pub fn build_proof_for_role(role: &str, secret: &str, geohash: &str, nonce: &str) -> Result<Proof, RoleProofError> {
+    let role = role.trim().to_uppercase();
+    if !ROLE_PERMISSIONS.contains_key(&role) {
+        return Err(RoleProofError::UnknownRole(role));
+    }
+    let secret = BigUint::from_str_radix(secret.trim(), 10).map_err(|_| RoleProofError::InvalidSecret)?;
+    if secret < BigUint::from(2u32) || secret >= *crate::crypto::Q {
+        return Err(RoleProofError::InvalidSecret);
+    }
+    Ok(SchnorrProver::new(secret).generate_proof(geohash.to_string(), nonce))
+}
+
+/// Role secrets for a campus. The default tenant uses `ROLES` as-is; every other tenant gets
+/// its own secrets derived from them, so a role key from one campus is meaningless on another.
+pub fn tenant_role_secrets(tenant: &str) -> HashMap<String, BigUint> {
+    if tenant == crate::tenant::DEFAULT_TENANT {
+        return ROLES.clone();
+    }
+    ROLES.iter()
+        .map(|(role, base)| {
+            let mut hasher = Sha256::new();
+            hasher.update(format!("{}:{}:{}", tenant, role, base).as_bytes());
+            let secret = BigUint::from_bytes_be(&hasher.finalize()) % &*crate::crypto::Q;
+            (role.clone(), secret)
+        })
+        .collect()
+}
+
+/// A role's own permissions and everything it inherits, most basic first. None for unknown roles.
 pub fn get_role_permissions(role_name: &str) -> Option<Vec<&'static str>> {
-    ROLE_PERMISSIONS.get(role_name).cloned()
+    resolve_permissions(role_name, &ROLE_PERMISSIONS, &ROLE_PARENTS)
+}
+
+fn resolve_permissions(role_name: &str, grants: &HashMap<String, Vec<&'static str>>, parents: &HashMap<String, String>) -> Option<Vec<&'static str>> {
+    grants.get(role_name)?;
+    // Walk up to the root; a cycle in the parent map stops at the first repeat
+    let mut chain: Vec<&str> = vec![role_name];
+    while let Some(parent) = parents.get(*chain.last().unwrap()) {
+        if chain.contains(&parent.as_str()) {
+            break;
+        }
+        chain.push(parent);
+    }
+    let mut permissions = Vec::new();
+    for role in chain.iter().rev() {
+        for &perm in grants.get(*role).into_iter().flatten() {
+            if !permissions.contains(&perm) {
+                permissions.push(perm);
+            }
+        }
+    }
+    Some(permissions)
+}
+ 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Read,
+    Write,
+    Delete,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Delete => "delete",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Permission> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "read" => Some(Permission::Read),
+            "write" => Some(Permission::Write),
+            "delete" => Some(Permission::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// An authenticated caller.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub id: String,
+    pub role: String,
+    pub session_id: Option<String>,
+    // Set for API keys, which carry their own grants instead of a role's
+    pub permissions: Option<Vec<String>>,
+}
+
+impl Principal {
+    pub fn allows(&self, required: Permission) -> bool {
+        match &self.permissions {
+            Some(perms) => perms.iter().any(|p| p == required.as_str()),
+            None => role_allows(&self.role, required),
+        }
+    }
+}
+
+/// Ways a caller can prove who they are.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    /// `id` is `admins::DEFAULT_ADMIN_ID` for callers that send only a password. `totp` is
+    /// required once the admin has enrolled an authenticator app
+    AdminPassword { id: String, password: String, totp: Option<String> },
+    FacultyPin { id: String, pin: String },
+    SessionToken(String),
+    ApiKey(String),
+    /// Session id from a signed admin cookie set by `/api/admin/login`
+    AdminSession(String),
+}
+
+impl Credentials {
+    /// Pull credentials from request headers: a bearer session token, `X-Api-Key`,
+    /// `X-Admin-Password` (with `X-Admin-Totp` once enrolled), `X-Faculty-Id` + `X-Faculty-Pin`,
+    /// or the admin session cookie, in that order of preference.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Credentials> {
+        let header = |name: &str| headers.get(name).and_then(|h| h.to_str().ok()).map(|v| v.trim().to_string());
+
+        if let Some(token) = crate::session::bearer_token(headers) {
+            return Some(Credentials::SessionToken(token.to_string()));
+        }
+        if let Some(key) = header("x-api-key") {
+            return Some(Credentials::ApiKey(key));
+        }
+        if let Some(password) = header("x-admin-password") {
+            let id = header("x-admin-id").filter(|id| !id.is_empty()).unwrap_or_else(|| crate::admins::DEFAULT_ADMIN_ID.to_string());
+            let totp = header("x-admin-totp");
+            return Some(Credentials::AdminPassword { id, password, totp });
+        }
+        if let (Some(id), Some(pin)) = (header("x-faculty-id"), header("x-faculty-pin")) {
+            return Some(Credentials::FacultyPin { id, pin });
+        }
+        crate::session::admin_session_id(headers).map(Credentials::AdminSession)
+    }
+}
+
+pub fn role_names() -> Vec<String> {
+    let mut roles: Vec<String> = ROLE_PERMISSIONS.keys().cloned().collect();
+    roles.sort();
+    roles
+}
+
+/// Whether `role` holds `permission`, itself or by inheritance. Gate endpoints on this rather
+/// than on role names.
+pub fn has_permission(role: &str, permission: &str) -> bool {
+    get_role_permissions(role).is_some_and(|perms| perms.contains(&permission))
+}
+
+pub fn role_allows(role: &str, required: Permission) -> bool {
+    has_permission(role, required.as_str())
+}
+
+/// Resolve credentials to a principal without checking permissions. Session tokens are only
+/// accepted on the tenant that issued them. Admin passwords and faculty PINs, the credentials
+/// that can be guessed, are refused outright while their account is locked out, and a password
+/// only counts with a current TOTP code for admins who have enrolled one.
+pub fn authenticate(credentials: &Credentials, tenant: &str) -> Result<Principal, ApiError> {
+    match credentials {
+        Credentials::AdminPassword { id, password, totp } => {
+            let account = lockout_account("ADMIN", id);
+            check_not_locked(&account)?;
+            let admin = crate::admins::find(id.trim());
+            let password_ok = match &admin {
+                Some(a) => verify_password(a.password_hash(), password.trim()),
+                None => {
+                    let _ = verify_password(DUMMY_PIN_HASH, password.trim());
+                    false
+                }
+            };
+            let Some(admin) = admin.filter(|_| password_ok) else {
+                record_login_failure(&account);
+                return Err(ApiError::unauthorized("Incorrect Admin Password"));
+            };
+            // Wrong codes count against the account like wrong passwords
+            check_admin_totp(&admin.id, totp.as_deref())?;
+            LOGIN_LOCKOUT.record_success(&account);
+            Ok(Principal { id: admin.id, role: "ADMIN".to_string(), session_id: None, permissions: None })
+        }
+        Credentials::FacultyPin { id, pin } => {
+            let (id, pin) = (id.trim(), pin.trim());
+            let account = lockout_account("FACULTY", id);
+            check_not_locked(&account)?;
+            let faculty = crate::faculty::find(id);
+            let pin_ok = match &faculty {
+                Some(f) => verify_pin(f, pin),
+                None => {
+                    let _ = verify_password(DUMMY_PIN_HASH, pin);
+                    false
+                }
+            };
+            let Some(faculty) = faculty.filter(|_| pin_ok) else {
+                record_login_failure(&account);
+                return Err(ApiError::unauthorized("Invalid Faculty ID or PIN"));
+            };
+            LOGIN_LOCKOUT.record_success(&account);
+            Ok(Principal { id: faculty.id, role: "FACULTY".to_string(), session_id: None, permissions: None })
+        }
+        Credentials::SessionToken(token) => {
+            let claims = crate::session::validate_token(token)
+                .map_err(|e| ApiError::unauthorized(e.message()))?;
+            if claims.tenant != tenant {
+                return Err(ApiError::unauthorized("Session belongs to another campus"));
+            }
+            if claims.role == "FACULTY" && crate::faculty::find(&claims.sub).is_none() {
+                return Err(ApiError::unauthorized("Faculty account has been removed"));
+            }
+            if claims.role == "ADMIN" && crate::admins::find(&claims.sub).is_none() {
+                return Err(ApiError::unauthorized("Admin account has been removed"));
+            }
+            Ok(Principal { id: claims.sub, role: claims.role, session_id: Some(claims.sid), permissions: None })
+        }
+        Credentials::AdminSession(sid) => {
+            let admin_id = crate::session::touch_admin_session(sid, tenant)
+                .map_err(|e| ApiError::unauthorized(e.message()))?;
+            if crate::admins::find(&admin_id).is_none() {
+                return Err(ApiError::unauthorized("Admin account has been removed"));
+            }
+            Ok(Principal { id: admin_id, role: "ADMIN".to_string(), session_id: None, permissions: None })
+        }
+        Credentials::ApiKey(key) => {
+            let key = crate::apikeys::find(key)
+                .filter(|k| k.tenant == tenant)
+                .ok_or_else(|| ApiError::unauthorized("Invalid or revoked API key"))?;
+            Ok(Principal { id: format!("key:{}", key.id), role: "API_KEY".to_string(), session_id: None, permissions: Some(key.permissions) })
+        }
+    }
+}
+
+/// Authenticate the given credentials and check the resulting role holds `required`.
+pub fn authorize_credentials(credentials: Option<&Credentials>, tenant: &str, required: Permission) -> Result<Principal, ApiError> {
+    let credentials = credentials.ok_or_else(|| ApiError::unauthorized("Authentication required"))?;
+    let principal = authenticate(credentials, tenant)?;
+    if !principal.allows(required) {
+        return Err(ApiError::forbidden(format!("Role {} lacks '{}' permission", principal.role, required.as_str())));
+    }
+    Ok(principal)
+}
+
+/// Guard for protected routes: extract credentials from the request headers and require `required`.
+pub fn authorize(headers: &HeaderMap, tenant: &str, required: Permission) -> Result<Principal, ApiError> {
+    authorize_credentials(Credentials::from_headers(headers).as_ref(), tenant, required)
+}
+
+/// Admins and guests get in on a password or pass; everyone else also proves their role key.
+pub fn proves_identity(role: &str) -> bool {
+    !matches!(role, "ADMIN" | "GUEST")
+}
+
+/// Where the section a request names stands, for the roles that enter by section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionState {
+    /// None was sent
+    Missing,
+    /// Not one of the configured sections
+    Invalid,
+    /// No room has it
+    Unassigned,
+    /// Assigned to the door being opened
+    Here,
+    /// Assigned to another room; `own` if the faculty member asking is the one who assigned it
+    Elsewhere { own: bool },
+}
+
+/// Everything `evaluate_access` weighs, gathered by the caller. A check left as None wasn't run,
+/// which for a check the role needs counts as failed.
+#[derive(Debug, Clone)]
+pub struct AccessRequest<'a> {
+    pub role: &'a str,
+    pub door: &'a Door,
+    /// Server local time, for the door's schedule
+    pub now: NaiveDateTime,
+    /// Whether this role must show it's at the door; see `Config::requires_proximity`
+    pub proximity_required: bool,
+    /// QR freshness, floor, and beacon or GPS
+    pub location: Option<Result<(), DenialReason>>,
+    /// Password and TOTP, PIN or passkey, or guest pass, depending on the role
+    pub credentials: Option<Result<(), DenialReason>>,
+    pub section: SectionState,
+    /// Challenge, revocation and the identity proof itself
+    pub proof: Option<Result<(), DenialReason>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessDecision {
+    Grant,
+    Deny(DenialReason),
+}
+
+/// Whether `door`'s schedule lets `role` in at `now`. Admins can open a door any time.
+pub fn within_schedule(door: &Door, role: &str, now: NaiveDateTime) -> bool {
+    match &door.schedule {
+        Some(schedule) if role != "ADMIN" && schedule.applies_to(role) => schedule.is_open(now),
+        _ => true,
+    }
+}
+
+/// Whether `section` lets `role` through this door. Students need their section assigned here;
+/// faculty may name none, or take one unless a colleague holds it in another room.
+pub fn section_allows(role: &str, section: SectionState) -> Result<(), DenialReason> {
+    match (role, section) {
+        ("STUDENT", SectionState::Here) => Ok(()),
+        ("STUDENT", SectionState::Elsewhere { .. }) => Err(DenialReason::WrongRoom),
+        ("STUDENT", SectionState::Unassigned) => Err(DenialReason::NoRoomAssigned),
+        ("STUDENT", SectionState::Missing | SectionState::Invalid) => Err(DenialReason::InvalidSection),
+        ("FACULTY", SectionState::Invalid) => Err(DenialReason::InvalidSection),
+        ("FACULTY", SectionState::Elsewhere { own: false }) => Err(DenialReason::SectionConflict),
+        _ => Ok(()),
+    }
+}
+
+// A check the role needs: its own failure, or `unchecked` if it was never run
+fn required(check: Option<Result<(), DenialReason>>, unchecked: DenialReason) -> Result<(), DenialReason> {
+    check.unwrap_or(Err(unchecked))
+}
+
+// Each check in the order the policy weighs them, stopping at the first that fails
+fn first_failure(request: &AccessRequest) -> Result<(), DenialReason> {
+    if !matches!(request.role, "ADMIN" | "FACULTY" | "STUDENT" | "GUEST") {
+        return Err(DenialReason::InvalidCredentials);
+    }
+    if !within_schedule(request.door, request.role, request.now) {
+        return Err(DenialReason::OutsideSchedule);
+    }
+    if request.proximity_required {
+        required(request.location, DenialReason::Location)?;
+    }
+    required(request.credentials, DenialReason::InvalidCredentials)?;
+    section_allows(request.role, request.section)?;
+    if proves_identity(request.role) {
+        required(request.proof, DenialReason::InvalidProof)?;
+    }
+    Ok(())
+}
+
+/// The access policy for `/api/verify`, without any I/O: schedule, then location, credentials,
+/// section and identity proof, with the first failure as the reason for a denial.
+pub fn evaluate_access(request: AccessRequest) -> AccessDecision {
+    match first_failure(&request) {
+        Ok(()) => AccessDecision::Grant,
+        Err(reason) => AccessDecision::Deny(reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_inherits_faculty_and_student_permissions() {
+        assert_eq!(get_role_permissions("STUDENT").unwrap(), vec!["read"]);
+        assert_eq!(get_role_permissions("FACULTY").unwrap(), vec!["read", "write"]);
+        assert_eq!(get_role_permissions("ADMIN").unwrap(), vec!["read", "write", "delete"]);
+        assert_eq!(get_role_permissions("GUEST"), None);
+
+        assert!(has_permission("ADMIN", "read"));
+        assert!(has_permission("FACULTY", "read"));
+        assert!(!has_permission("FACULTY", "delete"));
+        assert!(!has_permission("STUDENT", "write"));
+        assert!(role_allows("ADMIN", Permission::Write));
+    }
+
+    #[test]
+    fn inheritance_follows_the_parent_map() {
+        let grants: HashMap<String, Vec<&'static str>> = [("A", vec!["a"]), ("B", vec!["b"]), ("C", vec!["c", "a"])]
+            .into_iter()
+            .map(|(role, perms)| (role.to_string(), perms))
+            .collect();
+        let mut parents: HashMap<String, String> = [("A", "B"), ("B", "C")]
+            .into_iter()
+            .map(|(role, parent)| (role.to_string(), parent.to_string()))
+            .collect();
+        assert_eq!(resolve_permissions("A", &grants, &parents).unwrap(), vec!["c", "a", "b"]);
+
+        // A cycle doesn't loop forever
+        parents.insert("C".to_string(), "A".to_string());
+        assert_eq!(resolve_permissions("B", &grants, &parents).unwrap(), vec!["a", "c", "b"]);
+    }
+
+    // 2026-03-02 is a Monday
+    fn monday(hour: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn door() -> Door {
+        Door::new("Room 101", "secret", "tdr1v9", 1).unwrap()
+    }
+
+    // Every check run and passed
+    fn request<'a>(role: &'a str, door: &'a Door) -> AccessRequest<'a> {
+        AccessRequest {
+            role,
+            door,
+            now: monday(10),
+            proximity_required: true,
+            location: Some(Ok(())),
+            credentials: Some(Ok(())),
+            section: SectionState::Here,
+            proof: Some(Ok(())),
+        }
+    }
+
+    #[test]
+    fn every_role_is_granted_when_its_checks_pass() {
+        let door = door();
+        for role in ["ADMIN", "FACULTY", "STUDENT", "GUEST"] {
+            assert_eq!(evaluate_access(request(role, &door)), AccessDecision::Grant, "{}", role);
+        }
+        assert_eq!(evaluate_access(request("JANITOR", &door)), AccessDecision::Deny(DenialReason::InvalidCredentials));
+    }
+
+    #[test]
+    fn the_first_failed_check_is_the_reason() {
+        let door = door();
+        let failing = AccessRequest {
+            location: Some(Err(DenialReason::QrReused)),
+            credentials: Some(Err(DenialReason::InvalidCredentials)),
+            proof: Some(Err(DenialReason::ProofExpired)),
+            ..request("STUDENT", &door)
+        };
+        assert_eq!(evaluate_access(failing.clone()), AccessDecision::Deny(DenialReason::QrReused));
+        let failing = AccessRequest { location: Some(Ok(())), section: SectionState::Unassigned, ..failing };
+        assert_eq!(evaluate_access(failing.clone()), AccessDecision::Deny(DenialReason::InvalidCredentials));
+        let failing = AccessRequest { credentials: Some(Ok(())), ..failing };
+        assert_eq!(evaluate_access(failing.clone()), AccessDecision::Deny(DenialReason::NoRoomAssigned));
+        let failing = AccessRequest { section: SectionState::Here, ..failing };
+        assert_eq!(evaluate_access(failing), AccessDecision::Deny(DenialReason::ProofExpired));
+    }
+
+    #[test]
+    fn checks_that_were_not_run_count_only_where_needed() {
+        let door = door();
+        let unchecked = AccessRequest { location: None, proof: None, ..request("STUDENT", &door) };
+        assert_eq!(evaluate_access(unchecked.clone()), AccessDecision::Deny(DenialReason::Location));
+        let exempt = AccessRequest { proximity_required: false, ..unchecked };
+        assert_eq!(evaluate_access(exempt), AccessDecision::Deny(DenialReason::InvalidProof));
+
+        // Admins and guests send no proof and no section
+        for role in ["ADMIN", "GUEST"] {
+            let request = AccessRequest { proof: None, section: SectionState::Missing, ..request(role, &door) };
+            assert_eq!(evaluate_access(request), AccessDecision::Grant, "{}", role);
+        }
+        let request = AccessRequest { credentials: None, ..request("GUEST", &door) };
+        assert_eq!(evaluate_access(request), AccessDecision::Deny(DenialReason::InvalidCredentials));
+    }
+
+    #[test]
+    fn sections_bind_students_to_rooms_and_faculty_to_their_own() {
+        assert_eq!(section_allows("STUDENT", SectionState::Elsewhere { own: false }), Err(DenialReason::WrongRoom));
+        assert_eq!(section_allows("STUDENT", SectionState::Missing), Err(DenialReason::InvalidSection));
+        assert_eq!(section_allows("FACULTY", SectionState::Missing), Ok(()));
+        assert_eq!(section_allows("FACULTY", SectionState::Unassigned), Ok(()));
+        assert_eq!(section_allows("FACULTY", SectionState::Elsewhere { own: true }), Ok(()));
+        assert_eq!(section_allows("FACULTY", SectionState::Elsewhere { own: false }), Err(DenialReason::SectionConflict));
+    }
+
+    #[test]
+    fn a_closed_door_turns_away_everyone_but_admins() {
+        let schedule = toml::from_str(r#"mon = ["08:00-12:00"]"#).unwrap();
+        let door = door().with_schedule(schedule);
+        let late = |role| AccessRequest { now: monday(13), ..request(role, &door) };
+        assert_eq!(evaluate_access(late("FACULTY")), AccessDecision::Deny(DenialReason::OutsideSchedule));
+        assert_eq!(evaluate_access(late("ADMIN")), AccessDecision::Grant);
+        assert_eq!(evaluate_access(request("FACULTY", &door)), AccessDecision::Grant);
+    }
+
+    #[test]
+    fn section_lists_must_be_unique_and_non_empty() {
+        assert_eq!(parse_sections(" A, b ,CS-1,").unwrap(), vec!["A", "b", "CS-1"]);
+        assert!(parse_sections("A,B,a").unwrap_err().contains("twice"));
+        assert!(parse_sections(" , ").is_err());
+        assert!(parse_sections("A,B C").is_err());
+
+        let sections = parse_sections("CS-1,CS-2").unwrap();
+        assert_eq!(find_section(&sections, " cs-2"), Some("CS-2"));
+        assert_eq!(find_section(&sections, "CS-3"), None);
+    }
 }
- 
\ No newline at end of file
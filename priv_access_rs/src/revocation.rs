@@ -0,0 +1,60 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::RwLock;
+
+const REVOKED_PATH: &str = "revoked_keys.json";
+
+/// Public keys whose proofs are refused even when they verify, e.g. after a device is lost.
+/// Kept in `revoked_keys.json` so revocations survive a restart.
+pub struct RevocationList {
+    keys: RwLock<BTreeSet<String>>,
+}
+
+// Decimal keys are unaffected; secp256k1 keys are hex, which clients may send in either case
+fn normalize(public_key: &str) -> String {
+    public_key.trim().to_ascii_lowercase()
+}
+
+impl RevocationList {
+    pub fn load() -> Self {
+        let keys = fs::read_to_string(REVOKED_PATH)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        RevocationList { keys: RwLock::new(keys) }
+    }
+
+    fn save(keys: &BTreeSet<String>) {
+        if let Ok(content) = serde_json::to_string_pretty(keys) {
+            let _ = fs::write(REVOKED_PATH, content);
+        }
+    }
+
+    pub fn is_revoked(&self, public_key: &str) -> bool {
+        self.keys.read().unwrap().contains(&normalize(public_key))
+    }
+
+    /// Returns false if the key was already revoked.
+    pub fn revoke(&self, public_key: &str) -> bool {
+        let mut keys = self.keys.write().unwrap();
+        let added = keys.insert(normalize(public_key));
+        if added {
+            Self::save(&keys);
+        }
+        added
+    }
+
+    /// Returns false if the key wasn't revoked.
+    pub fn restore(&self, public_key: &str) -> bool {
+        let mut keys = self.keys.write().unwrap();
+        let removed = keys.remove(&normalize(public_key));
+        if removed {
+            Self::save(&keys);
+        }
+        removed
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.keys.read().unwrap().iter().cloned().collect()
+    }
+}
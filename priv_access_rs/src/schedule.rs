@@ -0,0 +1,168 @@
+use chrono::{Datelike, Duration, NaiveDateTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// Days never looked past when searching for the next open window
+const SEARCH_DAYS: i64 = 7;
+
+/// One door's `[door.schedule]` table as written in `doors*.toml`, e.g.
+/// `mon = ["08:00-12:00", "13:00-18:00"]`. Days left out are closed.
+#[derive(Serialize, Deserialize, Default)]
+struct ScheduleTable {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mon: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tue: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    wed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    thu: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fri: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sat: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sun: Vec<String>,
+    // Empty: the schedule restricts every role
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<String>,
+}
+
+/// When a door may be unlocked, as time windows per weekday in the server's local time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ScheduleTable", into = "ScheduleTable")]
+pub struct WeeklySchedule {
+    // Monday first; each day's [start, end) windows in minutes after midnight, sorted by start
+    days: [Vec<(u32, u32)>; 7],
+    roles: Vec<String>,
+}
+
+// "08:30" -> 510; "24:00" is allowed as the end of the day
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    let total = hours * 60 + minutes;
+    (minutes < 60 && total <= MINUTES_PER_DAY).then_some(total)
+}
+
+fn parse_window(window: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("schedule window '{}' must look like 08:00-18:00", window);
+    let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+    let (start, end) = (parse_time(start).ok_or_else(invalid)?, parse_time(end).ok_or_else(invalid)?);
+    if start >= end {
+        return Err(format!("schedule window '{}' ends before it starts", window));
+    }
+    Ok((start, end))
+}
+
+fn format_window((start, end): (u32, u32)) -> String {
+    format!("{:02}:{:02}-{:02}:{:02}", start / 60, start % 60, end / 60, end % 60)
+}
+
+impl TryFrom<ScheduleTable> for WeeklySchedule {
+    type Error = String;
+
+    fn try_from(table: ScheduleTable) -> Result<Self, String> {
+        let mut days: [Vec<(u32, u32)>; 7] = Default::default();
+        let tables = [table.mon, table.tue, table.wed, table.thu, table.fri, table.sat, table.sun];
+        for (day, windows) in days.iter_mut().zip(tables) {
+            *day = windows.iter().map(|w| parse_window(w)).collect::<Result<_, _>>()?;
+            day.sort();
+        }
+        let roles = table.roles.iter().map(|r| r.trim().to_uppercase()).collect();
+        Ok(WeeklySchedule { days, roles })
+    }
+}
+
+impl From<WeeklySchedule> for ScheduleTable {
+    fn from(schedule: WeeklySchedule) -> Self {
+        let [mon, tue, wed, thu, fri, sat, sun] = schedule.days.map(|day| day.into_iter().map(format_window).collect());
+        ScheduleTable { mon, tue, wed, thu, fri, sat, sun, roles: schedule.roles }
+    }
+}
+
+impl WeeklySchedule {
+    fn windows(&self, day: Weekday) -> &[(u32, u32)] {
+        &self.days[day.num_days_from_monday() as usize]
+    }
+
+    /// Whether the schedule restricts `role`. Without a `roles` list it restricts everyone;
+    /// callers decide who bypasses it altogether.
+    pub fn applies_to(&self, role: &str) -> bool {
+        self.roles.is_empty() || self.roles.iter().any(|r| r.eq_ignore_ascii_case(role))
+    }
+
+    pub fn is_open(&self, at: NaiveDateTime) -> bool {
+        let minute = at.time().signed_duration_since(chrono::NaiveTime::MIN).num_minutes() as u32;
+        self.windows(at.weekday()).iter().any(|&(start, end)| start <= minute && minute < end)
+    }
+
+    /// The first moment at or after `at` the door is open, or None if no day has a window.
+    pub fn next_open(&self, at: NaiveDateTime) -> Option<NaiveDateTime> {
+        if self.is_open(at) {
+            return Some(at);
+        }
+        (0..=SEARCH_DAYS).find_map(|offset| {
+            let midnight = (at.date() + Duration::days(offset)).and_time(chrono::NaiveTime::MIN);
+            self.windows(midnight.weekday()).iter()
+                .map(|&(start, _)| midnight + Duration::minutes(start as i64))
+                .find(|&opens| opens > at)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn schedule(toml: &str) -> WeeklySchedule {
+        toml::from_str(toml).unwrap()
+    }
+
+    // 2026-03-02 is a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn open_only_inside_a_window() {
+        let s = schedule(r#"mon = ["08:00-12:00", "13:00-18:00"]"#);
+        assert!(s.is_open(at(2, 8, 0)));
+        assert!(!s.is_open(at(2, 12, 30)));
+        assert!(!s.is_open(at(2, 18, 0)));
+        // Tuesday has no windows
+        assert!(!s.is_open(at(3, 9, 0)));
+    }
+
+    #[test]
+    fn next_open_is_later_today_or_a_later_day() {
+        let s = schedule(r#"mon = ["08:00-12:00", "13:00-18:00"]
+wed = ["09:30-11:00"]"#);
+        assert_eq!(s.next_open(at(2, 12, 30)), Some(at(2, 13, 0)));
+        assert_eq!(s.next_open(at(2, 19, 0)), Some(at(4, 9, 30)));
+        // From Thursday it wraps round to next Monday
+        assert_eq!(s.next_open(at(5, 7, 0)), Some(at(9, 8, 0)));
+        assert_eq!(schedule("").next_open(at(2, 9, 0)), None);
+    }
+
+    #[test]
+    fn bad_windows_are_rejected() {
+        for window in ["8-18", "18:00-08:00", "08:00-24:30", "08:60-09:00"] {
+            assert!(toml::from_str::<WeeklySchedule>(&format!("mon = [\"{}\"]", window)).is_err(), "{}", window);
+        }
+    }
+
+    #[test]
+    fn roles_narrow_who_is_restricted() {
+        assert!(schedule("").applies_to("FACULTY"));
+        let s = schedule(r#"roles = ["student"]"#);
+        assert!(s.applies_to("STUDENT"));
+        assert!(!s.applies_to("FACULTY"));
+        assert_eq!(schedule(&toml::to_string(&s).unwrap()), s);
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use axum::{async_trait, extract::FromRequestParts, http::{request::Parts, HeaderMap}};
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::ApiError;
+use crate::rbac::{Credentials, Permission, Principal};
+use crate::tenant::Tenant;
+
+// Session tokens are valid for one working day
+pub const SESSION_TTL_SECS: u64 = 8 * 60 * 60;
+
+// Refresh tokens last a month; every use swaps the token for a new one
+pub const REFRESH_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+// HMAC key for session JWTs. Set PRIVACCESS_JWT_SECRET to keep tokens valid across restarts,
+// otherwise a fresh key is generated on every boot.
+static JWT_SECRET: Lazy<Vec<u8>> = Lazy::new(|| {
+    match std::env::var("PRIVACCESS_JWT_SECRET") {
+        Ok(s) if !s.is_empty() => s.into_bytes(),
+        _ => {
+            use rand::RngCore;
+            let mut key = vec![0u8; 32];
+            rand::rngs::OsRng.fill_bytes(&mut key);
+            key
+        }
+    }
+});
+
+// Cookie holding an admin dashboard session id, signed so it can't be forged or altered
+pub const ADMIN_COOKIE: &str = "privaccess_admin";
+
+// Signing key for admin cookies. Set PRIVACCESS_COOKIE_SECRET to keep admins logged in across
+// restarts, otherwise a fresh key is generated on every boot.
+static COOKIE_KEY: Lazy<Key> = Lazy::new(|| {
+    let secret = match std::env::var("PRIVACCESS_COOKIE_SECRET") {
+        Ok(s) if !s.is_empty() => s.into_bytes(),
+        _ => {
+            use rand::RngCore;
+            let mut key = vec![0u8; 64];
+            rand::rngs::OsRng.fill_bytes(&mut key);
+            key
+        }
+    };
+    // Key wants 64 bytes, whatever length the configured secret is
+    use sha2::Digest;
+    Key::from(&sha2::Sha512::digest(&secret))
+});
+
+struct AdminSession {
+    admin_id: String,
+    tenant: String,
+    // When the password (and TOTP, if enrolled) was last checked for this session
+    started: u64,
+    last_seen: u64,
+}
+
+// Live admin cookie sessions by id. Logging out removes the entry, so a copied cookie dies with it.
+static ADMIN_SESSIONS: Lazy<Mutex<HashMap<String, AdminSession>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Token generation per tenant. Every JWT carries its campus's epoch when it was minted; bumping
+// a campus's epoch invalidates all of that campus's outstanding tokens at once.
+static TOKEN_EPOCHS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Session ids revoked individually before their expiry
+static REVOKED_SESSIONS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+struct RefreshGrant {
+    sub: String,
+    role: String,
+    tenant: String,
+    // Session last issued through this grant; revoking it (logging out) ends the grant too
+    sid: String,
+    exp: u64,
+}
+
+// Live refresh tokens by SHA-256 digest, so the map itself holds nothing a caller could use
+static REFRESH_GRANTS: Lazy<Mutex<HashMap<String, RefreshGrant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Claims {
+    pub sub: String,
+    pub role: String,
+    pub tenant: String,
+    pub sid: String,
+    pub epoch: u64,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+    Malformed,
+    Expired,
+    Revoked,
+}
+
+impl SessionError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            SessionError::Malformed => "Invalid session token",
+            SessionError::Expired => "Session expired",
+            SessionError::Revoked => "Session revoked, please log in again",
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+pub fn current_epoch(tenant: &str) -> u64 {
+    TOKEN_EPOCHS.lock().unwrap().get(tenant).copied().unwrap_or(0)
+}
+
+/// Mint a signed session token for an authenticated principal.
+pub fn issue_token(sub: &str, role: &str, tenant: &str) -> (String, Claims) {
+    use rand::Rng;
+    let sid: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect();
+
+    let iat = now_secs();
+    let claims = Claims {
+        sub: sub.to_string(),
+        role: role.to_string(),
+        tenant: tenant.to_string(),
+        sid,
+        epoch: current_epoch(tenant),
+        iat,
+        exp: iat + SESSION_TTL_SECS,
+    };
+    let token = encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(&JWT_SECRET))
+        .expect("HS256 encoding cannot fail");
+    (token, claims)
+}
+
+/// Check signature, expiry, epoch and per-session revocation.
+pub fn validate_token(token: &str) -> Result<Claims, SessionError> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+    let data = decode::<Claims>(token, &DecodingKey::from_secret(&JWT_SECRET), &validation).map_err(|e| {
+        match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => SessionError::Expired,
+            _ => SessionError::Malformed,
+        }
+    })?;
+    let claims = data.claims;
+
+    if claims.epoch != current_epoch(&claims.tenant) {
+        return Err(SessionError::Revoked);
+    }
+    if REVOKED_SESSIONS.lock().unwrap().contains(&claims.sid) {
+        return Err(SessionError::Revoked);
+    }
+    Ok(claims)
+}
+
+fn refresh_digest(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// A refresh token for the session `claims` describes, to exchange for a new access token
+/// once that one expires.
+pub fn issue_refresh_token(claims: &Claims) -> String {
+    use rand::Rng;
+    let token: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect();
+    let now = now_secs();
+    let grant = RefreshGrant {
+        sub: claims.sub.clone(),
+        role: claims.role.clone(),
+        tenant: claims.tenant.clone(),
+        sid: claims.sid.clone(),
+        exp: now + REFRESH_TTL_SECS,
+    };
+    let mut grants = REFRESH_GRANTS.lock().unwrap();
+    grants.retain(|_, g| g.exp > now);
+    grants.insert(refresh_digest(&token), grant);
+    token
+}
+
+/// Swap a refresh token for a new access token and a new refresh token. The one passed in
+/// stops working, so a copy used by someone else turns up as revoked for whoever used it second.
+pub fn refresh(token: &str, tenant: &str) -> Result<(String, Claims, String), SessionError> {
+    let grant = {
+        let mut grants = REFRESH_GRANTS.lock().unwrap();
+        let digest = refresh_digest(token);
+        match grants.get(&digest) {
+            None => return Err(SessionError::Revoked),
+            Some(g) if g.tenant != tenant => return Err(SessionError::Malformed),
+            Some(_) => grants.remove(&digest).unwrap(),
+        }
+    };
+    if grant.exp <= now_secs() {
+        return Err(SessionError::Expired);
+    }
+    let (access, claims) = issue_token(&grant.sub, &grant.role, &grant.tenant);
+    let refresh = issue_refresh_token(&claims);
+    Ok((access, claims, refresh))
+}
+
+/// Invalidate every token issued so far on `tenant`, refresh tokens included. Other campuses'
+/// sessions are untouched. Returns the campus's new epoch.
+pub fn revoke_all(tenant: &str) -> u64 {
+    let epoch = {
+        let mut epochs = TOKEN_EPOCHS.lock().unwrap();
+        let epoch = epochs.entry(tenant.to_string()).or_insert(0);
+        *epoch += 1;
+        *epoch
+    };
+    REFRESH_GRANTS.lock().unwrap().retain(|_, g| g.tenant != tenant);
+    epoch
+}
+
+pub fn revoke_session(sid: &str) {
+    REVOKED_SESSIONS.lock().unwrap().insert(sid.to_string());
+    REFRESH_GRANTS.lock().unwrap().retain(|_, g| g.sid != sid);
+}
+
+/// Start an admin cookie session. Return the jar from the handler to set the signed cookie.
+pub fn start_admin_session(admin_id: &str, tenant: &str) -> SignedCookieJar {
+    use rand::Rng;
+    let sid: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let now = now_secs();
+    let session = AdminSession { admin_id: admin_id.to_string(), tenant: tenant.to_string(), started: now, last_seen: now };
+    ADMIN_SESSIONS.lock().unwrap().insert(sid.clone(), session);
+
+    let cookie = Cookie::build((ADMIN_COOKIE, sid))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .build();
+    SignedCookieJar::new(COOKIE_KEY.clone()).add(cookie)
+}
+
+/// Session id from a correctly signed admin cookie, if the request carries one.
+pub fn admin_session_id(headers: &HeaderMap) -> Option<String> {
+    SignedCookieJar::from_headers(headers, COOKIE_KEY.clone())
+        .get(ADMIN_COOKIE)
+        .map(|c| c.value().to_string())
+        .filter(|sid| !sid.is_empty())
+}
+
+/// End the request's admin cookie session server-side, and whether it was live. Return the jar
+/// from the handler to clear the cookie in the browser.
+pub fn end_admin_session(headers: &HeaderMap) -> (bool, SignedCookieJar) {
+    let ended = admin_session_id(headers)
+        .is_some_and(|sid| ADMIN_SESSIONS.lock().unwrap().remove(&sid).is_some());
+    let jar = SignedCookieJar::from_headers(headers, COOKIE_KEY.clone())
+        .remove(Cookie::build(ADMIN_COOKIE).path("/"));
+    (ended, jar)
+}
+
+/// Check an admin cookie session is live, on this campus and not idle too long, and mark it used.
+/// Returns the admin it belongs to.
+pub fn touch_admin_session(sid: &str, tenant: &str) -> Result<String, SessionError> {
+    let mut sessions = ADMIN_SESSIONS.lock().unwrap();
+    let now = now_secs();
+    let session = sessions.get_mut(sid).ok_or(SessionError::Revoked)?;
+    if now.saturating_sub(session.last_seen) > crate::config::CONFIG.admin_idle_secs {
+        sessions.remove(sid);
+        return Err(SessionError::Expired);
+    }
+    if session.tenant != tenant {
+        return Err(SessionError::Malformed);
+    }
+    session.last_seen = now;
+    Ok(session.admin_id.clone())
+}
+
+/// Whether the request carries a live cookie session for `admin_id` on `tenant` that logged in
+/// within the last `max_age_secs`. For actions a stolen password alone mustn't be enough for.
+pub fn fresh_admin_session(headers: &HeaderMap, admin_id: &str, tenant: &str, max_age_secs: u64) -> bool {
+    let Some(sid) = admin_session_id(headers) else {
+        return false;
+    };
+    ADMIN_SESSIONS.lock().unwrap().get(&sid).is_some_and(|s| {
+        s.admin_id.eq_ignore_ascii_case(admin_id) && s.tenant == tenant && now_secs().saturating_sub(s.started) <= max_age_secs
+    })
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.trim())
+}
+
+/// The caller behind a valid `Authorization: Bearer` session token. Handlers that need a
+/// session take this as an argument; anything else (no token, bad signature, expired,
+/// revoked, other campus) is rejected with a 401 before the handler runs.
+pub struct AuthedPrincipal(pub Principal);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthedPrincipal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers)
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| ApiError::unauthorized("Session token required"))?
+            .to_string();
+        let Ok(Tenant(tenant)) = Tenant::from_request_parts(parts, state).await;
+        crate::rbac::authenticate(&Credentials::SessionToken(token), &tenant.id).map(AuthedPrincipal)
+    }
+}
+
+/// An admin, by session cookie or any other credentials carrying the `delete` permission.
+/// Admin-only handlers take this as an argument; everyone else is turned away with a 401 or
+/// 403 before the handler runs.
+pub struct AdminAuth(pub Principal);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AdminAuth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Ok(Tenant(tenant)) = Tenant::from_request_parts(parts, state).await;
+        crate::rbac::authorize(&parts.headers, &tenant.id, Permission::Delete).map(AdminAuth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // revoke_all ends every session on a campus, so these tests take turns
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn serial() -> std::sync::MutexGuard<'static, ()> {
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // A token for `claims`, signed with `key`
+    fn sign(claims: &Claims, key: &[u8]) -> String {
+        encode(&Header::new(Algorithm::HS256), claims, &EncodingKey::from_secret(key)).unwrap()
+    }
+
+    #[test]
+    fn tokens_are_checked_for_signature_and_expiry() {
+        let _serial = serial();
+        let (token, issued) = issue_token("Fac1", "FACULTY", "north");
+        let claims = validate_token(&token).unwrap();
+        assert_eq!((claims.sub.as_str(), claims.role.as_str(), claims.tenant.as_str()), ("Fac1", "FACULTY", "north"));
+        assert_eq!((claims.sid, claims.exp - claims.iat), (issued.sid.clone(), SESSION_TTL_SECS));
+
+        let expired = Claims { iat: now_secs() - 120, exp: now_secs() - 60, ..issued.clone() };
+        assert_eq!(validate_token(&sign(&expired, &JWT_SECRET)).err(), Some(SessionError::Expired));
+
+        // Someone else's key, or a payload edited after signing
+        assert_eq!(validate_token(&sign(&issued, b"not-the-server-key")).err(), Some(SessionError::Malformed));
+        let (header, rest) = token.split_once('.').unwrap();
+        let signature = rest.split_once('.').unwrap().1;
+        let admin = Claims { role: "ADMIN".to_string(), ..issued };
+        let forged_payload = sign(&admin, b"unused").split('.').nth(1).unwrap().to_string();
+        assert_eq!(validate_token(&format!("{}.{}.{}", header, forged_payload, signature)).err(), Some(SessionError::Malformed));
+        for garbage in ["", "not-a-token", "a.b.c"] {
+            assert_eq!(validate_token(garbage).err(), Some(SessionError::Malformed), "{:?}", garbage);
+        }
+    }
+
+    #[test]
+    fn revoke_all_ends_sessions_issued_before_it() {
+        let _serial = serial();
+        let (before, _) = issue_token("Fac1", "FACULTY", "default");
+        assert!(validate_token(&before).is_ok());
+
+        revoke_all("default");
+        assert_eq!(validate_token(&before).err(), Some(SessionError::Revoked));
+        let (after, _) = issue_token("Fac1", "FACULTY", "default");
+        assert!(validate_token(&after).is_ok());
+    }
+
+    #[test]
+    fn revoke_all_leaves_other_campuses_signed_in() {
+        let _serial = serial();
+        let (north, north_claims) = issue_token("Fac1", "FACULTY", "north");
+        let (here, here_claims) = issue_token("Fac1", "FACULTY", "default");
+        let north_refresh = issue_refresh_token(&north_claims);
+        let here_refresh = issue_refresh_token(&here_claims);
+
+        revoke_all("default");
+        assert_eq!(validate_token(&here).err(), Some(SessionError::Revoked));
+        assert_eq!(refresh(&here_refresh, "default").err(), Some(SessionError::Revoked));
+        assert!(validate_token(&north).is_ok());
+        assert!(refresh(&north_refresh, "north").is_ok());
+    }
+
+    #[test]
+    fn a_revoked_session_fails_alone() {
+        let _serial = serial();
+        let (kept, _) = issue_token("Fac1", "FACULTY", "default");
+        let (revoked, claims) = issue_token("Fac2", "FACULTY", "default");
+        revoke_session(&claims.sid);
+        assert_eq!(validate_token(&revoked).err(), Some(SessionError::Revoked));
+        assert!(validate_token(&kept).is_ok());
+    }
+
+    #[test]
+    fn refresh_issues_a_new_session_and_rotates_the_token() {
+        let _serial = serial();
+        let (_, claims) = issue_token("Fac1", "FACULTY", "default");
+        let refresh_token = issue_refresh_token(&claims);
+
+        let (access, fresh, rotated) = refresh(&refresh_token, "default").unwrap();
+        assert_eq!(validate_token(&access).unwrap().sub, "Fac1");
+        assert_eq!(fresh.role, "FACULTY");
+        assert_ne!(fresh.sid, claims.sid);
+        assert_ne!(rotated, refresh_token);
+    }
+
+    #[test]
+    fn a_rotated_out_refresh_token_is_refused() {
+        let _serial = serial();
+        let (_, claims) = issue_token("Fac1", "FACULTY", "default");
+        let refresh_token = issue_refresh_token(&claims);
+        let (_, _, rotated) = refresh(&refresh_token, "default").unwrap();
+
+        assert_eq!(refresh(&refresh_token, "default").err(), Some(SessionError::Revoked));
+        assert!(refresh(&rotated, "default").is_ok());
+    }
+
+    #[test]
+    fn refresh_tokens_are_revoked_with_everything_else() {
+        let _serial = serial();
+        let (_, claims) = issue_token("admin", "ADMIN", "default");
+        let refresh_token = issue_refresh_token(&claims);
+        revoke_all("default");
+        assert_eq!(refresh(&refresh_token, "default").err(), Some(SessionError::Revoked));
+
+        // Logging out ends the session's refresh token too
+        let (_, claims) = issue_token("admin", "ADMIN", "default");
+        let refresh_token = issue_refresh_token(&claims);
+        revoke_session(&claims.sid);
+        assert_eq!(refresh(&refresh_token, "default").err(), Some(SessionError::Revoked));
+    }
+
+    async fn extract<T: FromRequestParts<()>>(bearer: Option<&str>) -> Result<T, T::Rejection> {
+        let mut request = axum::http::Request::builder();
+        if let Some(token) = bearer {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        T::from_request_parts(&mut parts, &()).await
+    }
+
+    #[test]
+    fn guards_turn_away_missing_and_insufficient_credentials() {
+        let _serial = serial();
+        let student = issue_token("student", "STUDENT", crate::tenant::DEFAULT_TENANT).0;
+        let admin = issue_token(crate::admins::DEFAULT_ADMIN_ID, "ADMIN", crate::tenant::DEFAULT_TENANT).0;
+        let status = |e: ApiError| e.status.as_u16();
+
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+            assert_eq!(extract::<AuthedPrincipal>(None).await.err().map(status), Some(401));
+            assert_eq!(extract::<AuthedPrincipal>(Some("not-a-token")).await.err().map(status), Some(401));
+            let AuthedPrincipal(principal) = extract::<AuthedPrincipal>(Some(&student)).await.unwrap();
+            assert_eq!((principal.id.as_str(), principal.role.as_str()), ("student", "STUDENT"));
+
+            assert_eq!(extract::<AdminAuth>(None).await.err().map(status), Some(401));
+            assert_eq!(extract::<AdminAuth>(Some(&student)).await.err().map(status), Some(403));
+            let AdminAuth(principal) = extract::<AdminAuth>(Some(&admin)).await.unwrap();
+            assert_eq!(principal.role, "ADMIN");
+        });
+    }
+}
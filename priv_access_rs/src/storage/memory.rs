@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use axum::async_trait;
+
+use super::{now_secs, write_archive, AccessHistory, Archive, Assignment, Storage, StorageError};
+use crate::tenant::DEFAULT_TENANT;
+use crate::zkp::Proof;
+
+/// Single-instance storage: state lives in process memory, access logs are mirrored
+/// to `access_history*.json` (and stored proofs to `access_proofs*.json`) so they survive a restart.
+#[derive(Default)]
+pub struct MemoryStorage {
+    // tenant -> history, loaded from disk on first use
+    logs: Mutex<HashMap<String, Vec<AccessHistory>>>,
+    // tenant -> log id -> proof, loaded the same way
+    proofs: Mutex<HashMap<String, HashMap<u64, Proof>>>,
+    assignments: Mutex<HashMap<String, HashMap<String, Assignment>>>,
+    // (tenant, nonce) -> expiry
+    nonces: Mutex<HashMap<(String, String), u64>>,
+    // (tenant, challenge nonce) -> (door, expiry)
+    challenges: Mutex<HashMap<(String, String), (String, u64)>>,
+}
+
+// The default campus keeps the original file name so existing history carries over
+fn history_path(tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        "access_history.json".to_string()
+    } else {
+        format!("access_history_{}.json", tenant)
+    }
+}
+
+fn proofs_path(tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        "access_proofs.json".to_string()
+    } else {
+        format!("access_proofs_{}.json", tenant)
+    }
+}
+
+fn load_proofs(path: &str) -> HashMap<u64, Proof> {
+    fs::read_to_string(path).ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_proofs(path: &str, proofs: &HashMap<u64, Proof>) {
+    if let Ok(content) = serde_json::to_string_pretty(proofs) {
+        let _ = fs::write(path, content);
+    }
+}
+
+fn load_history(path: &str) -> Vec<AccessHistory> {
+    if let Ok(content) = fs::read_to_string(path) {
+        if let Ok(logs) = serde_json::from_str(&content) {
+            return logs;
+        }
+    }
+    Vec::new()
+}
+
+fn save_history(path: &str, logs: &Vec<AccessHistory>) {
+    if let Ok(content) = serde_json::to_string_pretty(logs) {
+        let _ = fs::write(path, content);
+    }
+}
+
+// Like save_history, but reports failure; used for the final write on shutdown
+fn write_file(path: &str, content: serde_json::Result<String>) -> Result<(), StorageError> {
+    let content = content.map_err(|e| StorageError(format!("{}: {}", path, e)))?;
+    fs::write(path, content).map_err(|e| StorageError(format!("{}: {}", path, e)))
+}
+
+impl MemoryStorage {
+    fn with_logs<T>(&self, tenant: &str, f: impl FnOnce(&mut Vec<AccessHistory>) -> T) -> T {
+        let mut all = self.logs.lock().unwrap();
+        let logs = all.entry(tenant.to_string()).or_insert_with(|| load_history(&history_path(tenant)));
+        f(logs)
+    }
+
+    fn with_proofs<T>(&self, tenant: &str, f: impl FnOnce(&mut HashMap<u64, Proof>) -> T) -> T {
+        let mut all = self.proofs.lock().unwrap();
+        let proofs = all.entry(tenant.to_string()).or_insert_with(|| load_proofs(&proofs_path(tenant)));
+        f(proofs)
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn append_log(&self, tenant: &str, mut entry: AccessHistory) -> Result<u64, StorageError> {
+        let (id, full) = self.with_logs(tenant, |logs| {
+            entry.id = logs.iter().map(|l| l.id).max().unwrap_or(0) + 1;
+            let id = entry.id;
+            logs.push(entry);
+            save_history(&history_path(tenant), logs);
+            let max = crate::config::CONFIG.max_memory_logs;
+            (id, max > 0 && logs.len() > max)
+        });
+        // Everything here lives in process memory, so past the cap the history is rotated out
+        if full {
+            match self.archive_logs(tenant).await {
+                Ok(archive) => tracing::info!(%tenant, entries = archive.count, path = %archive.path, "Access log over the in-memory limit, rotated"),
+                Err(e) => tracing::warn!(%tenant, error = %e.0, "Failed to rotate full access log"),
+            }
+        }
+        Ok(id)
+    }
+
+    async fn logs(&self, tenant: &str) -> Result<Vec<AccessHistory>, StorageError> {
+        Ok(self.with_logs(tenant, |logs| logs.clone()))
+    }
+
+    async fn clear_logs(&self, tenant: &str) -> Result<usize, StorageError> {
+        let count = self.with_logs(tenant, |logs| {
+            let count = logs.len();
+            logs.clear();
+            save_history(&history_path(tenant), logs);
+            count
+        });
+        self.with_proofs(tenant, |proofs| {
+            proofs.clear();
+            save_proofs(&proofs_path(tenant), proofs);
+        });
+        Ok(count)
+    }
+
+    async fn archive_logs(&self, tenant: &str) -> Result<Archive, StorageError> {
+        // Both locks held throughout, so no entry or proof lands between the write and the clear
+        let mut all_logs = self.logs.lock().unwrap();
+        let mut all_proofs = self.proofs.lock().unwrap();
+        let logs = all_logs.entry(tenant.to_string()).or_insert_with(|| load_history(&history_path(tenant)));
+        let proofs = all_proofs.entry(tenant.to_string()).or_insert_with(|| load_proofs(&proofs_path(tenant)));
+        let path = write_archive(tenant, logs, proofs)?;
+        let count = logs.len();
+        logs.clear();
+        proofs.clear();
+        save_history(&history_path(tenant), logs);
+        save_proofs(&proofs_path(tenant), proofs);
+        Ok(Archive { count, path })
+    }
+
+    async fn save_proof(&self, tenant: &str, log_id: u64, proof: &Proof) -> Result<(), StorageError> {
+        self.with_proofs(tenant, |proofs| {
+            proofs.insert(log_id, proof.clone());
+            save_proofs(&proofs_path(tenant), proofs);
+        });
+        Ok(())
+    }
+
+    async fn proof(&self, tenant: &str, log_id: u64) -> Result<Option<Proof>, StorageError> {
+        Ok(self.with_proofs(tenant, |proofs| proofs.get(&log_id).cloned()))
+    }
+
+    async fn assignment(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError> {
+        let map = self.assignments.lock().unwrap();
+        Ok(map.get(tenant).and_then(|m| m.get(section)).cloned())
+    }
+
+    async fn assign(&self, tenant: &str, section: &str, assignment: Assignment) -> Result<Option<Assignment>, StorageError> {
+        let mut map = self.assignments.lock().unwrap();
+        Ok(map.entry(tenant.to_string()).or_default().insert(section.to_string(), assignment))
+    }
+
+    async fn unassign(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError> {
+        let mut map = self.assignments.lock().unwrap();
+        Ok(map.get_mut(tenant).and_then(|m| m.remove(section)))
+    }
+
+    async fn room_sections(&self, tenant: &str, room_id: &str) -> Result<Vec<(String, String)>, StorageError> {
+        let map = self.assignments.lock().unwrap();
+        let mut sections: Vec<(String, String)> = map.get(tenant)
+            .into_iter()
+            .flatten()
+            .filter(|(_, (room, _))| room == room_id)
+            .map(|(section, (_, faculty))| (section.clone(), faculty.clone()))
+            .collect();
+        sections.sort();
+        Ok(sections)
+    }
+
+    async fn unassign_room(&self, tenant: &str, room_id: &str) -> Result<Vec<String>, StorageError> {
+        let mut map = self.assignments.lock().unwrap();
+        let Some(sections) = map.get_mut(tenant) else {
+            return Ok(Vec::new());
+        };
+        let orphaned: Vec<String> = sections.iter()
+            .filter(|(_, (room, _))| room == room_id)
+            .map(|(section, _)| section.clone())
+            .collect();
+        for section in &orphaned {
+            sections.remove(section);
+        }
+        Ok(orphaned)
+    }
+
+    async fn claim_nonce(&self, tenant: &str, nonce: &str, ttl_secs: u64) -> Result<bool, StorageError> {
+        let now = now_secs();
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.retain(|_, expiry| *expiry > now);
+        let key = (tenant.to_string(), nonce.to_string());
+        if nonces.contains_key(&key) {
+            return Ok(false);
+        }
+        nonces.insert(key, now + ttl_secs);
+        Ok(true)
+    }
+
+    async fn issue_challenge(&self, tenant: &str, door_id: &str, nonce: &str, ttl_secs: u64) -> Result<(), StorageError> {
+        let now = now_secs();
+        let mut challenges = self.challenges.lock().unwrap();
+        challenges.retain(|_, (_, expiry)| *expiry > now);
+        challenges.insert((tenant.to_string(), nonce.to_string()), (door_id.to_string(), now + ttl_secs));
+        Ok(())
+    }
+
+    async fn take_challenge(&self, tenant: &str, nonce: &str) -> Result<Option<String>, StorageError> {
+        let removed = self.challenges.lock().unwrap().remove(&(tenant.to_string(), nonce.to_string()));
+        Ok(removed.filter(|(_, expiry)| *expiry > now_secs()).map(|(door_id, _)| door_id))
+    }
+
+    // Every change is already written through, so this only retries the writes once more in
+    // case an earlier one failed silently
+    async fn close(&self) -> Result<(), StorageError> {
+        for (tenant, logs) in self.logs.lock().unwrap().iter() {
+            write_file(&history_path(tenant), serde_json::to_string_pretty(logs))?;
+        }
+        for (tenant, proofs) in self.proofs.lock().unwrap().iter() {
+            write_file(&proofs_path(tenant), serde_json::to_string_pretty(proofs))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ApiError;
+use crate::metrics::DenialReason;
+use crate::zkp::Proof;
+
+mod memory;
+mod postgres_store;
+mod redis_store;
+mod sqlite_store;
+
+pub use memory::MemoryStorage;
+pub use postgres_store::PostgresStorage;
+pub use redis_store::RedisStorage;
+pub use sqlite_store::SqliteStorage;
+
+/// One access decision as it appears in the history.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccessHistory {
+    // Assigned by storage on append; entries written before ids existed read back as 0
+    #[serde(default)]
+    pub id: u64,
+    pub role: String,
+    pub door_name: String,
+    pub section: String,
+    pub timestamp: String,
+    pub status: String,
+    pub faculty_name: Option<String>,
+    pub faculty_id: Option<String>,
+    /// The authenticated account behind an attempt: the admin or faculty id, so remote unlocks
+    /// can be told apart. Set on grants, and on denials that came after the credentials checked
+    /// out. None for roles without accounts and for entries from before it existed;
+    /// left out of the JSON then, so those entries keep their Merkle leaf hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<String>,
+    /// Why a denied entry was denied; `status` keeps the message the client saw. None for grants
+    /// and older entries, and left out of the JSON the same way as `actor_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denial_reason: Option<DenialReason>,
+}
+
+/// Section -> (RoomID, FacultyName), same shape the handlers have always used
+pub type Assignment = (String, String);
+
+#[derive(Debug)]
+pub struct StorageError(pub String);
+
+/// A tenant's history moved out of live storage by `archive_logs`.
+#[derive(Debug, Clone)]
+pub struct Archive {
+    /// How many entries were archived
+    pub count: usize,
+    /// The JSON file they were written to
+    pub path: String,
+}
+
+// Layout of an archive file: the entries oldest first, and any proofs kept with them by log id
+#[derive(Serialize)]
+struct ArchiveFile<'a> {
+    tenant: &'a str,
+    archived_at: String,
+    entries: &'a [AccessHistory],
+    proofs: &'a HashMap<u64, Proof>,
+}
+
+/// Write `entries` to a new `access_archive_<tenant>_<UTC time>.json` next to the live history.
+/// Backends call this before dropping the entries, so a failed write leaves them in place.
+pub(crate) fn write_archive(tenant: &str, entries: &[AccessHistory], proofs: &HashMap<u64, Proof>) -> Result<String, StorageError> {
+    let now = chrono::Utc::now();
+    let path = format!("access_archive_{}_{}.json", tenant, now.format("%Y%m%dT%H%M%S%.3fZ"));
+    let file = ArchiveFile { tenant, archived_at: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true), entries, proofs };
+    let content = serde_json::to_string_pretty(&file).map_err(|e| StorageError(format!("{}: {}", path, e)))?;
+    std::fs::write(&path, content).map_err(|e| StorageError(format!("{}: {}", path, e)))?;
+    Ok(path)
+}
+
+impl From<redis::RedisError> for StorageError {
+    fn from(e: redis::RedisError) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(e: sqlx::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(e: StorageError) -> Self {
+        tracing::warn!(error = %e.0, "Storage unavailable");
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Access state unavailable, try again shortly")
+    }
+}
+
+/// Narrows a history query. Every field is optional; `since` is inclusive and `until` exclusive,
+/// both compared as text against the RFC 3339 timestamps (so `2026-03-02` works as a day bound).
+#[derive(Debug, Default, Clone)]
+pub struct LogFilter {
+    pub role: Option<String>,
+    pub door_name: Option<String>,
+    /// true: only GRANTED entries, false: only DENIED ones
+    pub granted: Option<bool>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Keep only the most recent `limit` matches
+    pub limit: Option<usize>,
+    /// Skip this many of the most recent matches first, to page back through history
+    pub offset: usize,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &AccessHistory) -> bool {
+        self.role.as_ref().is_none_or(|r| &entry.role == r)
+            && self.door_name.as_ref().is_none_or(|d| &entry.door_name == d)
+            && self.granted.is_none_or(|g| if g { entry.status == "GRANTED" } else { entry.status.starts_with("DENIED") })
+            && self.since.as_ref().is_none_or(|s| entry.timestamp.as_str() >= s.as_str())
+            && self.until.as_ref().is_none_or(|u| entry.timestamp.as_str() < u.as_str())
+    }
+
+    // Drop the newest `offset` entries of an oldest-first list, then all but the newest `limit`
+    fn truncate(&self, mut entries: Vec<AccessHistory>) -> Vec<AccessHistory> {
+        entries.truncate(entries.len().saturating_sub(self.offset));
+        if let Some(limit) = self.limit {
+            let excess = entries.len().saturating_sub(limit);
+            entries.drain(..excess);
+        }
+        entries
+    }
+}
+
+/// Everything the server persists: access logs (with their proofs), section assignments and the nonce
+/// registry. All keys are scoped by tenant id. The backend is picked once at startup.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    // --- Access logs ---
+
+    /// Append an entry, returning the id it was stored under.
+    async fn append_log(&self, tenant: &str, entry: AccessHistory) -> Result<u64, StorageError>;
+
+    /// Full history for a tenant, oldest first.
+    async fn logs(&self, tenant: &str) -> Result<Vec<AccessHistory>, StorageError>;
+
+    /// History entries matching `filter`, oldest first. Backends that can push the filter
+    /// down to a query should override this.
+    async fn query_logs(&self, tenant: &str, filter: &LogFilter) -> Result<Vec<AccessHistory>, StorageError> {
+        let matching = self.logs(tenant).await?.into_iter().filter(|e| filter.matches(e)).collect();
+        Ok(filter.truncate(matching))
+    }
+
+    /// How many entries match `filter`, ignoring its `limit` and `offset`.
+    async fn count_logs(&self, tenant: &str, filter: &LogFilter) -> Result<usize, StorageError> {
+        Ok(self.logs(tenant).await?.iter().filter(|e| filter.matches(e)).count())
+    }
+
+    /// Delete a tenant's history and any proofs stored with it, returning how many entries were removed.
+    async fn clear_logs(&self, tenant: &str) -> Result<usize, StorageError>;
+
+    /// Move a tenant's whole history, with its proofs, into an archive file and empty the live log.
+    /// Nothing is removed unless the file was written.
+    async fn archive_logs(&self, tenant: &str) -> Result<Archive, StorageError>;
+
+    /// Keep the proof behind an accepted access so it can be re-verified later.
+    async fn save_proof(&self, tenant: &str, log_id: u64, proof: &Proof) -> Result<(), StorageError>;
+
+    async fn proof(&self, tenant: &str, log_id: u64) -> Result<Option<Proof>, StorageError>;
+
+    // --- Section assignments ---
+
+    async fn assignment(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError>;
+
+    /// Point a section at a room, returning what it was assigned to before.
+    async fn assign(&self, tenant: &str, section: &str, assignment: Assignment) -> Result<Option<Assignment>, StorageError>;
+
+    /// Remove one section's assignment, returning what it was.
+    async fn unassign(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError>;
+
+    /// Every section assigned to `room_id` with the faculty who assigned it, ordered by section.
+    /// Several sections can share a room, e.g. for a combined lecture.
+    async fn room_sections(&self, tenant: &str, room_id: &str) -> Result<Vec<(String, String)>, StorageError>;
+
+    /// Drop every section assigned to `room_id`, returning the sections that were removed.
+    async fn unassign_room(&self, tenant: &str, room_id: &str) -> Result<Vec<String>, StorageError>;
+
+    // --- Registry ---
+
+    /// Record a QR nonce as spent. Returns false if it was already used within `ttl_secs`.
+    async fn claim_nonce(&self, tenant: &str, nonce: &str, ttl_secs: u64) -> Result<bool, StorageError>;
+
+    /// Remember a proof challenge nonce issued for `door_id` for `ttl_secs`.
+    async fn issue_challenge(&self, tenant: &str, door_id: &str, nonce: &str, ttl_secs: u64) -> Result<(), StorageError>;
+
+    /// Consume a challenge nonce, returning the door it was issued for. None if it was never
+    /// issued, has expired or was already used.
+    async fn take_challenge(&self, tenant: &str, nonce: &str) -> Result<Option<String>, StorageError>;
+
+    // --- Lifecycle ---
+
+    /// Write out anything still pending and release connections. Called once on shutdown,
+    /// after the last request has finished.
+    async fn close(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// Pick the backend from config: PRIVACCESS_DATABASE_URL (Postgres or SQLite, by scheme) wins,
+/// then PRIVACCESS_REDIS_URL, otherwise everything stays in memory.
+pub async fn from_config(config: &crate::config::Config) -> Result<std::sync::Arc<dyn Storage>, StorageError> {
+    if let Some(url) = &config.database_url {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Ok(std::sync::Arc::new(PostgresStorage::connect(url).await?));
+        }
+        return Ok(std::sync::Arc::new(SqliteStorage::connect(url).await?));
+    }
+    if let Some(url) = &config.redis_url {
+        return Ok(std::sync::Arc::new(RedisStorage::connect(url).await?));
+    }
+    Ok(std::sync::Arc::new(MemoryStorage::default()))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{postgres_url, redis_url};
+
+    // A tenant of its own per test, so runs against a shared server don't see each other's state
+    fn scratch_tenant(name: &str) -> String {
+        format!("test-{}-{}", name, rand::random::<u32>())
+    }
+
+    fn room(id: &str, faculty: &str) -> Assignment {
+        (id.to_string(), faculty.to_string())
+    }
+
+    fn entry(role: &str, status: &str, timestamp: &str) -> AccessHistory {
+        AccessHistory {
+            id: 0,
+            role: role.to_string(),
+            door_name: "Lab".to_string(),
+            section: "A".to_string(),
+            timestamp: timestamp.to_string(),
+            status: status.to_string(),
+            faculty_name: None,
+            faculty_id: None,
+            actor_id: None,
+            denial_reason: None,
+        }
+    }
+
+    // What every backend must do the same way
+    async fn behaves_like_storage(store: &dyn Storage) {
+        let tenant = scratch_tenant("suite");
+        let other = scratch_tenant("suite-other");
+
+        let first = store.append_log(&tenant, entry("STUDENT", "GRANTED", "2026-03-01T09:00:00Z")).await.unwrap();
+        let second = store.append_log(&tenant, entry("STUDENT", "DENIED: Wrong Room", "2026-03-02T09:00:00Z")).await.unwrap();
+        let third = store.append_log(&tenant, entry("FACULTY", "GRANTED", "2026-03-03T09:00:00Z")).await.unwrap();
+        assert!(first < second && second < third);
+        let logs = store.logs(&tenant).await.unwrap();
+        assert_eq!(logs.iter().map(|l| l.id).collect::<Vec<_>>(), [first, second, third]);
+        assert!(store.logs(&other).await.unwrap().is_empty());
+
+        let students = LogFilter { role: Some("STUDENT".to_string()), ..LogFilter::default() };
+        assert_eq!(store.count_logs(&tenant, &students).await.unwrap(), 2);
+        let denied = LogFilter { granted: Some(false), ..LogFilter::default() };
+        assert_eq!(store.query_logs(&tenant, &denied).await.unwrap().iter().map(|l| l.id).collect::<Vec<_>>(), [second]);
+        let window = LogFilter { since: Some("2026-03-02".to_string()), until: Some("2026-03-03".to_string()), ..LogFilter::default() };
+        assert_eq!(store.count_logs(&tenant, &window).await.unwrap(), 1);
+        // The page before the newest entry
+        let page = LogFilter { limit: Some(1), offset: 1, ..LogFilter::default() };
+        assert_eq!(store.query_logs(&tenant, &page).await.unwrap().iter().map(|l| l.id).collect::<Vec<_>>(), [second]);
+        assert_eq!(store.count_logs(&tenant, &page).await.unwrap(), 3);
+
+        let proof = crate::zkp::SchnorrProver::new(42u32.into()).generate_proof("t1q7hk9vj".to_string(), "nonce");
+        store.save_proof(&tenant, first, &proof).await.unwrap();
+        assert_eq!(store.proof(&tenant, first).await.unwrap().map(|p| p.response), Some(proof.response));
+        assert!(store.proof(&tenant, second).await.unwrap().is_none());
+
+        store.assign(&tenant, "B", room("lab-1", "Dr. One")).await.unwrap();
+        store.assign(&tenant, "A", room("lab-1", "Dr. Two")).await.unwrap();
+        store.assign(&tenant, "C", room("lab-2", "Dr. One")).await.unwrap();
+        let sections = store.room_sections(&tenant, "lab-1").await.unwrap();
+        assert_eq!(sections, [("A".to_string(), "Dr. Two".to_string()), ("B".to_string(), "Dr. One".to_string())]);
+        assert!(store.assignment(&other, "A").await.unwrap().is_none());
+        let mut removed = store.unassign_room(&tenant, "lab-1").await.unwrap();
+        removed.sort();
+        assert_eq!(removed, ["A", "B"]);
+        assert_eq!(store.assignment(&tenant, "C").await.unwrap(), Some(room("lab-2", "Dr. One")));
+
+        assert!(store.claim_nonce(&tenant, "qr-1", 60).await.unwrap());
+        assert!(!store.claim_nonce(&tenant, "qr-1", 60).await.unwrap());
+        assert!(store.claim_nonce(&other, "qr-1", 60).await.unwrap());
+        store.issue_challenge(&tenant, "lab-1", "challenge-1", 60).await.unwrap();
+        assert_eq!(store.take_challenge(&other, "challenge-1").await.unwrap(), None);
+        assert_eq!(store.take_challenge(&tenant, "challenge-1").await.unwrap().as_deref(), Some("lab-1"));
+        assert_eq!(store.take_challenge(&tenant, "challenge-1").await.unwrap(), None);
+
+        assert_eq!(store.clear_logs(&tenant).await.unwrap(), 3);
+        assert!(store.logs(&tenant).await.unwrap().is_empty());
+        assert!(store.proof(&tenant, first).await.unwrap().is_none());
+    }
+
+    // A SQLite file of its own, removed with its WAL files afterwards
+    struct ScratchDb(std::path::PathBuf);
+
+    impl ScratchDb {
+        fn new(name: &str) -> Self {
+            ScratchDb(std::env::temp_dir().join(format!("privaccess-{}-{}.db", name, rand::random::<u32>())))
+        }
+
+        fn url(&self) -> String {
+            format!("sqlite://{}", self.0.display())
+        }
+    }
+
+    impl Drop for ScratchDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", self.0.display(), suffix));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_storage_passes_the_suite() {
+        let store = MemoryStorage::default();
+        behaves_like_storage(&store).await;
+        // It mirrors each tenant's history to the working directory
+        for file in std::fs::read_dir(".").unwrap().flatten() {
+            let name = file.file_name().to_string_lossy().into_owned();
+            if name.starts_with("access_history_test-suite") || name.starts_with("access_proofs_test-suite") {
+                let _ = std::fs::remove_file(file.path());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sqlite_storage_passes_the_suite() {
+        let db = ScratchDb::new("suite");
+        behaves_like_storage(&SqliteStorage::connect(&db.url()).await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_connections_to_one_file_share_state() {
+        let db = ScratchDb::new("shared");
+        let a = SqliteStorage::connect(&db.url()).await.unwrap();
+        let b = SqliteStorage::connect(&db.url()).await.unwrap();
+        shared_between(&a, &b).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres server at PRIVACCESS_TEST_POSTGRES_URL"]
+    async fn postgres_storage_passes_the_suite() {
+        let url = postgres_url();
+        behaves_like_storage(&PostgresStorage::connect(&url).await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres server at PRIVACCESS_TEST_POSTGRES_URL"]
+    async fn postgres_replicas_share_assignments_and_nonces() {
+        let url = postgres_url();
+        let a = PostgresStorage::connect(&url).await.unwrap();
+        let b = PostgresStorage::connect(&url).await.unwrap();
+        shared_between(&a, &b).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at PRIVACCESS_TEST_REDIS_URL"]
+    async fn redis_storage_passes_the_suite() {
+        let url = redis_url();
+        behaves_like_storage(&RedisStorage::connect(&url).await.unwrap()).await;
+    }
+
+    // What one replica writes, another reads: assignments, spent QR nonces and challenges
+    async fn shared_between(a: &dyn Storage, b: &dyn Storage) {
+        let tenant = scratch_tenant("shared");
+        assert_eq!(a.assign(&tenant, "A", room("lab-1", "Dr. One")).await.unwrap(), None);
+        assert_eq!(b.assignment(&tenant, "A").await.unwrap(), Some(room("lab-1", "Dr. One")));
+        assert_eq!(b.assign(&tenant, "A", room("lab-2", "Dr. Two")).await.unwrap(), Some(room("lab-1", "Dr. One")));
+        assert_eq!(a.assignment(&tenant, "A").await.unwrap(), Some(room("lab-2", "Dr. Two")));
+        assert_eq!(a.unassign(&tenant, "A").await.unwrap(), Some(room("lab-2", "Dr. Two")));
+        assert_eq!(b.assignment(&tenant, "A").await.unwrap(), None);
+
+        assert!(a.claim_nonce(&tenant, "qr-1", 60).await.unwrap());
+        assert!(!b.claim_nonce(&tenant, "qr-1", 60).await.unwrap());
+
+        a.issue_challenge(&tenant, "lab-1", "challenge-1", 60).await.unwrap();
+        assert_eq!(b.take_challenge(&tenant, "challenge-1").await.unwrap().as_deref(), Some("lab-1"));
+        assert_eq!(a.take_challenge(&tenant, "challenge-1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at PRIVACCESS_TEST_REDIS_URL"]
+    async fn redis_replicas_share_assignments_and_nonces() {
+        let url = redis_url();
+        let a = RedisStorage::connect(&url).await.unwrap();
+        let b = RedisStorage::connect(&url).await.unwrap();
+        shared_between(&a, &b).await;
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+
+use super::{write_archive, AccessHistory, Archive, Assignment, Storage, StorageError};
+use crate::zkp::Proof;
+
+/// Storage shared between replicas through Redis. Keys are prefixed `privaccess:<tenant>:`.
+pub struct RedisStorage {
+    conn: redis::aio::MultiplexedConnection,
+}
+
+impl RedisStorage {
+    pub async fn connect(url: &str) -> Result<Self, StorageError> {
+        let client = redis::Client::open(url)?;
+        let conn = client.get_multiplexed_async_connection().await?;
+        Ok(RedisStorage { conn })
+    }
+}
+
+fn logs_key(tenant: &str) -> String {
+    format!("privaccess:{}:logs", tenant)
+}
+
+// Log ids come from this counter, which survives clearing the log so ids are never reused
+fn log_seq_key(tenant: &str) -> String {
+    format!("privaccess:{}:log_seq", tenant)
+}
+
+fn proofs_key(tenant: &str) -> String {
+    format!("privaccess:{}:proofs", tenant)
+}
+
+fn assignments_key(tenant: &str) -> String {
+    format!("privaccess:{}:assignments", tenant)
+}
+
+fn decode_assignment(raw: Option<String>) -> Option<Assignment> {
+    raw.and_then(|s| serde_json::from_str(&s).ok())
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn append_log(&self, tenant: &str, mut entry: AccessHistory) -> Result<u64, StorageError> {
+        let mut conn = self.conn.clone();
+        entry.id = redis::cmd("INCR").arg(log_seq_key(tenant)).query_async(&mut conn).await?;
+        let value = serde_json::to_string(&entry).expect("log entry serializes");
+        let _: i64 = redis::cmd("RPUSH").arg(logs_key(tenant)).arg(value).query_async(&mut conn).await?;
+        Ok(entry.id)
+    }
+
+    async fn logs(&self, tenant: &str) -> Result<Vec<AccessHistory>, StorageError> {
+        let mut conn = self.conn.clone();
+        let raw: Vec<String> = redis::cmd("LRANGE").arg(logs_key(tenant)).arg(0).arg(-1).query_async(&mut conn).await?;
+        Ok(raw.iter().filter_map(|s| serde_json::from_str(s).ok()).collect())
+    }
+
+    async fn clear_logs(&self, tenant: &str) -> Result<usize, StorageError> {
+        let mut conn = self.conn.clone();
+        let key = logs_key(tenant);
+        let (count, _): (usize, i64) = redis::pipe()
+            .atomic()
+            .cmd("LLEN").arg(&key)
+            .cmd("DEL").arg(&key).arg(proofs_key(tenant))
+            .query_async(&mut conn)
+            .await?;
+        Ok(count)
+    }
+
+    async fn archive_logs(&self, tenant: &str) -> Result<Archive, StorageError> {
+        let mut conn = self.conn.clone();
+        let key = logs_key(tenant);
+        let (raw_entries, raw_proofs): (Vec<String>, HashMap<u64, String>) = redis::pipe()
+            .atomic()
+            .cmd("LRANGE").arg(&key).arg(0).arg(-1)
+            .cmd("HGETALL").arg(proofs_key(tenant))
+            .query_async(&mut conn)
+            .await?;
+        let entries: Vec<AccessHistory> = raw_entries.iter().filter_map(|s| serde_json::from_str(s).ok()).collect();
+        let proofs: HashMap<u64, Proof> = raw_proofs.iter()
+            .filter_map(|(id, s)| serde_json::from_str(s).ok().map(|p| (*id, p)))
+            .collect();
+        let path = write_archive(tenant, &entries, &proofs)?;
+
+        // Entries appended meanwhile went on the tail, so trimming the archived head keeps them
+        let mut trim = redis::pipe();
+        trim.atomic().cmd("LTRIM").arg(&key).arg(raw_entries.len()).arg(-1).ignore();
+        if !raw_proofs.is_empty() {
+            trim.cmd("HDEL").arg(proofs_key(tenant)).arg(raw_proofs.keys().collect::<Vec<_>>()).ignore();
+        }
+        let _: () = trim.query_async(&mut conn).await?;
+        Ok(Archive { count: entries.len(), path })
+    }
+
+    async fn save_proof(&self, tenant: &str, log_id: u64, proof: &Proof) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        let value = serde_json::to_string(proof).expect("proof serializes");
+        let _: i64 = redis::cmd("HSET").arg(proofs_key(tenant)).arg(log_id).arg(value).query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn proof(&self, tenant: &str, log_id: u64) -> Result<Option<Proof>, StorageError> {
+        let mut conn = self.conn.clone();
+        let raw: Option<String> = redis::cmd("HGET").arg(proofs_key(tenant)).arg(log_id).query_async(&mut conn).await?;
+        Ok(raw.and_then(|s| serde_json::from_str(&s).ok()))
+    }
+
+    async fn assignment(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError> {
+        let mut conn = self.conn.clone();
+        let raw: Option<String> = redis::cmd("HGET").arg(assignments_key(tenant)).arg(section).query_async(&mut conn).await?;
+        Ok(decode_assignment(raw))
+    }
+
+    async fn assign(&self, tenant: &str, section: &str, assignment: Assignment) -> Result<Option<Assignment>, StorageError> {
+        let mut conn = self.conn.clone();
+        let key = assignments_key(tenant);
+        let value = serde_json::to_string(&assignment).expect("assignment serializes");
+        // MULTI so the old value we report is the one we actually replaced
+        let (previous, _): (Option<String>, i64) = redis::pipe()
+            .atomic()
+            .cmd("HGET").arg(&key).arg(section)
+            .cmd("HSET").arg(&key).arg(section).arg(value)
+            .query_async(&mut conn)
+            .await?;
+        Ok(decode_assignment(previous))
+    }
+
+    async fn unassign(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError> {
+        let mut conn = self.conn.clone();
+        let key = assignments_key(tenant);
+        let (previous, _): (Option<String>, i64) = redis::pipe()
+            .atomic()
+            .cmd("HGET").arg(&key).arg(section)
+            .cmd("HDEL").arg(&key).arg(section)
+            .query_async(&mut conn)
+            .await?;
+        Ok(decode_assignment(previous))
+    }
+
+    async fn room_sections(&self, tenant: &str, room_id: &str) -> Result<Vec<(String, String)>, StorageError> {
+        let mut conn = self.conn.clone();
+        let all: HashMap<String, String> = redis::cmd("HGETALL").arg(assignments_key(tenant)).query_async(&mut conn).await?;
+        let mut sections: Vec<(String, String)> = all.into_iter()
+            .filter_map(|(section, raw)| match decode_assignment(Some(raw)) {
+                Some((room, faculty)) if room == room_id => Some((section, faculty)),
+                _ => None,
+            })
+            .collect();
+        sections.sort();
+        Ok(sections)
+    }
+
+    async fn unassign_room(&self, tenant: &str, room_id: &str) -> Result<Vec<String>, StorageError> {
+        let mut conn = self.conn.clone();
+        let key = assignments_key(tenant);
+        let all: HashMap<String, String> = redis::cmd("HGETALL").arg(&key).query_async(&mut conn).await?;
+        let orphaned: Vec<String> = all.into_iter()
+            .filter(|(_, raw)| decode_assignment(Some(raw.clone())).is_some_and(|(room, _)| room == room_id))
+            .map(|(section, _)| section)
+            .collect();
+        if !orphaned.is_empty() {
+            let _: i64 = redis::cmd("HDEL").arg(&key).arg(&orphaned).query_async(&mut conn).await?;
+        }
+        Ok(orphaned)
+    }
+
+    async fn claim_nonce(&self, tenant: &str, nonce: &str, ttl_secs: u64) -> Result<bool, StorageError> {
+        let mut conn = self.conn.clone();
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(format!("privaccess:{}:nonce:{}", tenant, nonce))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut conn)
+            .await?;
+        Ok(claimed.is_some())
+    }
+
+    async fn issue_challenge(&self, tenant: &str, door_id: &str, nonce: &str, ttl_secs: u64) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        redis::cmd("SET")
+            .arg(format!("privaccess:{}:challenge:{}", tenant, nonce))
+            .arg(door_id)
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn take_challenge(&self, tenant: &str, nonce: &str) -> Result<Option<String>, StorageError> {
+        // GETDEL makes the read and the delete one step, so two replicas can't both consume it
+        let mut conn = self.conn.clone();
+        let door_id: Option<String> = redis::cmd("GETDEL")
+            .arg(format!("privaccess:{}:challenge:{}", tenant, nonce))
+            .query_async(&mut conn)
+            .await?;
+        Ok(door_id)
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::async_trait;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{QueryBuilder, Row};
+
+use super::{now_secs, write_archive, AccessHistory, Archive, Assignment, LogFilter, Storage, StorageError};
+use crate::zkp::Proof;
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS access_logs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        tenant TEXT NOT NULL,
+        role TEXT NOT NULL,
+        door_name TEXT NOT NULL,
+        section TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        status TEXT NOT NULL,
+        faculty_name TEXT,
+        faculty_id TEXT,
+        actor_id TEXT,
+        denial_reason TEXT
+    )",
+    "CREATE INDEX IF NOT EXISTS access_logs_tenant ON access_logs (tenant, id)",
+    // Proofs behind accepted accesses, kept for audit re-verification
+    "CREATE TABLE IF NOT EXISTS access_proofs (
+        tenant TEXT NOT NULL,
+        log_id INTEGER NOT NULL,
+        proof TEXT NOT NULL,
+        PRIMARY KEY (tenant, log_id)
+    )",
+    "CREATE TABLE IF NOT EXISTS assignments (
+        tenant TEXT NOT NULL,
+        section TEXT NOT NULL,
+        room_id TEXT NOT NULL,
+        faculty_name TEXT NOT NULL,
+        PRIMARY KEY (tenant, section)
+    )",
+    "CREATE INDEX IF NOT EXISTS assignments_room ON assignments (tenant, room_id)",
+    "CREATE TABLE IF NOT EXISTS used_nonces (
+        tenant TEXT NOT NULL,
+        nonce TEXT NOT NULL,
+        expires_at INTEGER NOT NULL,
+        PRIMARY KEY (tenant, nonce)
+    )",
+    "CREATE TABLE IF NOT EXISTS challenges (
+        tenant TEXT NOT NULL,
+        nonce TEXT NOT NULL,
+        door_id TEXT NOT NULL,
+        expires_at INTEGER NOT NULL,
+        PRIMARY KEY (tenant, nonce)
+    )",
+];
+
+/// Durable storage in a SQLite file, e.g. `sqlite://privaccess.db`.
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(url: &str) -> Result<Self, StorageError> {
+        // WAL lets door handlers read while another writes; concurrent writers queue on the
+        // busy timeout instead of failing with SQLITE_BUSY
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(5));
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+        // Tables created before logs recorded who acted and why they were denied; SQLite has no
+        // ADD COLUMN IF NOT EXISTS
+        let columns = sqlx::query("SELECT name FROM pragma_table_info('access_logs')").fetch_all(&pool).await?;
+        for column in ["actor_id", "denial_reason"] {
+            if !columns.iter().any(|c| c.get::<String, _>("name") == column) {
+                sqlx::query(&format!("ALTER TABLE access_logs ADD COLUMN {} TEXT", column)).execute(&pool).await?;
+            }
+        }
+        Ok(SqliteStorage { pool })
+    }
+}
+
+// `select` over a tenant's log entries matching `filter`, for query_logs and count_logs
+fn filtered_logs<'a>(select: &str, tenant: &'a str, filter: &'a LogFilter) -> QueryBuilder<'a, Sqlite> {
+    let mut query = QueryBuilder::<Sqlite>::new(select);
+    query.push(" FROM access_logs WHERE tenant = ").push_bind(tenant);
+    if let Some(role) = &filter.role {
+        query.push(" AND role = ").push_bind(role);
+    }
+    if let Some(door) = &filter.door_name {
+        query.push(" AND door_name = ").push_bind(door);
+    }
+    match filter.granted {
+        Some(true) => { query.push(" AND status = 'GRANTED'"); }
+        Some(false) => { query.push(" AND status LIKE 'DENIED%'"); }
+        None => {}
+    }
+    if let Some(since) = &filter.since {
+        query.push(" AND timestamp >= ").push_bind(since);
+    }
+    if let Some(until) = &filter.until {
+        query.push(" AND timestamp < ").push_bind(until);
+    }
+    query
+}
+
+fn row_to_log(row: &SqliteRow) -> AccessHistory {
+    AccessHistory {
+        id: row.get::<i64, _>("id") as u64,
+        role: row.get("role"),
+        door_name: row.get("door_name"),
+        section: row.get("section"),
+        timestamp: row.get("timestamp"),
+        status: row.get("status"),
+        faculty_name: row.get("faculty_name"),
+        faculty_id: row.get("faculty_id"),
+        actor_id: row.get("actor_id"),
+        // Unknown values (say, from a newer build) read back as None rather than failing the query
+        denial_reason: row.get::<Option<String>, _>("denial_reason").and_then(|r| r.parse().ok()),
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn append_log(&self, tenant: &str, entry: AccessHistory) -> Result<u64, StorageError> {
+        let row = sqlx::query(
+            "INSERT INTO access_logs (tenant, role, door_name, section, timestamp, status, faculty_name, faculty_id, actor_id, denial_reason)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+        )
+        .bind(tenant)
+        .bind(&entry.role)
+        .bind(&entry.door_name)
+        .bind(&entry.section)
+        .bind(&entry.timestamp)
+        .bind(&entry.status)
+        .bind(&entry.faculty_name)
+        .bind(&entry.faculty_id)
+        .bind(&entry.actor_id)
+        .bind(entry.denial_reason.map(|r| r.as_str()))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get::<i64, _>("id") as u64)
+    }
+
+    async fn logs(&self, tenant: &str) -> Result<Vec<AccessHistory>, StorageError> {
+        let rows = sqlx::query("SELECT * FROM access_logs WHERE tenant = ? ORDER BY id")
+            .bind(tenant)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(row_to_log).collect())
+    }
+
+    async fn query_logs(&self, tenant: &str, filter: &LogFilter) -> Result<Vec<AccessHistory>, StorageError> {
+        let mut query = filtered_logs("SELECT *", tenant, filter);
+        // Newest first so LIMIT keeps the most recent, then flipped back to oldest first
+        query.push(" ORDER BY id DESC");
+        // SQLite only takes OFFSET after a LIMIT; -1 is no limit
+        let limit = filter.limit.map_or(-1, |l| l.min(i64::MAX as usize) as i64);
+        query.push(" LIMIT ").push_bind(limit);
+        if filter.offset > 0 {
+            query.push(" OFFSET ").push_bind(filter.offset.min(i64::MAX as usize) as i64);
+        }
+        let rows = query.build().fetch_all(&self.pool).await?;
+        Ok(rows.iter().rev().map(row_to_log).collect())
+    }
+
+    async fn count_logs(&self, tenant: &str, filter: &LogFilter) -> Result<usize, StorageError> {
+        let row = filtered_logs("SELECT COUNT(*)", tenant, filter).build().fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>(0) as usize)
+    }
+
+    async fn clear_logs(&self, tenant: &str) -> Result<usize, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM access_proofs WHERE tenant = ?")
+            .bind(tenant)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM access_logs WHERE tenant = ?")
+            .bind(tenant)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn archive_logs(&self, tenant: &str) -> Result<Archive, StorageError> {
+        // The rows are deleted in the transaction and only committed once the file is written
+        let mut tx = self.pool.begin().await?;
+        let proof_rows = sqlx::query("DELETE FROM access_proofs WHERE tenant = ? RETURNING log_id, proof")
+            .bind(tenant)
+            .fetch_all(&mut *tx)
+            .await?;
+        let log_rows = sqlx::query("DELETE FROM access_logs WHERE tenant = ? RETURNING *")
+            .bind(tenant)
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut entries: Vec<AccessHistory> = log_rows.iter().map(row_to_log).collect();
+        entries.sort_by_key(|e| e.id);
+        let proofs: HashMap<u64, Proof> = proof_rows.iter()
+            .filter_map(|r| serde_json::from_str(r.get::<&str, _>("proof")).ok().map(|p| (r.get::<i64, _>("log_id") as u64, p)))
+            .collect();
+        let path = write_archive(tenant, &entries, &proofs)?;
+        tx.commit().await?;
+        Ok(Archive { count: entries.len(), path })
+    }
+
+    async fn save_proof(&self, tenant: &str, log_id: u64, proof: &Proof) -> Result<(), StorageError> {
+        sqlx::query("INSERT INTO access_proofs (tenant, log_id, proof) VALUES (?, ?, ?) ON CONFLICT (tenant, log_id) DO UPDATE SET proof = excluded.proof")
+            .bind(tenant)
+            .bind(log_id as i64)
+            .bind(serde_json::to_string(proof).expect("proof serializes"))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn proof(&self, tenant: &str, log_id: u64) -> Result<Option<Proof>, StorageError> {
+        let row = sqlx::query("SELECT proof FROM access_proofs WHERE tenant = ? AND log_id = ?")
+            .bind(tenant)
+            .bind(log_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.and_then(|r| serde_json::from_str(r.get::<&str, _>("proof")).ok()))
+    }
+
+    async fn assignment(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError> {
+        let row = sqlx::query("SELECT room_id, faculty_name FROM assignments WHERE tenant = ? AND section = ?")
+            .bind(tenant)
+            .bind(section)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| (r.get("room_id"), r.get("faculty_name"))))
+    }
+
+    async fn assign(&self, tenant: &str, section: &str, assignment: Assignment) -> Result<Option<Assignment>, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let previous = sqlx::query("SELECT room_id, faculty_name FROM assignments WHERE tenant = ? AND section = ?")
+            .bind(tenant)
+            .bind(section)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|r| (r.get("room_id"), r.get("faculty_name")));
+        sqlx::query(
+            "INSERT INTO assignments (tenant, section, room_id, faculty_name) VALUES (?, ?, ?, ?)
+             ON CONFLICT (tenant, section) DO UPDATE SET room_id = excluded.room_id, faculty_name = excluded.faculty_name",
+        )
+        .bind(tenant)
+        .bind(section)
+        .bind(&assignment.0)
+        .bind(&assignment.1)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(previous)
+    }
+
+    async fn unassign(&self, tenant: &str, section: &str) -> Result<Option<Assignment>, StorageError> {
+        let row = sqlx::query("DELETE FROM assignments WHERE tenant = ? AND section = ? RETURNING room_id, faculty_name")
+            .bind(tenant)
+            .bind(section)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| (r.get("room_id"), r.get("faculty_name"))))
+    }
+
+    async fn room_sections(&self, tenant: &str, room_id: &str) -> Result<Vec<(String, String)>, StorageError> {
+        let rows = sqlx::query("SELECT section, faculty_name FROM assignments WHERE tenant = ? AND room_id = ? ORDER BY section")
+            .bind(tenant)
+            .bind(room_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|r| (r.get("section"), r.get("faculty_name"))).collect())
+    }
+
+    async fn unassign_room(&self, tenant: &str, room_id: &str) -> Result<Vec<String>, StorageError> {
+        let rows = sqlx::query("DELETE FROM assignments WHERE tenant = ? AND room_id = ? RETURNING section")
+            .bind(tenant)
+            .bind(room_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(|r| r.get("section")).collect())
+    }
+
+    async fn claim_nonce(&self, tenant: &str, nonce: &str, ttl_secs: u64) -> Result<bool, StorageError> {
+        let now = now_secs() as i64;
+        sqlx::query("DELETE FROM used_nonces WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let result = sqlx::query("INSERT OR IGNORE INTO used_nonces (tenant, nonce, expires_at) VALUES (?, ?, ?)")
+            .bind(tenant)
+            .bind(nonce)
+            .bind(now + ttl_secs as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn issue_challenge(&self, tenant: &str, door_id: &str, nonce: &str, ttl_secs: u64) -> Result<(), StorageError> {
+        let now = now_secs() as i64;
+        sqlx::query("DELETE FROM challenges WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("INSERT INTO challenges (tenant, nonce, door_id, expires_at) VALUES (?, ?, ?, ?)")
+            .bind(tenant)
+            .bind(nonce)
+            .bind(door_id)
+            .bind(now + ttl_secs as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn take_challenge(&self, tenant: &str, nonce: &str) -> Result<Option<String>, StorageError> {
+        // Deleting as we read means a nonce can only ever be taken once
+        let row = sqlx::query("DELETE FROM challenges WHERE tenant = ? AND nonce = ? RETURNING door_id, expires_at")
+            .bind(tenant)
+            .bind(nonce)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row
+            .filter(|row| row.get::<i64, _>("expires_at") > now_secs() as i64)
+            .map(|row| row.get("door_id")))
+    }
+
+    // Closing the last connection also checkpoints the WAL back into the database file
+    async fn close(&self) -> Result<(), StorageError> {
+        self.pool.close().await;
+        Ok(())
+    }
+}
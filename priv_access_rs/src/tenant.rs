@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use num_bigint::BigUint;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::doors::Door;
+use crate::error::ApiError;
+
+pub const DEFAULT_TENANT: &str = "default";
+
+// One change to a tenant's section assignments
+#[derive(Serialize, Clone, Debug)]
+pub struct AssignmentAudit {
+    pub actor_id: String,
+    pub actor_role: String,
+    pub section: String,
+    pub old_room: Option<String>,
+    pub new_room: Option<String>,
+    pub action: String,
+    pub timestamp: String,
+}
+
+/// Everything one campus owns. Nothing in here is shared with other tenants.
+pub struct TenantState {
+    pub id: String,
+    pub doors: RwLock<HashMap<String, Door>>,
+    // Trail of every change to the section assignments: who moved which section from where to where
+    pub assignment_audit: Mutex<Vec<AssignmentAudit>>,
+    pub role_secrets: HashMap<String, BigUint>,
+}
+
+impl TenantState {
+    pub fn new(id: &str) -> Self {
+        let doors = match crate::doors::load(id) {
+            Ok(doors) => doors,
+            Err(e) => {
+                println!("\n❌ DOORS ERROR: {}", e);
+                ::std::process::exit(1);
+            }
+        };
+        TenantState {
+            id: id.to_string(),
+            doors: RwLock::new(doors),
+            assignment_audit: Mutex::new(Vec::new()),
+            role_secrets: crate::rbac::tenant_role_secrets(id),
+        }
+    }
+
+    /// URL prefix this campus is served under: empty for the default campus, `/t/<id>` otherwise.
+    pub fn base_path(&self) -> String {
+        if self.id == DEFAULT_TENANT {
+            String::new()
+        } else {
+            format!("/t/{}", self.id)
+        }
+    }
+
+    pub fn door(&self, door_id: &str) -> Option<Door> {
+        self.doors.read().unwrap().get(door_id).cloned()
+    }
+
+    pub fn role_secret(&self, role: &str) -> Option<BigUint> {
+        self.role_secrets.get(role).cloned()
+    }
+
+    /// The public key a modp proof for `role` must carry on this campus.
+    pub fn role_public_key(&self, role: &str) -> Option<BigUint> {
+        self.role_secrets.get(role).map(crate::crypto::power_g)
+    }
+
+    pub fn record_assignment_change(&self, actor_id: &str, actor_role: &str, section: &str, old_room: Option<String>, new_room: Option<String>) {
+        let action = match (&old_room, &new_room) {
+            (None, Some(_)) => "assign",
+            (Some(_), Some(_)) => "reassign",
+            (Some(_), None) => "unassign",
+            (None, None) => return,
+        };
+        let entry = AssignmentAudit {
+            actor_id: actor_id.to_string(),
+            actor_role: actor_role.to_string(),
+            section: section.to_string(),
+            old_room,
+            new_room,
+            action: action.to_string(),
+            timestamp: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+        tracing::info!(tenant = %self.id, by = %entry.actor_id, action = %entry.action, section = %entry.section, old_room = ?entry.old_room, new_room = ?entry.new_room, "Section assignment changed");
+        self.assignment_audit.lock().unwrap().push(entry);
+    }
+}
+
+static TENANTS: Lazy<HashMap<String, Arc<TenantState>>> = Lazy::new(|| {
+    let mut tenants = HashMap::new();
+    tenants.insert(DEFAULT_TENANT.to_string(), Arc::new(TenantState::new(DEFAULT_TENANT)));
+    for id in &crate::config::CONFIG.tenants {
+        tenants.entry(id.clone()).or_insert_with(|| Arc::new(TenantState::new(id)));
+    }
+    tenants
+});
+
+pub fn get_tenant(id: &str) -> Option<Arc<TenantState>> {
+    TENANTS.get(id).cloned()
+}
+
+pub fn tenant_ids() -> Vec<String> {
+    let mut ids: Vec<String> = TENANTS.keys().cloned().collect();
+    ids.sort();
+    ids
+}
+
+/// The campus a request belongs to, resolved by `resolve_tenant`.
+#[derive(Clone)]
+pub struct Tenant(pub Arc<TenantState>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Tenant {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let tenant = parts.extensions.get::<Tenant>().cloned();
+        Ok(tenant.unwrap_or_else(|| Tenant(get_tenant(DEFAULT_TENANT).expect("default tenant always exists"))))
+    }
+}
+
+/// Outer middleware: strips a `/t/:tenant` prefix from the path and attaches that tenant
+/// to the request. Un-prefixed paths belong to the default tenant; unknown tenants are a 404.
+pub async fn resolve_tenant(mut req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    let (tenant_id, rest) = match path.strip_prefix("/t/") {
+        Some(scoped) => {
+            let (id, rest) = scoped.split_once('/').unwrap_or((scoped, ""));
+            (id.to_string(), Some(format!("/{}", rest)))
+        }
+        None => (DEFAULT_TENANT.to_string(), None),
+    };
+
+    let tenant = match get_tenant(&tenant_id) {
+        Some(t) => t,
+        None => return ApiError::new(StatusCode::NOT_FOUND, "Unknown Campus").into_response(),
+    };
+
+    if let Some(rest) = rest {
+        let path_and_query = match req.uri().query() {
+            Some(q) => format!("{}?{}", rest, q),
+            None => rest,
+        };
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = path_and_query.parse().ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = uri;
+        }
+    }
+
+    req.extensions_mut().insert(Tenant(tenant));
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campus() -> TenantState {
+        TenantState {
+            id: "audit-test".to_string(),
+            doors: RwLock::new(HashMap::new()),
+            assignment_audit: Mutex::new(Vec::new()),
+            role_secrets: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn reassignment_records_both_rooms_and_who_moved_it() {
+        let campus = campus();
+        campus.record_assignment_change("Fac1", "FACULTY", "B", None, Some("lab-1".to_string()));
+        campus.record_assignment_change("Fac2", "FACULTY", "B", Some("lab-1".to_string()), Some("lab-2".to_string()));
+        // Nothing moved, nothing to record
+        campus.record_assignment_change("Fac2", "FACULTY", "B", None, None);
+
+        let audit = campus.assignment_audit.lock().unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].action, "assign");
+        let moved = &audit[1];
+        assert_eq!((moved.actor_id.as_str(), moved.section.as_str(), moved.action.as_str()), ("Fac2", "B", "reassign"));
+        assert_eq!(moved.old_room.as_deref(), Some("lab-1"));
+        assert_eq!(moved.new_room.as_deref(), Some("lab-2"));
+    }
+}
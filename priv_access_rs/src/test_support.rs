@@ -0,0 +1,19 @@
+//! Helpers for the unit tests that need a real server. Those tests are `#[ignore]`d, so a plain
+//! `cargo test` reports them as ignored; run them with the server's URL set, e.g.
+//!
+//! ```text
+//! PRIVACCESS_TEST_REDIS_URL=redis://127.0.0.1/ cargo test -- --ignored
+//! PRIVACCESS_TEST_POSTGRES_URL=postgres://postgres@127.0.0.1/privaccess_test cargo test -- --ignored
+//! ```
+
+fn required(var: &str) -> String {
+    std::env::var(var).unwrap_or_else(|_| panic!("{} must be set to run this test", var))
+}
+
+pub fn redis_url() -> String {
+    required("PRIVACCESS_TEST_REDIS_URL")
+}
+
+pub fn postgres_url() -> String {
+    required("PRIVACCESS_TEST_POSTGRES_URL")
+}
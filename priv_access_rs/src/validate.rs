@@ -0,0 +1,139 @@
+// Length and charset checks for strings clients send, applied before a handler sees them. Values
+// end up in logs, storage and URLs, so anything oversized or carrying control characters (which
+// could forge log lines) is refused up front with the field named.
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Query, Request},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::{ApiError, ErrorCode};
+
+/// Door, faculty, admin and room ids.
+pub const MAX_ID_LEN: usize = 64;
+/// People's and keys' display names.
+pub const MAX_NAME_LEN: usize = 100;
+/// Roles, permissions and other short keywords.
+pub const MAX_KEYWORD_LEN: usize = 32;
+/// Timestamps, nonces, place names and similar free text.
+pub const MAX_TEXT_LEN: usize = 200;
+/// Passwords, PINs and TOTP codes. They're hashed with Argon2, so this also bounds the work one
+/// request can cause.
+pub const MAX_SECRET_LEN: usize = 256;
+/// Guest passes and public keys.
+pub const MAX_TOKEN_LEN: usize = 4096;
+
+/// A request field that failed validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &'static str, message: String) -> Self {
+        FieldError { field, code: ErrorCode::BadRequest, message }
+    }
+}
+
+impl From<FieldError> for ApiError {
+    fn from(e: FieldError) -> Self {
+        ApiError::coded(StatusCode::BAD_REQUEST, e.code, e.message).with_field(e.field)
+    }
+}
+
+/// A request body or query string that can check its own fields.
+pub trait Validate {
+    fn validate(&self) -> Result<(), FieldError>;
+}
+
+/// At most `max` characters and no control characters.
+pub fn text(field: &'static str, value: &str, max: usize) -> Result<(), FieldError> {
+    if value.chars().count() > max {
+        return Err(FieldError::new(field, format!("{} is longer than {} characters", field, max)));
+    }
+    if value.chars().any(char::is_control) {
+        return Err(FieldError::new(field, format!("{} contains control characters", field)));
+    }
+    Ok(())
+}
+
+pub fn opt_text(field: &'static str, value: &Option<String>, max: usize) -> Result<(), FieldError> {
+    value.as_deref().map_or(Ok(()), |v| text(field, v, max))
+}
+
+/// Length only: a password is never logged or echoed, and may be anything the user typed.
+pub fn secret(field: &'static str, value: &str) -> Result<(), FieldError> {
+    if value.chars().count() > MAX_SECRET_LEN {
+        return Err(FieldError::new(field, format!("{} is longer than {} characters", field, MAX_SECRET_LEN)));
+    }
+    Ok(())
+}
+
+pub fn opt_secret(field: &'static str, value: &Option<String>) -> Result<(), FieldError> {
+    value.as_deref().map_or(Ok(()), |v| secret(field, v))
+}
+
+/// One of the configured sections (either case), or left out. An empty value counts as left
+/// out, so handlers that need a section still say so themselves.
+pub fn section(field: &'static str, value: &Option<String>) -> Result<(), FieldError> {
+    let section = value.as_deref().unwrap_or("").trim();
+    let sections = &crate::config::CONFIG.sections;
+    if section.is_empty() || crate::rbac::find_section(sections, section).is_some() {
+        return Ok(());
+    }
+    Err(FieldError {
+        field,
+        code: ErrorCode::InvalidSection,
+        message: format!("{} must be one of {}", field, sections.join(", ")),
+    })
+}
+
+/// A geohash in the base32 alphabet, or empty for requests that don't use one.
+pub fn geohash(field: &'static str, value: &str) -> Result<(), FieldError> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    crate::geo::validate(value).map(|_| ()).map_err(|e| FieldError { field, code: ErrorCode::InvalidLocation, message: e.to_string() })
+}
+
+/// `Json<T>` that also runs `T::validate`. Malformed JSON is rejected exactly as `Json` would.
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        value.validate().map_err(|e| ApiError::from(e).into_response())?;
+        Ok(ValidJson(value))
+    }
+}
+
+/// `Query<T>` that also runs `T::validate`.
+pub struct ValidQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await.map_err(IntoResponse::into_response)?;
+        value.validate().map_err(|e| ApiError::from(e).into_response())?;
+        Ok(ValidQuery(value))
+    }
+}
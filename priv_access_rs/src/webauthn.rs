@@ -0,0 +1,550 @@
+//! WebAuthn passkeys for faculty, as an alternative to their PIN.
+//!
+//! Only what the door flow needs: ES256 (P-256) credentials from platform authenticators, user
+//! verification required, and no attestation. The authenticator's make and model aren't checked,
+//! only that it holds the key it registered.
+
+use std::fs;
+use std::sync::RwLock;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ciborium::Value;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::faculty::Faculty;
+
+const PASSKEYS_PATH: &str = "passkeys.json";
+
+/// How long a browser gives the user to touch the authenticator, in milliseconds.
+pub const CEREMONY_TIMEOUT_MS: u64 = 60_000;
+
+// COSE algorithm -7: ECDSA with SHA-256 on P-256
+const ES256: i64 = -7;
+
+// authenticatorData flags
+const USER_PRESENT: u8 = 0x01;
+const USER_VERIFIED: u8 = 0x04;
+const ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Why a registration or assertion was refused.
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnError {
+    /// A field isn't base64url, JSON or CBOR of the expected shape
+    Malformed { field: &'static str },
+    /// clientDataJSON is from the other ceremony, e.g. a registration sent to login
+    WrongCeremony,
+    ChallengeMismatch,
+    /// The browser made the credential call from another site
+    OriginMismatch,
+    /// The authenticator scoped the response to another relying party
+    RpIdMismatch,
+    UserNotPresent,
+    /// The authenticator didn't check a PIN or biometric
+    UserNotVerified,
+    /// Only ES256 keys are accepted
+    UnsupportedAlgorithm,
+    BadSignature,
+    /// The signature counter went backwards, as it does when an authenticator has been cloned
+    CounterRegressed,
+}
+
+impl WebAuthnError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            WebAuthnError::Malformed { .. } => "webauthn_malformed",
+            WebAuthnError::WrongCeremony => "webauthn_wrong_ceremony",
+            WebAuthnError::ChallengeMismatch => "webauthn_challenge_mismatch",
+            WebAuthnError::OriginMismatch => "webauthn_origin_mismatch",
+            WebAuthnError::RpIdMismatch => "webauthn_rp_id_mismatch",
+            WebAuthnError::UserNotPresent => "webauthn_user_not_present",
+            WebAuthnError::UserNotVerified => "webauthn_user_not_verified",
+            WebAuthnError::UnsupportedAlgorithm => "webauthn_unsupported_algorithm",
+            WebAuthnError::BadSignature => "webauthn_bad_signature",
+            WebAuthnError::CounterRegressed => "webauthn_counter_regressed",
+        }
+    }
+
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            WebAuthnError::Malformed { field } => Some(field),
+            _ => None,
+        }
+    }
+
+    /// Whether the request itself is malformed (400), as opposed to failing verification.
+    pub fn is_bad_request(&self) -> bool {
+        matches!(self, WebAuthnError::Malformed { .. })
+    }
+}
+
+impl std::fmt::Display for WebAuthnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebAuthnError::Malformed { field } => write!(f, "WebAuthn field '{}' is malformed", field),
+            WebAuthnError::WrongCeremony => write!(f, "WebAuthn response is for the wrong ceremony"),
+            WebAuthnError::ChallengeMismatch => write!(f, "WebAuthn response answers another challenge"),
+            WebAuthnError::OriginMismatch => write!(f, "WebAuthn response came from another origin"),
+            WebAuthnError::RpIdMismatch => write!(f, "WebAuthn response is for another relying party"),
+            WebAuthnError::UserNotPresent => write!(f, "Authenticator reported no user presence"),
+            WebAuthnError::UserNotVerified => write!(f, "Authenticator didn't verify the user"),
+            WebAuthnError::UnsupportedAlgorithm => write!(f, "Only ES256 passkeys are supported"),
+            WebAuthnError::BadSignature => write!(f, "Invalid passkey signature"),
+            WebAuthnError::CounterRegressed => write!(f, "Passkey signature counter went backwards"),
+        }
+    }
+}
+
+impl std::error::Error for WebAuthnError {}
+
+/// The browser's reply to `navigator.credentials.create()`, with binary fields as base64url.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegistrationResponse {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+/// The browser's reply to `navigator.credentials.get()`, with binary fields as base64url.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AssertionResponse {
+    pub id: String,
+    pub response: AuthenticatorAssertion,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AuthenticatorAssertion {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(rename = "userHandle")]
+    pub user_handle: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony: String,
+    challenge: String,
+    origin: String,
+    #[serde(default, rename = "crossOrigin")]
+    cross_origin: bool,
+}
+
+// Browsers send unpadded base64url, but some client libraries pad it
+fn decode(value: &str, field: &'static str) -> Result<Vec<u8>, WebAuthnError> {
+    URL_SAFE_NO_PAD.decode(value.trim().trim_end_matches('=')).map_err(|_| WebAuthnError::Malformed { field })
+}
+
+fn parse_client_data(client_data_json: &str) -> Result<(Vec<u8>, ClientData), WebAuthnError> {
+    let bytes = decode(client_data_json, "clientDataJSON")?;
+    let client_data = serde_json::from_slice(&bytes).map_err(|_| WebAuthnError::Malformed { field: "clientDataJSON" })?;
+    Ok((bytes, client_data))
+}
+
+/// The challenge a response answers, so the caller can look up (and spend) the one it issued.
+pub fn challenge_of(client_data_json: &str) -> Result<String, WebAuthnError> {
+    parse_client_data(client_data_json).map(|(_, client_data)| client_data.challenge)
+}
+
+/// A fresh random challenge, base64url as it goes to the browser and comes back in clientDataJSON.
+pub fn new_challenge() -> String {
+    let mut bytes = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The WebAuthn user handle for a faculty member: their id, lowercased since ids are case-insensitive.
+pub fn user_handle(faculty_id: &str) -> String {
+    URL_SAFE_NO_PAD.encode(faculty_id.trim().to_ascii_lowercase())
+}
+
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    // Credential id and COSE public key, present on registration
+    credential: Option<(Vec<u8>, Value)>,
+}
+
+fn parse_authenticator_data(bytes: &[u8]) -> Result<AuthenticatorData<'_>, WebAuthnError> {
+    const MALFORMED: WebAuthnError = WebAuthnError::Malformed { field: "authenticatorData" };
+    if bytes.len() < 37 {
+        return Err(MALFORMED);
+    }
+    let flags = bytes[32];
+    let sign_count = u32::from_be_bytes(bytes[33..37].try_into().unwrap());
+    let credential = if flags & ATTESTED_CREDENTIAL != 0 {
+        // aaguid (16) || credential id length (2) || credential id || COSE key
+        let rest = bytes.get(37 + 16..).ok_or(MALFORMED)?;
+        let len = rest.get(..2).map(|l| u16::from_be_bytes([l[0], l[1]]) as usize).ok_or(MALFORMED)?;
+        let id = rest.get(2..2 + len).ok_or(MALFORMED)?.to_vec();
+        let key: Value = ciborium::from_reader(&rest[2 + len..]).map_err(|_| MALFORMED)?;
+        Some((id, key))
+    } else {
+        None
+    };
+    Ok(AuthenticatorData { rp_id_hash: &bytes[..32], flags, sign_count, credential })
+}
+
+fn cbor_field(map: &Value, key: Value) -> Option<&Value> {
+    map.as_map()?.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+}
+
+// An EC2 COSE key on P-256 for ES256, as an uncompressed SEC1 point
+fn es256_key(cose: &Value) -> Result<VerifyingKey, WebAuthnError> {
+    let int = |label: i64| cbor_field(cose, Value::Integer(label.into())).and_then(Value::as_integer).map(i128::from);
+    let bytes = |label: i64| cbor_field(cose, Value::Integer(label.into())).and_then(Value::as_bytes);
+    // kty 2 (EC2), alg -7, crv 1 (P-256)
+    if int(1) != Some(2) || int(3) != Some(ES256.into()) || int(-1) != Some(1) {
+        return Err(WebAuthnError::UnsupportedAlgorithm);
+    }
+    let (x, y) = bytes(-2).zip(bytes(-3)).filter(|(x, y)| x.len() == 32 && y.len() == 32)
+        .ok_or(WebAuthnError::Malformed { field: "publicKey" })?;
+    let point = p256::EncodedPoint::from_affine_coordinates(x.as_slice().into(), y.as_slice().into(), false);
+    VerifyingKey::from_encoded_point(&point).map_err(|_| WebAuthnError::Malformed { field: "publicKey" })
+}
+
+/// A registered passkey. Kept per faculty member rather than per campus, like the accounts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Passkey {
+    /// Credential id, base64url
+    pub credential_id: String,
+    pub faculty_id: String,
+    /// Uncompressed SEC1 P-256 point, hex
+    pub public_key: String,
+    pub sign_count: u32,
+    pub created_at: u64,
+}
+
+/// The site passkeys are bound to: an origin like `https://door.example.edu` and its host as
+/// the RP id. A credential made for one RP id can't sign for another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelyingParty {
+    pub id: String,
+    pub origin: String,
+}
+
+impl RelyingParty {
+    pub fn from_origin(origin: &str) -> Option<Self> {
+        let origin = origin.trim().trim_end_matches('/');
+        let (_, authority) = origin.split_once("://")?;
+        // The RP id is the bare host: no port, no path
+        let host = authority.rsplit_once(':').map_or(authority, |(host, _)| host);
+        if host.is_empty() || authority.contains('/') {
+            return None;
+        }
+        Some(RelyingParty { id: host.to_ascii_lowercase(), origin: origin.to_string() })
+    }
+
+    /// `publicKey` options for `navigator.credentials.create()`. Existing passkeys are excluded
+    /// so the same authenticator isn't registered twice.
+    pub fn creation_options(&self, faculty: &Faculty, challenge: &str, existing: &[Passkey]) -> serde_json::Value {
+        json!({
+            "challenge": challenge,
+            "rp": { "id": self.id, "name": "PrivAccess" },
+            "user": { "id": user_handle(&faculty.id), "name": faculty.id, "displayName": faculty.display_name() },
+            "pubKeyCredParams": [{ "type": "public-key", "alg": ES256 }],
+            "timeout": CEREMONY_TIMEOUT_MS,
+            "attestation": "none",
+            "authenticatorSelection": {
+                "authenticatorAttachment": "platform",
+                "residentKey": "preferred",
+                "userVerification": "required"
+            },
+            "excludeCredentials": existing.iter()
+                .map(|p| json!({ "type": "public-key", "id": p.credential_id }))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// `publicKey` options for `navigator.credentials.get()`, allowing any of `passkeys`.
+    pub fn request_options(&self, challenge: &str, passkeys: &[Passkey]) -> serde_json::Value {
+        json!({
+            "challenge": challenge,
+            "rpId": self.id,
+            "timeout": CEREMONY_TIMEOUT_MS,
+            "userVerification": "required",
+            "allowCredentials": passkeys.iter()
+                .map(|p| json!({ "type": "public-key", "id": p.credential_id }))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    fn check_client_data(&self, client_data: &ClientData, ceremony: &str, challenge: &str) -> Result<(), WebAuthnError> {
+        if client_data.ceremony != ceremony {
+            return Err(WebAuthnError::WrongCeremony);
+        }
+        if client_data.challenge != challenge {
+            return Err(WebAuthnError::ChallengeMismatch);
+        }
+        if client_data.cross_origin || !client_data.origin.eq_ignore_ascii_case(&self.origin) {
+            return Err(WebAuthnError::OriginMismatch);
+        }
+        Ok(())
+    }
+
+    fn check_authenticator_data(&self, data: &AuthenticatorData) -> Result<(), WebAuthnError> {
+        if data.rp_id_hash != Sha256::digest(self.id.as_bytes()).as_slice() {
+            return Err(WebAuthnError::RpIdMismatch);
+        }
+        if data.flags & USER_PRESENT == 0 {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+        if data.flags & USER_VERIFIED == 0 {
+            return Err(WebAuthnError::UserNotVerified);
+        }
+        Ok(())
+    }
+
+    /// Check a registration against the challenge issued for it and return the new passkey.
+    /// The attestation statement is ignored, since none was asked for.
+    pub fn finish_registration(&self, response: &RegistrationResponse, challenge: &str, faculty_id: &str, now: u64) -> Result<Passkey, WebAuthnError> {
+        let (_, client_data) = parse_client_data(&response.response.client_data_json)?;
+        self.check_client_data(&client_data, "webauthn.create", challenge)?;
+
+        let attestation = decode(&response.response.attestation_object, "attestationObject")?;
+        let attestation: Value = ciborium::from_reader(attestation.as_slice())
+            .map_err(|_| WebAuthnError::Malformed { field: "attestationObject" })?;
+        let auth_data = cbor_field(&attestation, Value::Text("authData".to_string()))
+            .and_then(Value::as_bytes)
+            .ok_or(WebAuthnError::Malformed { field: "attestationObject" })?;
+        let data = parse_authenticator_data(auth_data)?;
+        self.check_authenticator_data(&data)?;
+
+        let (credential_id, cose) = data.credential.as_ref().ok_or(WebAuthnError::Malformed { field: "authenticatorData" })?;
+        if URL_SAFE_NO_PAD.encode(credential_id) != response.id.trim().trim_end_matches('=') {
+            return Err(WebAuthnError::Malformed { field: "id" });
+        }
+        let key = es256_key(cose)?;
+        Ok(Passkey {
+            credential_id: URL_SAFE_NO_PAD.encode(credential_id),
+            faculty_id: faculty_id.to_string(),
+            public_key: hex::encode(key.to_encoded_point(false).as_bytes()),
+            sign_count: data.sign_count,
+            created_at: now,
+        })
+    }
+
+    /// Check an assertion by `passkey` against the challenge issued for it. Returns the
+    /// authenticator's new signature counter, to be stored.
+    pub fn verify_assertion(&self, response: &AssertionResponse, challenge: &str, passkey: &Passkey) -> Result<u32, WebAuthnError> {
+        let (client_data_bytes, client_data) = parse_client_data(&response.response.client_data_json)?;
+        self.check_client_data(&client_data, "webauthn.get", challenge)?;
+
+        let auth_data = decode(&response.response.authenticator_data, "authenticatorData")?;
+        let data = parse_authenticator_data(&auth_data)?;
+        self.check_authenticator_data(&data)?;
+
+        if let Some(handle) = &response.response.user_handle {
+            if handle.trim().trim_end_matches('=') != user_handle(&passkey.faculty_id) {
+                return Err(WebAuthnError::BadSignature);
+            }
+        }
+
+        let key = hex::decode(&passkey.public_key).ok()
+            .and_then(|k| VerifyingKey::from_sec1_bytes(&k).ok())
+            .ok_or(WebAuthnError::Malformed { field: "publicKey" })?;
+        let signature = decode(&response.response.signature, "signature")?;
+        let signature = Signature::from_der(&signature).map_err(|_| WebAuthnError::Malformed { field: "signature" })?;
+        // The authenticator signs authenticatorData || sha256(clientDataJSON)
+        let mut message = auth_data.clone();
+        message.extend_from_slice(&Sha256::digest(&client_data_bytes));
+        key.verify(&message, &signature).map_err(|_| WebAuthnError::BadSignature)?;
+
+        // Synced passkeys report 0 every time; otherwise the counter only moves forward
+        if (data.sign_count != 0 || passkey.sign_count != 0) && data.sign_count <= passkey.sign_count {
+            return Err(WebAuthnError::CounterRegressed);
+        }
+        Ok(data.sign_count)
+    }
+}
+
+/// Passkeys faculty have registered, kept in `passkeys.json`.
+pub struct PasskeyRegistry {
+    passkeys: RwLock<Vec<Passkey>>,
+}
+
+impl PasskeyRegistry {
+    pub fn load() -> Self {
+        let passkeys = fs::read_to_string(PASSKEYS_PATH)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        PasskeyRegistry { passkeys: RwLock::new(passkeys) }
+    }
+
+    fn save(passkeys: &[Passkey]) {
+        if let Ok(content) = serde_json::to_string_pretty(passkeys) {
+            let _ = fs::write(PASSKEYS_PATH, content);
+        }
+    }
+
+    /// Returns false if the credential is already registered.
+    pub fn register(&self, passkey: Passkey) -> bool {
+        let mut passkeys = self.passkeys.write().unwrap();
+        if passkeys.iter().any(|p| p.credential_id == passkey.credential_id) {
+            return false;
+        }
+        passkeys.push(passkey);
+        Self::save(&passkeys);
+        true
+    }
+
+    pub fn for_faculty(&self, faculty_id: &str) -> Vec<Passkey> {
+        self.passkeys.read().unwrap().iter().filter(|p| p.faculty_id.eq_ignore_ascii_case(faculty_id)).cloned().collect()
+    }
+
+    /// The passkey with this credential id, if it belongs to `faculty_id`.
+    pub fn find(&self, faculty_id: &str, credential_id: &str) -> Option<Passkey> {
+        let credential_id = credential_id.trim().trim_end_matches('=');
+        self.passkeys.read().unwrap().iter()
+            .find(|p| p.credential_id == credential_id && p.faculty_id.eq_ignore_ascii_case(faculty_id))
+            .cloned()
+    }
+
+    pub fn update_sign_count(&self, credential_id: &str, sign_count: u32) {
+        let mut passkeys = self.passkeys.write().unwrap();
+        if let Some(passkey) = passkeys.iter_mut().find(|p| p.credential_id == credential_id) {
+            passkey.sign_count = sign_count;
+            Self::save(&passkeys);
+        }
+    }
+
+    /// Drop every passkey of a faculty member, e.g. when their account is removed. Returns how many.
+    pub fn remove_faculty(&self, faculty_id: &str) -> usize {
+        let mut passkeys = self.passkeys.write().unwrap();
+        let before = passkeys.len();
+        passkeys.retain(|p| !p.faculty_id.eq_ignore_ascii_case(faculty_id));
+        let removed = before - passkeys.len();
+        if removed > 0 {
+            Self::save(&passkeys);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::SigningKey;
+
+    const ORIGIN: &str = "https://door.example.edu";
+    const CHALLENGE: &str = "c2VydmVyLWNoYWxsZW5nZQ";
+
+    fn rp() -> RelyingParty {
+        RelyingParty::from_origin(ORIGIN).unwrap()
+    }
+
+    fn client_data(ceremony: &str, challenge: &str, origin: &str) -> String {
+        URL_SAFE_NO_PAD.encode(json!({ "type": ceremony, "challenge": challenge, "origin": origin }).to_string())
+    }
+
+    fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn register(key: &SigningKey, credential_id: &[u8]) -> RegistrationResponse {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer(ES256.into())),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (Value::Integer((-2).into()), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::Integer((-3).into()), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        let mut auth_data = authenticator_data("door.example.edu", USER_PRESENT | USER_VERIFIED | ATTESTED_CREDENTIAL, 0);
+        auth_data.extend_from_slice(&[0u8; 16]);
+        auth_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(credential_id);
+        ciborium::into_writer(&cose, &mut auth_data).unwrap();
+        let attestation = Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("none".into())),
+            (Value::Text("attStmt".into()), Value::Map(vec![])),
+            (Value::Text("authData".into()), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_object = Vec::new();
+        ciborium::into_writer(&attestation, &mut attestation_object).unwrap();
+        RegistrationResponse {
+            id: URL_SAFE_NO_PAD.encode(credential_id),
+            response: AttestationResponse {
+                client_data_json: client_data("webauthn.create", CHALLENGE, ORIGIN),
+                attestation_object: URL_SAFE_NO_PAD.encode(attestation_object),
+            },
+        }
+    }
+
+    fn assert(key: &SigningKey, passkey: &Passkey, client_data_json: String, auth_data: Vec<u8>) -> AssertionResponse {
+        let mut message = auth_data.clone();
+        message.extend_from_slice(&Sha256::digest(URL_SAFE_NO_PAD.decode(&client_data_json).unwrap()));
+        let signature: Signature = key.sign(&message);
+        AssertionResponse {
+            id: passkey.credential_id.clone(),
+            response: AuthenticatorAssertion {
+                client_data_json,
+                authenticator_data: URL_SAFE_NO_PAD.encode(auth_data),
+                signature: URL_SAFE_NO_PAD.encode(signature.to_der().as_bytes()),
+                user_handle: Some(user_handle(&passkey.faculty_id)),
+            },
+        }
+    }
+
+    #[test]
+    fn relying_party_id_is_the_bare_host() {
+        assert_eq!(rp().id, "door.example.edu");
+        assert_eq!(RelyingParty::from_origin("http://localhost:3000/").unwrap().id, "localhost");
+        assert_eq!(RelyingParty::from_origin("localhost:3000"), None);
+    }
+
+    #[test]
+    fn registered_passkey_signs_in() {
+        let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let passkey = rp().finish_registration(&register(&key, b"cred-1"), CHALLENGE, "Fac1", 0).unwrap();
+        assert_eq!(passkey.credential_id, URL_SAFE_NO_PAD.encode(b"cred-1"));
+
+        let flags = USER_PRESENT | USER_VERIFIED;
+        let login = assert(&key, &passkey, client_data("webauthn.get", CHALLENGE, ORIGIN), authenticator_data("door.example.edu", flags, 5));
+        assert_eq!(rp().verify_assertion(&login, CHALLENGE, &passkey), Ok(5));
+        // Replayed once the counter has moved on
+        let seen = Passkey { sign_count: 5, ..passkey.clone() };
+        assert_eq!(rp().verify_assertion(&login, CHALLENGE, &seen), Err(WebAuthnError::CounterRegressed));
+        assert_eq!(rp().verify_assertion(&login, "b3RoZXI", &passkey), Err(WebAuthnError::ChallengeMismatch));
+
+        let other_key = SigningKey::from_bytes(&[9u8; 32].into()).unwrap();
+        let forged = assert(&other_key, &passkey, client_data("webauthn.get", CHALLENGE, ORIGIN), authenticator_data("door.example.edu", flags, 6));
+        assert_eq!(rp().verify_assertion(&forged, CHALLENGE, &passkey), Err(WebAuthnError::BadSignature));
+    }
+
+    #[test]
+    fn phished_or_unverified_assertions_are_rejected() {
+        let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let passkey = rp().finish_registration(&register(&key, b"cred-1"), CHALLENGE, "Fac1", 0).unwrap();
+        let flags = USER_PRESENT | USER_VERIFIED;
+
+        let phished = assert(&key, &passkey, client_data("webauthn.get", CHALLENGE, "https://door-example.evil"), authenticator_data("door.example.edu", flags, 1));
+        assert_eq!(rp().verify_assertion(&phished, CHALLENGE, &passkey), Err(WebAuthnError::OriginMismatch));
+        let other_rp = assert(&key, &passkey, client_data("webauthn.get", CHALLENGE, ORIGIN), authenticator_data("example.edu", flags, 1));
+        assert_eq!(rp().verify_assertion(&other_rp, CHALLENGE, &passkey), Err(WebAuthnError::RpIdMismatch));
+        let no_pin = assert(&key, &passkey, client_data("webauthn.get", CHALLENGE, ORIGIN), authenticator_data("door.example.edu", USER_PRESENT, 1));
+        assert_eq!(rp().verify_assertion(&no_pin, CHALLENGE, &passkey), Err(WebAuthnError::UserNotVerified));
+        let registration = assert(&key, &passkey, client_data("webauthn.create", CHALLENGE, ORIGIN), authenticator_data("door.example.edu", flags, 1));
+        assert_eq!(rp().verify_assertion(&registration, CHALLENGE, &passkey), Err(WebAuthnError::WrongCeremony));
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use tokio::sync::broadcast;
+
+use crate::storage::AccessHistory;
+
+// First retry waits this long, each later one twice as long as the one before
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+pub const SIGNATURE_HEADER: &str = "x-privaccess-signature";
+pub const TIMESTAMP_HEADER: &str = "x-privaccess-timestamp";
+pub const EVENT_HEADER: &str = "x-privaccess-event";
+pub const TENANT_HEADER: &str = "x-privaccess-tenant";
+
+// HMAC key receivers check deliveries with. Without PRIVACCESS_WEBHOOK_SECRET deliveries go
+// unsigned, since a key made up at boot is one no receiver could know.
+static WEBHOOK_SECRET: Lazy<Option<Vec<u8>>> = Lazy::new(|| {
+    std::env::var("PRIVACCESS_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty()).map(String::into_bytes)
+});
+
+/// Every grant and denial as (tenant, entry), with its log id filled in.
+pub static ACCESS_EVENTS: Lazy<broadcast::Sender<(String, AccessHistory)>> = Lazy::new(|| {
+    let (tx, _) = broadcast::channel(100);
+    tx
+});
+
+/// `access.granted` or `access.denied`; None for log entries that aren't access decisions,
+/// such as room lookups.
+pub fn event_name(entry: &AccessHistory) -> Option<&'static str> {
+    if entry.status == "GRANTED" {
+        Some("access.granted")
+    } else if entry.status.starts_with("DENIED") {
+        Some("access.denied")
+    } else {
+        None
+    }
+}
+
+/// Announce a logged entry if it's an access decision. Never waits on anyone listening.
+pub fn publish(tenant: &str, entry: &AccessHistory) {
+    if event_name(entry).is_some() {
+        let _ = ACCESS_EVENTS.send((tenant.to_string(), entry.clone()));
+    }
+}
+
+/// `sha256=` and the hex HMAC of `{timestamp}.{body}`. Receivers recompute it, and can refuse
+/// old timestamps so a captured delivery can't be replayed.
+pub fn signature(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// An HTTP client for deliveries. reqwest leaves the TLS crypto to rustls's process-wide
+/// provider, so install the one the HTTPS listener already uses if nothing else has.
+pub fn client(timeout: Duration) -> Result<reqwest::Client, reqwest::Error> {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    reqwest::Client::builder().timeout(timeout).build()
+}
+
+/// POST each access event to PRIVACCESS_WEBHOOK_URL, if set. Call once at startup, from within
+/// the runtime. Every delivery runs on its own task, so a slow receiver holds up nothing else.
+pub fn start() {
+    let config = &crate::config::CONFIG;
+    let Some(url) = config.webhook_url.clone() else {
+        return;
+    };
+    if WEBHOOK_SECRET.is_none() {
+        tracing::warn!("PRIVACCESS_WEBHOOK_SECRET is not set, webhook deliveries are unsigned");
+    }
+    let client = match client(Duration::from_secs(config.webhook_timeout_secs)) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!(error = %e, "Webhook client unavailable, access events won't be sent");
+            return;
+        }
+    };
+
+    let mut rx = ACCESS_EVENTS.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let (tenant, entry) = match rx.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!(missed = n, "Webhook missed access events");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let (client, url) = (client.clone(), url.clone());
+            tokio::spawn(async move {
+                let secret = WEBHOOK_SECRET.as_deref();
+                if let Err(e) = deliver(&client, &url, secret, &tenant, &entry, config.webhook_retries).await {
+                    tracing::warn!(log_id = entry.id, error = %e, "Webhook gave up on log entry");
+                }
+            });
+        }
+    });
+}
+
+/// Send one entry, retrying with backoff until it gets a 2xx or runs out of retries.
+pub async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    secret: Option<&[u8]>,
+    tenant: &str,
+    entry: &AccessHistory,
+    retries: u32,
+) -> Result<(), String> {
+    let event = event_name(entry).unwrap_or("access.logged");
+    let body = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        // Re-signed each attempt, so the timestamp says when this one was sent
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let mut request = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event)
+            .header(TENANT_HEADER, tenant)
+            .header(TIMESTAMP_HEADER, timestamp);
+        if let Some(secret) = secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, timestamp, &body));
+        }
+        let error = match request.body(body.clone()).send().await {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => format!("HTTP {}", res.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt >= retries {
+            return Err(error);
+        }
+        tracing::debug!(url = %url, attempt, error = %error, "Webhook delivery failed, retrying");
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use axum::http::{HeaderMap, StatusCode};
+
+    fn entry(status: &str) -> AccessHistory {
+        AccessHistory {
+            id: 7,
+            role: "STUDENT".to_string(),
+            door_name: "Room 101".to_string(),
+            section: "A".to_string(),
+            timestamp: "2026-03-02T09:00:00Z".to_string(),
+            status: status.to_string(),
+            faculty_name: None,
+            faculty_id: None,
+            actor_id: None,
+            denial_reason: None,
+        }
+    }
+
+    #[test]
+    fn only_grants_and_denials_are_events() {
+        assert_eq!(event_name(&entry("GRANTED")), Some("access.granted"));
+        assert_eq!(event_name(&entry("DENIED: QR Expired")), Some("access.denied"));
+        assert_eq!(event_name(&entry("ASSIGNMENT FETCHED")), None);
+    }
+
+    #[tokio::test]
+    async fn delivery_is_signed_and_retried_until_accepted() {
+        // Refuses the first delivery, then records what it's sent
+        let received: Arc<Mutex<Vec<(HeaderMap, axum::body::Bytes)>>> = Arc::default();
+        let log = received.clone();
+        let receiver = axum::Router::new().route("/hook", axum::routing::post(move |headers: HeaderMap, body: axum::body::Bytes| {
+            let log = log.clone();
+            async move {
+                let mut log = log.lock().unwrap();
+                log.push((headers, body));
+                if log.len() == 1 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::NO_CONTENT }
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let client = client(Duration::from_secs(5)).unwrap();
+        deliver(&client, &url, Some(b"shared"), "north", &entry("GRANTED"), 1).await.unwrap();
+
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            let (headers, body) = &received[1];
+            assert_eq!(headers[EVENT_HEADER], "access.granted");
+            assert_eq!(headers[TENANT_HEADER], "north");
+            let timestamp: u64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+            assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), signature(b"shared", timestamp, body));
+            assert_eq!(serde_json::from_slice::<serde_json::Value>(body).unwrap()["id"], 7);
+        }
+
+        // Out of retries, the last failure is reported
+        let refused = deliver(&client, "http://127.0.0.1:9/hook", None, "north", &entry("GRANTED"), 0).await;
+        assert!(refused.is_err());
+    }
+}
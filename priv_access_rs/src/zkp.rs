@@ -1,100 +1,555 @@
-use crate::crypto::{P, G, Q, power_mod};
+use crate::crypto::{CryptoError, P, Q, power_g, power_mod, get_random_secret};
+use crate::geo::GeoError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use k256::elliptic_curve::bigint::U256;
+use k256::elliptic_curve::group::Group;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::elliptic_curve::{Field, PrimeField};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar};
 use num_bigint::BigUint;
 use num_traits::Num;
+use once_cell::sync::Lazy;
 use sha2::{Sha256, Digest};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+// Only this many leading geohash characters (roughly 5m x 5m) go into the challenge
+pub const CHALLENGE_GEOHASH_LEN: usize = 9;
+
+/// The part of a proof's geohash that goes into its challenge. Provers and verifiers both go
+/// through this, so a longer or shorter fix on either side can't make them hash different cells.
+pub fn challenge_geohash(geohash: &str) -> Result<&str, VerifyError> {
+    crate::geo::prefix(geohash, CHALLENGE_GEOHASH_LEN).map_err(VerifyError::Geohash)
+}
+
+// Server-issued challenge nonces are 16 random bytes in hex
+pub const CHALLENGE_NONCE_LEN: usize = 32;
+
+/// Which group a proof is made in. Clients that predate the field send modp proofs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofScheme {
+    /// The 2048-bit multiplicative group, numbers as decimal strings
+    #[default]
+    Modp,
+    /// secp256k1, points and scalars as hex (see `EccSchnorr`)
+    Secp256k1,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Proof {
+    #[serde(default)]
+    pub scheme: ProofScheme,
     pub public_key: String,
     pub commitment: String,
     pub response: String,
     pub geohash: String, // New: geohash as part of the proof
+    /// Single-use nonce from `GET /api/challenge`, appended to the challenge input so a proof
+    /// only ever unlocks once. Empty for proofs made before nonces existed.
+    #[serde(default)]
+    pub nonce: String,
+    /// Unix time the proof was made, also bound into the challenge. Servers refuse proofs
+    /// outside their `ProofWindow`.
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// How far a proof's timestamp may be from the verifier's clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProofWindow {
+    /// Oldest proof accepted, in seconds
+    pub max_age_secs: u64,
+    /// How far a prover's clock may be off, either way
+    pub max_skew_secs: u64,
+}
+
+impl ProofWindow {
+    pub fn check(&self, proof: &Proof, now: u64) -> Result<(), VerifyError> {
+        if proof.timestamp > now.saturating_add(self.max_skew_secs) {
+            return Err(VerifyError::FromFuture { ahead_secs: proof.timestamp - now });
+        }
+        // A clock running behind makes a fresh proof look older
+        let age = now.saturating_sub(proof.timestamp);
+        if age > self.max_age_secs.saturating_add(self.max_skew_secs) {
+            return Err(VerifyError::Expired { age_secs: age, max_secs: self.max_age_secs });
+        }
+        Ok(())
+    }
+}
+
+/// Schnorr signature over an arbitrary message, in the same group as the access proofs.
+/// The challenge is c = Hash(R, Y, message), exactly as for a proof with the message in
+/// place of the geohash.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Signature {
+    pub commitment: String,
+    pub response: String,
+}
+
+// Key the server signs its responses with. Set PRIVACCESS_SIGNING_KEY (a decimal secret below Q)
+// to keep the public key stable across restarts, otherwise a fresh one is generated on every boot.
+pub static SERVER_SIGNER: Lazy<SchnorrProver> = Lazy::new(|| {
+    let configured = std::env::var("PRIVACCESS_SIGNING_KEY").ok().filter(|k| !k.trim().is_empty());
+    let secret = match configured.map(|k| BigUint::from_str_radix(k.trim(), 10)) {
+        Some(Ok(k)) if k > BigUint::from(1u32) && k < *Q => k,
+        Some(_) => {
+            println!("WARNING: PRIVACCESS_SIGNING_KEY is not a number in [2, Q), using a random signing key");
+            get_random_secret()
+        }
+        None => get_random_secret(),
+    };
+    SchnorrProver::new(secret)
+});
+
+fn challenge(r_comm: &BigUint, y: &BigUint, message: &[u8]) -> BigUint {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}{}", r_comm, y).as_bytes());
+    hasher.update(message);
+    BigUint::from_bytes_be(&hasher.finalize()) % &*Q
+}
+
+/// Why a proof was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// A number field is longer than the configured maximum
+    FieldTooLong { field: &'static str, max: usize },
+    /// A number field doesn't parse: not decimal for modp, not hex of the right length for secp256k1
+    Malformed { field: &'static str },
+    /// A number is outside its valid range (`s` in `[0, Q)`, `R` and `Y` in `[2, P-1]`)
+    OutOfRange { field: &'static str },
+    /// `R` or `Y` is in range but outside the order-Q subgroup, e.g. the order-2 element P-1
+    NotInSubgroup { field: &'static str },
+    /// The geohash bound into the challenge isn't a geohash
+    Geohash(GeoError),
+    /// The numbers parse but G^s != R * Y^c (mod P)
+    EquationMismatch,
+    /// The proof was made longer ago than the window allows
+    Expired { age_secs: u64, max_secs: u64 },
+    /// The proof's timestamp is ahead of the verifier's clock by more than the allowed skew
+    FromFuture { ahead_secs: u64 },
+    /// The configured modp group doesn't parse, so no modp proof can be checked
+    Params(CryptoError),
+}
+
+impl VerifyError {
+    /// Stable identifier for API clients and logs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            VerifyError::FieldTooLong { .. } => "proof_field_too_long",
+            VerifyError::Malformed { .. } => "proof_malformed",
+            VerifyError::OutOfRange { .. } => "proof_out_of_range",
+            VerifyError::NotInSubgroup { .. } => "proof_not_in_subgroup",
+            VerifyError::Geohash(e) => e.code(),
+            VerifyError::EquationMismatch => "proof_mismatch",
+            VerifyError::Expired { .. } => "proof_expired",
+            VerifyError::FromFuture { .. } => "proof_from_future",
+            VerifyError::Params(_) => "crypto_params_invalid",
+        }
+    }
+
+    /// The proof field a malformed-input error is about.
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            VerifyError::FieldTooLong { field, .. }
+            | VerifyError::Malformed { field }
+            | VerifyError::OutOfRange { field }
+            | VerifyError::NotInSubgroup { field } => Some(field),
+            VerifyError::Geohash(_) => Some("geohash"),
+            _ => None,
+        }
+    }
+
+    /// Malformed input is the client's fault; a proof that doesn't check out, or is stale, is a
+    /// denial. A misconfigured group is neither.
+    pub fn is_bad_request(&self) -> bool {
+        !matches!(self, VerifyError::EquationMismatch | VerifyError::Expired { .. } | VerifyError::FromFuture { .. } | VerifyError::Params(_))
+    }
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::FieldTooLong { field, max } => write!(f, "Proof field '{}' exceeds {} characters", field, max),
+            VerifyError::Malformed { field } => write!(f, "Proof field '{}' is not a valid number for its scheme", field),
+            VerifyError::OutOfRange { field } => write!(f, "Proof field '{}' is out of range", field),
+            VerifyError::NotInSubgroup { field } => write!(f, "Proof field '{}' is not in the prime-order subgroup", field),
+            VerifyError::Geohash(e) => write!(f, "Invalid proof location: {}", e),
+            VerifyError::EquationMismatch => write!(f, "Invalid Zero-Knowledge Proof"),
+            VerifyError::Expired { age_secs, max_secs } => write!(f, "Proof is {}s old, older than the {}s allowed", age_secs, max_secs),
+            VerifyError::FromFuture { ahead_secs } => write!(f, "Proof is timestamped {}s in the future; check the device clock", ahead_secs),
+            VerifyError::Params(e) => write!(f, "The proof group is misconfigured: {}", e),
+        }
+    }
+}
+
+impl Proof {
+    /// Reject oversized numbers before they are parsed: a multi-megabyte decimal string
+    /// would cost far more to parse and exponentiate than any real proof.
+    pub fn check_field_sizes(&self, max_len: usize) -> Result<(), VerifyError> {
+        let fields = [
+            ("public_key", &self.public_key),
+            ("commitment", &self.commitment),
+            ("response", &self.response),
+        ];
+        for (field, value) in fields {
+            if value.len() > max_len {
+                return Err(VerifyError::FieldTooLong { field, max: max_len });
+            }
+        }
+        if self.nonce.len() > CHALLENGE_NONCE_LEN {
+            return Err(VerifyError::FieldTooLong { field: "nonce", max: CHALLENGE_NONCE_LEN });
+        }
+        Ok(())
+    }
+
+    /// The proof in its compact form, for QR codes: base64url (unpadded) over
+    ///
+    /// ```text
+    /// version (1) | scheme (1) | timestamp (8) | public_key | commitment | response | geohash | nonce
+    /// ```
+    ///
+    /// Numbers are fixed width and big-endian: for modp, `public_key` and `commitment` take as
+    /// many bytes as P and `response` as many as Q; for secp256k1 the points are compressed SEC1
+    /// (33 bytes) and the response 32 bytes. The geohash and nonce are prefixed by their length
+    /// as one byte. A 2048-bit modp proof is about 1,100 characters against 1,900 as JSON.
+    pub fn to_compact(&self) -> Result<String, VerifyError> {
+        let mut out = vec![COMPACT_VERSION, match self.scheme {
+            ProofScheme::Modp => 0,
+            ProofScheme::Secp256k1 => 1,
+        }];
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        let widths = compact_widths(self.scheme)?;
+        let numbers = [("public_key", &self.public_key), ("commitment", &self.commitment), ("response", &self.response)];
+        for ((field, value), width) in numbers.into_iter().zip(widths) {
+            let bytes = match self.scheme {
+                ProofScheme::Modp => parse_field(field, value)?.to_bytes_be(),
+                ProofScheme::Secp256k1 => hex::decode(value).map_err(|_| VerifyError::Malformed { field })?,
+            };
+            if bytes.len() > width {
+                return Err(VerifyError::OutOfRange { field });
+            }
+            out.resize(out.len() + width - bytes.len(), 0);
+            out.extend_from_slice(&bytes);
+        }
+        for (field, text) in [("geohash", &self.geohash), ("nonce", &self.nonce)] {
+            let len = u8::try_from(text.len()).map_err(|_| VerifyError::FieldTooLong { field, max: u8::MAX as usize })?;
+            out.push(len);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Ok(URL_SAFE_NO_PAD.encode(out))
+    }
+
+    /// Read a proof back from `to_compact`'s form. Only the layout is checked here; the numbers
+    /// are range-checked when the proof is verified, as for JSON.
+    pub fn from_compact(compact: &str) -> Result<Proof, VerifyError> {
+        let malformed = VerifyError::Malformed { field: "proof" };
+        let bytes = URL_SAFE_NO_PAD.decode(compact.trim()).map_err(|_| malformed.clone())?;
+        let mut rest = bytes.as_slice();
+        let mut take = |n: usize| -> Result<&[u8], VerifyError> {
+            if rest.len() < n {
+                return Err(malformed.clone());
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+        if take(1)?[0] != COMPACT_VERSION {
+            return Err(malformed.clone());
+        }
+        let scheme = match take(1)?[0] {
+            0 => ProofScheme::Modp,
+            1 => ProofScheme::Secp256k1,
+            _ => return Err(malformed.clone()),
+        };
+        let timestamp = u64::from_be_bytes(take(8)?.try_into().unwrap());
+        let [public_key, commitment, response] = compact_widths(scheme)?.map(|width| take(width).map(|bytes| match scheme {
+            ProofScheme::Modp => BigUint::from_bytes_be(bytes).to_string(),
+            ProofScheme::Secp256k1 => hex::encode(bytes),
+        }));
+        let (public_key, commitment, response) = (public_key?, commitment?, response?);
+        let mut text = || -> Result<String, VerifyError> {
+            let len = take(1)?[0] as usize;
+            String::from_utf8(take(len)?.to_vec()).map_err(|_| malformed.clone())
+        };
+        let (geohash, nonce) = (text()?, text()?);
+        if !rest.is_empty() {
+            return Err(malformed);
+        }
+        Ok(Proof { scheme, public_key, commitment, response, geohash, nonce, timestamp })
+    }
+
+    /// Parse every field as its scheme requires (decimal numbers for modp, hex points and
+    /// scalars for secp256k1) and range-check it, without doing the expensive check itself.
+    /// An `Err` here is always a malformed proof, never a wrong one.
+    pub fn check_format(&self) -> Result<(), VerifyError> {
+        match self.scheme {
+            ProofScheme::Modp => ModpStatement::parse(self).map(|_| ()),
+            ProofScheme::Secp256k1 => EccSchnorr::statement(self).map(|_| ()),
+        }
+    }
+}
+
+// First byte of a compact proof; a new layout gets a new version
+const COMPACT_VERSION: u8 = 1;
+
+// Bytes for (public_key, commitment, response) in a compact proof
+fn compact_widths(scheme: ProofScheme) -> Result<[usize; 3], VerifyError> {
+    match scheme {
+        ProofScheme::Modp => {
+            let params = crate::crypto::params().map_err(VerifyError::Params)?;
+            let (p, q) = (params.p.bits().div_ceil(8) as usize, params.q.bits().div_ceil(8) as usize);
+            Ok([p, p, q])
+        }
+        ProofScheme::Secp256k1 => Ok([33, 33, 32]),
+    }
+}
+
+fn parse_field(field: &'static str, value: &str) -> Result<BigUint, VerifyError> {
+    BigUint::from_str_radix(value, 10).map_err(|_| VerifyError::Malformed { field })
 }
 
 pub struct SchnorrVerifier;
 
 impl SchnorrVerifier {
+    /// Verify a fresh proof: its timestamp must fall in `window` and the proof itself must check out.
+    pub fn verify_proof_within(proof: &Proof, window: &ProofWindow) -> Result<(), VerifyError> {
+        window.check(proof, unix_now())?;
+        Self::verify_proof(proof)
+    }
+
+    /// Verify a proof in whichever scheme it declares, however old. Re-checks of stored proofs
+    /// use this; live access decisions go through `verify_proof_within`.
+    pub fn verify_proof(proof: &Proof) -> Result<(), VerifyError> {
+        proof.check_field_sizes(crate::config::CONFIG.max_proof_field_len)?;
+        match proof.scheme {
+            ProofScheme::Modp => Self::verify_modp(proof),
+            ProofScheme::Secp256k1 => EccSchnorr::verify(proof),
+        }
+    }
+
+    /// The challenge `c` the verifier derives from `proof`: decimal for modp, 32-byte hex for
+    /// secp256k1. Lets a client check its own challenge hashing before it sends a proof.
+    pub fn challenge(proof: &Proof) -> Result<String, VerifyError> {
+        proof.check_field_sizes(crate::config::CONFIG.max_proof_field_len)?;
+        match proof.scheme {
+            ProofScheme::Modp => Ok(ModpStatement::parse(proof)?.c.to_string()),
+            ProofScheme::Secp256k1 => EccSchnorr::statement(proof).map(|(_, _, _, c)| hex::encode(c.to_bytes())),
+        }
+    }
+
     /// Verify the ZK Proof.
     /// Proof contains: { "public_key": Y, "commitment": R, "response": s }
     /// Verification Equation: G^s == R * Y^c  (mod P)
-    /// Where c = Hash(R, Y)
-    pub fn verify_proof(proof: &Proof) -> bool {
-        let y = match BigUint::from_str_radix(&proof.public_key, 10) {
-            Ok(val) => val,
-            Err(_) => return false,
-        };
-        let r_comm = match BigUint::from_str_radix(&proof.commitment, 10) {
-            Ok(val) => val,
-            Err(_) => return false,
-        };
-        let s = match BigUint::from_str_radix(&proof.response, 10) {
-            Ok(val) => val,
-            Err(_) => return false,
+    /// Where c = Hash(R, Y, geohash prefix, nonce, timestamp)
+    fn verify_modp(proof: &Proof) -> Result<(), VerifyError> {
+        let statement = ModpStatement::parse(proof)?;
+        tracing::debug!(geofence = %statement.geohash_prefix, challenge_input = %statement.challenge_input, c = %statement.c, "Verifying modp proof");
+
+        // 2. Compute LHS: G^s mod P, from the precomputed table since G is fixed
+        let lhs = power_g(&statement.s);
+
+        // 3. Compute RHS: R * Y^c mod P. Y varies, but c is a 256-bit hash, so this is the cheap
+        // half; modpow already works in Montgomery form for an odd modulus
+        let rhs_part2 = power_mod(&statement.y, &statement.c, &P);
+        let rhs = (&statement.r_comm * &rhs_part2) % &*P;
+
+        tracing::debug!(%lhs, %rhs, "Verification equation");
+
+        // 4. Check Equality
+        let is_valid = lhs == rhs;
+        tracing::debug!(scheme = "modp", result = if is_valid { "passed" } else { "failed" }, "Verification result");
+        if is_valid {
+            Ok(())
+        } else {
+            Err(VerifyError::EquationMismatch)
+        }
+    }
+
+    /// Check many proofs at once, e.g. stored proofs when the dashboard loads history; one
+    /// result per proof, in order. Modp proofs are checked together with a random linear
+    /// combination, G^(sum z_i*s_i) == prod R_i^z_i * Y_i^(z_i*c_i), which costs one full-size
+    /// exponentiation instead of one per proof. A failing batch is split in half until the bad
+    /// proofs are found, so one bad proof doesn't hide the rest.
+    pub fn verify_batch(proofs: &[Proof]) -> Vec<bool> {
+        let max_len = crate::config::CONFIG.max_proof_field_len;
+        let mut results = vec![false; proofs.len()];
+        let mut batch = Vec::new();
+        for (i, proof) in proofs.iter().enumerate() {
+            if proof.check_field_sizes(max_len).is_err() {
+                continue;
+            }
+            match proof.scheme {
+                ProofScheme::Secp256k1 => results[i] = EccSchnorr::verify(proof).is_ok(),
+                // Parsing checks R and Y are in the prime-order subgroup, where the combination is sound
+                ProofScheme::Modp => {
+                    if let Ok(statement) = ModpStatement::parse(proof) {
+                        batch.push((i, statement));
+                    }
+                }
+            }
+        }
+        settle_batch(&batch, &mut results);
+        results
+    }
+
+    /// Check a signature made by `SchnorrProver::sign`. The server never needs this itself;
+    /// it's what a client holding the server's public key runs.
+    pub fn verify_signature(public_key: &BigUint, message: &[u8], signature: &Signature) -> bool {
+        if crate::crypto::params().is_err() {
+            return false;
+        }
+        let (Ok(r_comm), Ok(s)) = (
+            BigUint::from_str_radix(&signature.commitment, 10),
+            BigUint::from_str_radix(&signature.response, 10),
+        ) else {
+            return false;
         };
+        if r_comm < BigUint::from(2u32) || r_comm >= *P || s >= *Q || crate::crypto::jacobi(&r_comm, &P) != 1 {
+            return false;
+        }
+        let c = challenge(&r_comm, public_key, message);
+        power_g(&s) == (&r_comm * power_mod(public_key, &c, &P)) % &*P
+    }
+}
 
-        // 1. Recompute Challenge c = Hash(R, Y, geohash)
-        let geohash_prefix = if proof.geohash.len() >= 9 { &proof.geohash[0..9] } else { &proof.geohash };
-        println!("TERMINAL: [ZKP] Verifying Identity for geofence: {}", geohash_prefix);
-        
-        let challenge_input = format!("{}{}{}", r_comm, y, geohash_prefix);
-        println!("TERMINAL: [ZKP] Challenge Input (Rust): {}", challenge_input);
+// Random weights this wide make a bad proof slip through a batch with probability 2^-128
+const BATCH_WEIGHT_BITS: u64 = 128;
+
+// A range-checked modp proof and its recomputed challenge
+struct ModpStatement {
+    y: BigUint,
+    r_comm: BigUint,
+    s: BigUint,
+    c: BigUint,
+    geohash_prefix: String,
+    challenge_input: String,
+}
+
+impl ModpStatement {
+    fn parse(proof: &Proof) -> Result<Self, VerifyError> {
+        // Every modp check goes through here, so a bad configured group is an error, not a panic
+        crate::crypto::params().map_err(VerifyError::Params)?;
+        let y = parse_field("public_key", &proof.public_key)?;
+        let r_comm = parse_field("commitment", &proof.commitment)?;
+        let s = parse_field("response", &proof.response)?;
+
+        // 0. Range checks. 0 and 1 are degenerate (Y = 1 makes the equation independent of the
+        // secret), values >= P aren't reduced, and an honest prover always reduces s mod Q.
+        let two = BigUint::from(2u32);
+        let p_minus_one = &*P - 1u32;
+        if y < two || y > p_minus_one {
+            return Err(VerifyError::OutOfRange { field: "public_key" });
+        }
+        if r_comm < two || r_comm > p_minus_one {
+            return Err(VerifyError::OutOfRange { field: "commitment" });
+        }
+        if s >= *Q {
+            return Err(VerifyError::OutOfRange { field: "response" });
+        }
+        // Outside the order-Q subgroup, Y^c only takes a few values (P-1 gives just ±1), so a
+        // proof can be forged without the secret. For a safe prime the subgroup is exactly the
+        // quadratic residues, so the Jacobi symbol decides membership as Y^Q == 1 would, far cheaper.
+        if crate::crypto::jacobi(&y, &P) != 1 {
+            return Err(VerifyError::NotInSubgroup { field: "public_key" });
+        }
+        if crate::crypto::jacobi(&r_comm, &P) != 1 {
+            return Err(VerifyError::NotInSubgroup { field: "commitment" });
+        }
+
+        // 1. Recompute Challenge c = Hash(R, Y, geohash, nonce, timestamp)
+        let geohash_prefix = challenge_geohash(&proof.geohash)?.to_string();
+        let challenge_input = format!("{}{}{}{}{}", r_comm, y, geohash_prefix, proof.nonce, proof.timestamp);
         let mut hasher = Sha256::new();
         hasher.update(challenge_input.as_bytes());
-        let result = hasher.finalize();
-        let c_hash = BigUint::from_bytes_be(&result);
-        let c = c_hash % &*Q;
-        println!("TERMINAL: [ZKP] Compute Challenge c = {}", c);
+        let c = BigUint::from_bytes_be(&hasher.finalize()) % &*Q;
+
+        Ok(ModpStatement { y, r_comm, s, c, geohash_prefix, challenge_input })
+    }
 
-        // 2. Compute LHS: G^s mod P
-        let lhs = power_mod(&G, &s, &P);
+    fn holds(&self) -> bool {
+        power_g(&self.s) == (&self.r_comm * power_mod(&self.y, &self.c, &P)) % &*P
+    }
+}
 
-        // 3. Compute RHS: R * Y^c mod P
-        let rhs_part2 = power_mod(&y, &c, &P);
-        let rhs = (&r_comm * &rhs_part2) % &*P;
+// Mark every proof in `batch` that verifies, bisecting whenever the combined check fails
+fn settle_batch(batch: &[(usize, ModpStatement)], results: &mut [bool]) {
+    match batch {
+        [] => {}
+        [(i, statement)] => results[*i] = statement.holds(),
+        _ if combination_holds(batch) => {
+            for (i, _) in batch {
+                results[*i] = true;
+            }
+        }
+        _ => {
+            let (left, right) = batch.split_at(batch.len() / 2);
+            settle_batch(left, results);
+            settle_batch(right, results);
+        }
+    }
+}
 
-        println!("TERMINAL: [ZKP] Verification EQUATION: LHS={} | RHS={}", lhs, rhs);
+fn combination_holds(batch: &[(usize, ModpStatement)]) -> bool {
+    use num_bigint::RandBigInt;
 
-        // 4. Check Equality
-        let is_valid = lhs == rhs;
-        println!("TERMINAL: [ZKP] Verification RESULT: {}", if is_valid { "PASSED" } else { "FAILED" });
-        is_valid
+    let mut rng = rand::thread_rng();
+    let mut s_sum = BigUint::from(0u32);
+    let mut rhs = BigUint::from(1u32);
+    for (_, statement) in batch {
+        let z = rng.gen_biguint(BATCH_WEIGHT_BITS) + 1u32;
+        s_sum = (s_sum + &z * &statement.s) % &*Q;
+        let zc = &z * &statement.c;
+        rhs = rhs * power_mod(&statement.r_comm, &z, &P) % &*P;
+        rhs = rhs * power_mod(&statement.y, &zc, &P) % &*P;
     }
+    power_g(&s_sum) == rhs
 }
 
-#[allow(dead_code)]
 pub struct SchnorrProver {
     private_key: BigUint,
     public_key: BigUint,
 }
 
-#[allow(dead_code)]
 impl SchnorrProver {
+    /// Panics if the configured group doesn't parse; `try_new` returns the error instead.
     pub fn new(private_key: BigUint) -> Self {
-        let public_key = power_mod(&G, &private_key, &P);
+        let public_key = power_g(&private_key);
         SchnorrProver {
             private_key,
             public_key,
         }
     }
 
-    pub fn generate_proof(&self, geohash: String) -> Proof {
-        use crate::crypto::Q;
-        use num_bigint::{RandBigInt, BigUint};
-        use num_traits::One;
-        
-        // 1. Random nonce r
-        let mut rng = rand::thread_rng();
-        let limit = &*Q - BigUint::one();
-        let start = num_traits::One::one();
-        let r = rng.gen_biguint_range(&start, &limit);
+    /// As `new`, or why the configured group (`PRIVACCESS_MODP_PRIME` / `PRIVACCESS_MODP_GENERATOR`)
+    /// can't be used.
+    pub fn try_new(private_key: BigUint) -> Result<Self, CryptoError> {
+        crate::crypto::params()?;
+        Ok(Self::new(private_key))
+    }
+
+    /// Proof bound to `geohash`, the server's challenge `nonce` and the current time.
+    pub fn generate_proof(&self, geohash: String, nonce: &str) -> Proof {
+        self.generate_proof_at(geohash, nonce, unix_now())
+    }
+
+    /// As `generate_proof`, stamped `timestamp` instead of the local time. Clients whose clock
+    /// is off pass `unix_now()` corrected by their offset from `GET /api/time`.
+    pub fn generate_proof_at(&self, geohash: String, nonce: &str, timestamp: u64) -> Proof {
+        // 1. Random nonce r, uniform in [1, Q)
+        let r = get_random_secret();
 
         // 2. Commitment R = G^r mod P
-        let r_comm = power_mod(&G, &r, &P);
+        let r_comm = power_g(&r);
 
-        // 3. Challenge c = Hash(R, Public Key, geohash_prefix)
-        let geohash_prefix = if geohash.len() >= 9 { &geohash[0..9] } else { &geohash };
-        let challenge_input = format!("{}{}{}", r_comm, self.public_key, geohash_prefix);
+        // 3. Challenge c = Hash(R, Public Key, geohash_prefix, nonce, timestamp)
+        // The verifier rejects anything that isn't a geohash, so an invalid one just yields a bad proof
+        let geohash_prefix = challenge_geohash(&geohash).unwrap_or(&geohash);
+        let challenge_input = format!("{}{}{}{}{}", r_comm, self.public_key, geohash_prefix, nonce, timestamp);
         let mut hasher = Sha256::new();
         hasher.update(challenge_input.as_bytes());
         let result = hasher.finalize();
@@ -107,11 +562,423 @@ impl SchnorrProver {
         let s = numerator % &*Q;
 
         Proof {
+            scheme: ProofScheme::Modp,
             public_key: self.public_key.to_string(),
             commitment: r_comm.to_string(),
             response: s.to_string(),
             geohash,
+            nonce: nonce.to_string(),
+            timestamp,
+        }
+    }
+
+    pub fn public_key(&self) -> &BigUint {
+        &self.public_key
+    }
+
+    /// Sign `message`: R = G^r, c = Hash(R, Y, message), s = r + c * x mod Q.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        let r = get_random_secret();
+        let r_comm = power_g(&r);
+        let c = challenge(&r_comm, &self.public_key, message);
+        let s = (&r + &c * &self.private_key) % &*Q;
+        Signature { commitment: r_comm.to_string(), response: s.to_string() }
+    }
+}
+ 
+/// Schnorr over secp256k1. Same proof shape as the modp scheme, but `public_key` and
+/// `commitment` are compressed SEC1 points (66 hex chars) and `response` is a 32-byte scalar
+/// in hex, so a proof is ~200 bytes instead of ~1.9KB and far cheaper to compute on a phone.
+/// Verification: s*G == R + c*Y, with c = SHA256(hex(R) || hex(Y) || geohash prefix || nonce || timestamp) mod n
+/// over the lowercase compressed encodings, with the timestamp in decimal.
+pub struct EccSchnorr;
+
+fn parse_point(field: &'static str, value: &str) -> Result<ProjectivePoint, VerifyError> {
+    let bytes = hex::decode(value).map_err(|_| VerifyError::Malformed { field })?;
+    let encoded = EncodedPoint::from_bytes(&bytes).map_err(|_| VerifyError::Malformed { field })?;
+    // Off-curve points and the point at infinity are both rejected
+    let point: ProjectivePoint = Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded))
+        .ok_or(VerifyError::OutOfRange { field })?
+        .into();
+    if bool::from(point.is_identity()) {
+        return Err(VerifyError::OutOfRange { field });
+    }
+    Ok(point)
+}
+
+fn parse_scalar(field: &'static str, value: &str) -> Result<Scalar, VerifyError> {
+    let bytes = hex::decode(value).map_err(|_| VerifyError::Malformed { field })?;
+    if bytes.len() != 32 {
+        return Err(VerifyError::Malformed { field });
+    }
+    // Values >= n are refused rather than reduced, like s >= Q in the modp scheme
+    Option::from(Scalar::from_repr(*FieldBytes::from_slice(&bytes))).ok_or(VerifyError::OutOfRange { field })
+}
+
+fn point_hex(point: &ProjectivePoint) -> String {
+    hex::encode(point.to_affine().to_encoded_point(true).as_bytes())
+}
+
+fn ecc_challenge(r_comm: &ProjectivePoint, y: &ProjectivePoint, geohash_prefix: &str, nonce: &str, timestamp: u64) -> Scalar {
+    let mut hasher = Sha256::new();
+    hasher.update(point_hex(r_comm).as_bytes());
+    hasher.update(point_hex(y).as_bytes());
+    hasher.update(geohash_prefix.as_bytes());
+    hasher.update(nonce.as_bytes());
+    hasher.update(timestamp.to_string().as_bytes());
+    <Scalar as Reduce<U256>>::reduce_bytes(&hasher.finalize())
+}
+
+impl EccSchnorr {
+    // Y, R, s and the recomputed challenge c
+    fn statement(proof: &Proof) -> Result<(ProjectivePoint, ProjectivePoint, Scalar, Scalar), VerifyError> {
+        let y = parse_point("public_key", &proof.public_key)?;
+        let r_comm = parse_point("commitment", &proof.commitment)?;
+        let s = parse_scalar("response", &proof.response)?;
+        let geohash_prefix = challenge_geohash(&proof.geohash)?;
+
+        let c = ecc_challenge(&r_comm, &y, geohash_prefix, &proof.nonce, proof.timestamp);
+        Ok((y, r_comm, s, c))
+    }
+
+    pub fn verify(proof: &Proof) -> Result<(), VerifyError> {
+        let (y, r_comm, s, c) = Self::statement(proof)?;
+        let is_valid = ProjectivePoint::GENERATOR * s == r_comm + y * c;
+        tracing::debug!(scheme = "secp256k1", result = if is_valid { "passed" } else { "failed" }, "Verification result");
+        if is_valid {
+            Ok(())
+        } else {
+            Err(VerifyError::EquationMismatch)
+        }
+    }
+
+    /// Prove knowledge of `secret` bound to `geohash`, `nonce` and the current time, the client side of `verify`.
+    pub fn prove(secret: &Scalar, geohash: &str, nonce: &str) -> Proof {
+        let y = ProjectivePoint::GENERATOR * secret;
+        let k = Scalar::random(&mut rand::rngs::OsRng);
+        let r_comm = ProjectivePoint::GENERATOR * k;
+        let timestamp = unix_now();
+        let geohash_prefix = challenge_geohash(geohash).unwrap_or(geohash);
+        let c = ecc_challenge(&r_comm, &y, geohash_prefix, nonce, timestamp);
+        let s = k + c * secret;
+        Proof {
+            scheme: ProofScheme::Secp256k1,
+            public_key: point_hex(&y),
+            commitment: point_hex(&r_comm),
+            response: hex::encode(s.to_bytes()),
+            geohash: geohash.to_string(),
+            nonce: nonce.to_string(),
+            timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::G;
+    use proptest::prelude::*;
+
+    const NONCE: &str = "0123456789abcdef0123456789abcdef";
+    const GEOHASH: &str = "tepg7vtny";
+
+    fn prove(x: u64) -> Proof {
+        SchnorrProver::new(BigUint::from(x)).generate_proof(GEOHASH.to_string(), NONCE)
+    }
+
+    // Change the last digit of a decimal field, keeping it a number of the same size
+    fn bump(field: &mut String) {
+        let last = field.pop().unwrap();
+        field.push(if last == '1' { '2' } else { '1' });
+    }
+
+    #[test]
+    fn honest_proof_verifies() {
+        assert_eq!(SchnorrVerifier::verify_proof(&prove(123_456_789)), Ok(()));
+        let random = SchnorrProver::new(get_random_secret()).generate_proof(GEOHASH.to_string(), NONCE);
+        assert_eq!(SchnorrVerifier::verify_proof(&random), Ok(()));
+    }
+
+    #[test]
+    fn a_valid_group_gives_a_prover_and_a_bad_one_is_the_servers_fault() {
+        let proof = SchnorrProver::try_new(BigUint::from(42u32)).unwrap().generate_proof(GEOHASH.to_string(), NONCE);
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Ok(()));
+        let misconfigured = VerifyError::Params(CryptoError::InvalidHex { field: "PRIVACCESS_MODP_PRIME" });
+        assert!(!misconfigured.is_bad_request());
+        assert_eq!(misconfigured.code(), "crypto_params_invalid");
+    }
+
+    #[test]
+    fn honest_ecc_proof_verifies() {
+        let proof = EccSchnorr::prove(&Scalar::random(&mut rand::rngs::OsRng), GEOHASH, NONCE);
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Ok(()));
+    }
+
+    #[test]
+    fn compact_form_round_trips_to_the_same_json() {
+        let ecc = EccSchnorr::prove(&Scalar::random(&mut rand::rngs::OsRng), GEOHASH, NONCE);
+        for proof in [prove(42), prove(123_456_789), ecc] {
+            let compact = proof.to_compact().unwrap();
+            let json = serde_json::to_string(&proof).unwrap();
+            assert!(compact.len() < json.len(), "{} >= {}", compact.len(), json.len());
+            let back = Proof::from_compact(&compact).unwrap();
+            assert_eq!(serde_json::to_string(&back).unwrap(), json);
+            assert_eq!(SchnorrVerifier::verify_proof(&back), Ok(()));
+        }
+
+        // Fixed width: a small number takes as many bytes as any other
+        let small = Proof { response: "7".to_string(), ..prove(42) };
+        assert_eq!(small.to_compact().unwrap().len(), prove(42).to_compact().unwrap().len());
+
+        let compact = prove(42).to_compact().unwrap();
+        assert_eq!(Proof::from_compact(&compact[..compact.len() - 4]).unwrap_err(), VerifyError::Malformed { field: "proof" });
+        assert_eq!(Proof::from_compact("not base64!").unwrap_err(), VerifyError::Malformed { field: "proof" });
+        let too_big = Proof { commitment: P.to_string() + "0", ..prove(42) };
+        assert_eq!(too_big.to_compact().unwrap_err(), VerifyError::OutOfRange { field: "commitment" });
+    }
+
+    #[test]
+    fn challenge_is_bound_to_the_nonce() {
+        let proof = prove(42);
+        let c = SchnorrVerifier::challenge(&proof).unwrap();
+        assert_eq!(SchnorrVerifier::challenge(&proof), Ok(c.clone()));
+        let other = SchnorrVerifier::challenge(&Proof { nonce: "other".to_string(), ..proof }).unwrap();
+        assert_ne!(c, other);
+
+        let ecc = EccSchnorr::prove(&Scalar::random(&mut rand::rngs::OsRng), GEOHASH, NONCE);
+        assert_eq!(SchnorrVerifier::challenge(&ecc).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn check_format_separates_malformed_from_wrong() {
+        let mut proof = prove(42);
+        bump(&mut proof.response);
+        assert_eq!(proof.check_format(), Ok(()));
+
+        proof.commitment = "12ab".to_string();
+        let err = proof.check_format().unwrap_err();
+        assert_eq!(err, VerifyError::Malformed { field: "commitment" });
+        assert!(err.is_bad_request());
+        assert_eq!(err.field(), Some("commitment"));
+
+        let mut ecc = EccSchnorr::prove(&Scalar::random(&mut rand::rngs::OsRng), GEOHASH, NONCE);
+        ecc.response.truncate(10);
+        assert_eq!(ecc.check_format(), Err(VerifyError::Malformed { field: "response" }));
+    }
+
+    #[test]
+    fn tampered_response_is_rejected() {
+        let mut proof = prove(42);
+        bump(&mut proof.response);
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+    }
+
+    #[test]
+    fn tampered_commitment_is_rejected() {
+        // R * G stays in the subgroup, so only the equation can catch it
+        let mut proof = prove(42);
+        let r_comm = BigUint::from_str_radix(&proof.commitment, 10).unwrap();
+        proof.commitment = (r_comm * &*G % &*P).to_string();
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+    }
+
+    #[test]
+    fn proof_for_another_key_is_rejected() {
+        let mut proof = prove(42);
+        proof.public_key = prove(43).public_key;
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+    }
+
+    #[test]
+    fn tampered_geohash_is_rejected() {
+        let mut proof = prove(42);
+        proof.geohash = "tepg7vtnz".to_string();
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+    }
+
+    #[test]
+    fn prover_and_verifier_agree_at_any_precision() {
+        for geohash in ["tepg", "tepg7vtny", "tepg7vtnyzzz"] {
+            let proof = SchnorrProver::new(BigUint::from(42u32)).generate_proof(geohash.to_string(), NONCE);
+            assert_eq!(SchnorrVerifier::verify_proof(&proof), Ok(()), "{}", geohash);
+            let ecc = EccSchnorr::prove(&Scalar::random(&mut rand::rngs::OsRng), geohash, NONCE);
+            assert_eq!(SchnorrVerifier::verify_proof(&ecc), Ok(()), "{}", geohash);
+        }
+        assert_eq!(challenge_geohash("tepg7vtnyzzz"), Ok("tepg7vtny"));
+        assert!(challenge_geohash("tépg7vtny").is_err());
+    }
+
+    #[test]
+    fn geohash_beyond_the_challenge_prefix_is_not_bound() {
+        let mut proof = prove(42);
+        proof.geohash = format!("{}zz", GEOHASH);
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Ok(()));
+    }
+
+    #[test]
+    fn tampered_nonce_or_timestamp_is_rejected() {
+        let mut proof = prove(42);
+        proof.nonce = "f".repeat(CHALLENGE_NONCE_LEN);
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+
+        let mut proof = prove(42);
+        proof.timestamp += 1;
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+    }
+
+    #[test]
+    fn non_numeric_fields_are_malformed() {
+        for field in ["public_key", "commitment", "response"] {
+            for bad in ["", "12ab", "-5", "0x1f", " 7", "1.5"] {
+                let mut proof = prove(42);
+                match field {
+                    "public_key" => proof.public_key = bad.to_string(),
+                    "commitment" => proof.commitment = bad.to_string(),
+                    _ => proof.response = bad.to_string(),
+                }
+                assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::Malformed { field }), "{} = {:?}", field, bad);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_values_are_out_of_range() {
+        let mut proof = prove(42);
+        proof.public_key = "1".to_string();
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::OutOfRange { field: "public_key" }));
+
+        let mut proof = prove(42);
+        proof.response = Q.to_string();
+        assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::OutOfRange { field: "response" }));
+    }
+
+    #[test]
+    fn responses_of_q_and_above_are_refused_even_when_the_equation_holds() {
+        // G has order Q, so s + Q satisfies G^s == R * Y^c just as s does; only the bound stops
+        // one honest proof turning into many
+        let honest = prove(42);
+        let s = BigUint::from_str_radix(&honest.response, 10).unwrap();
+        for shifted in [&s + &*Q, &s + &*Q * 2u32] {
+            let proof = Proof { response: shifted.to_string(), ..honest.clone() };
+            assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::OutOfRange { field: "response" }));
+            assert!(!SchnorrVerifier::verify_batch(&[proof])[0]);
+        }
+        // Q - 1 is in range, just not the right answer
+        let edge = Proof { response: (&*Q - 1u32).to_string(), ..honest };
+        assert_eq!(SchnorrVerifier::verify_proof(&edge), Err(VerifyError::EquationMismatch));
+
+        let signer = SchnorrProver::new(BigUint::from(42u32));
+        let signature = signer.sign(b"body");
+        let s = BigUint::from_str_radix(&signature.response, 10).unwrap();
+        let shifted = Signature { response: (s + &*Q).to_string(), ..signature.clone() };
+        assert!(SchnorrVerifier::verify_signature(signer.public_key(), b"body", &signature));
+        assert!(!SchnorrVerifier::verify_signature(signer.public_key(), b"body", &shifted));
+    }
+
+    #[test]
+    fn small_order_elements_are_rejected() {
+        // Y = P-1 has order 2, so Y^c is -1 for odd c. With R = -G^s the equation G^s == R * Y^c
+        // then holds for any s, without knowing a secret
+        let p_minus_one = &*P - 1u32;
+        let forged = (1u64..)
+            .map(|s| {
+                let r_comm = power_g(&BigUint::from(s)) * &p_minus_one % &*P;
+                Proof { public_key: p_minus_one.to_string(), commitment: r_comm.to_string(), response: s.to_string(), ..prove(42) }
+            })
+            .find(|proof| {
+                let input = format!("{}{}{}{}{}", proof.commitment, proof.public_key, &proof.geohash[..CHALLENGE_GEOHASH_LEN], proof.nonce, proof.timestamp);
+                let c = BigUint::from_bytes_be(&Sha256::digest(input.as_bytes())) % &*Q;
+                c.bit(0)
+            })
+            .unwrap();
+        assert_eq!(SchnorrVerifier::verify_proof(&forged), Err(VerifyError::NotInSubgroup { field: "public_key" }));
+
+        let mut proof = prove(42);
+        let r_comm = BigUint::from_str_radix(&proof.commitment, 10).unwrap();
+        proof.commitment = (r_comm * &p_minus_one % &*P).to_string();
+        let err = proof.check_format().unwrap_err();
+        assert_eq!(err, VerifyError::NotInSubgroup { field: "commitment" });
+        assert!(err.is_bad_request());
+        assert!(!SchnorrVerifier::verify_batch(&[proof])[0]);
+    }
+
+    #[test]
+    fn oversized_fields_are_rejected_before_parsing() {
+        let mut proof = prove(42);
+        proof.response = "9".repeat(crate::config::CONFIG.max_proof_field_len + 1);
+        assert!(matches!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::FieldTooLong { field: "response", .. })));
+    }
+
+    #[test]
+    fn every_rejection_has_its_own_code() {
+        let cases = [
+            (VerifyError::FieldTooLong { field: "response", max: 10 }, "proof_field_too_long", Some("response"), true),
+            (VerifyError::Malformed { field: "commitment" }, "proof_malformed", Some("commitment"), true),
+            (VerifyError::OutOfRange { field: "response" }, "proof_out_of_range", Some("response"), true),
+            (VerifyError::NotInSubgroup { field: "public_key" }, "proof_not_in_subgroup", Some("public_key"), true),
+            (VerifyError::Geohash(GeoError::InvalidChar('a')), "geohash_invalid_char", Some("geohash"), true),
+            (VerifyError::EquationMismatch, "proof_mismatch", None, false),
+            (VerifyError::Expired { age_secs: 90, max_secs: 60 }, "proof_expired", None, false),
+            (VerifyError::FromFuture { ahead_secs: 30 }, "proof_from_future", None, false),
+        ];
+        for (err, code, field, bad_request) in cases {
+            assert_eq!((err.code(), err.field(), err.is_bad_request()), (code, field, bad_request), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn batch_flags_only_the_tampered_proofs() {
+        let mut proofs: Vec<Proof> = (1..=5).map(prove).collect();
+        bump(&mut proofs[1].response);
+        proofs[3].geohash = "s00000000".to_string();
+        assert_eq!(SchnorrVerifier::verify_batch(&proofs), vec![true, false, true, false, true]);
+    }
+
+    #[test]
+    fn proof_window_rejects_stale_and_future_proofs() {
+        let window = ProofWindow { max_age_secs: 60, max_skew_secs: 10 };
+        let proof = prove(42);
+        assert_eq!(window.check(&proof, proof.timestamp + 60), Ok(()));
+        // The skew covers clocks running behind too
+        assert_eq!(window.check(&proof, proof.timestamp + 70), Ok(()));
+        assert!(matches!(window.check(&proof, proof.timestamp + 71), Err(VerifyError::Expired { .. })));
+        assert!(matches!(window.check(&proof, proof.timestamp - 11), Err(VerifyError::FromFuture { .. })));
+
+        let behind = SchnorrProver::new(BigUint::from(42u32)).generate_proof_at("t1q7hk9vj".to_string(), NONCE, proof.timestamp - 65);
+        assert_eq!(window.check(&behind, proof.timestamp), Ok(()));
+        assert_eq!(SchnorrVerifier::verify_proof(&behind), Ok(()));
+    }
+
+    fn geohash() -> impl Strategy<Value = String> {
+        "[0-9b-hjkmnp-z]{9,12}"
+    }
+
+    // Each case costs a few 2048-bit exponentiations, so keep the case count modest
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(24))]
+
+        #[test]
+        fn any_key_and_location_proves_and_verifies(key in any::<[u8; 32]>(), geohash in geohash()) {
+            let x = BigUint::from_bytes_be(&key) % &*Q;
+            prop_assume!(x > BigUint::from(0u32));
+            let proof = SchnorrProver::new(x).generate_proof(geohash, NONCE);
+            prop_assert_eq!(SchnorrVerifier::verify_proof(&proof), Ok(()));
+        }
+
+        #[test]
+        fn proof_does_not_transfer_to_another_location(key in any::<[u8; 32]>(), from in geohash(), to in geohash()) {
+            prop_assume!(from[..CHALLENGE_GEOHASH_LEN] != to[..CHALLENGE_GEOHASH_LEN]);
+            let x = BigUint::from_bytes_be(&key) % &*Q;
+            prop_assume!(x > BigUint::from(0u32));
+            let mut proof = SchnorrProver::new(x).generate_proof(from, NONCE);
+            proof.geohash = to;
+            prop_assert_eq!(SchnorrVerifier::verify_proof(&proof), Err(VerifyError::EquationMismatch));
+        }
+
+        #[test]
+        fn arbitrary_fields_never_panic(public_key in ".{0,40}", commitment in ".{0,40}", response in ".{0,40}", geohash in ".{0,16}") {
+            let proof = Proof { public_key, commitment, response, geohash, ..prove(42) };
+            let _ = SchnorrVerifier::verify_proof(&proof);
         }
     }
 }
- 
\ No newline at end of file
@@ -104,7 +104,7 @@ async function runGeohashZKP(allowedPrefix) {
             // Wait a small bit to simulate work
             await new Promise(r => setTimeout(r, 1500));
 
-            // Send a "Demo" payload that the backend will recognize
+            // Send a "Demo" payload; only servers running with PRIVACCESS_DEMO_MODE honour it
             payload = {
                 demo: true,
                 userHash: userHashStr,
@@ -10,6 +10,17 @@ const P = BigInt("0x" + P_HEX);
 const G = 2n;
 const Q = (P - 1n) / 2n;
 
+// Leading geohash characters bound into the challenge. The server's value (CHALLENGE_GEOHASH_LEN)
+// is passed to generateProof by the page; this is only the fallback.
+const DEFAULT_GEOHASH_PRECISION = 9;
+
+// Seconds to add to the local clock to get the server's, from GET /api/time
+let clockOffsetSecs = 0;
+
+function setClockOffset(serverUnixTime) {
+    clockOffsetSecs = serverUnixTime - Math.floor(Date.now() / 1000);
+}
+
 // Modular Exponentiation: (base^exp) % mod
 function powerMod(base, exp, mod) {
     let result = 1n;
@@ -131,7 +142,7 @@ class SchnorrProverJS {
         this.publicKey = powerMod(G, this.privateKey, P);
     }
 
-    async generateProof(geohash) {
+    async generateProof(geohash, nonce, precision = DEFAULT_GEOHASH_PRECISION) {
         // 1. Random nonce r
         const array = new Uint8Array(32);
         if (typeof crypto !== 'undefined' && crypto.getRandomValues) {
@@ -146,9 +157,11 @@ class SchnorrProverJS {
         // 2. Commitment R = G^r mod P
         let R = powerMod(G, r, P);
 
-        // 3. Challenge c = Hash(R, Public Key, geohash_prefix)
-        const geohashPrefix = geohash.substring(0, 9);
-        let challengeInput = R.toString() + this.publicKey.toString() + geohashPrefix;
+        // 3. Challenge c = Hash(R, Public Key, geohash_prefix, nonce, timestamp)
+        // The nonce comes from /api/challenge and is only accepted once; the timestamp makes the proof expire
+        const geohashPrefix = geohash.substring(0, precision);
+        const timestamp = Math.floor(Date.now() / 1000) + clockOffsetSecs;
+        let challengeInput = R.toString() + this.publicKey.toString() + geohashPrefix + (nonce || "") + timestamp.toString();
         console.log("DEBUG: ZKP Challenge Input (JS):", challengeInput);
         let cBig = await sha256(challengeInput);
         let c = cBig % Q;
@@ -160,7 +173,9 @@ class SchnorrProverJS {
             "public_key": this.publicKey.toString(),
             "commitment": R.toString(),
             "response": s.toString(),
-            "geohash": geohash
+            "geohash": geohash,
+            "nonce": nonce || "",
+            "timestamp": timestamp
         };
     }
 }